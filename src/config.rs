@@ -1,11 +1,14 @@
 //! Configuration management for the Ollama Downloader in Rust (ODIR).
 use directories::ProjectDirs;
 use log::{LevelFilter, info, warn};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 
 /// Settings for connecting to the Ollama server.
@@ -36,6 +39,76 @@ impl Default for OllamaServer {
     }
 }
 
+impl OllamaServer {
+    /// Resolve the URL of the Ollama server to connect to.
+    ///
+    /// The `OLLAMA_HOST` environment variable, as used by Ollama itself, takes precedence
+    /// over the configured `url` when it is set and not empty.
+    ///
+    /// # Returns
+    /// * `String` - The resolved URL of the Ollama server
+    pub fn resolved_url(&self) -> String {
+        match env::var("OLLAMA_HOST") {
+            Ok(host) if !host.trim().is_empty() => {
+                let url = normalize_ollama_host(&host);
+                info!("Using Ollama server URL {} from OLLAMA_HOST", url);
+                url
+            }
+            _ => {
+                info!("Using Ollama server URL {} from settings", self.url);
+                self.url.clone()
+            }
+        }
+    }
+}
+
+/// Normalize an `OLLAMA_HOST` value into a URL, following the rules Ollama uses.
+///
+/// A missing scheme defaults to `http`. A missing port defaults to 11434, unless
+/// the scheme is given explicitly, in which case it defaults to 80 or 443.
+/// A missing host defaults to 127.0.0.1.
+///
+/// # Arguments
+/// * `host` - The value of `OLLAMA_HOST`, e.g., `0.0.0.0:11434` or `https://ollama.example.com`
+///
+/// # Returns
+/// * `String` - The normalized URL, e.g., `http://0.0.0.0:11434`
+fn normalize_ollama_host(host: &str) -> String {
+    let host = host.trim();
+    let (scheme, rest, default_port) = match host.split_once("://") {
+        Some(("https", rest)) => ("https", rest, "443"),
+        Some(("http", rest)) => ("http", rest, "80"),
+        Some((scheme, rest)) => (scheme, rest, "11434"),
+        None => ("http", host, "11434"),
+    };
+    let (host_port, path) = match rest.split_once('/') {
+        Some((host_port, path)) => (host_port, path),
+        None => (rest, ""),
+    };
+
+    // Separate the port, taking care not to split inside a bracketed IPv6 address
+    let (hostname, port) = match host_port.rsplit_once(':') {
+        Some((hostname, port))
+            if !port.contains(']') && (hostname.starts_with('[') || !hostname.contains(':')) =>
+        {
+            (hostname, port)
+        }
+        _ => (host_port, ""),
+    };
+    let hostname = if hostname.is_empty() {
+        "127.0.0.1"
+    } else {
+        hostname
+    };
+    let port = if port.is_empty() { default_port } else { port };
+
+    if path.is_empty() {
+        format!("{}://{}:{}", scheme, hostname, port)
+    } else {
+        format!("{}://{}:{}/{}", scheme, hostname, port, path)
+    }
+}
+
 /// Settings for accessing the Ollama library and storing models locally.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OllamaLibrary {
@@ -53,6 +126,63 @@ pub struct OllamaLibrary {
 
     /// Timeout for HTTP requests in seconds.
     pub timeout: f64,
+
+    /// Directory for temporary, partially downloaded, files.
+    /// If not set, a `.tmp` subdirectory of `models_path` is used.
+    pub temp_dir: Option<String>,
+
+    /// Number of times a failed request is retried while paging through Hugging Face models.
+    pub pagination_retries: u32,
+
+    /// Additional HTTP headers sent with every request, e.g., for registries behind an auth proxy.
+    pub extra_headers: BTreeMap<String, String>,
+
+    /// Maximum total size of a model download in bytes. If not set, downloads of any size are allowed.
+    pub max_download_size: Option<u64>,
+
+    /// Timeout for downloading a single BLOB in seconds. If not set, `timeout` applies.
+    pub blob_timeout: Option<f64>,
+
+    /// Deadline for downloading all BLOBs of a model in seconds. If not set, there is no deadline.
+    pub download_deadline: Option<f64>,
+
+    /// Maximum download bandwidth in bytes per second, where 0 means unlimited.
+    pub max_bandwidth_bytes_per_sec: u64,
+
+    /// Host directory under `manifests` in which Hugging Face models are stored, which is
+    /// also the host part of their names in Ollama, e.g., `hf.co/{username}/{repository}`.
+    pub hf_manifest_host: String,
+
+    /// Maximum number of times per second a download progress bar is redrawn.
+    pub progress_refresh_hz: u8,
+
+    /// Local IP address that requests are sent from, e.g., on a host with several interfaces.
+    /// If not set, the operating system chooses.
+    pub local_address: Option<IpAddr>,
+
+    /// Time in seconds after which a BLOB download whose throughput stays below
+    /// `min_throughput_bytes_per_sec` is aborted as stalled. If not set, stalls are not detected.
+    pub stall_timeout_secs: Option<f64>,
+
+    /// Throughput in bytes per second below which a BLOB download counts as stalled,
+    /// where 0 means that stalls are not detected.
+    pub min_throughput_bytes_per_sec: u64,
+
+    /// Number of inodes that must remain free on the filesystem of the models path after a
+    /// download, on filesystems that have a fixed number of them, where 0 turns the check off.
+    pub min_free_inodes: u64,
+
+    /// Timeout in seconds for fetching the models list of the Ollama library, which may be
+    /// a large page. If not set, `timeout` applies.
+    pub library_request_timeout: Option<f64>,
+
+    /// Template of the download progress bars, in the syntax of `indicatif`, e.g.,
+    /// `{msg} {wide_bar} {percent}%`. If not set, or if it is invalid, the default is used.
+    pub progress_template: Option<String>,
+
+    /// Number of the most recent failed downloads recorded in `failures.jsonl` in the config
+    /// directory, where 0 turns the record off.
+    pub failure_log_entries: u32,
 }
 
 impl Default for OllamaLibrary {
@@ -63,13 +193,158 @@ impl Default for OllamaLibrary {
             library_base_url: "https://ollama.com/library/".to_string(),
             verify_ssl: true,
             timeout: 120.0,
+            temp_dir: None,
+            pagination_retries: 3,
+            extra_headers: BTreeMap::new(),
+            max_download_size: None,
+            blob_timeout: None,
+            download_deadline: None,
+            max_bandwidth_bytes_per_sec: 0,
+            hf_manifest_host: "hf.co".to_string(),
+            progress_refresh_hz: 20,
+            local_address: None,
+            stall_timeout_secs: None,
+            min_throughput_bytes_per_sec: 0,
+            min_free_inodes: 1000,
+            library_request_timeout: None,
+            progress_template: None,
+            failure_log_entries: 0,
+        }
+    }
+}
+
+/// An address to connect to for a host name instead of resolving it, as with curl's `--resolve`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOverride {
+    /// The host name whose resolution is overridden
+    pub host: String,
+
+    /// The address to connect to
+    pub addr: SocketAddr,
+}
+
+/// The modification time given to saved BLOBs, so that they do not carry the time they
+/// happened to be downloaded at, e.g., for reproducible archives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PreserveMtime {
+    /// The UNIX epoch, 1970-01-01T00:00:00Z
+    Epoch,
+
+    /// The time the source reports for the BLOB: the `Last-Modified` header of a download,
+    /// or the modification time recorded in an imported archive
+    Source,
+}
+
+/// An IP address family that connections can be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// IPv4 addresses only
+    Ipv4,
+
+    /// IPv6 addresses only
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Check whether an IP address belongs to this address family.
+    ///
+    /// # Arguments
+    /// * `ip` - The IP address
+    ///
+    /// # Returns
+    /// * `bool` - True if the address belongs to this family
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match self {
+            AddressFamily::Ipv4 => ip.is_ipv4(),
+            AddressFamily::Ipv6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl std::fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => write!(f, "IPv4"),
+            AddressFamily::Ipv6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Patterns that mark a header name as sensitive, so that its value is not logged
+const SENSITIVE_HEADER_PATTERNS: &[&str] = &["auth", "token", "key"];
+
+/// Check whether a header name is sensitive, i.e., whether its value is likely a credential.
+///
+/// # Arguments
+/// * `name` - The header name
+///
+/// # Returns
+/// * `bool` - True if the header name is sensitive
+pub fn is_sensitive_header(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    SENSITIVE_HEADER_PATTERNS.iter().any(|p| lower.contains(p))
+}
+
+impl OllamaLibrary {
+    /// Build the map of additional HTTP headers to send with every request.
+    ///
+    /// # Returns
+    /// * `Result<HeaderMap, String>` - The headers, or a message naming the first invalid header
+    pub fn extra_header_map(&self) -> Result<HeaderMap, String> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.extra_headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| format!("Invalid extra header name '{}': {}", name, e))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|e| format!("Invalid value for extra header '{}': {}", name, e))?;
+            headers.insert(header_name, header_value);
         }
+        Ok(headers)
+    }
+
+    /// Describe the additional HTTP headers for logging, redacting the values of sensitive ones.
+    ///
+    /// # Returns
+    /// * `String` - The headers as comma-separated `name: value` pairs
+    pub fn redacted_extra_headers(&self) -> String {
+        self.extra_headers
+            .iter()
+            .map(|(name, value)| {
+                if is_sensitive_header(name) {
+                    format!("{}: <redacted>", name)
+                } else {
+                    format!("{}: {}", name, value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
     }
 }
 
+/// Version of the settings file schema written by this version of ODIR.
+///
+/// Version 1 is the schema of ODIR 0.1.0, whose settings files have no `schema_version`.
+/// Version 2 added `temp_dir`, `pagination_retries`, `extra_headers`, `max_download_size`,
+/// `blob_timeout`, `download_deadline`, `max_bandwidth_bytes_per_sec`, `hf_manifest_host`,
+/// `progress_refresh_hz`, `local_address`, `stall_timeout_secs`, `min_throughput_bytes_per_sec`,
+/// `min_free_inodes`, `library_request_timeout`, `progress_template` and `failure_log_entries`
+/// to the `ollama_library` settings.
+pub const SETTINGS_SCHEMA_VERSION: u32 = 2;
+
+/// Schema version of settings files that do not record one
+const UNVERSIONED_SCHEMA_VERSION: u32 = 1;
+
+fn current_schema_version() -> u32 {
+    SETTINGS_SCHEMA_VERSION
+}
+
 /// Application settings for the Ollama Downloader.
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
+    /// Version of the settings file schema, used to migrate older settings files.
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
+
     /// Settings for the Ollama server connection.
     pub ollama_server: OllamaServer,
 
@@ -77,6 +352,16 @@ pub struct AppSettings {
     pub ollama_library: OllamaLibrary,
 }
 
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
+            ollama_server: OllamaServer::default(),
+            ollama_library: OllamaLibrary::default(),
+        }
+    }
+}
+
 impl AppSettings {
     /// Load settings from the configuration file, or create default settings if the file does not exist.
     /// If the file exists but has validation errors, attempts to repair it with defaults.
@@ -110,8 +395,165 @@ impl AppSettings {
     /// # Returns
     /// * `Result<Self, io::Error>` - The loaded settings or an error
     pub fn load_settings<P: AsRef<Path>>(settings_file: P) -> io::Result<Self> {
-        let content = fs::read_to_string(settings_file)?;
-        match serde_json::from_str(&content) {
+        let content = fs::read_to_string(&settings_file)?;
+        let settings = match Self::schema_version_of(&content) {
+            Some(version) if version > SETTINGS_SCHEMA_VERSION => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Settings file has schema version {}, but this version of ODIR only supports up to {}",
+                        version, SETTINGS_SCHEMA_VERSION
+                    ),
+                ));
+            }
+            Some(version) if version < SETTINGS_SCHEMA_VERSION => {
+                Self::migrate_settings(&settings_file, &content, version)?
+            }
+            _ => Self::parse_settings(&content)?,
+        };
+        settings
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(settings)
+    }
+
+    /// Get the value of a single setting, addressed by its dotted path in the settings JSON,
+    /// e.g., `ollama_library.models_path`.
+    ///
+    /// # Arguments
+    /// * `key` - The dotted path of the setting
+    ///
+    /// # Returns
+    /// * `Option<Value>` - The value of the setting, or None if there is no such setting
+    pub fn value_at(&self, key: &str) -> Option<Value> {
+        let mut value = serde_json::to_value(self).ok()?;
+        for part in key.split('.') {
+            value = match value {
+                Value::Object(mut fields) => fields.remove(part)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Validate the settings that cannot be checked by deserialization alone, i.e., that the
+    /// extra headers are valid HTTP headers and that durations are positive and finite.
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Ok if the settings are valid, or a description of the first invalid one
+    pub fn validate(&self) -> Result<(), String> {
+        let library = &self.ollama_library;
+        library.extra_header_map()?;
+
+        let durations = [
+            ("timeout", Some(library.timeout)),
+            ("blob_timeout", library.blob_timeout),
+            ("download_deadline", library.download_deadline),
+            ("stall_timeout_secs", library.stall_timeout_secs),
+            ("library_request_timeout", library.library_request_timeout),
+        ];
+        for (name, seconds) in durations {
+            if let Some(seconds) = seconds
+                && !(seconds > 0.0 && seconds.is_finite())
+            {
+                return Err(format!(
+                    "Invalid 'ollama_library.{}': {} is not a positive number of seconds",
+                    name, seconds
+                ));
+            }
+        }
+        if library.max_bandwidth_bytes_per_sec > 0
+            && library.max_bandwidth_bytes_per_sec < library.min_throughput_bytes_per_sec
+            && library.stall_timeout_secs.is_some()
+        {
+            return Err(format!(
+                "Invalid 'ollama_library.min_throughput_bytes_per_sec': {} is above the \
+                max_bandwidth_bytes_per_sec of {}, so every download would stall",
+                library.min_throughput_bytes_per_sec, library.max_bandwidth_bytes_per_sec
+            ));
+        }
+        if library.progress_refresh_hz == 0 {
+            return Err(
+                "Invalid 'ollama_library.progress_refresh_hz': it must be at least 1".to_string(),
+            );
+        }
+        validate_manifest_host(&library.hf_manifest_host)
+            .map_err(|e| format!("Invalid 'ollama_library.hf_manifest_host': {}", e))?;
+        Ok(())
+    }
+
+    /// Get the schema version recorded in settings JSON content.
+    ///
+    /// # Arguments
+    /// * `content` - The JSON content as a string
+    ///
+    /// # Returns
+    /// * `Option<u32>` - The schema version, 1 if none is recorded, or None if the content is not a JSON object
+    fn schema_version_of(content: &str) -> Option<u32> {
+        let parsed: Value = serde_json::from_str(content).ok()?;
+        let object = parsed.as_object()?;
+        match object.get("schema_version") {
+            Some(version) => version.as_u64().and_then(|v| u32::try_from(v).ok()),
+            None => Some(UNVERSIONED_SCHEMA_VERSION),
+        }
+    }
+
+    /// Migrate settings of an older schema version to the current one,
+    /// and rewrite the settings file with the migrated settings.
+    ///
+    /// # Arguments
+    /// * `settings_file` - Path to the settings file
+    /// * `content` - The JSON content of the settings file
+    /// * `from_version` - The schema version of the settings file
+    ///
+    /// # Returns
+    /// * `Result<Self, io::Error>` - The migrated settings or an error
+    fn migrate_settings<P: AsRef<Path>>(
+        settings_file: P,
+        content: &str,
+        from_version: u32,
+    ) -> io::Result<Self> {
+        info!(
+            "Migrating settings file '{}' from schema version {} to {}",
+            settings_file.as_ref().display(),
+            from_version,
+            SETTINGS_SCHEMA_VERSION
+        );
+
+        // Every schema version so far only added fields, which the lenient loader
+        // fills in with their defaults, logging each of them
+        let settings = Self::load_settings_lenient(content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to migrate settings: {}", e),
+            )
+        })?;
+
+        match settings.save_settings(&settings_file) {
+            Ok(()) => info!(
+                "Rewrote settings file '{}' with schema version {}",
+                settings_file.as_ref().display(),
+                SETTINGS_SCHEMA_VERSION
+            ),
+            Err(e) => warn!(
+                "Failed to rewrite migrated settings file '{}': {}",
+                settings_file.as_ref().display(),
+                e
+            ),
+        }
+
+        Ok(settings)
+    }
+
+    /// Parse settings from JSON content, falling back to lenient deserialization.
+    ///
+    /// # Arguments
+    /// * `content` - The JSON content as a string
+    ///
+    /// # Returns
+    /// * `Result<Self, io::Error>` - The parsed settings or an error
+    fn parse_settings(content: &str) -> io::Result<Self> {
+        match serde_json::from_str(content) {
             Ok(settings) => Ok(settings),
             Err(e) => {
                 // If deserialization fails, try lenient loading with defaults
@@ -119,7 +561,7 @@ impl AppSettings {
                     "Strict deserialization failed: {}. Attempting to load with defaults...",
                     e
                 );
-                Self::load_settings_lenient(&content).map_err(|lenient_err| {
+                Self::load_settings_lenient(content).map_err(|lenient_err| {
                     io::Error::new(
                         io::ErrorKind::InvalidData,
                         format!(
@@ -244,9 +686,116 @@ impl AppSettings {
                 ),
             );
         }
+        if !ollama_library.contains_key("temp_dir") {
+            warn!("Missing field 'ollama_library.temp_dir', using default: None");
+            ollama_library.insert("temp_dir".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("pagination_retries") {
+            warn!(
+                "Missing field 'ollama_library.pagination_retries', using default: {}",
+                defaults.pagination_retries
+            );
+            ollama_library.insert(
+                "pagination_retries".to_string(),
+                Value::from(defaults.pagination_retries),
+            );
+        }
+        if !ollama_library.contains_key("extra_headers") {
+            warn!("Missing field 'ollama_library.extra_headers', using default: {{}}");
+            ollama_library.insert("extra_headers".to_string(), json!({}));
+        }
+        if !ollama_library.contains_key("max_download_size") {
+            warn!("Missing field 'ollama_library.max_download_size', using default: None");
+            ollama_library.insert("max_download_size".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("blob_timeout") {
+            warn!("Missing field 'ollama_library.blob_timeout', using default: None");
+            ollama_library.insert("blob_timeout".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("download_deadline") {
+            warn!("Missing field 'ollama_library.download_deadline', using default: None");
+            ollama_library.insert("download_deadline".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("max_bandwidth_bytes_per_sec") {
+            warn!(
+                "Missing field 'ollama_library.max_bandwidth_bytes_per_sec', using default: {}",
+                defaults.max_bandwidth_bytes_per_sec
+            );
+            ollama_library.insert(
+                "max_bandwidth_bytes_per_sec".to_string(),
+                Value::from(defaults.max_bandwidth_bytes_per_sec),
+            );
+        }
+        if !ollama_library.contains_key("hf_manifest_host") {
+            warn!(
+                "Missing field 'ollama_library.hf_manifest_host', using default: {}",
+                defaults.hf_manifest_host
+            );
+            ollama_library.insert(
+                "hf_manifest_host".to_string(),
+                Value::String(defaults.hf_manifest_host.clone()),
+            );
+        }
+        if !ollama_library.contains_key("progress_refresh_hz") {
+            warn!(
+                "Missing field 'ollama_library.progress_refresh_hz', using default: {}",
+                defaults.progress_refresh_hz
+            );
+            ollama_library.insert(
+                "progress_refresh_hz".to_string(),
+                Value::from(defaults.progress_refresh_hz),
+            );
+        }
+        if !ollama_library.contains_key("local_address") {
+            warn!("Missing field 'ollama_library.local_address', using default: None");
+            ollama_library.insert("local_address".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("stall_timeout_secs") {
+            warn!("Missing field 'ollama_library.stall_timeout_secs', using default: None");
+            ollama_library.insert("stall_timeout_secs".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("min_throughput_bytes_per_sec") {
+            warn!(
+                "Missing field 'ollama_library.min_throughput_bytes_per_sec', using default: {}",
+                defaults.min_throughput_bytes_per_sec
+            );
+            ollama_library.insert(
+                "min_throughput_bytes_per_sec".to_string(),
+                Value::from(defaults.min_throughput_bytes_per_sec),
+            );
+        }
+        if !ollama_library.contains_key("min_free_inodes") {
+            warn!(
+                "Missing field 'ollama_library.min_free_inodes', using default: {}",
+                defaults.min_free_inodes
+            );
+            ollama_library.insert(
+                "min_free_inodes".to_string(),
+                Value::from(defaults.min_free_inodes),
+            );
+        }
+        if !ollama_library.contains_key("library_request_timeout") {
+            warn!("Missing field 'ollama_library.library_request_timeout', using default: None");
+            ollama_library.insert("library_request_timeout".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("progress_template") {
+            warn!("Missing field 'ollama_library.progress_template', using default: None");
+            ollama_library.insert("progress_template".to_string(), Value::Null);
+        }
+        if !ollama_library.contains_key("failure_log_entries") {
+            warn!(
+                "Missing field 'ollama_library.failure_log_entries', using default: {}",
+                defaults.failure_log_entries
+            );
+            ollama_library.insert(
+                "failure_log_entries".to_string(),
+                Value::from(defaults.failure_log_entries),
+            );
+        }
 
         // Reconstruct the settings object with filled-in values
         let settings_object = json!({
+            "schema_version": SETTINGS_SCHEMA_VERSION,
             "ollama_server": ollama_server,
             "ollama_library": ollama_library,
         });
@@ -255,6 +804,114 @@ impl AppSettings {
             .map_err(|e| format!("Failed to deserialize settings with defaults: {}", e))
     }
 
+    /// Override the Ollama registry base URL, if an override is given.
+    ///
+    /// # Arguments
+    /// * `registry_base_url` - Optional registry base URL to use instead of the configured one
+    ///
+    /// # Returns
+    /// * `Self` - The settings with the override applied
+    pub fn with_registry_base_url(mut self, registry_base_url: Option<String>) -> Self {
+        if let Some(url) = registry_base_url {
+            info!("Overriding Ollama registry base URL with {}", url);
+            self.ollama_library.registry_base_url = url;
+        }
+        self
+    }
+
+    /// Override the Ollama library base URL, if an override is given.
+    ///
+    /// # Arguments
+    /// * `library_base_url` - Optional library base URL to use instead of the configured one
+    ///
+    /// # Returns
+    /// * `Self` - The settings with the override applied
+    pub fn with_library_base_url(mut self, library_base_url: Option<String>) -> Self {
+        if let Some(url) = library_base_url {
+            info!("Overriding Ollama library base URL with {}", url);
+            self.ollama_library.library_base_url = url;
+        }
+        self
+    }
+
+    /// Override the maximum download size, if an override is given.
+    ///
+    /// # Arguments
+    /// * `max_download_size` - Optional size limit in bytes to use instead of the configured one
+    ///
+    /// # Returns
+    /// * `Self` - The settings with the override applied
+    pub fn with_max_download_size(mut self, max_download_size: Option<u64>) -> Self {
+        if let Some(limit) = max_download_size {
+            info!("Overriding maximum download size with {} bytes", limit);
+            self.ollama_library.max_download_size = Some(limit);
+        }
+        self
+    }
+
+    /// Override the maximum download bandwidth, if an override is given. Stall detection is
+    /// turned off for the run if its minimum throughput is above the bandwidth, since every
+    /// download would stall otherwise.
+    ///
+    /// # Arguments
+    /// * `max_bandwidth` - Optional bandwidth in bytes per second to use instead of the configured one
+    ///
+    /// # Returns
+    /// * `Self` - The settings with the override applied
+    pub fn with_max_bandwidth(mut self, max_bandwidth: Option<u64>) -> Self {
+        if let Some(rate) = max_bandwidth {
+            info!(
+                "Overriding maximum download bandwidth with {} bytes per second",
+                rate
+            );
+            self.ollama_library.max_bandwidth_bytes_per_sec = rate;
+            if rate < self.ollama_library.min_throughput_bytes_per_sec
+                && self.ollama_library.stall_timeout_secs.is_some()
+            {
+                warn!(
+                    "The minimum throughput of {} bytes per second is above the bandwidth limit, \
+                    so stall detection is off for this run",
+                    self.ollama_library.min_throughput_bytes_per_sec
+                );
+                self.ollama_library.stall_timeout_secs = None;
+            }
+        }
+        self
+    }
+
+    /// Override the timeout for downloading a single BLOB, if an override is given.
+    ///
+    /// # Arguments
+    /// * `blob_timeout` - Optional timeout in seconds to use instead of the configured one
+    ///
+    /// # Returns
+    /// * `Self` - The settings with the override applied
+    pub fn with_blob_timeout(mut self, blob_timeout: Option<f64>) -> Self {
+        if let Some(seconds) = blob_timeout {
+            info!("Overriding BLOB timeout with {} seconds", seconds);
+            self.ollama_library.blob_timeout = Some(seconds);
+        }
+        self
+    }
+
+    /// Disable the verification of TLS certificates for this run, if asked to, regardless of
+    /// the configured `verify_ssl`, e.g., for testing against a host with a self-signed certificate.
+    ///
+    /// # Arguments
+    /// * `allow_insecure` - Whether to disable the verification of TLS certificates
+    ///
+    /// # Returns
+    /// * `Self` - The settings with the override applied
+    pub fn with_allow_insecure(mut self, allow_insecure: bool) -> Self {
+        if allow_insecure {
+            warn!(
+                "TLS certificate verification is DISABLED for this run (--allow-insecure): connections can be intercepted without notice"
+            );
+            self.ollama_library.verify_ssl = false;
+        }
+        self
+    }
+
     /// Save the application settings to the configuration file.
     ///
     /// # Arguments
@@ -269,11 +926,233 @@ impl AppSettings {
             && !parent.as_os_str().is_empty()
             && !parent.exists()
         {
-            fs::create_dir_all(parent)?;
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(settings_path, json)?;
+        Ok(())
+    }
+}
+
+/// Options that apply to a single run only, given on the command line instead of stored in
+/// the settings file, and passed to the downloaders along with the settings.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Addresses to connect to for host names, instead of resolving them.
+    pub resolve_overrides: Vec<ResolveOverride>,
+
+    /// Whether download progress is reported as JSON events instead of progress bars.
+    pub progress_json: bool,
+
+    /// The only address family that connections use. If not set, both are used.
+    pub address_family: Option<AddressFamily>,
+
+    /// Whether partially downloaded files are left on disk on errors and interrupts.
+    pub keep_partial_files: bool,
+
+    /// Whether models that are already up to date are downloaded again, and failed checks of
+    /// the free space for a download only warn.
+    pub force: bool,
+
+    /// Whether downloads over plain HTTP from remote hosts are allowed.
+    pub allow_insecure_http: bool,
+
+    /// Whether downloads do without the lock on the models path.
+    pub no_lock: bool,
+
+    /// Whether downloads require an explicit tag instead of assuming `latest`.
+    pub strict_tag: bool,
+
+    /// The modification time given to saved BLOBs. If not set, BLOBs keep the time they were
+    /// saved at.
+    pub preserve_mtime: Option<PreserveMtime>,
+
+    /// Bytes that must remain free on the filesystem of the models path after a download.
+    pub min_free_after: u64,
+
+    /// The file that fetched manifests are written to, exactly as received.
+    pub raw_manifest_out: Option<PathBuf>,
+}
+
+impl RunOptions {
+    /// Allow downloads over plain HTTP from hosts other than the local machine, if
+    /// asked to. Without it, such downloads are refused.
+    ///
+    /// # Arguments
+    /// * `allow_insecure_http` - Whether to allow downloads over plain HTTP from remote hosts
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_allow_insecure_http(mut self, allow_insecure_http: bool) -> Self {
+        self.allow_insecure_http = allow_insecure_http;
+        self
+    }
+
+    /// Connect to the given addresses for host names instead of resolving them.
+    ///
+    /// # Arguments
+    /// * `resolve_overrides` - The addresses to connect to for host names
+    ///
+    /// # Returns
+    /// * `Self` - The options with the overrides applied
+    pub fn with_resolve_overrides(mut self, resolve_overrides: Vec<ResolveOverride>) -> Self {
+        for resolve_override in &resolve_overrides {
+            info!(
+                "Resolving {} to {} instead of using DNS",
+                resolve_override.host,
+                resolve_override.addr.ip()
+            );
+        }
+        self.resolve_overrides = resolve_overrides;
+        self
+    }
+
+    /// Report download progress as newline-delimited JSON events on stderr instead of
+    /// progress bars, if asked to.
+    ///
+    /// # Arguments
+    /// * `progress_json` - Whether to report progress as JSON events
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_progress_json(mut self, progress_json: bool) -> Self {
+        self.progress_json = progress_json;
+        self
+    }
+
+    /// Leave partially downloaded files on disk on errors and interrupts, if asked to, so
+    /// that they can be inspected. This also overrides the configured
+    /// `remove_downloaded_on_error`.
+    ///
+    /// # Arguments
+    /// * `no_cleanup` - Whether to leave partially downloaded files on disk
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_no_cleanup(mut self, no_cleanup: bool) -> Self {
+        if no_cleanup {
+            info!("Partially downloaded files will be left on disk on errors");
+            self.keep_partial_files = true;
+        }
+        self
+    }
+
+    /// Download models again even if they are already up to date, and only warn, instead of
+    /// aborting the download, if the filesystem of the models path has too little free space
+    /// for a download, if asked to.
+    ///
+    /// # Arguments
+    /// * `force` - Whether to download despite an up-to-date model or too little free space
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Download without taking the lock on the models path, if asked to, e.g., if another
+    /// process is known not to download into it.
+    ///
+    /// # Arguments
+    /// * `no_lock` - Whether to download without the lock
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_no_lock(mut self, no_lock: bool) -> Self {
+        if no_lock {
+            warn!("Not locking the models path: concurrent downloads into it may corrupt it");
+        }
+        self.no_lock = no_lock;
+        self
+    }
+
+    /// Require downloads to name a tag or digest explicitly instead of assuming `latest`,
+    /// e.g., to catch scripts that do not pin the version they pull.
+    ///
+    /// # Arguments
+    /// * `strict_tag` - Whether an explicit tag is required
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_strict_tag(mut self, strict_tag: bool) -> Self {
+        self.strict_tag = strict_tag;
+        self
+    }
+
+    /// Give saved BLOBs a deterministic modification time, if one is given.
+    ///
+    /// # Arguments
+    /// * `preserve_mtime` - The modification time to give saved BLOBs, if any
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_preserve_mtime(mut self, preserve_mtime: Option<PreserveMtime>) -> Self {
+        self.preserve_mtime = preserve_mtime;
+        self
+    }
+
+    /// Keep some space free on the filesystem of the models path after a download, if an
+    /// amount is given.
+    ///
+    /// # Arguments
+    /// * `min_free_after` - Bytes that must remain free after a download, if any
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_min_free_after(mut self, min_free_after: Option<u64>) -> Self {
+        if let Some(min_free_after) = min_free_after {
+            self.min_free_after = min_free_after;
+        }
+        self
+    }
+
+    /// Write fetched manifests to a file, exactly as received, if a file is given, e.g., to
+    /// reproduce a problem with parsing them without the registry.
+    ///
+    /// # Arguments
+    /// * `raw_manifest_out` - The file to write fetched manifests to, if any
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_raw_manifest_out(mut self, raw_manifest_out: Option<PathBuf>) -> Self {
+        self.raw_manifest_out = raw_manifest_out;
+        self
+    }
+
+    /// Restrict connections to one address family, if one is given.
+    ///
+    /// # Arguments
+    /// * `address_family` - The only address family to connect with, if any
+    ///
+    /// # Returns
+    /// * `Self` - The options with the override applied
+    pub fn with_address_family(mut self, address_family: Option<AddressFamily>) -> Self {
+        if let Some(family) = address_family {
+            info!("Connecting over {} only", family);
+        }
+        self.address_family = address_family;
+        self
+    }
+
+    /// Validate the options against the settings they are used with, i.e., that the
+    /// configured local address belongs to the address family connections are restricted to.
+    ///
+    /// # Arguments
+    /// * `library` - The library settings the options are used with
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Ok if the options are valid, or a description of why they are not
+    pub fn validate(&self, library: &OllamaLibrary) -> Result<(), String> {
+        if let (Some(local_address), Some(family)) = (library.local_address, self.address_family)
+            && !family.contains(&local_address)
+        {
+            return Err(format!(
+                "Invalid 'ollama_library.local_address': {} is not an {} address",
+                local_address, family
+            ));
         }
-
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write(settings_path, json)?;
         Ok(())
     }
 }
@@ -336,32 +1215,305 @@ impl Config {
     }
 }
 
-/// Get the path to the settings file using OS-standard user config directories.
+/// Validate that a string is an absolute HTTP or HTTPS URL with a host.
+///
+/// The URL is returned with a trailing slash, so that paths can be appended to it
+/// the same way as to the configured base URLs.
+///
+/// # Arguments
+/// * `value` - The string to validate
+///
+/// # Returns
+/// * `Result<String, String>` - The normalized URL, or a description of why it is invalid
+pub fn validate_string_as_http_url(value: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(value).map_err(|e| format!("'{}' is not a URL: {}", value, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "'{}' must use the http or https scheme, not {}",
+            value,
+            url.scheme()
+        ));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(format!("'{}' does not have a host", value));
+    }
+
+    if value.ends_with('/') {
+        Ok(value.to_string())
+    } else {
+        Ok(format!("{}/", value))
+    }
+}
+
+/// Parse a positive duration in seconds, e.g., `30` or `2.5`.
+///
+/// # Arguments
+/// * `s` - The duration to parse
+///
+/// # Returns
+/// * `Result<f64, String>` - The number of seconds, or a description of why it is invalid
+pub fn parse_seconds(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(format!(
+            "'{}' is not a positive number of seconds",
+            s.trim()
+        )),
+    }
+}
+
+/// Parse an address override in the `HOST:PORT:ADDR` format of curl's `--resolve`,
+/// e.g., `registry.staging.example:443:10.0.0.5` or `registry.staging.example:443:[::1]`.
+///
+/// # Arguments
+/// * `value` - The string to parse
+///
+/// # Returns
+/// * `Result<ResolveOverride, String>` - The override, or a description of why it is invalid
+pub fn parse_resolve(value: &str) -> Result<ResolveOverride, String> {
+    let invalid = |reason: &str| format!("'{}' is not HOST:PORT:ADDR: {}", value, reason);
+    let mut parts = value.splitn(3, ':');
+    let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("expected three parts separated by colons"));
+    };
+    if host.is_empty() {
+        return Err(invalid("the host is empty"));
+    }
+    let port: u16 = port
+        .parse()
+        .map_err(|_| invalid(&format!("'{}' is not a port", port)))?;
+    let ip: IpAddr = addr
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| invalid(&format!("'{}' is not an IP address", addr)))?;
+    Ok(ResolveOverride {
+        host: host.to_lowercase(),
+        addr: SocketAddr::new(ip, port),
+    })
+}
+
+/// Validate a host under which manifests are stored, which must be a single directory name
+/// that is also valid as the host part of a model name, e.g., `hf.co`.
+///
+/// # Arguments
+/// * `host` - The host to validate
+///
+/// # Returns
+/// * `Result<(), String>` - Ok if the host is valid, or a description of why it is not
+pub fn validate_manifest_host(host: &str) -> Result<(), String> {
+    let valid = !host.is_empty()
+        && host != "."
+        && host != ".."
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid host name", host))
+    }
+}
+
+/// Parse a size in bytes, optionally followed by a unit, e.g., `700M`, `4GiB` or `1.5 TB`.
+///
+/// Units are case-insensitive. `K`, `M`, `G` and `T`, with or without a trailing `B`,
+/// are decimal (powers of 1000), while `KiB`, `MiB`, `GiB` and `TiB` are binary (powers of 1024).
+///
+/// # Arguments
+/// * `value` - The string to parse
+///
+/// # Returns
+/// * `Result<u64, String>` - The size in bytes, or a description of why it is invalid
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let split_at = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split_at);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' does not start with a number", value))?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        other => return Err(format!("'{}' has an unknown size unit '{}'", value, other)),
+    };
+
+    let bytes = number * multiplier as f64;
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(format!("'{}' is too large", value));
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Parse a download rate like curl's `--limit-rate`, with an optional `/s`. As with curl,
+/// the single-letter suffixes `k`, `M`, `G` and `T` are binary, e.g., `500k` is 512000 bytes
+/// per second, while the other units are those of [`parse_byte_size`], e.g., `500KB` is
+/// 500000 bytes per second.
+///
+/// # Arguments
+/// * `value` - The string to parse
+///
+/// # Returns
+/// * `Result<u64, String>` - The rate in bytes per second, or a description of why it is invalid
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let size = trimmed
+        .strip_suffix("/s")
+        .or_else(|| trimmed.strip_suffix("/S"))
+        .unwrap_or(trimmed)
+        .trim();
+    let binary_unit = match size.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => Some("KiB"),
+        Some('m') => Some("MiB"),
+        Some('g') => Some("GiB"),
+        Some('t') => Some("TiB"),
+        _ => None,
+    };
+    let rate = match binary_unit {
+        Some(unit) => parse_byte_size(&format!("{}{}", &size[..size.len() - 1], unit))
+            .map_err(|_| format!("'{}' is not a valid rate", value))?,
+        None => parse_byte_size(size)?,
+    };
+    match rate {
+        0 => Err(format!("'{}' is not a positive rate", value)),
+        rate => Ok(rate),
+    }
+}
+
+/// Environment variable that overrides the directory of the settings and manifest cache
+const CONFIG_DIR_ENV: &str = "ODIR_CONFIG_DIR";
+
+/// Directory inside the config directory that holds the settings files of profiles
+const PROFILES_DIR: &str = "profiles";
+
+/// Resolve the config directory of ODIR.
+///
+/// `ODIR_CONFIG_DIR` takes precedence if set. Otherwise, the OS-standard user config
+/// directory is used, and if that cannot be determined, e.g., in a container without a
+/// home directory, `$XDG_CONFIG_HOME/odir`.
+///
+/// # Arguments
+/// * `lookup` - Looks up the value of an environment variable
+/// * `project_dir` - The OS-standard user config directory of ODIR, if it can be determined
+///
+/// # Returns
+/// * `Option<PathBuf>` - The config directory, or None if it cannot be determined
+fn resolve_config_dir(
+    lookup: impl Fn(&str) -> Option<std::ffi::OsString>,
+    project_dir: Option<PathBuf>,
+) -> Option<PathBuf> {
+    let non_empty = |name: &str| lookup(name).filter(|value| !value.is_empty());
+    non_empty(CONFIG_DIR_ENV)
+        .map(PathBuf::from)
+        .or(project_dir)
+        .or_else(|| {
+            non_empty("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .map(|dir| dir.join("odir"))
+        })
+}
+
+/// Get the config directory of ODIR, holding the settings file and the manifest cache.
 ///
-/// Returns the path to `settings.json` in the user's config directory.
-/// On Linux: `~/.config/odir/settings.json`
-/// On macOS: `~/Library/Application Support/odir/settings.json`
-/// On Windows: `C:\Users\<user>\AppData\Roaming\odir\settings.json`
+/// On Linux: `~/.config/odir`
+/// On macOS: `~/Library/Application Support/odir`
+/// On Windows: `C:\Users\<user>\AppData\Roaming\odir`
 ///
-/// Creates the config directory if it doesn't exist.
+/// `ODIR_CONFIG_DIR` overrides it, and `$XDG_CONFIG_HOME/odir` is used if the home
+/// directory cannot be determined.
 ///
 /// # Returns
-/// * `PathBuf` - Path to the settings file
+/// * `io::Result<PathBuf>` - The config directory, or an error if it cannot be determined
+pub fn get_config_dir() -> io::Result<PathBuf> {
+    let project_dir =
+        ProjectDirs::from("", "", "odir").map(|proj_dirs| proj_dirs.config_dir().to_path_buf());
+    resolve_config_dir(|name| env::var_os(name), project_dir).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Failed to determine the config directory, since there is no home directory. Set {} to the directory to keep the settings in",
+                CONFIG_DIR_ENV
+            ),
+        )
+    })
+}
+
+/// Get the path to the settings file in a config directory, `settings.json`, or
+/// `profiles/<name>.json` for a profile, creating the directories if they don't exist.
+///
+/// # Arguments
+/// * `config_dir` - The config directory
+/// * `profile` - The name of the profile, if any
 ///
-/// # Panics
-/// Panics if the config directory cannot be determined or created.
-pub fn get_settings_file_path() -> PathBuf {
-    let proj_dirs =
-        ProjectDirs::from("", "", "odir").expect("Failed to determine config directory");
+/// # Returns
+/// * `io::Result<PathBuf>` - Path to the settings file, or an error if the directory cannot be created
+fn settings_file_path_in(config_dir: &Path, profile: Option<&str>) -> io::Result<PathBuf> {
+    let dir = match profile {
+        Some(_) => config_dir.join(PROFILES_DIR),
+        None => config_dir.to_path_buf(),
+    };
+    fs::create_dir_all(&dir).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to create config directory {:?}: {}", dir, e),
+        )
+    })?;
+    Ok(match profile {
+        Some(name) => dir.join(format!("{}.json", name)),
+        None => dir.join("settings.json"),
+    })
+}
 
-    let config_dir = proj_dirs.config_dir();
+/// Get the path to the settings file in the [config directory](get_config_dir), or to the
+/// settings file of a profile, creating the directories if they don't exist.
+///
+/// # Arguments
+/// * `profile` - The name of the profile, if any
+///
+/// # Returns
+/// * `io::Result<PathBuf>` - Path to the settings file, or an error if the config
+///   directory cannot be determined or created
+pub fn get_settings_file_path(profile: Option<&str>) -> io::Result<PathBuf> {
+    settings_file_path_in(&get_config_dir()?, profile)
+}
 
-    // Create the directory if it doesn't exist
-    if let Err(e) = fs::create_dir_all(config_dir) {
-        panic!("Failed to create config directory: {}", e);
+/// Parse the name of a settings profile, which names a file in the profiles directory and so
+/// may not be empty, start with a dot or contain a path separator.
+///
+/// # Arguments
+/// * `value` - The string to parse
+///
+/// # Returns
+/// * `Result<String, String>` - The profile name, or a description of why it is invalid
+pub fn parse_profile_name(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("the profile name is empty".to_string());
+    }
+    if value.starts_with('.') || value.contains(['/', '\\']) {
+        return Err(format!(
+            "'{}' is not a valid profile name, which may not start with a dot or contain a path separator",
+            value
+        ));
     }
+    Ok(value.to_string())
+}
 
-    config_dir.join("settings.json")
+/// Get the directory of the manifest cache inside the user's config directory.
+///
+/// # Returns
+/// * `Option<PathBuf>` - Path to the manifest cache, or None if the config directory cannot be determined
+pub fn get_manifest_cache_dir() -> Option<PathBuf> {
+    get_config_dir().ok().map(|dir| dir.join("manifests"))
 }
 
 /// Get the user agent string for HTTP requests.
@@ -374,7 +1526,54 @@ pub fn get_user_agent() -> String {
     format!("odir/{}", env!("CARGO_PKG_VERSION"))
 }
 
+/// Build metadata of the running ODIR binary, useful for bug reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    /// The crate version
+    pub version: String,
+
+    /// The operating system the binary runs on
+    pub os: String,
+
+    /// The CPU architecture the binary runs on
+    pub arch: String,
+
+    /// The target triple the binary was compiled for
+    pub target: String,
+
+    /// The version of the Rust compiler used for the build
+    pub rustc: String,
+
+    /// The time of the build in RFC 3339 format (UTC)
+    pub build_date: String,
+
+    /// The user agent sent with HTTP requests
+    pub user_agent: String,
+}
+
+impl BuildInfo {
+    /// Collect the build metadata of the running binary.
+    ///
+    /// # Returns
+    /// * `Self` - The build metadata
+    pub fn current() -> Self {
+        let build_timestamp = env!("ODIR_BUILD_TIMESTAMP").parse::<u64>().unwrap_or(0);
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            target: env!("ODIR_BUILD_TARGET").to_string(),
+            rustc: env!("ODIR_RUSTC_VERSION").to_string(),
+            build_date: crate::downloader::journal::format_rfc3339_utc(
+                std::time::UNIX_EPOCH + std::time::Duration::from_secs(build_timestamp),
+            ),
+            user_agent: get_user_agent(),
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use std::fs;
@@ -391,6 +1590,16 @@ mod tests {
         assert!(user_agent.starts_with("odir/"));
     }
 
+    #[test]
+    fn test_build_info() {
+        let info = BuildInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.os, env::consts::OS);
+        assert!(!info.target.is_empty());
+        assert!(info.build_date.ends_with('Z'));
+        assert_eq!(info.user_agent, get_user_agent());
+    }
+
     #[test]
     fn test_parse_log_level() {
         assert_eq!(Config::parse_log_level("DEBUG"), LevelFilter::Debug);
@@ -413,6 +1622,201 @@ mod tests {
         assert_eq!(server.check_model_presence, true);
     }
 
+    #[test]
+    fn test_normalize_ollama_host() {
+        assert_eq!(normalize_ollama_host("0.0.0.0"), "http://0.0.0.0:11434");
+        assert_eq!(
+            normalize_ollama_host("192.168.1.10:8080"),
+            "http://192.168.1.10:8080"
+        );
+        assert_eq!(normalize_ollama_host(":11435"), "http://127.0.0.1:11435");
+        assert_eq!(
+            normalize_ollama_host("http://ollama.local"),
+            "http://ollama.local:80"
+        );
+        assert_eq!(
+            normalize_ollama_host("https://ollama.example.com/api-proxy"),
+            "https://ollama.example.com:443/api-proxy"
+        );
+        assert_eq!(normalize_ollama_host("[::1]:11434"), "http://[::1]:11434");
+        assert_eq!(normalize_ollama_host("[::1]"), "http://[::1]:11434");
+    }
+
+    #[test]
+    fn test_validate_string_as_http_url() {
+        assert_eq!(
+            validate_string_as_http_url("https://registry.test/v2/library/"),
+            Ok("https://registry.test/v2/library/".to_string())
+        );
+        assert_eq!(
+            validate_string_as_http_url("http://localhost:5000/v2/library"),
+            Ok("http://localhost:5000/v2/library/".to_string())
+        );
+        assert!(validate_string_as_http_url("registry.test/v2/library/").is_err());
+        assert!(validate_string_as_http_url("ftp://registry.test/").is_err());
+        assert!(validate_string_as_http_url("file:///tmp/registry").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1024"), Ok(1024));
+        assert_eq!(parse_byte_size("700M"), Ok(700_000_000));
+        assert_eq!(parse_byte_size("700mb"), Ok(700_000_000));
+        assert_eq!(parse_byte_size("4GiB"), Ok(4 * 1024 * 1024 * 1024));
+        assert_eq!(parse_byte_size("1.5 KiB"), Ok(1536));
+        assert_eq!(parse_byte_size(" 2T "), Ok(2_000_000_000_000));
+        assert!(parse_byte_size("").is_err());
+        assert!(parse_byte_size("GiB").is_err());
+        assert!(parse_byte_size("4 parsecs").is_err());
+        assert!(parse_byte_size("1.2.3G").is_err());
+        assert!(parse_byte_size("99999999999TiB").is_err());
+    }
+
+    #[test]
+    fn test_parse_rate_and_with_max_bandwidth() {
+        // Single-letter suffixes are binary, as with curl
+        assert_eq!(parse_rate("2M"), Ok(2 << 20));
+        assert_eq!(parse_rate("500k"), Ok(512_000));
+        assert_eq!(parse_rate("500K/s"), Ok(512_000));
+        assert_eq!(parse_rate("1g"), Ok(1 << 30));
+        assert_eq!(parse_rate("500KB"), Ok(500_000));
+        assert_eq!(parse_rate("2 MB/s"), Ok(2_000_000));
+        assert_eq!(parse_rate("1MiB/s"), Ok(1 << 20));
+        assert_eq!(parse_rate("100"), Ok(100));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("0.0001").is_err());
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("2 furlongs").is_err());
+
+        let settings = AppSettings::default().with_max_bandwidth(None);
+        assert_eq!(settings.ollama_library.max_bandwidth_bytes_per_sec, 0);
+        let mut settings = settings;
+        settings.ollama_library.stall_timeout_secs = Some(30.0);
+        settings.ollama_library.min_throughput_bytes_per_sec = 1_000_000;
+        let settings = settings.with_max_bandwidth(Some(2_000_000));
+        assert_eq!(
+            settings.ollama_library.max_bandwidth_bytes_per_sec,
+            2_000_000
+        );
+        assert_eq!(settings.ollama_library.stall_timeout_secs, Some(30.0));
+        let settings = settings.with_max_bandwidth(Some(500_000));
+        assert_eq!(settings.ollama_library.max_bandwidth_bytes_per_sec, 500_000);
+        assert_eq!(settings.ollama_library.stall_timeout_secs, None);
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_seconds_and_with_blob_timeout() {
+        assert_eq!(parse_seconds("30"), Ok(30.0));
+        assert_eq!(parse_seconds(" 2.5 "), Ok(2.5));
+        assert!(parse_seconds("0").is_err());
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("soon").is_err());
+
+        let settings = AppSettings::default().with_blob_timeout(None);
+        assert_eq!(settings.ollama_library.blob_timeout, None);
+        let settings = settings.with_blob_timeout(Some(30.0));
+        assert_eq!(settings.ollama_library.blob_timeout, Some(30.0));
+    }
+
+    #[test]
+    fn test_parse_resolve() {
+        assert_eq!(
+            parse_resolve("Registry.Staging.example:443:10.0.0.5"),
+            Ok(ResolveOverride {
+                host: "registry.staging.example".to_string(),
+                addr: "10.0.0.5:443".parse().unwrap(),
+            })
+        );
+        assert_eq!(
+            parse_resolve("localhost:8080:[::1]").map(|r| r.addr),
+            Ok("[::1]:8080".parse().unwrap())
+        );
+        assert_eq!(
+            parse_resolve("localhost:8080:::1").map(|r| r.addr),
+            Ok("[::1]:8080".parse().unwrap())
+        );
+        assert!(parse_resolve("registry.example:443").is_err());
+        assert!(parse_resolve(":443:10.0.0.5").is_err());
+        assert!(parse_resolve("registry.example:https:10.0.0.5").is_err());
+        assert!(parse_resolve("registry.example:443:staging").is_err());
+    }
+
+    #[test]
+    fn test_parse_profile_name() {
+        assert_eq!(parse_profile_name("work"), Ok("work".to_string()));
+        assert_eq!(parse_profile_name("gpu-box.2"), Ok("gpu-box.2".to_string()));
+        assert!(parse_profile_name("").is_err());
+        assert!(parse_profile_name(".hidden").is_err());
+        assert!(parse_profile_name("..").is_err());
+        assert!(parse_profile_name("../settings").is_err());
+        assert!(parse_profile_name("a\\b").is_err());
+    }
+
+    #[test]
+    fn test_with_allow_insecure() {
+        let settings = AppSettings::default().with_allow_insecure(false);
+        assert!(settings.ollama_library.verify_ssl);
+        let settings = settings.with_allow_insecure(true);
+        assert!(!settings.ollama_library.verify_ssl);
+    }
+
+    #[test]
+    fn test_with_no_cleanup() {
+        let options = RunOptions::default().with_no_cleanup(false);
+        assert!(!options.keep_partial_files);
+        let options = options.with_no_cleanup(true);
+        assert!(options.keep_partial_files);
+    }
+
+    #[test]
+    fn test_with_max_download_size() {
+        let settings = AppSettings::default().with_max_download_size(None);
+        assert_eq!(settings.ollama_library.max_download_size, None);
+        let settings = settings.with_max_download_size(Some(1000));
+        assert_eq!(settings.ollama_library.max_download_size, Some(1000));
+    }
+
+    #[test]
+    fn test_with_min_free_after() {
+        let options = RunOptions::default().with_min_free_after(None);
+        assert_eq!(options.min_free_after, 0);
+        let options = options.with_min_free_after(Some(1 << 30));
+        assert_eq!(options.min_free_after, 1 << 30);
+    }
+
+    #[test]
+    fn test_with_base_url_overrides() {
+        let settings = AppSettings::default()
+            .with_registry_base_url(Some("https://registry.test/v2/library/".to_string()))
+            .with_library_base_url(None);
+        assert_eq!(
+            settings.ollama_library.registry_base_url,
+            "https://registry.test/v2/library/"
+        );
+        assert_eq!(
+            settings.ollama_library.library_base_url,
+            "https://ollama.com/library/"
+        );
+    }
+
+    #[test]
+    fn test_value_at() {
+        let settings = AppSettings::default();
+        assert_eq!(
+            settings.value_at("ollama_library.models_path"),
+            Some(json!("~/.ollama/models"))
+        );
+        assert_eq!(
+            settings.value_at("ollama_server.url"),
+            Some(json!(settings.ollama_server.url))
+        );
+        assert!(settings.value_at("ollama_server").unwrap().is_object());
+        assert_eq!(settings.value_at("ollama_library.no_such_setting"), None);
+        assert_eq!(settings.value_at("ollama_library.models_path.deeper"), None);
+    }
+
     #[test]
     fn test_default_ollama_library() {
         let library = OllamaLibrary::default();
@@ -424,11 +1828,41 @@ mod tests {
         assert_eq!(library.library_base_url, "https://ollama.com/library/");
         assert_eq!(library.verify_ssl, true);
         assert_eq!(library.timeout, 120.0);
+        assert_eq!(library.pagination_retries, 3);
+        assert_eq!(library.max_download_size, None);
+        assert_eq!(library.blob_timeout, None);
+        assert_eq!(library.download_deadline, None);
+        assert_eq!(library.max_bandwidth_bytes_per_sec, 0);
+        assert_eq!(library.hf_manifest_host, "hf.co");
+        assert_eq!(library.progress_refresh_hz, 20);
+        assert_eq!(library.local_address, None);
+        assert_eq!(library.stall_timeout_secs, None);
+        assert_eq!(library.min_throughput_bytes_per_sec, 0);
+        assert_eq!(library.min_free_inodes, 1000);
+        assert_eq!(library.library_request_timeout, None);
+        assert_eq!(library.progress_template, None);
+        assert_eq!(library.failure_log_entries, 0);
+    }
+
+    #[test]
+    fn test_temp_dir_defaults_to_none() {
+        assert_eq!(OllamaLibrary::default().temp_dir, None);
+
+        let mut ollama_library = serde_json::to_value(OllamaLibrary::default()).unwrap();
+        ollama_library.as_object_mut().unwrap().remove("temp_dir");
+        let content = json!({
+            "ollama_server": OllamaServer::default(),
+            "ollama_library": ollama_library,
+        })
+        .to_string();
+        let settings = AppSettings::load_settings_lenient(&content).unwrap();
+        assert_eq!(settings.ollama_library.temp_dir, None);
     }
 
     #[test]
     fn test_default_app_settings() {
         let settings = AppSettings::default();
+        assert_eq!(settings.schema_version, SETTINGS_SCHEMA_VERSION);
         assert_eq!(settings.ollama_server.url, "http://localhost:11434/");
         assert_eq!(settings.ollama_library.models_path, "~/.ollama/models");
     }
@@ -456,7 +1890,18 @@ mod tests {
                 "registry_base_url": "https://registry.test.com/",
                 "library_base_url": "https://library.test.com/",
                 "verify_ssl": false,
-                "timeout": 60.0
+                "timeout": 60.0,
+                "pagination_retries": 5,
+                "extra_headers": {"X-Team": "models"},
+                "max_bandwidth_bytes_per_sec": 0,
+                "hf_manifest_host": "huggingface.co",
+                "progress_refresh_hz": 5,
+                "local_address": "::1",
+                "min_throughput_bytes_per_sec": 1024,
+                "min_free_inodes": 0,
+                "library_request_timeout": 300.0,
+                "progress_template": "{msg} {wide_bar} {percent}%",
+                "failure_log_entries": 20
             }
         }"#;
 
@@ -466,6 +1911,197 @@ mod tests {
         assert_eq!(settings.ollama_library.models_path, "/test/path");
         assert_eq!(settings.ollama_library.verify_ssl, false);
         assert_eq!(settings.ollama_library.timeout, 60.0);
+        assert_eq!(settings.ollama_library.pagination_retries, 5);
+        assert_eq!(settings.ollama_library.hf_manifest_host, "huggingface.co");
+        assert_eq!(settings.ollama_library.progress_refresh_hz, 5);
+        assert_eq!(
+            settings.ollama_library.local_address,
+            Some("::1".parse().unwrap())
+        );
+        assert_eq!(settings.ollama_library.stall_timeout_secs, None);
+        assert_eq!(settings.ollama_library.min_throughput_bytes_per_sec, 1024);
+        assert_eq!(settings.ollama_library.min_free_inodes, 0);
+        assert_eq!(settings.ollama_library.library_request_timeout, Some(300.0));
+        assert_eq!(
+            settings.ollama_library.progress_template.as_deref(),
+            Some("{msg} {wide_bar} {percent}%")
+        );
+        assert_eq!(settings.ollama_library.failure_log_entries, 20);
+        assert_eq!(
+            settings.ollama_library.extra_headers.get("X-Team"),
+            Some(&"models".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extra_header_map() {
+        let mut library = OllamaLibrary::default();
+        assert!(library.extra_header_map().unwrap().is_empty());
+
+        library
+            .extra_headers
+            .insert("X-Api-Key".to_string(), "secret".to_string());
+        let headers = library.extra_header_map().unwrap();
+        assert_eq!(headers.get("x-api-key").unwrap(), "secret");
+
+        library
+            .extra_headers
+            .insert("Bad Header".to_string(), "value".to_string());
+        assert!(library.extra_header_map().is_err());
+
+        library.extra_headers.remove("Bad Header");
+        library
+            .extra_headers
+            .insert("X-Team".to_string(), "line\nbreak".to_string());
+        assert!(library.extra_header_map().is_err());
+    }
+
+    #[test]
+    fn test_redacted_extra_headers() {
+        let mut library = OllamaLibrary::default();
+        library
+            .extra_headers
+            .insert("Authorization".to_string(), "Bearer secret".to_string());
+        library
+            .extra_headers
+            .insert("X-Api-Key".to_string(), "secret".to_string());
+        library
+            .extra_headers
+            .insert("X-Team".to_string(), "models".to_string());
+
+        let redacted = library.redacted_extra_headers();
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("Authorization: <redacted>"));
+        assert!(redacted.contains("X-Team: models"));
+    }
+
+    #[test]
+    fn test_load_settings_rejects_invalid_extra_headers() {
+        let test_file = "target/test_invalid_extra_headers.json";
+        let mut settings = AppSettings::default();
+        settings
+            .ollama_library
+            .extra_headers
+            .insert("Bad Header".to_string(), "value".to_string());
+        settings.save_settings(test_file).unwrap();
+
+        let result = AppSettings::load_settings(test_file);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_validate_local_address_family() {
+        let mut settings = AppSettings::default();
+        settings.ollama_library.local_address = Some("192.0.2.1".parse().unwrap());
+        assert!(settings.validate().is_ok());
+        let options = RunOptions::default();
+        assert!(options.validate(&settings.ollama_library).is_ok());
+
+        let options = options.with_address_family(Some(AddressFamily::Ipv4));
+        assert!(options.validate(&settings.ollama_library).is_ok());
+
+        let options = options.with_address_family(Some(AddressFamily::Ipv6));
+        let error = options.validate(&settings.ollama_library).unwrap_err();
+        assert!(error.contains("not an IPv6 address"), "{}", error);
+    }
+
+    #[test]
+    fn test_validate_durations() {
+        assert!(AppSettings::default().validate().is_ok());
+
+        for timeout in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+            let mut settings = AppSettings::default();
+            settings.ollama_library.timeout = timeout;
+            let error = settings.validate().unwrap_err();
+            assert!(error.contains("ollama_library.timeout"), "{}", error);
+        }
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.blob_timeout = Some(0.0);
+        assert!(settings.validate().unwrap_err().contains("blob_timeout"));
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.library_request_timeout = Some(-1.0);
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("library_request_timeout")
+        );
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.stall_timeout_secs = Some(f64::NAN);
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("stall_timeout_secs")
+        );
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.stall_timeout_secs = Some(30.0);
+        settings.ollama_library.min_throughput_bytes_per_sec = 2048;
+        assert!(settings.validate().is_ok());
+        settings.ollama_library.max_bandwidth_bytes_per_sec = 1024;
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("min_throughput_bytes_per_sec")
+        );
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.progress_refresh_hz = 0;
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("progress_refresh_hz")
+        );
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.download_deadline = Some(-1.0);
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("download_deadline")
+        );
+    }
+
+    #[test]
+    fn test_validate_manifest_host() {
+        assert!(validate_manifest_host("hf.co").is_ok());
+        assert!(validate_manifest_host("huggingface.co").is_ok());
+        assert!(validate_manifest_host("hf-mirror.example").is_ok());
+        assert!(validate_manifest_host("").is_err());
+        assert!(validate_manifest_host("..").is_err());
+        assert!(validate_manifest_host("hf.co/models").is_err());
+        assert!(validate_manifest_host("hf co").is_err());
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.hf_manifest_host = "../outside".to_string();
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("hf_manifest_host")
+        );
+    }
+
+    #[test]
+    fn test_load_settings_rejects_invalid_timeout() {
+        let test_file = "target/test_invalid_timeout.json";
+        let mut settings = AppSettings::default();
+        settings.ollama_library.timeout = -5.0;
+        settings.save_settings(test_file).unwrap();
+
+        let result = AppSettings::load_settings(test_file);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(test_file).unwrap();
     }
 
     #[test]
@@ -592,6 +2228,56 @@ mod tests {
         ); // default
         assert_eq!(settings.ollama_library.verify_ssl, true); // default
         assert_eq!(settings.ollama_library.timeout, 120.0); // default
+        assert_eq!(settings.ollama_library.pagination_retries, 3); // default
+
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_load_settings_migrates_unversioned_file() {
+        let test_file = "target/test_migrate_unversioned.json";
+        let json_v1 = r#"{
+            "ollama_server": {
+                "url": "http://custom:8080/",
+                "api_key": null,
+                "remove_downloaded_on_error": true,
+                "check_model_presence": true
+            },
+            "ollama_library": {
+                "models_path": "/custom/models",
+                "registry_base_url": "https://registry.ollama.ai/v2/library/",
+                "library_base_url": "https://ollama.com/library/",
+                "verify_ssl": true,
+                "timeout": 120.0
+            }
+        }"#;
+        fs::write(test_file, json_v1).unwrap();
+
+        let settings = AppSettings::load_settings(test_file).unwrap();
+        assert_eq!(settings.schema_version, SETTINGS_SCHEMA_VERSION);
+        assert_eq!(settings.ollama_library.models_path, "/custom/models");
+
+        // The file is rewritten with the current schema version and the new fields
+        let rewritten: Value =
+            serde_json::from_str(&fs::read_to_string(test_file).unwrap()).unwrap();
+        assert_eq!(rewritten["schema_version"], SETTINGS_SCHEMA_VERSION);
+        assert_eq!(rewritten["ollama_library"]["pagination_retries"], 3);
+        assert_eq!(rewritten["ollama_server"]["url"], "http://custom:8080/");
+
+        fs::remove_file(test_file).unwrap();
+    }
+
+    #[test]
+    fn test_load_settings_rejects_newer_schema_version() {
+        let test_file = "target/test_newer_schema_version.json";
+        let settings = AppSettings {
+            schema_version: SETTINGS_SCHEMA_VERSION + 1,
+            ..Default::default()
+        };
+        settings.save_settings(test_file).unwrap();
+
+        let result = AppSettings::load_settings(test_file);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
 
         fs::remove_file(test_file).unwrap();
     }
@@ -630,4 +2316,54 @@ mod tests {
 
         fs::remove_file(test_file).unwrap();
     }
+
+    #[test]
+    fn test_config_dir_fallbacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let project_dir = Some(PathBuf::from("/home/user/.config/odir"));
+        let env = |vars: Vec<(&'static str, std::ffi::OsString)>| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.clone())
+            }
+        };
+
+        // ODIR_CONFIG_DIR takes precedence, and the settings file is created inside it
+        let config_dir = resolve_config_dir(
+            env(vec![("ODIR_CONFIG_DIR", dir.path().join("odir").into())]),
+            project_dir.clone(),
+        )
+        .unwrap();
+        assert_eq!(config_dir, dir.path().join("odir"));
+        assert_eq!(
+            settings_file_path_in(&config_dir, None).unwrap(),
+            dir.path().join("odir/settings.json")
+        );
+        assert_eq!(
+            settings_file_path_in(&config_dir, Some("work")).unwrap(),
+            dir.path().join("odir/profiles/work.json")
+        );
+        assert!(config_dir.join("profiles").is_dir());
+        assert!(config_dir.is_dir());
+
+        assert_eq!(
+            resolve_config_dir(
+                env(vec![("ODIR_CONFIG_DIR", "".into())]),
+                project_dir.clone()
+            ),
+            project_dir
+        );
+
+        // Without a home directory, XDG_CONFIG_HOME is used if it is absolute
+        assert_eq!(
+            resolve_config_dir(env(vec![("XDG_CONFIG_HOME", "/xdg".into())]), None),
+            Some(PathBuf::from("/xdg/odir"))
+        );
+        assert_eq!(
+            resolve_config_dir(env(vec![("XDG_CONFIG_HOME", "xdg".into())]), None),
+            None
+        );
+        assert_eq!(resolve_config_dir(env(vec![]), None), None);
+    }
 }
@@ -0,0 +1,664 @@
+//! Model archives for the Ollama Downloader in Rust (ODIR),
+//! exporting a downloaded model as a tar archive of its manifest and BLOBs,
+//! and importing such an archive into a models directory, verifying every BLOB.
+use crate::config::PreserveMtime;
+use crate::downloader::manifest::{ImageManifest, blob_file_name};
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use crate::downloader::utils::{
+    Ownership, apply_blob_mtime, ensure_ownership, ensure_ownership_for_dir_tree,
+    verify_blob_digest, write_file_atomically,
+};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use log::{debug, error, info, warn};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+/// The first two bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Largest manifest accepted from an archive, far above the few KiB of a real manifest, so
+/// that a crafted archive cannot make the import read an unbounded entry into memory
+const MAX_MANIFEST_ENTRY_SIZE: u64 = 4 << 20;
+
+/// Outcome of exporting a model
+#[derive(Debug)]
+pub struct ExportSummary {
+    /// Path of the manifest in the archive, e.g., manifests/registry.ollama.ai/library/llama3.1/8b
+    pub manifest: String,
+
+    /// Number of BLOBs in the archive
+    pub blobs: usize,
+
+    /// Total size of the BLOBs in bytes
+    pub total_bytes: u64,
+}
+
+/// Find the manifest of a downloaded model in a models directory.
+///
+/// The model may be given as {model}:{tag} for Ollama library models, as
+/// {username}/{repository}:{quantisation} for Hugging Face models, or with the
+/// registry host, e.g., registry.ollama.ai/library/llama3.1:8b. If no tag is
+/// given, 'latest' is assumed.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `model_tag` - The model to find
+///
+/// # Returns
+/// * `Result<PathBuf>` - Path to the manifest, relative to the models directory
+pub fn find_local_manifest(models_root: &Path, model_tag: &str) -> Result<PathBuf> {
+    let (name, tag) = match model_tag.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => (name, tag),
+        _ => (model_tag, "latest"),
+    };
+    if name.is_empty() || tag.is_empty() || !is_safe_relative_path(Path::new(name)) {
+        return Err(DownloaderError::InvalidIdentifier(model_tag.to_string()));
+    }
+
+    let mut found = model_dir_candidates(models_root, name)?
+        .into_iter()
+        .map(|candidate| candidate.join(tag))
+        .filter(|candidate| models_root.join(candidate).is_file());
+    let manifest = found
+        .next()
+        .ok_or_else(|| DownloaderError::ModelNotFound(model_tag.to_string()))?;
+    if let Some(other) = found.next() {
+        warn!(
+            "Model {} was downloaded from several registries, using {:?} rather than {:?}",
+            model_tag, manifest, other
+        );
+    }
+    Ok(manifest)
+}
+
+/// Find the directory holding the manifests of all downloaded tags of a model.
+///
+/// The model may be given as {model} for Ollama library models, as {username}/{repository}
+/// for Hugging Face models, or with the registry host, e.g., registry.ollama.ai/library/llama3.1.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `model` - The model to find, without a tag
+///
+/// # Returns
+/// * `Result<PathBuf>` - Path to the directory, relative to the models directory
+pub fn find_local_model_dir(models_root: &Path, model: &str) -> Result<PathBuf> {
+    if model.is_empty() || model.contains(':') || !is_safe_relative_path(Path::new(model)) {
+        return Err(DownloaderError::InvalidIdentifier(model.to_string()));
+    }
+
+    let mut found = model_dir_candidates(models_root, model)?
+        .into_iter()
+        .filter(|candidate| models_root.join(candidate).is_dir());
+    let model_dir = found
+        .next()
+        .ok_or_else(|| DownloaderError::ModelNotFound(model.to_string()))?;
+    if let Some(other) = found.next() {
+        warn!(
+            "Model {} was downloaded from several registries, using {:?} rather than {:?}",
+            model, model_dir, other
+        );
+    }
+    Ok(model_dir)
+}
+
+/// Get the directories, relative to the models directory, that may hold the manifests of
+/// a model: one per registry host in the models directory, unless the model names its host.
+fn model_dir_candidates(models_root: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    if name.matches('/').count() >= 2 {
+        return Ok(vec![Path::new("manifests").join(name)]);
+    }
+
+    let manifests_dir = models_root.join("manifests");
+    let mut candidates = Vec::new();
+    if manifests_dir.is_dir() {
+        let mut hosts: Vec<PathBuf> = fs::read_dir(&manifests_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        hosts.sort();
+        for host in hosts {
+            let Some(host) = host.file_name() else {
+                continue;
+            };
+            let mut candidate = Path::new("manifests").join(host);
+            if !name.contains('/') {
+                candidate.push("library");
+            }
+            candidates.push(candidate.join(name));
+        }
+    }
+    Ok(candidates)
+}
+
+/// Export a downloaded model as a tar archive, optionally compressed with gzip, keeping
+/// the `blobs` and `manifests` layout of the models directory.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `model_tag` - The model to export
+/// * `output` - Path of the archive, which must not exist yet
+/// * `compress` - Whether to compress the archive with gzip
+///
+/// # Returns
+/// * `Result<ExportSummary>` - What was exported
+pub fn export_model(
+    models_root: &Path,
+    model_tag: &str,
+    output: &Path,
+    compress: bool,
+) -> Result<ExportSummary> {
+    let manifest_path = find_local_manifest(models_root, model_tag)?;
+    let manifest = read_manifest(&models_root.join(&manifest_path))?;
+
+    let blobs: Vec<(String, u64)> =
+        std::iter::once((&manifest.config.digest, manifest.config.size))
+            .chain(
+                manifest
+                    .unique_layers()
+                    .into_iter()
+                    .map(|layer| (&layer.digest, layer.size)),
+            )
+            .map(|(digest, size)| (blob_file_name(digest), size))
+            .collect();
+    for (blob, _) in &blobs {
+        if !models_root.join("blobs").join(blob).is_file() {
+            return Err(DownloaderError::Other(format!(
+                "BLOB {} of model {} is missing from the models directory",
+                blob, model_tag
+            )));
+        }
+    }
+
+    let file = File::options().write(true).create_new(true).open(output)?;
+    let result = if compress {
+        write_archive(
+            GzEncoder::new(file, Compression::default()),
+            models_root,
+            &manifest_path,
+            &blobs,
+        )
+        .and_then(|encoder| encoder.finish().map_err(DownloaderError::from))
+        .map(|_| ())
+    } else {
+        write_archive(file, models_root, &manifest_path, &blobs).map(|_| ())
+    };
+    if let Err(e) = result {
+        if let Err(remove_error) = fs::remove_file(output) {
+            warn!(
+                "Failed to remove incomplete archive {:?}: {}",
+                output, remove_error
+            );
+        }
+        return Err(e);
+    }
+
+    let manifest = manifest_path.to_string_lossy().replace('\\', "/");
+    info!("Exported {} to {:?}", manifest, output);
+    Ok(ExportSummary {
+        manifest,
+        blobs: blobs.len(),
+        total_bytes: blobs.iter().map(|(_, size)| size).sum(),
+    })
+}
+
+/// Write the BLOBs and then the manifest of a model into a tar archive.
+fn write_archive<W: Write>(
+    writer: W,
+    models_root: &Path,
+    manifest_path: &Path,
+    blobs: &[(String, u64)],
+) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for (blob, _) in blobs {
+        let relative = Path::new("blobs").join(blob);
+        debug!("Adding {:?} to archive", relative);
+        builder.append_path_with_name(models_root.join(&relative), &relative)?;
+    }
+    builder.append_path_with_name(models_root.join(manifest_path), manifest_path)?;
+    Ok(builder.into_inner()?)
+}
+
+/// Import a model archive created by `export_model` into a models directory.
+///
+/// The digest of every BLOB is verified as it is extracted, and a BLOB that does not
+/// match its file name fails the import. Manifests are only written once all BLOBs they
+/// reference are in place, so a failed import never leaves a model that Ollama would list
+/// but cannot load. A manifest that already exists with other content is only replaced if
+/// forced, and the import fails before extracting anything else otherwise.
+///
+/// # Arguments
+/// * `archive` - Path of the archive, compressed with gzip or not
+/// * `models_root` - Path to the Ollama models directory
+/// * `models_dir_ownership` - Ownership to apply to the imported files when running as root
+/// * `preserve_mtime` - Modification time to give the imported BLOBs, if any, where the
+///   source time is the one recorded in the archive
+/// * `force` - Whether existing manifests with other content are replaced
+///
+/// # Returns
+/// * `Result<Vec<String>>` - Paths of the imported manifests, relative to the models directory
+pub fn import_model(
+    archive: &Path,
+    models_root: &Path,
+    models_dir_ownership: Option<Ownership>,
+    preserve_mtime: Option<PreserveMtime>,
+    force: bool,
+) -> Result<Vec<String>> {
+    let mut magic = [0u8; 2];
+    let is_gzip = File::open(archive)?.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+
+    let file = File::open(archive)?;
+    if is_gzip {
+        import_entries(
+            GzDecoder::new(file),
+            models_root,
+            models_dir_ownership,
+            preserve_mtime,
+            force,
+        )
+    } else {
+        import_entries(
+            file,
+            models_root,
+            models_dir_ownership,
+            preserve_mtime,
+            force,
+        )
+    }
+}
+
+/// Extract the BLOBs of a tar archive and then write its manifests.
+fn import_entries<R: Read>(
+    reader: R,
+    models_root: &Path,
+    models_dir_ownership: Option<Ownership>,
+    preserve_mtime: Option<PreserveMtime>,
+    force: bool,
+) -> Result<Vec<String>> {
+    let blobs_dir = models_root.join("blobs");
+    fs::create_dir_all(&blobs_dir)?;
+
+    let mut manifests: Vec<(PathBuf, String)> = Vec::new();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        if !is_safe_relative_path(&path) {
+            return Err(invalid_entry(&path));
+        }
+
+        let mut components = path.components();
+        match components.next().map(|c| c.as_os_str()) {
+            Some(dir) if dir == "blobs" => {
+                let blob = components.as_path();
+                let file_name = blob.to_str().filter(|name| !name.contains('/'));
+                let Some(named_digest) = file_name.and_then(|name| name.strip_prefix("sha256-"))
+                else {
+                    return Err(invalid_entry(&path));
+                };
+                let source_mtime = entry
+                    .header()
+                    .mtime()
+                    .ok()
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+                let target = extract_blob(
+                    &mut entry,
+                    &format!("sha256:{}", named_digest),
+                    &blobs_dir,
+                    models_root,
+                    models_dir_ownership,
+                )?;
+                if let Some(preserve_mtime) = preserve_mtime {
+                    apply_blob_mtime(&target, preserve_mtime, source_mtime)?;
+                }
+            }
+            Some(dir) if dir == "manifests" => {
+                let mut content = String::new();
+                entry
+                    .by_ref()
+                    .take(MAX_MANIFEST_ENTRY_SIZE + 1)
+                    .read_to_string(&mut content)?;
+                if content.len() as u64 > MAX_MANIFEST_ENTRY_SIZE {
+                    return Err(DownloaderError::InvalidManifest(format!(
+                        "Manifest {:?} in the archive is larger than {} bytes",
+                        path, MAX_MANIFEST_ENTRY_SIZE
+                    )));
+                }
+                let target = models_root.join(&path);
+                if !force
+                    && target.is_file()
+                    && fs::read_to_string(&target).ok().as_deref() != Some(content.as_str())
+                {
+                    return Err(DownloaderError::Other(format!(
+                        "Manifest {:?} already exists with other content; use --force to replace it",
+                        target
+                    )));
+                }
+                manifests.push((path, content));
+            }
+            _ => return Err(invalid_entry(&path)),
+        }
+    }
+
+    if manifests.is_empty() {
+        return Err(DownloaderError::Other(
+            "The archive does not contain any manifest".to_string(),
+        ));
+    }
+
+    let mut imported = Vec::new();
+    for (path, content) in manifests {
+        let manifest = ImageManifest::parse(&content)
+            .inspect_err(|_| error!("Failed to import the manifest {:?}", path))?;
+        let missing = std::iter::once(&manifest.config.digest)
+            .chain(
+                manifest
+                    .unique_layers()
+                    .into_iter()
+                    .map(|layer| &layer.digest),
+            )
+            .map(|digest| blob_file_name(digest))
+            .find(|blob| !blobs_dir.join(blob).is_file());
+        if let Some(blob) = missing {
+            return Err(DownloaderError::Other(format!(
+                "BLOB {} referenced by manifest {:?} is missing from the archive",
+                blob, path
+            )));
+        }
+
+        let target = models_root.join(&path);
+        if target.is_file() {
+            warn!("Replacing the existing manifest {:?}", target);
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+            if let Some(ownership) = models_dir_ownership {
+                ensure_ownership_for_dir_tree(models_root, parent, ownership);
+            }
+        }
+        write_file_atomically(&target, content.as_bytes())?;
+        if let Some(ownership) = models_dir_ownership {
+            ensure_ownership(models_root, &target, ownership);
+        }
+        info!("Imported manifest {:?}", target);
+        imported.push(path.to_string_lossy().replace('\\', "/"));
+    }
+
+    Ok(imported)
+}
+
+/// Extract a BLOB into the BLOBs directory, verifying its digest on the way, and get its path.
+fn extract_blob<R: Read>(
+    entry: &mut R,
+    named_digest: &str,
+    blobs_dir: &Path,
+    models_root: &Path,
+    models_dir_ownership: Option<Ownership>,
+) -> Result<PathBuf> {
+    let mut temp_file = NamedTempFile::new_in(blobs_dir)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let bytes_read = entry.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+        temp_file.write_all(&buffer[..bytes_read])?;
+    }
+
+    let computed_digest = format!("{:x}", hasher.finalize());
+    verify_blob_digest(named_digest, &computed_digest, true)?;
+
+    let target = blobs_dir.join(blob_file_name(named_digest));
+    temp_file
+        .persist(&target)
+        .map_err(|e| DownloaderError::IoError(e.error))?;
+    if let Some(ownership) = models_dir_ownership {
+        ensure_ownership(models_root, &target, ownership);
+    }
+    debug!("Extracted BLOB {:?}", target);
+    Ok(target)
+}
+
+/// Read and parse a manifest file.
+fn read_manifest(path: &Path) -> Result<ImageManifest> {
+    let content = fs::read_to_string(path)?;
+    ImageManifest::parse(&content)
+}
+
+/// Check that a path is relative and does not climb out of the directory it is joined to.
+fn is_safe_relative_path(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn invalid_entry(path: &Path) -> DownloaderError {
+    DownloaderError::Other(format!(
+        "Unexpected entry {:?} in the archive, which should only contain blobs and manifests",
+        path
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lay out a model with a configuration and a model BLOB in a models directory
+    fn create_model(models_root: &Path) {
+        let blobs_dir = models_root.join("blobs");
+        fs::create_dir_all(&blobs_dir).unwrap();
+        let mut digests = Vec::new();
+        for content in ["config", "weights"] {
+            let digest = format!("{:x}", Sha256::digest(content.as_bytes()));
+            fs::write(blobs_dir.join(format!("sha256-{}", digest)), content).unwrap();
+            digests.push(digest);
+        }
+
+        let manifest_dir = models_root.join("manifests/registry.test/library/model");
+        fs::create_dir_all(&manifest_dir).unwrap();
+        fs::write(
+            manifest_dir.join("latest"),
+            format!(
+                r#"{{
+                    "schemaVersion": 2,
+                    "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                    "config": {{"mediaType": "application/vnd.docker.container.image.v1+json", "size": 6, "digest": "sha256:{}"}},
+                    "layers": [{{"mediaType": "application/vnd.ollama.image.model", "size": 7, "digest": "sha256:{}"}}]
+                }}"#,
+                digests[0], digests[1]
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_local_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        create_model(dir.path());
+        let expected = Path::new("manifests/registry.test/library/model/latest");
+
+        assert_eq!(find_local_manifest(dir.path(), "model").unwrap(), expected);
+        assert_eq!(
+            find_local_manifest(dir.path(), "model:latest").unwrap(),
+            expected
+        );
+        assert_eq!(
+            find_local_manifest(dir.path(), "registry.test/library/model:latest").unwrap(),
+            expected
+        );
+        assert!(matches!(
+            find_local_manifest(dir.path(), "model:8b"),
+            Err(DownloaderError::ModelNotFound(_))
+        ));
+        assert!(matches!(
+            find_local_manifest(dir.path(), "../model:latest"),
+            Err(DownloaderError::InvalidIdentifier(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_and_import_round_trip() {
+        for compress in [false, true] {
+            let source = tempfile::tempdir().unwrap();
+            create_model(source.path());
+            let archive_dir = tempfile::tempdir().unwrap();
+            let archive = archive_dir.path().join("model.tar");
+
+            let summary = export_model(source.path(), "model", &archive, compress).unwrap();
+            assert_eq!(
+                summary.manifest,
+                "manifests/registry.test/library/model/latest"
+            );
+            assert_eq!(summary.blobs, 2);
+            assert_eq!(summary.total_bytes, 13);
+
+            // The archive is never overwritten
+            assert!(export_model(source.path(), "model", &archive, compress).is_err());
+
+            let target = tempfile::tempdir().unwrap();
+            let imported = import_model(&archive, target.path(), None, None, false).unwrap();
+            assert_eq!(imported, vec![summary.manifest.clone()]);
+            assert_eq!(
+                fs::read(target.path().join(&summary.manifest)).unwrap(),
+                fs::read(source.path().join(&summary.manifest)).unwrap()
+            );
+            assert_eq!(
+                fs::read_dir(target.path().join("blobs")).unwrap().count(),
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn test_import_preserves_blob_mtime() {
+        let source = tempfile::tempdir().unwrap();
+        create_model(source.path());
+        let source_mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for blob in fs::read_dir(source.path().join("blobs")).unwrap() {
+            apply_blob_mtime(
+                &blob.unwrap().path(),
+                PreserveMtime::Source,
+                Some(source_mtime),
+            )
+            .unwrap();
+        }
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive = archive_dir.path().join("model.tar");
+        export_model(source.path(), "model", &archive, false).unwrap();
+
+        for (preserve_mtime, expected) in [
+            (PreserveMtime::Source, source_mtime),
+            (PreserveMtime::Epoch, UNIX_EPOCH),
+        ] {
+            let target = tempfile::tempdir().unwrap();
+            import_model(&archive, target.path(), None, Some(preserve_mtime), false).unwrap();
+            for blob in fs::read_dir(target.path().join("blobs")).unwrap() {
+                let modified = blob.unwrap().metadata().unwrap().modified().unwrap();
+                assert_eq!(modified, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_corrupt_blob() {
+        let source = tempfile::tempdir().unwrap();
+        create_model(source.path());
+        let blob = fs::read_dir(source.path().join("blobs"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive = archive_dir.path().join("model.tar");
+        export_model(source.path(), "model", &archive, false).unwrap();
+
+        // Corrupt the BLOB inside the archive by rewriting it with different content
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        let name = Path::new("blobs").join(blob.file_name().unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(8);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &name, "tampered".as_bytes())
+            .unwrap();
+        builder.finish().unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        assert!(import_model(&archive, target.path(), None, None, false).is_err());
+        assert!(!target.path().join("manifests").exists());
+    }
+
+    #[test]
+    fn test_import_refuses_to_replace_other_manifests() {
+        let source = tempfile::tempdir().unwrap();
+        create_model(source.path());
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive = archive_dir.path().join("model.tar");
+        let summary = export_model(source.path(), "model", &archive, false).unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        import_model(&archive, target.path(), None, None, false).unwrap();
+        // Importing the same manifest again is fine
+        import_model(&archive, target.path(), None, None, false).unwrap();
+
+        let manifest_path = target.path().join(&summary.manifest);
+        fs::write(&manifest_path, "{}").unwrap();
+        let err = import_model(&archive, target.path(), None, None, false).unwrap_err();
+        assert!(err.to_string().contains("--force"), "{}", err);
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), "{}");
+
+        import_model(&archive, target.path(), None, None, true).unwrap();
+        assert_eq!(
+            fs::read(&manifest_path).unwrap(),
+            fs::read(source.path().join(&summary.manifest)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_import_rejects_oversized_manifest() {
+        let archive_dir = tempfile::tempdir().unwrap();
+        let archive = archive_dir.path().join("model.tar");
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        let content = vec![b' '; MAX_MANIFEST_ENTRY_SIZE as usize + 1];
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                "manifests/registry.test/library/model/latest",
+                content.as_slice(),
+            )
+            .unwrap();
+        builder.finish().unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let err = import_model(&archive, target.path(), None, None, false).unwrap_err();
+        assert!(
+            matches!(err, DownloaderError::InvalidManifest(_)),
+            "{}",
+            err
+        );
+        assert!(!target.path().join("manifests").exists());
+    }
+
+    #[test]
+    fn test_is_safe_relative_path() {
+        assert!(is_safe_relative_path(Path::new("blobs/sha256-abc")));
+        assert!(!is_safe_relative_path(Path::new("../blobs/sha256-abc")));
+        assert!(!is_safe_relative_path(Path::new("/blobs/sha256-abc")));
+    }
+}
@@ -0,0 +1,405 @@
+//! Batch downloads for the Ollama Downloader in Rust (ODIR),
+//! downloading several models, or listing their tags, one after another and reporting
+//! the outcome of each, so that one failing model does not obscure which ones succeeded.
+use crate::downloader::events::emit_event;
+use crate::downloader::failures::record_failure;
+use crate::downloader::model_downloader::{DownloadSummary, DownloaderError, Result};
+use log::{error, warn};
+use serde_json::json;
+use std::io::Read;
+use std::path::Path;
+
+/// Exit code of a batch in which every model was already up to date, so that scripts can
+/// tell that nothing was downloaded
+pub const UP_TO_DATE_EXIT_CODE: i32 = 4;
+
+/// Outcome of downloading a batch of models
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// The result of each attempted download, in the order of the batch
+    pub results: Vec<(String, Result<DownloadSummary>)>,
+
+    /// Models that were not attempted because the batch was interrupted
+    pub skipped: Vec<String>,
+}
+
+impl BatchReport {
+    /// Check whether every model in the batch was downloaded.
+    ///
+    /// # Returns
+    /// * `bool` - True if no download failed and none was skipped
+    pub fn all_succeeded(&self) -> bool {
+        self.skipped.is_empty() && self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// Get the process exit code that reports the outcome of the batch.
+    ///
+    /// # Returns
+    /// * `i32` - 0 if all models were downloaded, [`UP_TO_DATE_EXIT_CODE`] if all of them
+    ///   were already up to date, the exit code of the first failure if any download failed,
+    ///   or 1 if models were only skipped
+    pub fn exit_code(&self) -> i32 {
+        if self.all_succeeded() {
+            let up_to_date = !self.results.is_empty()
+                && self
+                    .results
+                    .iter()
+                    .all(|(_, result)| result.as_ref().is_ok_and(|summary| summary.up_to_date));
+            return if up_to_date { UP_TO_DATE_EXIT_CODE } else { 0 };
+        }
+        self.results
+            .iter()
+            .find_map(|(_, result)| result.as_ref().err())
+            .map_or(1, |e| e.exit_code())
+    }
+
+    /// Format a table with a line per model, marking each as downloaded, failed or skipped.
+    ///
+    /// # Returns
+    /// * `String` - The summary table
+    pub fn summary(&self) -> String {
+        let width = self
+            .results
+            .iter()
+            .map(|(identifier, _)| identifier)
+            .chain(&self.skipped)
+            .map(|identifier| identifier.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut lines: Vec<String> = self
+            .results
+            .iter()
+            .map(|(identifier, result)| match result {
+                Ok(summary) if summary.up_to_date => {
+                    format!("✓ {:width$}  already up to date", identifier)
+                }
+                Ok(summary) => format!("✓ {:width$}  downloaded, {}", identifier, summary.facts()),
+                Err(e) => format!("✗ {:width$}  failed: {}", identifier, e),
+            })
+            .collect();
+        lines.extend(
+            self.skipped
+                .iter()
+                .map(|identifier| format!("- {:width$}  skipped", identifier)),
+        );
+        lines.join("\n")
+    }
+}
+
+/// Download a batch of models one after another.
+///
+/// A failed download does not stop the batch, since each download cleans up its own
+/// partially downloaded files. An interrupt stops the batch, and the models that were
+/// not started yet are reported as skipped.
+///
+/// # Arguments
+/// * `identifiers` - The identifiers of the models to download
+/// * `download` - Downloads a single model
+///
+/// # Returns
+/// * `BatchReport` - The outcome of each download
+pub fn download_batch<F>(identifiers: &[String], mut download: F) -> BatchReport
+where
+    F: FnMut(&str) -> Result<DownloadSummary>,
+{
+    let mut report = BatchReport::default();
+    for (index, identifier) in identifiers.iter().enumerate() {
+        if crate::signal_handler::is_interrupted() {
+            warn!(
+                "Batch interrupted, skipping {} remaining model(s)",
+                identifiers.len() - index
+            );
+            report.skipped.extend_from_slice(&identifiers[index..]);
+            break;
+        }
+
+        let result = download(identifier);
+        if let Err(e) = &result {
+            error!("Error downloading model '{}': {}", identifier, e);
+            emit_event(
+                "model_failed",
+                json!({"identifier": identifier, "error": e.to_json()["error"]}),
+            );
+            record_failure(identifier, e);
+        }
+        report.results.push((identifier.clone(), result));
+    }
+    report
+}
+
+/// Outcome of listing the tags of a batch of models
+#[derive(Debug, Default)]
+pub struct TagListingReport {
+    /// The tags of each model that could be listed, in the order of the batch
+    pub listed: Vec<(String, Vec<String>)>,
+
+    /// The models whose tags could not be listed, with the reason
+    pub failed: Vec<(String, DownloaderError)>,
+
+    /// Models that were not attempted because the batch stopped early
+    pub skipped: Vec<String>,
+}
+
+impl TagListingReport {
+    /// Get the process exit code that reports the outcome of the batch.
+    ///
+    /// # Arguments
+    /// * `keep_going` - Whether the batch continued past failures, in which case it only
+    ///   fails if no model could be listed
+    ///
+    /// # Returns
+    /// * `i32` - 0 on success, or the exit code of the first failure
+    pub fn exit_code(&self, keep_going: bool) -> i32 {
+        let failed = if keep_going {
+            self.listed.is_empty()
+        } else {
+            !self.failed.is_empty() || !self.skipped.is_empty()
+        };
+        if !failed {
+            return 0;
+        }
+        self.failed.first().map_or(1, |(_, e)| e.exit_code())
+    }
+
+    /// Format a summary of the models whose tags could not be listed.
+    ///
+    /// # Returns
+    /// * `String` - A line per failed or skipped model
+    pub fn failure_summary(&self) -> String {
+        self.failed
+            .iter()
+            .map(|(identifier, e)| format!("✗ {}  failed: {}", identifier, e))
+            .chain(
+                self.skipped
+                    .iter()
+                    .map(|identifier| format!("- {}  skipped", identifier)),
+            )
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Check whether listing the tags of the other models is worthwhile after this error,
+/// which is the case if it only concerns the model itself.
+fn is_per_model_error(error: &DownloaderError) -> bool {
+    matches!(
+        error,
+        DownloaderError::ModelNotFound(_)
+            | DownloaderError::HttpError(_)
+            | DownloaderError::InvalidIdentifier(_)
+    )
+}
+
+/// List the tags of a batch of models one after another.
+///
+/// By default, the batch stops at the first failure. With `keep_going`, it continues past
+/// models that do not exist or whose listing fails with an HTTP error, but still stops at
+/// other errors, such as an interrupt.
+///
+/// # Arguments
+/// * `identifiers` - The identifiers of the models to list the tags of
+/// * `keep_going` - Whether to continue past models whose tags could not be listed
+/// * `list` - Lists the tags of a single model
+///
+/// # Returns
+/// * `TagListingReport` - The outcome of each listing
+pub fn list_tags_batch<F>(identifiers: &[String], keep_going: bool, mut list: F) -> TagListingReport
+where
+    F: FnMut(&str) -> Result<Vec<String>>,
+{
+    let mut report = TagListingReport::default();
+    for (index, identifier) in identifiers.iter().enumerate() {
+        if crate::signal_handler::is_interrupted() {
+            report.skipped.extend_from_slice(&identifiers[index..]);
+            break;
+        }
+
+        match list(identifier) {
+            Ok(tags) => report.listed.push((identifier.clone(), tags)),
+            Err(e) => {
+                error!("Error listing tags for model '{}': {}", identifier, e);
+                let stop = !keep_going || !is_per_model_error(&e);
+                report.failed.push((identifier.clone(), e));
+                if stop {
+                    report.skipped.extend_from_slice(&identifiers[index + 1..]);
+                    break;
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Parse a list of model identifiers, one per line. Blank lines are ignored, as is
+/// everything from a `#` to the end of a line, so that lists can be commented.
+///
+/// # Arguments
+/// * `content` - The list of identifiers
+///
+/// # Returns
+/// * `Vec<String>` - The identifiers, in the order of the list
+pub fn parse_identifier_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read a list of model identifiers from a file, or from the standard input if the
+/// path is `-`. See [`parse_identifier_list`] for the format.
+///
+/// # Arguments
+/// * `path` - The file to read, or `-` for the standard input
+///
+/// # Returns
+/// * `Result<Vec<String>>` - The identifiers, in the order of the list
+pub fn read_identifier_list(path: &Path) -> Result<Vec<String>> {
+    let content = if path == Path::new("-") {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(path).map_err(|e| {
+            DownloaderError::Other(format!(
+                "Failed to read the list of models from {:?}: {}",
+                path, e
+            ))
+        })?
+    };
+    Ok(parse_identifier_list(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn downloaded(identifier: &str) -> Result<DownloadSummary> {
+        Ok(DownloadSummary::new(
+            identifier,
+            1,
+            1,
+            Duration::from_secs(1),
+        ))
+    }
+
+    fn identifiers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_identifier_list() {
+        let content = "# Models for the lab\nllama3.1:8b\n\n  qwen3:4b  # small one\n#gemma3\r\nuser/repo:Q4_K_M\n";
+        assert_eq!(
+            parse_identifier_list(content),
+            identifiers(&["llama3.1:8b", "qwen3:4b", "user/repo:Q4_K_M"])
+        );
+        assert!(parse_identifier_list("# nothing\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_read_identifier_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("models.txt");
+        std::fs::write(&path, "a:1\n# b:2\nc:3\n").unwrap();
+        assert_eq!(
+            read_identifier_list(&path).unwrap(),
+            identifiers(&["a:1", "c:3"])
+        );
+        assert!(read_identifier_list(&dir.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn test_download_batch_continues_after_failure() {
+        let mut attempted = Vec::new();
+        let report = download_batch(&identifiers(&["a:1", "b:2", "c:3"]), |identifier| {
+            attempted.push(identifier.to_string());
+            if identifier == "b:2" {
+                Err(DownloaderError::ModelNotFound(identifier.to_string()))
+            } else {
+                downloaded(identifier)
+            }
+        });
+
+        assert_eq!(attempted, vec!["a:1", "b:2", "c:3"]);
+        assert!(!report.all_succeeded());
+        assert_eq!(report.exit_code(), 1);
+        let summary = report.summary();
+        assert!(summary.contains("✓ a:1  downloaded, 1 B in 1 BLOB(s), 1.0 s, 1 B/s"));
+        assert!(summary.contains("✗ b:2  failed: Model not found: b:2"));
+        assert!(summary.contains("✓ c:3  downloaded"));
+    }
+
+    #[test]
+    fn test_batch_report_exit_code() {
+        let report = download_batch(&identifiers(&["a:1"]), downloaded);
+        assert!(report.all_succeeded());
+        assert_eq!(report.exit_code(), 0);
+
+        let report = download_batch(&identifiers(&["a:1"]), |_| {
+            Err(DownloaderError::SizeLimitExceeded("too large".to_string()))
+        });
+        assert_eq!(report.exit_code(), 3);
+
+        let report = BatchReport {
+            results: vec![("a:1".to_string(), downloaded("a:1"))],
+            skipped: identifiers(&["b:2"]),
+        };
+        assert_eq!(report.exit_code(), 1);
+        assert!(report.summary().contains("- b:2  skipped"));
+
+        let up_to_date = |identifier: &str| {
+            Ok(DownloadSummary::already_up_to_date(
+                identifier,
+                Duration::ZERO,
+            ))
+        };
+        let report = download_batch(&identifiers(&["a:1", "b:2"]), up_to_date);
+        assert_eq!(report.exit_code(), UP_TO_DATE_EXIT_CODE);
+        assert!(report.summary().contains("✓ a:1  already up to date"));
+
+        let report = download_batch(&identifiers(&["a:1", "b:2"]), |identifier| {
+            if identifier == "a:1" {
+                up_to_date(identifier)
+            } else {
+                downloaded(identifier)
+            }
+        });
+        assert_eq!(report.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_list_tags_batch() {
+        let list = |identifier: &str| match identifier {
+            "missing" => Err(DownloaderError::ModelNotFound(identifier.to_string())),
+            "broken" => Err(DownloaderError::Other("interrupted".to_string())),
+            _ => Ok(vec![format!("{}:latest", identifier)]),
+        };
+
+        // Without --keep-going, the first failure stops the batch
+        let report = list_tags_batch(&identifiers(&["a", "missing", "b"]), false, list);
+        assert_eq!(report.listed.len(), 1);
+        assert_eq!(report.skipped, vec!["b"]);
+        assert_eq!(report.exit_code(false), 1);
+
+        // With --keep-going, models that are not found are skipped over
+        let report = list_tags_batch(&identifiers(&["a", "missing", "b"]), true, list);
+        assert_eq!(report.listed.len(), 2);
+        assert_eq!(report.exit_code(true), 0);
+        assert_eq!(
+            report.failure_summary(),
+            "✗ missing  failed: Model not found: missing"
+        );
+
+        // ... but other errors still stop it
+        let report = list_tags_batch(&identifiers(&["broken", "a"]), true, list);
+        assert_eq!(report.skipped, vec!["a"]);
+        assert_eq!(report.exit_code(true), 1);
+
+        let report = list_tags_batch(&identifiers(&["missing"]), true, list);
+        assert_eq!(report.exit_code(true), 1);
+    }
+}
@@ -0,0 +1,202 @@
+//! Portable model bundles for the Ollama Downloader in Rust (ODIR),
+//! laying out downloaded BLOBs and manifests under a fresh directory
+//! that can be copied into the models directory of another Ollama installation.
+use crate::downloader::manifest::{ImageManifest, blob_file_name};
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the index file written at the top of a bundle
+const INDEX_FILE: &str = "index.json";
+
+/// A model contained in a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleModel {
+    /// Name of the model as Ollama lists it, e.g., registry.ollama.ai/library/llama3.1:8b
+    pub name: String,
+
+    /// Path of the manifest relative to the bundle directory
+    pub manifest: String,
+
+    /// File names of the BLOBs the manifest references, relative to the `blobs` directory
+    pub blobs: Vec<String>,
+}
+
+/// Index of the models contained in a bundle
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleIndex {
+    /// The models in the bundle
+    pub models: Vec<BundleModel>,
+}
+
+/// Prepare a fresh bundle directory with empty `blobs` and `manifests` subdirectories.
+///
+/// # Arguments
+/// * `output_dir` - The bundle directory, which must not exist or be empty
+///
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn prepare_output_dir(output_dir: &Path) -> Result<()> {
+    if output_dir.exists() {
+        if !output_dir.is_dir() {
+            return Err(DownloaderError::Other(format!(
+                "Output path {:?} is not a directory",
+                output_dir
+            )));
+        }
+        if fs::read_dir(output_dir)?.next().is_some() {
+            return Err(DownloaderError::Other(format!(
+                "Output directory {:?} is not empty",
+                output_dir
+            )));
+        }
+    }
+
+    fs::create_dir_all(output_dir.join("blobs"))?;
+    fs::create_dir_all(output_dir.join("manifests"))?;
+    debug!("Prepared bundle directory {:?}", output_dir);
+
+    Ok(())
+}
+
+/// Write an index of all manifests found in a bundle directory.
+///
+/// # Arguments
+/// * `output_dir` - The bundle directory
+///
+/// # Returns
+/// * `Result<PathBuf>` - Path to the written index file
+pub fn write_index(output_dir: &Path) -> Result<PathBuf> {
+    let manifests_dir = output_dir.join("manifests");
+    let mut manifest_files = Vec::new();
+    collect_files(&manifests_dir, &mut manifest_files)?;
+    manifest_files.sort();
+
+    let mut index = BundleIndex::default();
+    for manifest_file in manifest_files {
+        let manifest: ImageManifest = match fs::read_to_string(&manifest_file)
+            .map_err(DownloaderError::from)
+            .and_then(|content| ImageManifest::parse(&content))
+        {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!("Skipping manifest {:?} in bundle: {}", manifest_file, e);
+                continue;
+            }
+        };
+
+        let Ok(relative) = manifest_file.strip_prefix(&manifests_dir) else {
+            continue;
+        };
+        let components: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let Some((tag, repository)) = components.split_last() else {
+            continue;
+        };
+
+        let blobs = std::iter::once(&manifest.config.digest)
+            .chain(manifest.unique_layers().into_iter().map(|l| &l.digest))
+            .map(|digest| blob_file_name(digest))
+            .collect();
+
+        index.models.push(BundleModel {
+            name: format!("{}:{}", repository.join("/"), tag),
+            manifest: format!("manifests/{}", components.join("/")),
+            blobs,
+        });
+    }
+
+    let index_file = output_dir.join(INDEX_FILE);
+    let json = serde_json::to_string_pretty(&index)
+        .map_err(|e| DownloaderError::Other(format!("Failed to serialize bundle index: {}", e)))?;
+    fs::write(&index_file, json)?;
+    info!(
+        "Wrote bundle index of {} model(s) to {:?}",
+        index.models.len(),
+        index_file
+    );
+
+    Ok(index_file)
+}
+
+/// Remove the default temporary directory from a bundle, if it was left empty.
+///
+/// # Arguments
+/// * `output_dir` - The bundle directory
+pub fn remove_empty_temp_dir(output_dir: &Path) {
+    let temp_dir = output_dir.join(".tmp");
+    if temp_dir.is_dir() && fs::remove_dir(&temp_dir).is_ok() {
+        debug!("Removed empty temporary directory {:?}", temp_dir);
+    }
+}
+
+/// Recursively collect all files below a directory.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_output_dir_creates_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("bundle");
+
+        prepare_output_dir(&output_dir).unwrap();
+
+        assert!(output_dir.join("blobs").is_dir());
+        assert!(output_dir.join("manifests").is_dir());
+    }
+
+    #[test]
+    fn test_prepare_output_dir_rejects_non_empty_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("existing"), "data").unwrap();
+
+        assert!(prepare_output_dir(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_write_index() {
+        let dir = tempfile::tempdir().unwrap();
+        prepare_output_dir(dir.path()).unwrap();
+        let manifest_dir = dir.path().join("manifests/registry.test/library/model");
+        fs::create_dir_all(&manifest_dir).unwrap();
+        fs::write(
+            manifest_dir.join("latest"),
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 1, "digest": "sha256:aaa"},
+                "layers": [{"mediaType": "application/vnd.ollama.image.model", "size": 2, "digest": "sha256:bbb"}]
+            }"#,
+        )
+        .unwrap();
+
+        let index_file = write_index(dir.path()).unwrap();
+        let index: BundleIndex =
+            serde_json::from_str(&fs::read_to_string(index_file).unwrap()).unwrap();
+
+        assert_eq!(index.models.len(), 1);
+        assert_eq!(index.models[0].name, "registry.test/library/model:latest");
+        assert_eq!(
+            index.models[0].manifest,
+            "manifests/registry.test/library/model/latest"
+        );
+        assert_eq!(index.models[0].blobs, vec!["sha256-aaa", "sha256-bbb"]);
+    }
+}
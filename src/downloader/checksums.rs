@@ -0,0 +1,117 @@
+//! Checksum files for the Ollama Downloader in Rust (ODIR), recording exactly what was
+//! downloaded, so that a download can be verified again later with standard tools, e.g.,
+//! `sha256sum -c`. Each line holds the hex SHA256 digest of a file, two spaces and its
+//! absolute path: one line for the manifest of each model and one for each of its BLOBs.
+use crate::downloader::manifest::{ImageManifest, blob_file_name};
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use log::info;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// List the checksums of a downloaded model: its manifest, followed by the BLOBs it
+/// references, in manifest order. The sizes of the BLOBs are recorded in the manifest,
+/// whose own checksum covers them.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `manifest_path` - Path of the manifest of the model
+///
+/// # Returns
+/// * `Result<Vec<String>>` - The checksum lines, or an error if the manifest cannot be read
+pub fn model_checksums(models_root: &Path, manifest_path: &Path) -> Result<Vec<String>> {
+    let manifest_json = fs::read_to_string(manifest_path)?;
+    let manifest = ImageManifest::parse(&manifest_json)?;
+
+    let mut lines = vec![checksum_line(
+        &format!("{:x}", Sha256::digest(&manifest_json)),
+        manifest_path,
+    )];
+    for blob in manifest.blobs() {
+        let hex = blob.digest.strip_prefix("sha256:").ok_or_else(|| {
+            DownloaderError::InvalidManifest(format!(
+                "BLOB digest {} is not a SHA256 digest",
+                blob.digest
+            ))
+        })?;
+        let path = models_root.join("blobs").join(blob_file_name(&blob.digest));
+        lines.push(checksum_line(hex, &path));
+    }
+    Ok(lines)
+}
+
+/// Write checksum lines to a file, replacing it if it exists.
+///
+/// # Arguments
+/// * `path` - Path of the checksum file
+/// * `lines` - The checksum lines
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if the file cannot be written
+pub fn write_checksum_file(path: &Path, lines: &[String]) -> Result<()> {
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    fs::write(path, content)?;
+    info!("Wrote {} checksum(s) to {:?}", lines.len(), path);
+    Ok(())
+}
+
+/// Format a checksum line as `sha256sum` does.
+fn checksum_line(hex: &str, path: &Path) -> String {
+    format!("{}  {}", hex, path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_checksums() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_digest = format!("sha256:{}", "a".repeat(64));
+        let layer_digest = format!("sha256:{}", "b".repeat(64));
+        let manifest = format!(
+            r#"{{"schemaVersion":2,"mediaType":"application/vnd.docker.distribution.manifest.v2+json","config":{{"mediaType":"application/vnd.docker.container.image.v1+json","size":10,"digest":"{}"}},"layers":[{{"mediaType":"application/vnd.ollama.image.model","size":20,"digest":"{}"}}]}}"#,
+            config_digest, layer_digest
+        );
+        let manifest_path = dir.path().join("manifest");
+        fs::write(&manifest_path, &manifest).unwrap();
+
+        let lines = model_checksums(dir.path(), &manifest_path).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "{:x}  {}",
+                    Sha256::digest(manifest.as_bytes()),
+                    manifest_path.display()
+                ),
+                format!(
+                    "{}  {}",
+                    "a".repeat(64),
+                    dir.path()
+                        .join("blobs")
+                        .join(blob_file_name(&config_digest))
+                        .display()
+                ),
+                format!(
+                    "{}  {}",
+                    "b".repeat(64),
+                    dir.path()
+                        .join("blobs")
+                        .join(blob_file_name(&layer_digest))
+                        .display()
+                ),
+            ]
+        );
+
+        let checksum_file = dir.path().join("SHA256SUMS");
+        write_checksum_file(&checksum_file, &lines).unwrap();
+        assert_eq!(
+            fs::read_to_string(&checksum_file).unwrap(),
+            format!("{}\n", lines.join("\n"))
+        );
+    }
+}
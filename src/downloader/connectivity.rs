@@ -0,0 +1,244 @@
+//! Connectivity checks for the Ollama Downloader in Rust (ODIR), telling whether the
+//! registries and libraries that models are downloaded from can be reached with the
+//! configured HTTP client, before starting a large download.
+use crate::downloader::utils::TracedRequest;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::time::Instant;
+
+/// Outcome of the TLS handshake with an endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsStatus {
+    /// The endpoint is reached over plain HTTP
+    NotUsed,
+
+    /// The certificate of the endpoint was verified
+    Verified,
+
+    /// The connection succeeded, but certificates are not verified, since verify_ssl is off
+    NotVerified,
+
+    /// The handshake failed, e.g., because the certificate is not trusted
+    Failed,
+
+    /// The endpoint could not be reached, so it is unknown whether the handshake would succeed
+    Unknown,
+}
+
+impl fmt::Display for TlsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            TlsStatus::NotUsed => "not used",
+            TlsStatus::Verified => "verified",
+            TlsStatus::NotVerified => "not verified",
+            TlsStatus::Failed => "failed",
+            TlsStatus::Unknown => "unknown",
+        };
+        f.write_str(status)
+    }
+}
+
+/// Outcome of checking whether an endpoint can be reached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointCheck {
+    /// What the endpoint is, e.g., registry_base_url
+    pub name: String,
+
+    /// The URL requested
+    pub url: String,
+
+    /// The HTTP status code of the response, if any was received
+    pub status: Option<u16>,
+
+    /// Outcome of the TLS handshake
+    pub tls: TlsStatus,
+
+    /// Time from sending the request until the response or the failure, in milliseconds
+    pub round_trip_ms: u64,
+
+    /// Why no response was received, if so
+    pub error: Option<String>,
+}
+
+impl EndpointCheck {
+    /// Whether the endpoint answered with any HTTP status, e.g., 401 from a registry that
+    /// requires a token is an answer too.
+    pub fn is_reachable(&self) -> bool {
+        self.status.is_some()
+    }
+
+    /// One line describing the check, e.g., `✓ registry_base_url  https://...  HTTP 200,
+    /// TLS verified, 85 ms`.
+    pub fn line(&self) -> String {
+        match (self.status, &self.error) {
+            (Some(status), _) => format!(
+                "✓ {}  {}  HTTP {}, TLS {}, {} ms",
+                self.name, self.url, status, self.tls, self.round_trip_ms
+            ),
+            (None, error) => format!(
+                "✗ {}  {}  unreachable after {} ms, TLS {}: {}",
+                self.name,
+                self.url,
+                self.round_trip_ms,
+                self.tls,
+                error.as_deref().unwrap_or("no response")
+            ),
+        }
+    }
+}
+
+/// Check whether an endpoint can be reached, with a HEAD request sent by the given client,
+/// so that the proxy, TLS and network settings the client was built with all apply.
+///
+/// # Arguments
+/// * `client` - The HTTP client, as used for downloads
+/// * `name` - What the endpoint is, e.g., registry_base_url
+/// * `url` - The URL to request
+/// * `verify_ssl` - Whether the client verifies certificates
+///
+/// # Returns
+/// * `EndpointCheck` - The outcome of the check
+pub fn check_endpoint(client: &Client, name: &str, url: &str, verify_ssl: bool) -> EndpointCheck {
+    let is_https = url.starts_with("https://");
+    let started = Instant::now();
+    let result = client.head(url).send_traced();
+    let round_trip_ms = started.elapsed().as_millis() as u64;
+
+    let (status, tls, error) = match result {
+        Ok(response) => {
+            let tls = match (is_https, verify_ssl) {
+                (false, _) => TlsStatus::NotUsed,
+                (true, true) => TlsStatus::Verified,
+                (true, false) => TlsStatus::NotVerified,
+            };
+            (Some(response.status().as_u16()), tls, None)
+        }
+        Err(e) => {
+            let tls = if !is_https {
+                TlsStatus::NotUsed
+            } else if is_tls_error(&e) {
+                TlsStatus::Failed
+            } else {
+                TlsStatus::Unknown
+            };
+            (None, tls, Some(error_chain(&e)))
+        }
+    };
+    EndpointCheck {
+        name: name.to_string(),
+        url: url.to_string(),
+        status,
+        tls,
+        round_trip_ms,
+        error,
+    }
+}
+
+/// Whether an error, or any error it was caused by, comes from TLS, e.g., a certificate
+/// that is not trusted.
+fn is_tls_error(error: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(e) = current {
+        let message = e.to_string().to_lowercase();
+        if ["certificate", "tls", "ssl", "handshake"]
+            .iter()
+            .any(|word| message.contains(word))
+        {
+            return true;
+        }
+        current = e.source();
+    }
+    false
+}
+
+/// Describe an error with the errors it was caused by, which reqwest keeps out of its message.
+fn error_chain(error: &(dyn Error + 'static)) -> String {
+    let mut description = error.to_string();
+    let mut current = error.source();
+    while let Some(e) = current {
+        description.push_str(": ");
+        description.push_str(&e.to_string());
+        current = e.source();
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    #[derive(Debug)]
+    struct TestError(&'static str, Option<Box<TestError>>);
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl Error for TestError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            self.1.as_deref().map(|e| e as &(dyn Error + 'static))
+        }
+    }
+
+    #[test]
+    fn test_is_tls_error() {
+        let tls = TestError(
+            "error sending request",
+            Some(Box::new(TestError(
+                "invalid peer certificate: UnknownIssuer",
+                None,
+            ))),
+        );
+        assert!(is_tls_error(&tls));
+        assert_eq!(
+            error_chain(&tls),
+            "error sending request: invalid peer certificate: UnknownIssuer"
+        );
+        let refused = TestError(
+            "error sending request",
+            Some(Box::new(TestError("Connection refused", None))),
+        );
+        assert!(!is_tls_error(&refused));
+    }
+
+    #[test]
+    fn test_check_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v2/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            stream
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+        let client = Client::new();
+
+        let check = check_endpoint(&client, "registry_base_url", &url, true);
+        assert!(check.is_reachable());
+        assert_eq!(check.status, Some(401));
+        assert_eq!(check.tls, TlsStatus::NotUsed);
+        assert!(check.line().starts_with("✓ registry_base_url"));
+        assert!(check.line().contains("HTTP 401, TLS not used"));
+
+        // Nothing listens on a port whose listener was closed
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/library", closed.local_addr().unwrap());
+        drop(closed);
+        let check = check_endpoint(&client, "library_base_url", &url, true);
+        assert!(!check.is_reachable());
+        assert!(check.error.is_some());
+        assert!(check.line().starts_with("✗ library_base_url"));
+    }
+}
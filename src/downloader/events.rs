@@ -0,0 +1,72 @@
+//! Run events for the Ollama Downloader in Rust (ODIR). With `--ndjson`, everything a run
+//! does, from loading the settings to the outcome of each model, is reported as typed,
+//! newline-delimited JSON events on stderr, alongside the progress events of the BLOBs, so
+//! that frontends wrapping ODIR can follow a whole command in a single stream.
+use log::debug;
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether run events are reported
+static EVENTS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Start reporting run events on stderr for the rest of the run.
+pub fn enable_events() {
+    EVENTS_ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Report a run event on stderr, if run events are reported.
+///
+/// # Arguments
+/// * `event` - The type of the event, e.g., `manifest_fetched`
+/// * `fields` - The fields of the event, as a JSON object
+pub fn emit_event(event: &str, fields: Value) {
+    if !EVENTS_ENABLED.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Err(e) = writeln!(io::stderr().lock(), "{}", event_line(event, fields)) {
+        debug!("Failed to report the {} event: {}", event, e);
+    }
+}
+
+/// Format a run event as a line of JSON, with its type in the `event` field.
+///
+/// # Arguments
+/// * `event` - The type of the event
+/// * `fields` - The fields of the event, ignored unless they are a JSON object
+///
+/// # Returns
+/// * `String` - The event as a line of JSON
+fn event_line(event: &str, fields: Value) -> String {
+    let mut line = Map::new();
+    line.insert("event".to_string(), Value::from(event));
+    if let Value::Object(fields) = fields {
+        line.extend(fields);
+    }
+    Value::Object(line).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_line() {
+        let line = event_line(
+            "manifest_fetched",
+            serde_json::json!({"url": "https://registry.test/v2/m/manifests/1", "cached": false}),
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            serde_json::json!({
+                "event": "manifest_fetched",
+                "url": "https://registry.test/v2/m/manifests/1",
+                "cached": false,
+            })
+        );
+        assert_eq!(
+            event_line("run_started", Value::Null),
+            r#"{"event":"run_started"}"#
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Failure log for the Ollama Downloader in Rust (ODIR), recording the most recent failed
+//! downloads in `<config_dir>/failures.jsonl`, so that patterns of failures in flaky
+//! environments can be spotted without any external logging.
+use crate::downloader::journal::format_rfc3339_utc;
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use crate::downloader::utils::write_file_atomically;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// Name of the failure log inside the config directory
+const FAILURES_FILE: &str = "failures.jsonl";
+
+/// Where failed downloads are recorded, and how many of them are kept, once enabled
+static FAILURE_LOG: OnceLock<(PathBuf, usize)> = OnceLock::new();
+
+/// A failed download in the failure log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureEntry {
+    /// Time of the failure in RFC 3339 format (UTC)
+    pub timestamp: String,
+
+    /// The model identifier whose download failed, e.g., llama3.1:8b
+    pub identifier: String,
+
+    /// The stable name of the kind of error, as in JSON error envelopes, e.g., `http`
+    pub kind: String,
+
+    /// The HTTP status code, if the failure was caused by one
+    pub status: Option<u16>,
+
+    /// The error message
+    pub message: String,
+}
+
+impl FailureEntry {
+    /// Create a failure entry for a failed download, timestamped now.
+    ///
+    /// # Arguments
+    /// * `identifier` - The model identifier whose download failed
+    /// * `error` - Why the download failed
+    ///
+    /// # Returns
+    /// * `Self` - The failure entry
+    pub fn new(identifier: &str, error: &DownloaderError) -> Self {
+        Self {
+            timestamp: format_rfc3339_utc(SystemTime::now()),
+            identifier: identifier.to_string(),
+            kind: error.kind().to_string(),
+            status: error.http_status(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Get the path of the failure log.
+///
+/// # Returns
+/// * `io::Result<PathBuf>` - Path to the failure log in the config directory
+pub fn failures_file_path() -> io::Result<PathBuf> {
+    Ok(crate::config::get_config_dir()?.join(FAILURES_FILE))
+}
+
+/// Start recording failed downloads for the rest of the run, keeping the most recent ones.
+///
+/// # Arguments
+/// * `max_entries` - How many failures the log keeps, where 0 leaves it off
+pub fn enable_failure_log(max_entries: u32) {
+    if max_entries == 0 {
+        return;
+    }
+    match failures_file_path() {
+        Ok(path) => {
+            let _ = FAILURE_LOG.set((path, max_entries as usize));
+        }
+        Err(e) => warn!("Failed downloads will not be recorded: {}", e),
+    }
+}
+
+/// Record a failed download in the failure log, if it is enabled. Failures to record it are
+/// logged, since the log is only an aid.
+///
+/// # Arguments
+/// * `identifier` - The model identifier whose download failed
+/// * `error` - Why the download failed
+pub fn record_failure(identifier: &str, error: &DownloaderError) {
+    let Some((path, max_entries)) = FAILURE_LOG.get() else {
+        return;
+    };
+    let entry = FailureEntry::new(identifier, error);
+    match append_entry(path, &entry, *max_entries) {
+        Ok(()) => debug!("Recorded the failure of {} in {:?}", identifier, path),
+        Err(e) => warn!("Failed to record the failure in {:?}: {}", path, e),
+    }
+}
+
+/// Append an entry to a failure log, keeping only its most recent entries.
+///
+/// # Arguments
+/// * `path` - Path of the failure log
+/// * `entry` - The entry to append
+/// * `max_entries` - How many entries the log keeps
+///
+/// # Returns
+/// * `Result<()>` - Success or error
+fn append_entry(path: &Path, entry: &FailureEntry, max_entries: usize) -> Result<()> {
+    let mut lines: Vec<String> = match fs::read_to_string(path) {
+        Ok(content) => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    lines.push(serde_json::to_string(entry).map_err(|e| {
+        DownloaderError::Other(format!("Failed to serialize failure entry: {}", e))
+    })?);
+    let excess = lines.len().saturating_sub(max_entries);
+
+    let mut content = lines[excess..].join("\n");
+    content.push('\n');
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_file_atomically(path, content.as_bytes())?;
+    Ok(())
+}
+
+/// Read the entries of a failure log, oldest first.
+///
+/// Lines that cannot be parsed are skipped with a warning.
+///
+/// # Arguments
+/// * `path` - Path of the failure log
+///
+/// # Returns
+/// * `Result<Vec<FailureEntry>>` - The failure entries, empty if there is no failure log
+pub fn read_entries(path: &Path) -> Result<Vec<FailureEntry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let entries = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(
+            |(index, line)| match serde_json::from_str::<FailureEntry>(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!(
+                        "Skipping malformed failure log line {} in {:?}: {}",
+                        index + 1,
+                        path,
+                        e
+                    );
+                    None
+                }
+            },
+        )
+        .collect();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_log_keeps_most_recent_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config").join(FAILURES_FILE);
+        assert!(read_entries(&path).unwrap().is_empty());
+
+        for model in ["a:1", "b:2", "c:3"] {
+            let error = DownloaderError::ModelNotFound(model.to_string());
+            append_entry(&path, &FailureEntry::new(model, &error), 2).unwrap();
+        }
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+
+        let entries = read_entries(&path).unwrap();
+        let identifiers: Vec<&str> = entries.iter().map(|e| e.identifier.as_str()).collect();
+        assert_eq!(identifiers, vec!["b:2", "c:3"]);
+        assert_eq!(entries[1].kind, "model_not_found");
+        assert_eq!(entries[1].status, None);
+        assert_eq!(entries[1].message, "Model not found: c:3");
+    }
+}
@@ -1,30 +1,179 @@
 //! Downloader implementation for Hugging Face Ollama compatible models.
-use crate::config::AppSettings;
-use crate::downloader::manifest::ImageManifest;
-use crate::downloader::model_downloader::{DownloaderError, ModelDownloader, Result};
+use crate::config::{AppSettings, RunOptions};
+use crate::downloader::connectivity::{EndpointCheck, check_endpoint};
+use crate::downloader::http_date;
+use crate::downloader::journal::{self, JournalEntry};
+use crate::downloader::lock::lock_models_path;
+use crate::downloader::manifest::{DigestFormat, ImageManifest, blob_url};
+use crate::downloader::manifest_cache::{self, ManifestCache};
+use crate::downloader::model_downloader::{
+    DownloadPlan, DownloadSummary, DownloaderError, ModelComparison, ModelDownloader,
+    ModelReference, Result, is_already_up_to_date, parse_download_identifier,
+    parse_model_identifier,
+};
+use crate::downloader::progress::{NoProgress, SharedProgress};
 use crate::downloader::utils::{
-    Ownership, cleanup_unnecessary_files, download_model_blob, expand_models_path,
-    infer_models_dir_ownership, is_model_present_in_ollama, save_blob, save_manifest,
-    warn_if_models_path_requires_root,
+    ActivitySpinner, BlobDownloadOptions, MISSING_BLOB_RETRY_DELAY, Ownership, TracedRequest,
+    apply_blob_mtime, apply_network_settings, blob_download_timeout, cleanup_unnecessary_files,
+    compare_tags, download_deadline, download_model_blob, ensure_blobs_intact,
+    ensure_enough_inodes, ensure_enough_space, ensure_manifest_has_model, ensure_models_dir,
+    ensure_ollama_compatible, ensure_within_size_limit, expand_models_path, explain_deadline,
+    fetch_json_blob, format_byte_size, infer_models_dir_ownership, is_blob_intact,
+    is_model_present_in_ollama, redirect_policy, report_kept_files, resolve_temp_dir,
+    retry_missing_blob, save_blob, save_manifest, sleep_unless_interrupted, stream_model_weights,
+    verify_saved_blob, warn_if_models_path_requires_root, write_raw_manifest,
 };
 use log::{debug, error, info, warn};
-use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{AUTHORIZATION, HeaderMap};
 use serde::Deserialize;
-use std::collections::HashSet;
-use std::fs;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
 
 const HF_BASE_URL: &str = "https://hf.co/v2/";
 
+/// Number of models beyond which Hugging Face does not allow paging
+const HF_MAX_PAGED_MODELS: u64 = 999;
+
 #[derive(Debug, Deserialize)]
 struct HfModel {
     #[serde(rename = "modelId")]
     model_id: String,
+    #[serde(rename = "lastModified")]
+    last_modified: Option<String>,
+}
+
+/// Order in which Hugging Face models are listed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HfModelSort {
+    /// Trending models first, sorted by name within the page
+    #[default]
+    Trending,
+
+    /// Most recently modified models first
+    Modified,
+}
+
+/// Kind of Hugging Face repository that GGUF files are published in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HfRepoType {
+    /// A model repository
+    #[default]
+    Model,
+
+    /// A dataset repository
+    Dataset,
+}
+
+impl HfRepoType {
+    /// Get the collection of the Hugging Face API that lists repositories of this kind.
+    ///
+    /// # Returns
+    /// * `&'static str` - The collection, e.g., `models`
+    fn api_collection(&self) -> &'static str {
+        match self {
+            HfRepoType::Model => "models",
+            HfRepoType::Dataset => "datasets",
+        }
+    }
+
+    /// Get the path of a repository of this kind on Hugging Face, e.g., in registry URLs,
+    /// where only datasets are prefixed.
+    ///
+    /// # Arguments
+    /// * `repo` - The repository, specified as {username}/{repository}
+    ///
+    /// # Returns
+    /// * `String` - The path of the repository, e.g., `datasets/{username}/{repository}`
+    fn repo_path(&self, repo: &str) -> String {
+        match self {
+            HfRepoType::Model => repo.to_string(),
+            HfRepoType::Dataset => format!("datasets/{}", repo),
+        }
+    }
+}
+
+/// Validate a date, or a date and time in UTC, in ISO 8601 format,
+/// e.g., 2026-02-20 or 2026-02-20T09:30:00Z.
+///
+/// # Arguments
+/// * `value` - The string to validate
+///
+/// # Returns
+/// * `std::result::Result<String, String>` - The date, or a description of why it is invalid
+pub fn parse_since_date(value: &str) -> std::result::Result<String, String> {
+    let pattern = regex::Regex::new(r"^(\d{4})-(\d{2})-(\d{2})(T\d{2}:\d{2}(:\d{2}(\.\d+)?)?Z)?$")
+        .map_err(|e| e.to_string())?;
+    let captures = pattern.captures(value.trim()).ok_or_else(|| {
+        format!(
+            "'{}' is not a date such as 2026-02-20 or 2026-02-20T09:30:00Z",
+            value
+        )
+    })?;
+    let month: u32 = captures[2].parse().unwrap_or(0);
+    let day: u32 = captures[3].parse().unwrap_or(0);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(format!("'{}' is not a valid date", value));
+    }
+    Ok(value.trim().to_string())
+}
+
+/// Validate the name of an app that Hugging Face models are filtered by, e.g., ollama or
+/// llama.cpp, which goes into the query of the models list as it is.
+///
+/// # Arguments
+/// * `value` - The string to validate
+///
+/// # Returns
+/// * `std::result::Result<String, String>` - The app name, or a description of why it is invalid
+pub fn parse_app_name(value: &str) -> std::result::Result<String, String> {
+    let name = value.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+    {
+        return Err(format!(
+            "'{}' is not an app name such as ollama or llama.cpp",
+            value
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Keep the models last modified at or after a point in time.
+///
+/// Timestamps are compared as text, which orders ISO 8601 timestamps in UTC chronologically.
+/// Models without a known modification time are left out.
+///
+/// # Arguments
+/// * `models` - The models to filter
+/// * `since` - The date, or date and time in UTC, to compare with
+///
+/// # Returns
+/// * `Vec<HfModel>` - The models modified at or after `since`
+fn filter_modified_since(models: Vec<HfModel>, since: &str) -> Vec<HfModel> {
+    // Without the zone designator, the bound sorts before any timestamp with the same prefix
+    let since = since.trim_end_matches('Z');
+    models
+        .into_iter()
+        .filter(|model| {
+            model
+                .last_modified
+                .as_deref()
+                .is_some_and(|last_modified| last_modified >= since)
+        })
+        .collect()
 }
 
 #[derive(Debug, Deserialize)]
 struct HfModelSibling {
     rfilename: String,
+    size: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,13 +181,250 @@ struct HfModelInfo {
     siblings: Vec<HfModelSibling>,
 }
 
+/// Get the quantisation of a GGUF file of a Hugging Face repository, from file names such
+/// as `model-Q4_K_M.gguf`, or `Q8_0/model-Q8_0-00001-of-00003.gguf` for a model split into
+/// several files. The directories of the path, e.g., `GGUF/`, are not part of the name.
+///
+/// # Arguments
+/// * `file_name` - The path of the file in the repository
+///
+/// # Returns
+/// * `Option<(String, bool)>` - The quantisation and whether the file is part of a split
+///   model, or None if the file is not a GGUF file
+fn gguf_quantisation(file_name: &str) -> Option<(String, bool)> {
+    let stem = file_name.rsplit('/').next()?.strip_suffix(".gguf")?;
+    let split_part = regex::Regex::new(r"-\d{5}-of-\d{5}$").ok()?;
+    let (stem, is_split) = match split_part.find(stem) {
+        Some(part) => (&stem[..part.start()], true),
+        None => (stem, false),
+    };
+    let quant = stem
+        .split('-')
+        .next_back()
+        .filter(|quant| !quant.is_empty())?;
+    Some((quant.to_string(), is_split))
+}
+
+/// Get the quantisations offered by the files of a Hugging Face repository, with their sizes.
+///
+/// A model split into several GGUF files counts as a single quantisation, whose size is the
+/// total of its files. Such quantisations are left out unless asked for, and so are those
+/// also offered as a single file. A quantisation offered as a single file more than once,
+/// e.g., in several directories, is listed with the size of its first file.
+///
+/// # Arguments
+/// * `siblings` - The files of the repository
+/// * `include_split` - Whether quantisations split into several files are included
+///
+/// # Returns
+/// * `Vec<(String, Option<u64>)>` - The quantisations, in the order of their files, with
+///   their sizes in bytes if the API reports them
+fn quantisations_of(
+    siblings: &[HfModelSibling],
+    include_split: bool,
+) -> Vec<(String, Option<u64>)> {
+    let mut quants: Vec<(String, Option<u64>)> = Vec::new();
+    let mut split_quants: Vec<(String, Option<u64>)> = Vec::new();
+    for sibling in siblings {
+        let Some((quant, is_split)) = gguf_quantisation(&sibling.rfilename) else {
+            continue;
+        };
+        let found = if is_split {
+            &mut split_quants
+        } else {
+            &mut quants
+        };
+        match found
+            .iter_mut()
+            .find(|(found_quant, _)| *found_quant == quant)
+        {
+            // Several files of the same split quantisation add up
+            Some((_, size)) if is_split => {
+                *size = size.zip(sibling.size).map(|(a, b)| a + b);
+            }
+            Some(_) => debug!(
+                "Ignoring {}, since another GGUF file offers quantisation {}",
+                sibling.rfilename, quant
+            ),
+            None => found.push((quant, sibling.size)),
+        }
+    }
+
+    split_quants.retain(|(split_quant, _)| !quants.iter().any(|(quant, _)| quant == split_quant));
+    if include_split {
+        quants.extend(split_quants);
+    } else if !split_quants.is_empty() {
+        info!(
+            "Leaving out {} quantisation(s) split into several GGUF files: {}",
+            split_quants.len(),
+            split_quants
+                .iter()
+                .map(|(quant, _)| quant.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    quants
+}
+
+/// How to choose a quantisation when a Hugging Face model is downloaded without one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuantPreference {
+    /// The quantisation with the smallest GGUF file
+    Smallest,
+
+    /// The quantisation with the largest GGUF file
+    Largest,
+
+    /// A named quantisation, e.g., Q4_K_M, or a family of quantisations, e.g., Q4
+    Named(String),
+}
+
+/// Parse a quantisation preference, e.g., `smallest`, `largest` or `q4`.
+///
+/// # Arguments
+/// * `value` - The string to parse
+///
+/// # Returns
+/// * `std::result::Result<QuantPreference, String>` - The preference, or why it is invalid
+pub fn parse_quant_preference(value: &str) -> std::result::Result<QuantPreference, String> {
+    match value.trim().to_lowercase().as_str() {
+        "" => Err("The quantisation preference must not be empty".to_string()),
+        "smallest" => Ok(QuantPreference::Smallest),
+        "largest" => Ok(QuantPreference::Largest),
+        _ => Ok(QuantPreference::Named(value.trim().to_string())),
+    }
+}
+
+/// Choose a quantisation according to a preference.
+///
+/// A named preference selects the quantisation of that name, ignoring case. Failing that,
+/// it selects the largest, and thus most accurate, quantisation whose name starts with it,
+/// so that `q4` picks among `Q4_0`, `Q4_K_S`, `Q4_K_M` and so on.
+///
+/// # Arguments
+/// * `quants` - The available quantisations with the sizes of their GGUF files, if known
+/// * `preference` - The quantisation preference
+///
+/// # Returns
+/// * `Option<&str>` - The chosen quantisation, if any matches the preference
+fn choose_quant<'a>(
+    quants: &'a [(String, Option<u64>)],
+    preference: &QuantPreference,
+) -> Option<&'a str> {
+    let sized = || {
+        quants
+            .iter()
+            .filter_map(|(quant, size)| size.map(|size| (quant, size)))
+    };
+    let chosen = match preference {
+        QuantPreference::Smallest => sized().min_by_key(|(_, size)| *size).map(|(q, _)| q),
+        QuantPreference::Largest => sized().max_by_key(|(_, size)| *size).map(|(q, _)| q),
+        QuantPreference::Named(name) => {
+            let name = name.to_lowercase();
+            quants
+                .iter()
+                .find(|(quant, _)| quant.to_lowercase() == name)
+                .or_else(|| {
+                    quants
+                        .iter()
+                        .filter(|(quant, _)| quant.to_lowercase().starts_with(&name))
+                        .max_by_key(|(_, size)| size.unwrap_or(0))
+                })
+                .map(|(q, _)| q)
+        }
+    };
+    chosen.map(String::as_str)
+}
+
+/// Check that a requested page does not extend beyond the models Hugging Face allows paging through.
+///
+/// # Arguments
+/// * `page` - The page number (1-indexed)
+/// * `page_size` - The number of models per page
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error describing by how many models the limit is exceeded
+fn check_paging_limit(page: u32, page_size: u32) -> Result<()> {
+    if page == 0 {
+        return Err(DownloaderError::Other(
+            "Page numbers start at 1".to_string(),
+        ));
+    }
+
+    // The 1-indexed position of the last model on the requested page
+    let last_index = (u64::from(page) - 1) * u64::from(page_size) + u64::from(page_size);
+    if last_index > HF_MAX_PAGED_MODELS {
+        warn!("Hugging Face currently does not allow paging beyond the first 999 models");
+        return Err(DownloaderError::Other(format!(
+            "Hugging Face currently does not allow obtaining information beyond the first 999 models. \
+            Your requested page {} with page size {} exceeds this limit by {} model(s).",
+            page,
+            page_size,
+            last_index - HF_MAX_PAGED_MODELS
+        )));
+    }
+
+    Ok(())
+}
+
+/// Extract the URL of the next page from the `Link` header of a paginated response.
+fn next_page_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("link")
+        .and_then(|link| link.to_str().ok())
+        .and_then(|link_str| {
+            // Parse Link header to extract "next" URL
+            link_str.split(',').find_map(|part| {
+                if part.contains("rel=\"next\"") {
+                    let url_part = part.split(';').next()?;
+                    let url = url_part
+                        .trim()
+                        .trim_start_matches('<')
+                        .trim_end_matches('>');
+                    Some(url.to_string())
+                } else {
+                    None
+                }
+            })
+        })
+}
+
 /// Downloader for Hugging Face models compatible with Ollama
 pub struct HuggingFaceModelDownloader {
     settings: AppSettings,
+    /// Options of this run, given on the command line
+    options: RunOptions,
     user_agent: String,
     client: Client,
     unnecessary_files: HashSet<PathBuf>,
     models_dir_ownership: Option<Ownership>,
+    /// Cache of previously fetched manifests, if the config directory is available
+    manifest_cache: Option<ManifestCache>,
+    /// Whether BLOBs whose digest does not match are rejected
+    verify_digest: bool,
+    /// How many times a BLOB that the registry reports as missing is retried
+    missing_blob_retries: u32,
+    /// Whether saved BLOBs are read back and verified against their digest
+    verify_after: bool,
+    /// Receiver of the progress of downloads
+    progress: SharedProgress,
+    /// URLs of the pages of the models list discovered so far, keyed by (page size, page)
+    page_urls: RefCell<HashMap<(u32, u32), String>>,
+    /// Order in which models are listed
+    sort: HfModelSort,
+    /// Only list models modified at or after this date, if set
+    since: Option<String>,
+    /// App that listed models must run with, if other than Ollama
+    app: Option<String>,
+    /// Whether gated models are listed too
+    include_gated: bool,
+    /// Whether quantisations split into several GGUF files are listed
+    include_split: bool,
+    /// Kind of repository that models are listed and downloaded from
+    repo_type: HfRepoType,
+    /// Whether BLOBs already intact in the models directory are kept instead of downloaded
+    skip_existing: bool,
 }
 
 impl HuggingFaceModelDownloader {
@@ -46,92 +432,349 @@ impl HuggingFaceModelDownloader {
     ///
     /// # Arguments
     /// * `settings` - Application settings
+    /// * `options` - Options of this run
     ///
     /// # Returns
     /// * `Result<Self>` - New downloader instance or error
-    pub fn new(settings: AppSettings) -> Result<Self> {
+    pub fn new(settings: AppSettings, options: RunOptions) -> Result<Self> {
+        options
+            .validate(&settings.ollama_library)
+            .map_err(DownloaderError::Other)?;
         let pkg_version = env!("CARGO_PKG_VERSION");
         let os_info = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
         let user_agent = format!("odir/{} ({})", pkg_version, os_info);
 
-        let client = Client::builder()
+        let extra_headers = settings
+            .ollama_library
+            .extra_header_map()
+            .map_err(DownloaderError::Other)?;
+        if !extra_headers.is_empty() {
+            debug!(
+                "Sending extra headers: {}",
+                settings.ollama_library.redacted_extra_headers()
+            );
+        }
+
+        let client_builder = Client::builder()
             .user_agent(&user_agent)
+            .redirect(redirect_policy(&extra_headers))
+            .default_headers(extra_headers)
             .danger_accept_invalid_certs(!settings.ollama_library.verify_ssl)
             .timeout(std::time::Duration::from_secs_f64(
                 settings.ollama_library.timeout,
-            ))
-            .build()?;
+            ));
+        let client =
+            apply_network_settings(client_builder, &settings.ollama_library, &options).build()?;
 
         let models_dir_ownership =
             infer_models_dir_ownership(&settings.ollama_library.models_path)?;
 
         Ok(Self {
             settings,
+            options,
             user_agent,
             client,
             unnecessary_files: HashSet::new(),
             models_dir_ownership,
+            manifest_cache: ManifestCache::in_config_dir(),
+            verify_digest: true,
+            missing_blob_retries: 0,
+            verify_after: false,
+            progress: Rc::new(RefCell::new(NoProgress)),
+            page_urls: RefCell::new(HashMap::new()),
+            sort: HfModelSort::default(),
+            since: None,
+            app: None,
+            include_gated: false,
+            include_split: false,
+            repo_type: HfRepoType::default(),
+            skip_existing: false,
         })
     }
 
-    /// Construct the manifest URL for a HuggingFace model
-    fn make_manifest_url(&self, model_identifier: &str) -> String {
-        // model_identifier should be like "user/repo:tag"
-        let url_part = model_identifier.replace(':', "/manifests/");
-        format!("{}{}", HF_BASE_URL, url_part)
+    /// Set the order of the models list and an optional filter on their modification date.
+    ///
+    /// # Arguments
+    /// * `sort` - Order in which models are listed
+    /// * `since` - Only list models modified at or after this date, if set
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the listing options applied
+    pub fn with_listing(mut self, sort: HfModelSort, since: Option<String>) -> Self {
+        self.sort = sort;
+        self.since = since;
+        self
     }
 
-    /// Fetch the manifest JSON for a HuggingFace model
-    fn fetch_manifest(&self, model_identifier: &str) -> Result<String> {
-        let url = self.make_manifest_url(model_identifier);
-        info!("Downloading manifest from {}", url);
+    /// Set which slice of the Hugging Face catalog models are listed from: the models of
+    /// an app other than Ollama, and whether gated models are listed too, which needs a
+    /// token in the `Authorization` header of the `extra_headers`.
+    ///
+    /// # Arguments
+    /// * `app` - The app that listed models must run with, or None for Ollama
+    /// * `include_gated` - Whether gated models are listed too
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the catalog filter applied
+    pub fn with_catalog_filter(mut self, app: Option<String>, include_gated: bool) -> Self {
+        self.app = app;
+        self.include_gated = include_gated;
+        self
+    }
 
-        let response = self.client.get(&url).send()?;
+    /// Set the kind of repository that models are listed and downloaded from, e.g., to
+    /// download GGUF files published in a dataset repository.
+    ///
+    /// # Arguments
+    /// * `repo_type` - The kind of repository
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the kind of repository set
+    pub fn with_repo_type(mut self, repo_type: HfRepoType) -> Self {
+        self.repo_type = repo_type;
+        self
+    }
 
-        if !response.status().is_success() {
-            return Err(DownloaderError::HttpError(
-                response.error_for_status().unwrap_err(),
-            ));
-        }
+    /// Set whether BLOBs that are already intact in the models directory are kept instead
+    /// of downloaded again, e.g., to update a model by fetching only its new BLOBs.
+    ///
+    /// # Arguments
+    /// * `skip_existing` - Whether intact BLOBs are kept
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with skipping of existing BLOBs set
+    pub fn with_skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    /// Set whether quantisations split into several GGUF files are listed, each as a single
+    /// quantisation. They are left out by default.
+    ///
+    /// # Arguments
+    /// * `include_split` - Whether split quantisations are listed
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the listing of split quantisations set
+    pub fn with_split_quantisations(mut self, include_split: bool) -> Self {
+        self.include_split = include_split;
+        self
+    }
+
+    /// Set how many times a BLOB that the registry reports as missing is retried, after
+    /// a growing delay, before the download fails.
+    ///
+    /// # Arguments
+    /// * `missing_blob_retries` - How many times a missing BLOB is retried
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the retries set
+    pub fn with_missing_blob_retries(mut self, missing_blob_retries: u32) -> Self {
+        self.missing_blob_retries = missing_blob_retries;
+        self
+    }
+
+    /// Set the receiver of the progress of downloads, e.g., progress bars on a terminal.
+    /// Without one, progress is not reported.
+    ///
+    /// # Arguments
+    /// * `progress` - The receiver of the progress of downloads
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the progress receiver set
+    pub fn with_progress(mut self, progress: SharedProgress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Set whether each BLOB is read back from the models directory once saved, and its
+    /// digest computed again, to catch corruption on disk. This costs an extra read of
+    /// every BLOB.
+    ///
+    /// # Arguments
+    /// * `verify_after` - Whether saved BLOBs are verified
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with verification after saving set
+    pub fn with_verify_after(mut self, verify_after: bool) -> Self {
+        self.verify_after = verify_after;
+        self
+    }
+
+    /// Set whether BLOBs whose digest does not match are rejected. Turning verification
+    /// off is an escape hatch for debugging registries, and is recorded in the journal.
+    ///
+    /// # Arguments
+    /// * `verify_digest` - Whether BLOBs whose digest does not match are rejected
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with digest verification set
+    pub fn with_digest_verification(mut self, verify_digest: bool) -> Self {
+        self.verify_digest = verify_digest;
+        self
+    }
 
-        Ok(response.text()?)
+    /// Construct the manifest URL for a HuggingFace model repository and a tag or digest
+    fn make_manifest_url(&self, model_repo: &str, reference: &str) -> String {
+        format!(
+            "{}{}/manifests/{}",
+            HF_BASE_URL,
+            self.repo_type.repo_path(model_repo),
+            reference
+        )
+    }
+
+    /// Fetch the manifest JSON for a HuggingFace model repository and a tag or digest
+    fn fetch_manifest(&self, model_repo: &str, reference: &str) -> Result<String> {
+        let url = self.make_manifest_url(model_repo, reference);
+        info!("Downloading manifest from {}", url);
+
+        let spinner = ActivitySpinner::start(&format!(
+            "Fetching manifest for {}:{}",
+            model_repo, reference
+        ));
+        let manifest_json = manifest_cache::fetch_manifest(
+            &self.client,
+            &url,
+            self.manifest_cache.as_ref(),
+            &spinner,
+        )?;
+        write_raw_manifest(self.options.raw_manifest_out.as_deref(), &manifest_json)?;
+        Ok(manifest_json)
     }
 
     /// Construct the blob URL for a HuggingFace model
     fn make_blob_url(&self, model_repo: &str, digest: &str) -> String {
-        format!("{}{}/blobs/{}", HF_BASE_URL, model_repo, digest)
+        blob_url(
+            HF_BASE_URL,
+            &self.repo_type.repo_path(model_repo),
+            digest,
+            DigestFormat::Plain,
+        )
     }
 
-    /// Download a model blob with progress tracking
+    /// Download a model blob with progress tracking, within the deadline for the model, if any
     fn download_model_blob(
         &mut self,
         model_repo: &str,
         named_digest: &str,
-    ) -> Result<(PathBuf, String)> {
+        expected_size: u64,
+        deadline: Option<Instant>,
+    ) -> Result<(PathBuf, String, Option<SystemTime>)> {
         let url = self.make_blob_url(model_repo, named_digest);
-        download_model_blob(
-            &self.client,
-            &url,
+        let temp_dir = resolve_temp_dir(
+            &self.settings.ollama_library.models_path,
+            self.settings.ollama_library.temp_dir.as_deref(),
+        )?;
+        retry_missing_blob(
             named_digest,
-            &mut self.unnecessary_files,
+            self.missing_blob_retries,
+            MISSING_BLOB_RETRY_DELAY,
+            deadline,
+            || {
+                let library = &self.settings.ollama_library;
+                let options = BlobDownloadOptions {
+                    timeout: blob_download_timeout(
+                        library.blob_timeout,
+                        library.timeout,
+                        deadline,
+                    )?,
+                    max_bandwidth_bytes_per_sec: library.max_bandwidth_bytes_per_sec,
+                    stall_timeout: library.stall_timeout_secs.map(Duration::from_secs_f64),
+                    min_throughput_bytes_per_sec: library.min_throughput_bytes_per_sec,
+                    keep_partial: self.options.keep_partial_files,
+                    expected_size: Some(expected_size),
+                };
+                download_model_blob(
+                    &self.client,
+                    &url,
+                    named_digest,
+                    &temp_dir,
+                    options,
+                    &mut *self.progress.borrow_mut(),
+                    &mut self.unnecessary_files,
+                )
+            },
         )
+        .map_err(|e| explain_deadline(e, deadline))
     }
 
-    /// Save the blob to the models directory
+    /// Save the blob to the models directory, giving it the modification time asked for, if any
     fn save_blob(
         &mut self,
         source: &Path,
         named_digest: &str,
         computed_digest: &str,
+        source_mtime: Option<SystemTime>,
     ) -> Result<PathBuf> {
-        save_blob(
+        let target_file = save_blob(
             &self.settings.ollama_library.models_path,
             source,
             named_digest,
             computed_digest,
+            self.verify_digest,
             self.models_dir_ownership,
             &mut self.unnecessary_files,
-        )
+        )?;
+        if self.verify_after {
+            verify_saved_blob(&target_file, named_digest, computed_digest)?;
+        }
+        if let Some(preserve_mtime) = self.options.preserve_mtime {
+            apply_blob_mtime(&target_file, preserve_mtime, source_mtime)?;
+        }
+        Ok(target_file)
+    }
+
+    /// Build the query parameters that select the slice of the catalog models are listed from
+    fn catalog_filter(&self) -> Result<String> {
+        // Datasets cannot be filtered by the apps that run them, so only those named after GGUF are listed
+        let mut filter = match self.repo_type {
+            HfRepoType::Model => format!("apps={}", self.app.as_deref().unwrap_or("ollama")),
+            HfRepoType::Dataset => {
+                if let Some(app) = &self.app {
+                    warn!("Datasets cannot be filtered by app, ignoring app {}", app);
+                }
+                "search=gguf".to_string()
+            }
+        };
+        if self.include_gated {
+            let has_token = self
+                .settings
+                .ollama_library
+                .extra_headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case(AUTHORIZATION.as_str()));
+            if !has_token {
+                return Err(DownloaderError::Other(
+                    "Listing gated models needs a Hugging Face token, sent in the Authorization header of the extra_headers setting".to_string(),
+                ));
+            }
+        } else {
+            filter.push_str("&gated=false");
+        }
+        Ok(filter)
+    }
+
+    /// Directory of the manifests of a model in the models directory
+    fn manifests_dir(&self, model_repo: &str) -> Result<PathBuf> {
+        let models_path = expand_models_path(&self.settings.ollama_library.models_path)?;
+        Ok(models_path
+            .join("manifests")
+            .join(self.hf_host())
+            .join(model_repo))
+    }
+
+    /// Read the manifest of a previously downloaded model from the models directory
+    fn read_local_manifest(&self, model_repo: &str, tag: &str) -> Result<String> {
+        let path = self.manifests_dir(model_repo)?.join(tag);
+        info!("Reading local manifest {:?}", path);
+        if !path.is_file() {
+            return Err(DownloaderError::ModelNotFound(format!(
+                "{}:{} (no local manifest at {:?})",
+                model_repo, tag, path
+            )));
+        }
+        Ok(std::fs::read_to_string(path)?)
     }
 
     /// Save the manifest to the models directory
@@ -139,18 +782,11 @@ impl HuggingFaceModelDownloader {
         let models_path = expand_models_path(&self.settings.ollama_library.models_path)?;
         let manifests_toplevel_dir = models_path.join("manifests");
 
-        // Parse HF hostname
-        let hf_host = HF_BASE_URL
-            .split("//")
-            .nth(1)
-            .and_then(|s| s.split('/').next())
-            .unwrap_or("hf.co");
-
         let parts: Vec<&str> = model_identifier.split(':').collect();
         let model_repo = parts[0];
         let tag = parts.get(1).unwrap_or(&"latest");
 
-        let manifests_dir = manifests_toplevel_dir.join(hf_host).join(model_repo);
+        let manifests_dir = self.manifests_dir(model_repo)?;
 
         save_manifest(
             data,
@@ -163,23 +799,257 @@ impl HuggingFaceModelDownloader {
         )
     }
 
-    /// Cleanup unnecessary files on error
+    /// The host under which HuggingFace models are stored, as configured. Ollama stores a
+    /// model pulled as `{host}/{username}/{repository}:{tag}` in
+    /// `manifests/{host}/{username}/{repository}/{tag}`, so this is also the host part of
+    /// the name under which Ollama lists the model.
+    fn hf_host(&self) -> &str {
+        &self.settings.ollama_library.hf_manifest_host
+    }
+
+    /// Check whether the Hugging Face registry can be reached with the HTTP client of the
+    /// downloader, as configured.
+    ///
+    /// # Returns
+    /// * `Vec<EndpointCheck>` - The outcome of checking it
+    pub fn check_connectivity(&self) -> Vec<EndpointCheck> {
+        vec![check_endpoint(
+            &self.client,
+            "hf.co",
+            HF_BASE_URL,
+            self.settings.ollama_library.verify_ssl,
+        )]
+    }
+
+    /// Names under which the Ollama server may list a HuggingFace model
+    fn model_names_in_ollama(&self, model_identifier: &str) -> Vec<String> {
+        vec![
+            format!("{}/{}", self.hf_host(), model_identifier),
+            format!("huggingface.co/{}", model_identifier),
+            model_identifier.to_string(),
+        ]
+    }
+
+    /// Send a HEAD request, retrying on connection failures and on server-side errors
+    /// as many times as configured for pagination.
+    ///
+    /// # Arguments
+    /// * `url` - The URL to send the request to
+    ///
+    /// # Returns
+    /// * `Result<Response>` - The successful response, or the error of the last attempt
+    fn head_with_retries(&self, url: &str, spinner: &ActivitySpinner) -> Result<Response> {
+        let retries = self.settings.ollama_library.pagination_retries;
+        let mut attempt = 0;
+        loop {
+            spinner.check_interrupt()?;
+            let (error, requested_wait) = match self.client.head(url).send_traced() {
+                Ok(response) if response.status().is_success() => {
+                    http_date::warn_on_clock_skew(response.headers());
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    http_date::warn_on_clock_skew(response.headers());
+                    let requested_wait =
+                        http_date::retry_after(response.headers(), SystemTime::now());
+                    let error = response.error_for_status().unwrap_err();
+                    // Client errors other than rate limiting will not go away by retrying
+                    if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                        return Err(DownloaderError::HttpError(error));
+                    }
+                    (error, requested_wait)
+                }
+                Err(error) => (error, None),
+            };
+
+            if attempt >= retries {
+                return Err(DownloaderError::HttpError(error));
+            }
+            attempt += 1;
+            warn!(
+                "Pagination request to {} failed: {}. Retrying ({}/{})",
+                url, error, attempt, retries
+            );
+            // Honour the wait requested by the server, which is clamped to a sane maximum but
+            // can still last minutes, so an interrupt ends it
+            let wait = requested_wait.unwrap_or_else(|| Duration::from_secs(u64::from(attempt)));
+            if !sleep_unless_interrupted(wait, || spinner.check_interrupt().is_err()) {
+                return Err(DownloaderError::Other(
+                    "Operation interrupted by user".to_string(),
+                ));
+            }
+        }
+    }
+
+    /// Find the URL of a page of the HuggingFace models list by following the `Link` headers,
+    /// starting from the closest page already discovered by this downloader.
+    ///
+    /// # Arguments
+    /// * `first_page_url` - URL of the first page of the models list
+    /// * `page` - The page number to find (1-indexed)
+    /// * `page_size` - The number of models per page
+    ///
+    /// # Returns
+    /// * `Result<String>` - URL of the requested page
+    fn find_page_url(
+        &self,
+        first_page_url: &str,
+        page: u32,
+        page_size: u32,
+        spinner: &ActivitySpinner,
+    ) -> Result<String> {
+        let mut page_urls = self.page_urls.borrow_mut();
+        let (mut current_page, mut url) = page_urls
+            .iter()
+            .filter(|((size, p), _)| *size == page_size && *p <= page)
+            .max_by_key(|((_, p), _)| *p)
+            .map(|((_, p), url)| (*p, url.clone()))
+            .unwrap_or((1, first_page_url.to_string()));
+        let mut visited_urls: HashSet<String> = HashSet::from([url.clone()]);
+
+        while current_page < page {
+            debug!("Checking pagination for page {}", current_page);
+
+            let response = self.head_with_retries(&url, spinner)?;
+
+            // Without a link to a next page, the current page is legitimately the last one
+            let next_url = next_page_link(response.headers()).ok_or_else(|| {
+                DownloaderError::Other(format!(
+                    "Requested page {} is beyond available data, which ends at page {} for page size {}",
+                    page, current_page, page_size
+                ))
+            })?;
+
+            // Guard against a misbehaving server sending us round in circles
+            if !visited_urls.insert(next_url.clone()) {
+                warn!("Pagination returned an already visited URL {}", next_url);
+                return Err(DownloaderError::Other(format!(
+                    "Pagination cycle detected after page {} while looking for page {}",
+                    current_page, page
+                )));
+            }
+
+            current_page += 1;
+            page_urls.insert((page_size, current_page), next_url.clone());
+            url = next_url;
+        }
+
+        Ok(url)
+    }
+
+    /// List the quantisations of a HuggingFace model with the sizes of their GGUF files.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier, specified as {username}/{repository}
+    ///
+    /// # Returns
+    /// * `Result<Vec<(String, Option<u64>)>>` - The quantisations, in their natural order as
+    ///   by `compare_tags`, with their sizes in bytes if the API reports them
+    pub fn list_quantisations(&self, model_identifier: &str) -> Result<Vec<(String, Option<u64>)>> {
+        let api_url = format!(
+            "https://huggingface.co/api/{}/{}?blobs=true",
+            self.repo_type.api_collection(),
+            model_identifier
+        );
+
+        debug!(
+            "Fetching tags for model {} from HuggingFace API",
+            model_identifier
+        );
+
+        let spinner = ActivitySpinner::start(&format!("Fetching tags for {}", model_identifier));
+        let response = self.client.get(&api_url).send_traced()?;
+        spinner.check_interrupt()?;
+
+        if !response.status().is_success() {
+            return Err(DownloaderError::HttpError(
+                response.error_for_status().unwrap_err(),
+            ));
+        }
+
+        let model_info: HfModelInfo = response.json()?;
+        spinner.check_interrupt()?;
+        let mut quants = quantisations_of(&model_info.siblings, self.include_split);
+
+        if quants.is_empty() {
+            return Err(DownloaderError::Other(format!(
+                "The model {} has no support for Ollama (no .gguf files found)",
+                model_identifier
+            )));
+        }
+
+        // Sort in their natural order, e.g., Q2_K before Q4_K_M
+        quants.sort_by(|(a, _), (b, _)| compare_tags(a, b));
+
+        Ok(quants)
+    }
+
+    /// Select a quantisation of a HuggingFace model according to a preference.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier, specified as {username}/{repository}
+    /// * `preference` - The quantisation preference
+    ///
+    /// # Returns
+    /// * `Result<String>` - The model identifier with the chosen quantisation, e.g., user/repo:Q4_K_M
+    pub fn select_quant(
+        &self,
+        model_identifier: &str,
+        preference: &QuantPreference,
+    ) -> Result<String> {
+        let quants = self.list_quantisations(model_identifier)?;
+        let quant = choose_quant(&quants, preference).ok_or_else(|| {
+            let available: Vec<&str> = quants.iter().map(|(quant, _)| quant.as_str()).collect();
+            DownloaderError::ModelNotFound(format!(
+                "No quantisation of {} matches the preference {:?}. Available quantisations: {}",
+                model_identifier,
+                preference,
+                available.join(", ")
+            ))
+        })?;
+
+        let size = quants
+            .iter()
+            .find(|(q, _)| q == quant)
+            .and_then(|(_, size)| *size)
+            .map_or_else(|| "unknown size".to_string(), format_byte_size);
+        eprintln!(
+            "Selected quantisation {} ({}) of {}",
+            quant, size, model_identifier
+        );
+
+        Ok(format!("{}:{}", model_identifier, quant))
+    }
+
+    /// Cleanup unnecessary files on error, unless they are to be kept for inspection
     fn cleanup_unnecessary_files(&mut self) {
-        cleanup_unnecessary_files(&mut self.unnecessary_files);
+        if self.options.keep_partial_files {
+            report_kept_files(&self.unnecessary_files);
+        } else {
+            cleanup_unnecessary_files(&mut self.unnecessary_files);
+        }
     }
 }
 
 impl ModelDownloader for HuggingFaceModelDownloader {
-    fn download_model(&self, model_identifier: &str) -> Result<bool> {
+    fn download_model(&self, model_identifier: &str) -> Result<DownloadSummary> {
+        let started = Instant::now();
         // Warn about ownership issues before attempting download
         warn_if_models_path_requires_root(&self.settings.ollama_library.models_path, true);
+        let models_root = ensure_models_dir(&self.settings.ollama_library.models_path)?;
+        // Held until the download has finished or been cleaned up
+        let _lock = lock_models_path(
+            &models_root,
+            self.models_dir_ownership,
+            self.options.no_lock,
+        )?;
 
-        let (model_repo, quant) = if model_identifier.contains(':') {
-            let parts: Vec<&str> = model_identifier.split(':').collect();
-            (parts[0].to_string(), parts[1].to_string())
-        } else {
-            (model_identifier.to_string(), "latest".to_string())
-        };
+        let (model_repo, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model_repo);
+        // The identifier under which the manifest is saved and listed by Ollama
+        let saved_identifier = format!("{}:{}", model_repo, reference.manifest_file_name());
 
         let parts: Vec<&str> = model_repo.split('/').collect();
         if parts.len() != 2 {
@@ -192,41 +1062,122 @@ impl ModelDownloader for HuggingFaceModelDownloader {
         let user = parts[0];
         let repo = parts[1];
 
-        println!(
-            "Downloading Hugging Face model {} from {} with {} quantisation",
-            repo, user, quant
-        );
+        match &reference {
+            ModelReference::Tag(quant) => eprintln!(
+                "Downloading Hugging Face model {} from {} with {} quantisation",
+                repo, user, quant
+            ),
+            ModelReference::Digest(digest) => eprintln!(
+                "Downloading Hugging Face model {} from {} pinned to {}",
+                repo, user, digest
+            ),
+        }
 
         // Make self mutable for this scope
         let mut self_mut = Self {
             settings: self.settings.clone(),
+            options: self.options.clone(),
             user_agent: self.user_agent.clone(),
             client: self.client.clone(),
             unnecessary_files: HashSet::new(),
             models_dir_ownership: self.models_dir_ownership,
+            manifest_cache: self.manifest_cache.clone(),
+            verify_digest: self.verify_digest,
+            missing_blob_retries: self.missing_blob_retries,
+            verify_after: self.verify_after,
+            progress: Rc::clone(&self.progress),
+            page_urls: RefCell::new(HashMap::new()),
+            sort: self.sort,
+            since: self.since.clone(),
+            app: self.app.clone(),
+            include_gated: self.include_gated,
+            include_split: self.include_split,
+            repo_type: self.repo_type,
+            skip_existing: self.skip_existing,
         };
 
         // Fetch and parse manifest
-        let manifest_json = match self_mut.fetch_manifest(model_identifier) {
+        let manifest_json = match self_mut
+            .fetch_manifest(&model_repo, reference.as_url_component())
+            .and_then(|json| reference.verify_manifest(&json).map(|_| json))
+        {
             Ok(json) => json,
             Err(e) => {
-                error!("Failed to fetch manifest for {}: {}", model_identifier, e);
+                error!("Failed to fetch manifest for {}: {}", identifier, e);
                 self_mut.cleanup_unnecessary_files();
                 return Err(e);
             }
         };
-        info!("Validating manifest for {}", model_identifier);
+        info!("Validating manifest for {}", identifier);
+
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+        ensure_ollama_compatible(&manifest, &identifier)?;
+        ensure_manifest_has_model(&manifest, &identifier)?;
+        // A model whose BLOBs are all in place is not downloaded again, unless forced
+        if !self.options.force
+            && is_already_up_to_date(
+                &models_root,
+                &self_mut
+                    .manifests_dir(&model_repo)?
+                    .join(reference.manifest_file_name()),
+                &identifier,
+                &manifest,
+            )?
+        {
+            eprintln!("Model {} is already up to date, nothing to do", identifier);
+            let summary = DownloadSummary::already_up_to_date(&identifier, started.elapsed());
+            self.progress.borrow_mut().on_model_complete(&summary);
+            return Ok(summary);
+        }
+        ensure_within_size_limit(
+            &manifest,
+            &identifier,
+            self.settings.ollama_library.max_download_size,
+        )?;
+        let models_root = expand_models_path(&self.settings.ollama_library.models_path)?;
+        ensure_enough_inodes(
+            &models_root,
+            manifest.unique_layers().len() + 2,
+            self.settings.ollama_library.min_free_inodes,
+            self.options.force,
+        )?;
+        ensure_enough_space(
+            &manifest,
+            &models_root,
+            &resolve_temp_dir(
+                &self.settings.ollama_library.models_path,
+                self.settings.ollama_library.temp_dir.as_deref(),
+            )?,
+            self.options.min_free_after,
+            self.options.force,
+        )?;
+        let needs_download = |digest: &str| -> Result<bool> {
+            Ok(!self.skip_existing || !is_blob_intact(&models_root, digest)?)
+        };
 
-        let manifest: ImageManifest = serde_json::from_str(&manifest_json)
-            .map_err(|e| DownloaderError::ParseError(format!("Failed to parse manifest: {}", e)))?;
+        // The deadline covers the downloads of all BLOBs of the model
+        let deadline = download_deadline(self.settings.ollama_library.download_deadline);
 
-        // Track files to be saved (source_path, named_digest, computed_digest)
-        let mut files_to_be_copied: Vec<(PathBuf, String, String)> = Vec::new();
+        // Track files to be saved (source_path, named_digest, computed_digest, source_mtime)
+        let mut files_to_be_copied: Vec<(PathBuf, String, String, Option<SystemTime>)> = Vec::new();
+        let mut fetched_bytes = 0;
+
+        if !self_mut.verify_digest {
+            warn!(
+                "Digest verification is DISABLED: the integrity of the downloaded BLOBs will NOT be verified"
+            );
+        }
 
         // Download model configuration BLOB
-        info!("Downloading model configuration {}", manifest.config.digest);
-        let (file_model_config, digest_model_config) =
-            match self_mut.download_model_blob(&model_repo, &manifest.config.digest) {
+        if needs_download(&manifest.config.digest)? {
+            info!("Downloading model configuration {}", manifest.config.digest);
+            let (file_model_config, digest_model_config, mtime_model_config) = match self_mut
+                .download_model_blob(
+                    &model_repo,
+                    &manifest.config.digest,
+                    manifest.config.size,
+                    deadline,
+                ) {
                 Ok(result) => result,
                 Err(e) => {
                     error!("Failed to download model configuration: {}", e);
@@ -234,57 +1185,72 @@ impl ModelDownloader for HuggingFaceModelDownloader {
                     return Err(e);
                 }
             };
-        files_to_be_copied.push((
-            file_model_config,
-            manifest.config.digest.clone(),
-            digest_model_config,
-        ));
+            files_to_be_copied.push((
+                file_model_config,
+                manifest.config.digest.clone(),
+                digest_model_config,
+                mtime_model_config,
+            ));
+            fetched_bytes += manifest.config.size;
+        }
 
-        // Download layers if present
-        if let Some(layers) = &manifest.layers {
-            for layer in layers {
-                debug!(
-                    "Layer: {}, Size: {} bytes, Digest: {}",
-                    layer.media_type, layer.size, layer.digest
-                );
+        // Download each distinct layer BLOB once, even if the manifest repeats its digest
+        let layers = manifest.unique_layers();
+        let repeated_layers = manifest.layers.as_ref().map_or(0, |l| l.len()) - layers.len();
+        if repeated_layers > 0 {
+            info!(
+                "Skipping {} layer(s) whose BLOB is already part of the download",
+                repeated_layers
+            );
+        }
+        for layer in layers {
+            debug!(
+                "Layer: {}, Size: {} bytes, Digest: {}",
+                layer.media_type, layer.size, layer.digest
+            );
+            if !needs_download(&layer.digest)? {
+                continue;
+            }
 
-                // Check for interruption between layer downloads
-                if crate::signal_handler::is_interrupted() {
-                    warn!("Download interrupted during layer download");
-                    self_mut.cleanup_unnecessary_files();
-                    return Err(DownloaderError::Other(
-                        "Download interrupted by user".to_string(),
-                    ));
-                }
-                if crate::signal_handler::confirm_pending_interrupt() {
-                    warn!("Download interrupted during layer download");
+            // Check for interruption between layer downloads
+            if crate::signal_handler::is_interrupted() {
+                warn!("Download interrupted during layer download");
+                self_mut.cleanup_unnecessary_files();
+                return Err(DownloaderError::Other(
+                    "Download interrupted by user".to_string(),
+                ));
+            }
+            if crate::signal_handler::confirm_pending_interrupt() {
+                warn!("Download interrupted during layer download");
+                self_mut.cleanup_unnecessary_files();
+                return Err(DownloaderError::Other(
+                    "Download interrupted by user".to_string(),
+                ));
+            }
+
+            info!("Downloading {} layer {}", layer.media_type, layer.digest);
+            let (file_layer, digest_layer, mtime_layer) = match self_mut.download_model_blob(
+                &model_repo,
+                &layer.digest,
+                layer.size,
+                deadline,
+            ) {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to download layer {}: {}", layer.digest, e);
                     self_mut.cleanup_unnecessary_files();
-                    return Err(DownloaderError::Other(
-                        "Download interrupted by user".to_string(),
-                    ));
+                    return Err(e);
                 }
-
-                info!("Downloading {} layer {}", layer.media_type, layer.digest);
-                let (file_layer, digest_layer) =
-                    match self_mut.download_model_blob(&model_repo, &layer.digest) {
-                        Ok(result) => result,
-                        Err(e) => {
-                            error!("Failed to download layer {}: {}", layer.digest, e);
-                            self_mut.cleanup_unnecessary_files();
-                            return Err(e);
-                        }
-                    };
-                files_to_be_copied.push((file_layer, layer.digest.clone(), digest_layer));
-            }
+            };
+            files_to_be_copied.push((file_layer, layer.digest.clone(), digest_layer, mtime_layer));
+            fetched_bytes += layer.size;
         }
 
         // All BLOBs downloaded, now save them
-        for (source, named_digest, computed_digest) in files_to_be_copied {
-            match self_mut.save_blob(&source, &named_digest, &computed_digest) {
-                Ok(_) => {
-                    // Cleanup source file
-                    let _ = fs::remove_file(&source);
-                }
+        let fetched_blobs = files_to_be_copied.len();
+        for (source, named_digest, computed_digest, source_mtime) in files_to_be_copied {
+            match self_mut.save_blob(&source, &named_digest, &computed_digest, source_mtime) {
+                Ok(_) => {}
                 Err(e) => {
                     error!("Failed to save BLOB {}: {}", named_digest, e);
                     self_mut.cleanup_unnecessary_files();
@@ -294,11 +1260,13 @@ impl ModelDownloader for HuggingFaceModelDownloader {
         }
 
         // Save the manifest
-        match self_mut.save_manifest(&manifest_json, model_identifier) {
+        match self_mut.save_manifest(&manifest_json, &saved_identifier) {
             Ok(_) => {}
             Err(e) => {
                 error!("Failed to save manifest: {}", e);
-                if self.settings.ollama_server.remove_downloaded_on_error {
+                if self.settings.ollama_server.remove_downloaded_on_error
+                    && !self.options.keep_partial_files
+                {
                     self_mut.cleanup_unnecessary_files();
                 }
                 return Err(e);
@@ -307,28 +1275,22 @@ impl ModelDownloader for HuggingFaceModelDownloader {
 
         // Verify the model is present in the Ollama server if configured
         if self.settings.ollama_server.check_model_presence {
-            let hf_host = HF_BASE_URL
-                .split("//")
-                .nth(1)
-                .and_then(|s| s.split('/').next())
-                .unwrap_or("hf.co");
-            let model_name = format!("{}/{}", hf_host, model_identifier);
-            let model_names = vec![
-                model_name.clone(),
-                format!("huggingface.co/{}", model_identifier),
-                model_identifier.to_string(),
-            ];
+            let model_names = self.model_names_in_ollama(&saved_identifier);
+            let model_name = model_names[0].clone();
 
             info!("Verifying model {} is present in Ollama server", model_name);
             let model_present = match is_model_present_in_ollama(
                 &self_mut.client,
-                &self.settings.ollama_server.url,
+                &self.settings.ollama_server.resolved_url(),
+                self.settings.ollama_server.api_key.as_deref(),
                 &model_names,
             ) {
                 Ok(present) => present,
                 Err(e) => {
                     error!("Failed to verify model with Ollama server: {}", e);
-                    if self.settings.ollama_server.remove_downloaded_on_error {
+                    if self.settings.ollama_server.remove_downloaded_on_error
+                        && !self.options.keep_partial_files
+                    {
                         info!("Removing downloaded files due to verification failure");
                         self_mut.cleanup_unnecessary_files();
                     }
@@ -342,7 +1304,9 @@ impl ModelDownloader for HuggingFaceModelDownloader {
                     model_name
                 );
                 error!("{}", err_msg);
-                if self.settings.ollama_server.remove_downloaded_on_error {
+                if self.settings.ollama_server.remove_downloaded_on_error
+                    && !self.options.keep_partial_files
+                {
                     info!("Removing downloaded files because model not found in Ollama server");
                     self_mut.cleanup_unnecessary_files();
                 }
@@ -357,123 +1321,227 @@ impl ModelDownloader for HuggingFaceModelDownloader {
         // Clear unnecessary files list on success
         self_mut.unnecessary_files.clear();
 
-        println!(
-            "HuggingFace model {} successfully downloaded",
-            model_identifier
-        );
+        let summary =
+            DownloadSummary::new(&identifier, fetched_bytes, fetched_blobs, started.elapsed());
+        self.progress.borrow_mut().on_model_complete(&summary);
+        let entry = JournalEntry {
+            digest_verified: self.verify_digest,
+            elapsed_secs: Some(summary.elapsed_secs),
+            ..JournalEntry::from_manifest(&identifier, self.hf_host(), &manifest)
+        };
+        if let Err(e) = journal::append_entry(
+            &self.settings.ollama_library.models_path,
+            &entry,
+            self.models_dir_ownership,
+        ) {
+            warn!("Failed to record download in journal: {}", e);
+        }
+
+        eprintln!("HuggingFace model {} successfully downloaded", identifier);
 
-        Ok(true)
+        Ok(summary)
     }
 
-    fn list_available_models(
-        &self,
-        page: Option<u32>,
-        page_size: Option<u32>,
-    ) -> Result<Vec<String>> {
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(25).min(100);
+    fn is_model_present(&self, model_identifier: &str) -> Result<bool> {
+        let model_names = self.model_names_in_ollama(model_identifier);
+        info!(
+            "Checking if model {} is present in Ollama server",
+            model_names[0]
+        );
+        is_model_present_in_ollama(
+            &self.client,
+            &self.settings.ollama_server.resolved_url(),
+            self.settings.ollama_server.api_key.as_deref(),
+            &model_names,
+        )
+    }
 
-        // Check HuggingFace limitation
-        if page_size * (page + 1) >= 1000 {
-            warn!("Hugging Face currently does not allow paging beyond the first 999 models");
-            return Err(DownloaderError::Other(format!(
-                "Hugging Face currently does not allow obtaining information beyond the first 999 models. \
-                Your requested page {} with page size {} exceeds this limit by {} model(s).",
-                page,
-                page_size,
-                (page + 1) * page_size - 999
-            )));
+    fn relink(&self, model_identifier: &str) -> Result<PathBuf> {
+        let models_root = ensure_models_dir(&self.settings.ollama_library.models_path)?;
+        let (model_repo, reference) = parse_model_identifier(model_identifier, "latest")?;
+        let identifier = reference.qualify(&model_repo);
+        if model_repo.split('/').count() != 2 {
+            return Err(DownloaderError::InvalidIdentifier(
+                "HuggingFace model identifier must be in format 'user/repository:quantization'"
+                    .to_string(),
+            ));
         }
-
-        let api_url = format!(
-            "https://huggingface.co/api/models?apps=ollama&gated=false&limit={}&sort=trendingScore",
-            page_size
+        info!(
+            "Relinking model {} to the BLOBs in {:?}",
+            identifier, models_root
         );
 
-        let mut next_page_url = Some(api_url.clone());
-        let mut current_page = 1u32;
-
-        // Navigate to the requested page
-        while current_page < page && next_page_url.is_some() {
-            let url = next_page_url.unwrap();
-            debug!("Checking pagination for page {}", current_page);
-
-            let response = self.client.head(&url).send()?;
+        let manifest_json = self.fetch_manifest(&model_repo, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+        ensure_ollama_compatible(&manifest, &identifier)?;
+        ensure_manifest_has_model(&manifest, &identifier)?;
+        ensure_blobs_intact(&models_root, &manifest, &identifier)?;
 
-            if !response.status().is_success() {
-                return Err(DownloaderError::HttpError(
-                    response.error_for_status().unwrap_err(),
-                ));
-            }
-
-            // Extract next page URL from Link header
-            next_page_url = response
-                .headers()
-                .get("link")
-                .and_then(|link| link.to_str().ok())
-                .and_then(|link_str| {
-                    // Parse Link header to extract "next" URL
-                    link_str.split(',').find_map(|part| {
-                        if part.contains("rel=\"next\"") {
-                            let url_part = part.split(';').next()?;
-                            let url = url_part
-                                .trim()
-                                .trim_start_matches('<')
-                                .trim_end_matches('>');
-                            Some(url.to_string())
-                        } else {
-                            None
-                        }
-                    })
-                });
+        // Nothing was downloaded, so nothing needs cleaning up if saving fails
+        let manifests_toplevel_dir = models_root.join("manifests");
+        let manifests_dir = self.manifests_dir(&model_repo)?;
+        save_manifest(
+            &manifest_json,
+            &models_root,
+            &manifests_dir,
+            &reference.manifest_file_name(),
+            self.models_dir_ownership,
+            &[&manifests_dir, &manifests_toplevel_dir],
+            &mut HashSet::new(),
+        )
+    }
 
-            current_page += 1;
+    fn manifest_path(&self, model_identifier: &str) -> Result<PathBuf> {
+        let (model_repo, reference) = parse_model_identifier(model_identifier, "latest")?;
+        if model_repo.split('/').count() != 2 {
+            return Err(DownloaderError::InvalidIdentifier(
+                "HuggingFace model identifier must be in format 'user/repository:quantization'"
+                    .to_string(),
+            ));
         }
+        Ok(self
+            .manifests_dir(&model_repo)?
+            .join(reference.manifest_file_name()))
+    }
 
-        if next_page_url.is_none() {
-            return Err(DownloaderError::Other(format!(
-                "Requested page {} is beyond available data",
-                page
-            )));
+    fn plan(&self, model_identifier: &str) -> Result<DownloadPlan> {
+        let (model_repo, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model_repo);
+        if model_repo.split('/').count() != 2 {
+            return Err(DownloaderError::InvalidIdentifier(
+                "HuggingFace model identifier must be in format 'user/repository:quantization'"
+                    .to_string(),
+            ));
         }
+        info!("Planning the download of model {}", identifier);
+
+        let manifest_json = self.fetch_manifest(&model_repo, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+        ensure_ollama_compatible(&manifest, &identifier)?;
+        ensure_manifest_has_model(&manifest, &identifier)?;
+
+        let models_root = expand_models_path(&self.settings.ollama_library.models_path)?;
+        DownloadPlan::new(
+            &identifier,
+            self.hf_host(),
+            &manifest_json,
+            &manifest,
+            &models_root,
+        )
+    }
 
-        let final_url = next_page_url.unwrap();
-
-        if current_page > 1 {
-            info!("Requesting page {} from {}", current_page, final_url);
+    fn model_config(&self, model_identifier: &str) -> Result<serde_json::Value> {
+        let (model_repo, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model_repo);
+        if model_repo.split('/').count() != 2 {
+            return Err(DownloaderError::InvalidIdentifier(
+                "HuggingFace model identifier must be in format 'user/repository:quantization'"
+                    .to_string(),
+            ));
         }
+        info!("Fetching the configuration of model {}", identifier);
 
-        let response = self.client.get(&final_url).send()?;
+        let manifest_json = self.fetch_manifest(&model_repo, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
 
-        if !response.status().is_success() {
-            return Err(DownloaderError::HttpError(
-                response.error_for_status().unwrap_err(),
+        let library = &self.settings.ollama_library;
+        fetch_json_blob(
+            &self.client,
+            &self.make_blob_url(&model_repo, &manifest.config.digest),
+            &manifest.config.digest,
+            &resolve_temp_dir(&library.models_path, library.temp_dir.as_deref())?,
+            library.blob_timeout.map(Duration::from_secs_f64),
+        )
+    }
+
+    fn stream_weights(&self, model_identifier: &str, out: &mut dyn std::io::Write) -> Result<u64> {
+        let (model_repo, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model_repo);
+        if model_repo.split('/').count() != 2 {
+            return Err(DownloaderError::InvalidIdentifier(
+                "HuggingFace model identifier must be in format 'user/repository:quantization'"
+                    .to_string(),
             ));
         }
 
-        let models: Vec<HfModel> = response.json()?;
-        let mut model_identifiers: Vec<String> = models.into_iter().map(|m| m.model_id).collect();
+        let manifest_json = self.fetch_manifest(&model_repo, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+
+        stream_model_weights(
+            &self.client,
+            &manifest,
+            &identifier,
+            |digest| self.make_blob_url(&model_repo, digest),
+            &self.settings.ollama_library,
+            &mut *self.progress.borrow_mut(),
+            out,
+        )
+    }
 
-        warn!("HuggingFace models are sorted in the context of the selected page only");
+    fn compare(&self, model_identifier: &str) -> Result<ModelComparison> {
+        let (model_repo, reference) = parse_model_identifier(model_identifier, "latest")?;
+        let identifier = reference.qualify(&model_repo);
+        info!("Comparing model {} with Hugging Face", identifier);
 
-        // Sort case-insensitively
-        model_identifiers.sort_by_key(|a| a.to_lowercase());
+        let remote_json = self.fetch_manifest(&model_repo, reference.as_url_component())?;
+        reference.verify_manifest(&remote_json)?;
+        let remote: ImageManifest = ImageManifest::parse(&remote_json)?;
 
-        Ok(model_identifiers)
+        let local = match self.read_local_manifest(&model_repo, &reference.manifest_file_name()) {
+            Ok(json) => Some(ImageManifest::parse(&json)?),
+            Err(DownloaderError::ModelNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(ModelComparison::new(&identifier, local.as_ref(), &remote))
     }
 
-    fn list_model_tags(&self, model_identifier: &str) -> Result<Vec<String>> {
+    fn list_available_models(
+        &self,
+        page: Option<u32>,
+        page_size: Option<u32>,
+    ) -> Result<Vec<String>> {
+        let page = page.unwrap_or(1);
+        let page_size = page_size.unwrap_or(25).min(100);
+
+        check_paging_limit(page, page_size)?;
+
+        let sort = match self.sort {
+            HfModelSort::Trending => "trendingScore",
+            HfModelSort::Modified => "lastModified&direction=-1",
+        };
+        // Modification times are only included in the full model information
+        let full = if self.sort == HfModelSort::Modified || self.since.is_some() {
+            "&full=true"
+        } else {
+            ""
+        };
         let api_url = format!(
-            "https://huggingface.co/api/models/{}?blobs=true",
-            model_identifier
+            "https://huggingface.co/api/{}?{}&limit={}&sort={}{}",
+            self.repo_type.api_collection(),
+            self.catalog_filter()?,
+            page_size,
+            sort,
+            full
         );
 
-        debug!(
-            "Fetching tags for model {} from HuggingFace API",
-            model_identifier
-        );
+        let spinner = ActivitySpinner::start("Fetching the Hugging Face models list");
+        let final_url = if page == 1 {
+            api_url
+        } else {
+            let url = self.find_page_url(&api_url, page, page_size, &spinner)?;
+            info!("Requesting page {} from {}", page, url);
+            url
+        };
 
-        let response = self.client.get(&api_url).send()?;
+        let response = self.client.get(&final_url).send_traced()?;
+        spinner.check_interrupt()?;
 
         if !response.status().is_success() {
             return Err(DownloaderError::HttpError(
@@ -481,48 +1549,502 @@ impl ModelDownloader for HuggingFaceModelDownloader {
             ));
         }
 
-        let model_info: HfModelInfo = response.json()?;
-        let mut tags: Vec<String> = Vec::new();
-
-        for sibling in model_info.siblings {
-            if sibling.rfilename.ends_with(".gguf") {
-                // Extract quantisation from filename
-                // Typically filenames are like: model-Q4_K_M.gguf
-                if let Some(tag_part) = sibling
-                    .rfilename
-                    .strip_suffix(".gguf")
-                    .and_then(|s| s.split('-').next_back())
-                {
-                    tags.push(format!("{}:{}", model_identifier, tag_part));
-                }
-            }
+        let mut models: Vec<HfModel> = response.json()?;
+        spinner.check_interrupt()?;
+        if let Some(since) = &self.since {
+            models = filter_modified_since(models, since);
+            debug!("Found {} models modified since {}", models.len(), since);
         }
+        let mut model_identifiers: Vec<String> = models.into_iter().map(|m| m.model_id).collect();
 
-        if tags.is_empty() {
-            return Err(DownloaderError::Other(format!(
-                "The model {} has no support for Ollama (no .gguf files found)",
-                model_identifier
-            )));
+        // Most recently modified models are listed as returned, newest first
+        if self.sort == HfModelSort::Trending {
+            warn!("HuggingFace models are sorted in the context of the selected page only");
+
+            // Sort case-insensitively
+            model_identifiers.sort_by_key(|a| a.to_lowercase());
         }
 
-        // Sort case-insensitively
-        tags.sort_by_key(|a| a.to_lowercase());
+        Ok(model_identifiers)
+    }
 
-        Ok(tags)
+    fn list_model_tags(&self, model_identifier: &str) -> Result<Vec<String>> {
+        Ok(self
+            .list_quantisations(model_identifier)?
+            .into_iter()
+            .map(|(quant, _)| format!("{}:{}", model_identifier, quant))
+            .collect())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::downloader::utils::MAX_REDIRECTS;
+    use crate::downloader::utils::tests::{redirect_to, serve_once};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn downloader_with_headers(headers: &[(&str, &str)]) -> HuggingFaceModelDownloader {
+        let mut settings = AppSettings::default();
+        for (name, value) in headers {
+            settings
+                .ollama_library
+                .extra_headers
+                .insert(name.to_string(), value.to_string());
+        }
+        HuggingFaceModelDownloader::new(settings, RunOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_redirect_to_other_host_drops_authorization() {
+        let (cdn, cdn_request) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nblob".to_string(),
+        );
+        let (origin, origin_request) = serve_once(redirect_to(&format!("{}/blob", cdn)));
+
+        let downloader =
+            downloader_with_headers(&[("Authorization", "Bearer secret"), ("X-Team", "models")]);
+        let response = downloader.client.get(&origin).send().unwrap();
+        assert_eq!(response.text().unwrap(), "blob");
+
+        assert!(
+            origin_request
+                .join()
+                .unwrap()
+                .contains("authorization: bearer secret")
+        );
+        let cdn_request = cdn_request.join().unwrap();
+        assert!(!cdn_request.contains("authorization"));
+        assert!(!cdn_request.contains("secret"));
+        assert!(cdn_request.contains("x-team: models"));
+    }
+
+    fn siblings(files: &[(&str, u64)]) -> Vec<HfModelSibling> {
+        files
+            .iter()
+            .map(|(name, size)| HfModelSibling {
+                rfilename: name.to_string(),
+                size: Some(*size),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gguf_quantisation() {
+        assert_eq!(
+            gguf_quantisation("Llama-3.2-1B-Instruct-Q4_K_M.gguf"),
+            Some(("Q4_K_M".to_string(), false))
+        );
+        assert_eq!(
+            gguf_quantisation("Q8_0/Llama-3.3-70B-Instruct-Q8_0-00001-of-00002.gguf"),
+            Some(("Q8_0".to_string(), true))
+        );
+        assert_eq!(
+            gguf_quantisation("GGUF/Llama-3.2-1B-Instruct-Q4_K_M.gguf"),
+            Some(("Q4_K_M".to_string(), false))
+        );
+        assert_eq!(
+            gguf_quantisation("models/gguf-v2/Llama-3.2-1B-Instruct-IQ2_XS.gguf"),
+            Some(("IQ2_XS".to_string(), false))
+        );
+        assert_eq!(gguf_quantisation("GGUF/README.md"), None);
+        assert_eq!(gguf_quantisation("README.md"), None);
+        assert_eq!(gguf_quantisation("-00001-of-00002.gguf"), None);
+    }
+
+    #[test]
+    fn test_quantisations_of_split_files() {
+        let files = siblings(&[
+            ("README.md", 1),
+            ("model-Q4_K_M.gguf", 40),
+            ("Q8_0/model-Q8_0-00001-of-00002.gguf", 50),
+            ("Q8_0/model-Q8_0-00002-of-00002.gguf", 30),
+            ("model-F16-00001-of-00002.gguf", 100),
+            ("model-F16-00002-of-00002.gguf", 60),
+            ("model-F16.gguf", 150),
+        ]);
+        assert_eq!(
+            quantisations_of(&files, false),
+            vec![
+                ("Q4_K_M".to_string(), Some(40)),
+                ("F16".to_string(), Some(150))
+            ]
+        );
+        assert_eq!(
+            quantisations_of(&files, true),
+            vec![
+                ("Q4_K_M".to_string(), Some(40)),
+                ("F16".to_string(), Some(150)),
+                ("Q8_0".to_string(), Some(80))
+            ]
+        );
+        assert!(
+            quantisations_of(&siblings(&[("model-Q8_0-00001-of-00002.gguf", 1)]), false).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_quantisations_of_files_in_directories() {
+        let files = siblings(&[
+            ("GGUF/model-Q4_K_M.gguf", 40),
+            ("GGUF/model-Q5_K_M.gguf", 50),
+            ("GGUF-imatrix/model-Q4_K_M.gguf", 41),
+            ("GGUF/Q8_0/model-Q8_0-00001-of-00002.gguf", 50),
+            ("GGUF/Q8_0/model-Q8_0-00002-of-00002.gguf", 30),
+        ]);
+        assert_eq!(
+            quantisations_of(&files, true),
+            vec![
+                ("Q4_K_M".to_string(), Some(40)),
+                ("Q5_K_M".to_string(), Some(50)),
+                ("Q8_0".to_string(), Some(80))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redirect_to_other_host_refused_with_credential_headers() {
+        let (origin, origin_request) = serve_once(redirect_to("http://127.0.0.1:9/blob"));
+
+        let downloader = downloader_with_headers(&[("X-Api-Key", "secret")]);
+        let error = downloader.client.get(&origin).send().unwrap_err();
+        assert!(error.is_redirect());
+        assert!(format!("{:?}", error).contains("x-api-key"));
+        origin_request.join().unwrap();
+    }
+
+    #[test]
+    fn test_resolve_overrides() {
+        let (address, request) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string(),
+        );
+        let port = address.rsplit(':').next().unwrap();
+        let resolve_override =
+            crate::config::parse_resolve(&format!("registry.staging.invalid:{}:127.0.0.1", port))
+                .unwrap();
+        let options = RunOptions::default().with_resolve_overrides(vec![resolve_override]);
+
+        let downloader = HuggingFaceModelDownloader::new(AppSettings::default(), options).unwrap();
+        let response = downloader
+            .client
+            .get(format!("http://registry.staging.invalid:{}/", port))
+            .send()
+            .unwrap();
+        assert_eq!(response.text().unwrap(), "ok");
+        assert!(
+            request
+                .join()
+                .unwrap()
+                .contains("host: registry.staging.invalid")
+        );
+    }
+
+    #[test]
+    fn test_address_family_and_local_address() {
+        let (address, _request) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string(),
+        );
+        let port = address.rsplit(':').next().unwrap();
+        let mut settings = AppSettings::default();
+        settings.ollama_library.local_address = Some("127.0.0.1".parse().unwrap());
+        let options =
+            RunOptions::default().with_address_family(Some(crate::config::AddressFamily::Ipv4));
+
+        let downloader = HuggingFaceModelDownloader::new(settings, options).unwrap();
+        let response = downloader
+            .client
+            .get(format!("http://localhost:{}/", port))
+            .send()
+            .unwrap();
+        assert_eq!(response.text().unwrap(), "ok");
+    }
+
+    #[test]
+    fn test_redirects_are_limited() {
+        // A server that redirects to itself, answering one more request than is followed
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let location = address.clone();
+        let handle = thread::spawn(move || {
+            for _ in 0..=MAX_REDIRECTS {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                stream.write_all(redirect_to(&location).as_bytes()).unwrap();
+            }
+        });
+
+        let downloader = downloader_with_headers(&[]);
+        let error = downloader.client.get(&address).send().unwrap_err();
+        assert!(error.is_redirect());
+        handle.join().unwrap();
+    }
 
     #[test]
     fn test_hf_downloader_creation() {
         let settings = AppSettings::default();
-        let downloader = HuggingFaceModelDownloader::new(settings);
+        let downloader = HuggingFaceModelDownloader::new(settings, RunOptions::default());
         assert!(downloader.is_ok());
     }
 
+    #[test]
+    fn test_check_paging_limit_within_limit() {
+        assert!(check_paging_limit(1, 25).is_ok());
+        assert!(check_paging_limit(39, 25).is_ok());
+        assert!(check_paging_limit(9, 100).is_ok());
+        assert!(check_paging_limit(999, 1).is_ok());
+        assert!(check_paging_limit(333, 3).is_ok());
+    }
+
+    #[test]
+    fn test_check_paging_limit_beyond_limit() {
+        let err = check_paging_limit(40, 25).unwrap_err().to_string();
+        assert!(err.contains("exceeds this limit by 1 model(s)"));
+
+        let err = check_paging_limit(10, 100).unwrap_err().to_string();
+        assert!(err.contains("exceeds this limit by 1 model(s)"));
+
+        let err = check_paging_limit(1000, 1).unwrap_err().to_string();
+        assert!(err.contains("exceeds this limit by 1 model(s)"));
+
+        let err = check_paging_limit(11, 100).unwrap_err().to_string();
+        assert!(err.contains("exceeds this limit by 101 model(s)"));
+    }
+
+    #[test]
+    fn test_check_paging_limit_rejects_page_zero() {
+        assert!(check_paging_limit(0, 25).is_err());
+    }
+
+    #[test]
+    fn test_dataset_urls() {
+        let downloader =
+            HuggingFaceModelDownloader::new(AppSettings::default(), RunOptions::default())
+                .unwrap()
+                .with_repo_type(HfRepoType::Dataset);
+        assert_eq!(
+            downloader.make_manifest_url("user/repo", "Q4_K_M"),
+            "https://hf.co/v2/datasets/user/repo/manifests/Q4_K_M"
+        );
+        assert_eq!(
+            downloader.make_blob_url("user/repo", "sha256:abc123"),
+            "https://hf.co/v2/datasets/user/repo/blobs/sha256:abc123"
+        );
+        assert_eq!(HfRepoType::Dataset.api_collection(), "datasets");
+        assert_eq!(HfRepoType::Model.repo_path("user/repo"), "user/repo");
+    }
+
+    #[test]
+    fn test_catalog_filter() {
+        let downloader =
+            HuggingFaceModelDownloader::new(AppSettings::default(), RunOptions::default()).unwrap();
+        assert_eq!(
+            downloader.catalog_filter().unwrap(),
+            "apps=ollama&gated=false"
+        );
+
+        let downloader = downloader.with_catalog_filter(Some("llama.cpp".to_string()), false);
+        assert_eq!(
+            downloader.catalog_filter().unwrap(),
+            "apps=llama.cpp&gated=false"
+        );
+
+        // Gated models are only listed with a token
+        let downloader = downloader.with_catalog_filter(None, true);
+        assert!(downloader.catalog_filter().is_err());
+        let mut settings = AppSettings::default();
+        settings
+            .ollama_library
+            .extra_headers
+            .insert("authorization".to_string(), "Bearer hf_x".to_string());
+        let downloader = HuggingFaceModelDownloader::new(settings, RunOptions::default())
+            .unwrap()
+            .with_catalog_filter(None, true);
+        assert_eq!(downloader.catalog_filter().unwrap(), "apps=ollama");
+
+        let downloader = downloader
+            .with_catalog_filter(Some("llama.cpp".to_string()), false)
+            .with_repo_type(HfRepoType::Dataset);
+        assert_eq!(
+            downloader.catalog_filter().unwrap(),
+            "search=gguf&gated=false"
+        );
+
+        assert_eq!(parse_app_name(" llama.cpp ").unwrap(), "llama.cpp");
+        assert!(parse_app_name("ollama&gated=true").is_err());
+        assert!(parse_app_name("").is_err());
+    }
+
+    #[test]
+    fn test_blob_url_and_file_name_match_ollama() {
+        let downloader =
+            HuggingFaceModelDownloader::new(AppSettings::default(), RunOptions::default()).unwrap();
+        assert_eq!(
+            downloader.make_blob_url("user/repo", "sha256:abc123"),
+            "https://hf.co/v2/user/repo/blobs/sha256:abc123"
+        );
+        // Stored under the same name as the same digest downloaded from the Ollama library
+        assert_eq!(
+            crate::downloader::manifest::blob_file_name("sha256:abc123"),
+            "sha256-abc123"
+        );
+    }
+
+    #[test]
+    fn test_parse_quant_preference() {
+        assert_eq!(
+            parse_quant_preference("Smallest"),
+            Ok(QuantPreference::Smallest)
+        );
+        assert_eq!(
+            parse_quant_preference("largest"),
+            Ok(QuantPreference::Largest)
+        );
+        assert_eq!(
+            parse_quant_preference("q4"),
+            Ok(QuantPreference::Named("q4".to_string()))
+        );
+        assert!(parse_quant_preference(" ").is_err());
+    }
+
+    #[test]
+    fn test_choose_quant() {
+        let quants = vec![
+            ("Q4_0".to_string(), Some(400)),
+            ("Q4_K_M".to_string(), Some(450)),
+            ("Q8_0".to_string(), Some(800)),
+            ("Q2_K".to_string(), Some(200)),
+            ("F16".to_string(), None),
+        ];
+        assert_eq!(
+            choose_quant(&quants, &QuantPreference::Smallest),
+            Some("Q2_K")
+        );
+        assert_eq!(
+            choose_quant(&quants, &QuantPreference::Largest),
+            Some("Q8_0")
+        );
+        assert_eq!(
+            choose_quant(&quants, &QuantPreference::Named("q4_0".to_string())),
+            Some("Q4_0")
+        );
+        assert_eq!(
+            choose_quant(&quants, &QuantPreference::Named("q4".to_string())),
+            Some("Q4_K_M")
+        );
+        assert_eq!(
+            choose_quant(&quants, &QuantPreference::Named("f16".to_string())),
+            Some("F16")
+        );
+        assert_eq!(
+            choose_quant(&quants, &QuantPreference::Named("q5".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_since_date() {
+        assert!(parse_since_date("2026-02-20").is_ok());
+        assert!(parse_since_date("2026-02-20T09:30:00Z").is_ok());
+        assert!(parse_since_date("2026-02-20T09:30:00.123Z").is_ok());
+        assert!(parse_since_date("2026-13-01").is_err());
+        assert!(parse_since_date("20-02-2026").is_err());
+        assert!(parse_since_date("2026-02-20T09:30:00+01:00").is_err());
+    }
+
+    #[test]
+    fn test_filter_modified_since() {
+        let model = |id: &str, last_modified: Option<&str>| HfModel {
+            model_id: id.to_string(),
+            last_modified: last_modified.map(str::to_string),
+        };
+        let models = vec![
+            model("new", Some("2026-02-21T08:00:00.000Z")),
+            model("exact", Some("2026-02-20T09:30:00.000Z")),
+            model("old", Some("2026-02-19T23:59:59.000Z")),
+            model("unknown", None),
+        ];
+
+        let ids = |models: Vec<HfModel>| -> Vec<String> {
+            models.into_iter().map(|m| m.model_id).collect()
+        };
+        let filtered = filter_modified_since(models, "2026-02-20T09:30:00Z");
+        assert_eq!(ids(filtered), vec!["new", "exact"]);
+
+        let models = vec![
+            model("new", Some("2026-02-21T08:00:00.000Z")),
+            model("old", Some("2026-02-19T23:59:59.000Z")),
+        ];
+        assert_eq!(
+            ids(filter_modified_since(models, "2026-02-20")),
+            vec!["new"]
+        );
+    }
+
+    #[test]
+    fn test_next_page_link() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(next_page_link(&headers), None);
+
+        headers.insert(
+            "link",
+            "<https://huggingface.co/api/models?cursor=abc>; rel=\"next\""
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            next_page_link(&headers),
+            Some("https://huggingface.co/api/models?cursor=abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_model_names_in_ollama() {
+        let downloader =
+            HuggingFaceModelDownloader::new(AppSettings::default(), RunOptions::default()).unwrap();
+        let names = downloader.model_names_in_ollama("user/repo:Q4_K_M");
+        assert_eq!(
+            names,
+            vec![
+                "hf.co/user/repo:Q4_K_M".to_string(),
+                "huggingface.co/user/repo:Q4_K_M".to_string(),
+                "user/repo:Q4_K_M".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_manifest_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut settings = AppSettings::default();
+        settings.ollama_library.models_path = dir.path().to_string_lossy().into_owned();
+
+        // Ollama looks for hf.co/{username}/{repository}:{tag} in manifests/hf.co/{username}/{repository}/{tag}
+        let mut downloader =
+            HuggingFaceModelDownloader::new(settings.clone(), RunOptions::default()).unwrap();
+        let path = downloader.save_manifest("{}", "user/repo:Q4_K_M").unwrap();
+        assert_eq!(path, dir.path().join("manifests/hf.co/user/repo/Q4_K_M"));
+        assert!(path.is_file());
+
+        settings.ollama_library.hf_manifest_host = "huggingface.co".to_string();
+        let mut downloader =
+            HuggingFaceModelDownloader::new(settings, RunOptions::default()).unwrap();
+        let path = downloader.save_manifest("{}", "user/repo").unwrap();
+        assert_eq!(
+            path,
+            dir.path().join("manifests/huggingface.co/user/repo/latest")
+        );
+        assert_eq!(
+            downloader.model_names_in_ollama("user/repo:latest")[0],
+            "huggingface.co/user/repo:latest"
+        );
+    }
+
     #[test]
     #[ignore] // Run manually with: cargo test -- --ignored
     fn test_hf_model_download() {
@@ -530,8 +2052,8 @@ mod tests {
         let _ = env_logger::builder().is_test(true).try_init();
 
         let settings = AppSettings::default();
-        let downloader =
-            HuggingFaceModelDownloader::new(settings).expect("Failed to create downloader");
+        let downloader = HuggingFaceModelDownloader::new(settings, RunOptions::default())
+            .expect("Failed to create downloader");
 
         // Download a small model for testing
         let model_identifier = "unsloth/SmolLM2-135M-Instruct-GGUF:Q4_K_M";
@@ -540,9 +2062,9 @@ mod tests {
         let result = downloader.download_model(model_identifier);
 
         match result {
-            Ok(success) => {
-                assert!(success, "Download should return true on success");
-                println!("Successfully downloaded {}", model_identifier);
+            Ok(summary) => {
+                assert!(summary.blobs > 0, "Download should fetch BLOBs");
+                println!("Successfully downloaded {}", summary.line());
             }
             Err(e) => {
                 panic!("Download failed: {:?}", e);
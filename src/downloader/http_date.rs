@@ -0,0 +1,213 @@
+//! HTTP date handling for the Ollama Downloader in Rust (ODIR),
+//! deriving waits from server dates without trusting the local clock too much,
+//! and warning when the local clock is noticeably out of step with the servers.
+use log::warn;
+use reqwest::header::{DATE, HeaderMap, RETRY_AFTER};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest wait that a server can make us sleep for
+pub const MAX_SERVER_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// Difference between the server's and the local clock beyond which a warning is logged
+pub const CLOCK_SKEW_THRESHOLD: Duration = Duration::from_secs(2 * 60);
+
+/// Whether clock skew has been reported already, so that it is only reported once per run
+static CLOCK_SKEW_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Parse an HTTP date in the preferred IMF-fixdate format, e.g., `Sun, 06 Nov 1994 08:49:37 GMT`.
+///
+/// # Arguments
+/// * `value` - The date to parse
+///
+/// # Returns
+/// * `Option<SystemTime>` - The point in time, or None if the date is not valid
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (_, rest) = value.trim().split_once(", ")?;
+    let parts: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hours, minutes, seconds] = time.as_slice() else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || *hours > 23 || *minutes > 59 || *seconds > 60 {
+        return None;
+    }
+
+    // Convert the civil date to days since the epoch (proleptic Gregorian calendar)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146_097 + doe - 719_468).ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hours * 3_600 + minutes * 60 + seconds))
+}
+
+/// Get the wait requested by a `Retry-After` header, clamped to at most `MAX_SERVER_WAIT`.
+///
+/// A date is measured against the server's own `Date` header, if present, so that a skewed
+/// local clock cannot turn it into a pathological sleep.
+///
+/// # Arguments
+/// * `headers` - The headers of the response
+/// * `now` - The local time
+///
+/// # Returns
+/// * `Option<Duration>` - The wait, or None if there is no valid `Retry-After` header
+pub fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let wait = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let retry_at = parse_http_date(value)?;
+            let reference = server_date(headers).unwrap_or(now);
+            // A date in the past means that there is no need to wait
+            retry_at.duration_since(reference).unwrap_or(Duration::ZERO)
+        }
+    };
+
+    if wait > MAX_SERVER_WAIT {
+        warn!(
+            "Server asked to wait {} seconds before retrying, waiting {} seconds instead",
+            wait.as_secs(),
+            MAX_SERVER_WAIT.as_secs()
+        );
+        return Some(MAX_SERVER_WAIT);
+    }
+    Some(wait)
+}
+
+/// Get the time of a response according to the server's `Date` header.
+fn server_date(headers: &HeaderMap) -> Option<SystemTime> {
+    parse_http_date(headers.get(DATE)?.to_str().ok()?)
+}
+
+/// Measure how far the local clock is from the server's `Date` header.
+///
+/// # Arguments
+/// * `headers` - The headers of the response
+/// * `now` - The local time
+///
+/// # Returns
+/// * `Option<(Duration, bool)>` - The difference and whether the local clock is ahead,
+///   or None if there is no valid `Date` header
+pub fn clock_skew(headers: &HeaderMap, now: SystemTime) -> Option<(Duration, bool)> {
+    let server_now = server_date(headers)?;
+    Some(match now.duration_since(server_now) {
+        Ok(ahead) => (ahead, true),
+        Err(e) => (e.duration(), false),
+    })
+}
+
+/// Warn, once per run, if the local clock differs from the server's `Date` header by more
+/// than `CLOCK_SKEW_THRESHOLD`, since such skew distorts anything derived from server dates.
+///
+/// # Arguments
+/// * `headers` - The headers of the response
+pub fn warn_on_clock_skew(headers: &HeaderMap) {
+    let Some((skew, ahead)) = clock_skew(headers, SystemTime::now()) else {
+        return;
+    };
+    if skew > CLOCK_SKEW_THRESHOLD && !CLOCK_SKEW_REPORTED.swap(true, Ordering::Relaxed) {
+        warn!(
+            "The local clock is {} seconds {} the server's clock. Check the system time, as waits and cache decisions based on server dates may be off.",
+            skew.as_secs(),
+            if ahead { "ahead of" } else { "behind" }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(entries: &[(reqwest::header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"),
+            Some(UNIX_EPOCH)
+        );
+        assert_eq!(
+            parse_http_date("Fri, 20 Feb 2026 09:30:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_771_579_800))
+        );
+        assert_eq!(parse_http_date("Fri, 20 Feb 2026 09:30:00 UTC"), None);
+        assert_eq!(parse_http_date("Fri, 20 Foo 2026 09:30:00 GMT"), None);
+        assert_eq!(parse_http_date("Fri, 20 Feb 2026 25:30:00 GMT"), None);
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_771_579_800);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+        assert_eq!(
+            retry_after(&headers(&[(RETRY_AFTER, "30")]), now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            retry_after(&headers(&[(RETRY_AFTER, "86400")]), now),
+            Some(MAX_SERVER_WAIT)
+        );
+
+        // Dates are measured against the server's clock, not the skewed local one
+        let skewed_now = now + Duration::from_secs(3_600);
+        let response_headers = headers(&[
+            (RETRY_AFTER, "Fri, 20 Feb 2026 09:31:00 GMT"),
+            (DATE, "Fri, 20 Feb 2026 09:30:00 GMT"),
+        ]);
+        assert_eq!(
+            retry_after(&response_headers, skewed_now),
+            Some(Duration::from_secs(60))
+        );
+
+        // Without a server date, a date in the past means no wait
+        assert_eq!(
+            retry_after(
+                &headers(&[(RETRY_AFTER, "Fri, 20 Feb 2026 09:29:00 GMT")]),
+                now
+            ),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_clock_skew() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_771_579_800);
+        let response_headers = headers(&[(DATE, "Fri, 20 Feb 2026 09:20:00 GMT")]);
+        assert_eq!(
+            clock_skew(&response_headers, now),
+            Some((Duration::from_secs(600), true))
+        );
+        let response_headers = headers(&[(DATE, "Fri, 20 Feb 2026 09:31:00 GMT")]);
+        assert_eq!(
+            clock_skew(&response_headers, now),
+            Some((Duration::from_secs(60), false))
+        );
+        assert_eq!(clock_skew(&HeaderMap::new(), now), None);
+    }
+}
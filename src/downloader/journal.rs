@@ -0,0 +1,277 @@
+//! Download journal for the Ollama Downloader in Rust (ODIR),
+//! recording a provenance entry for every successfully downloaded model
+//! in `<models_path>/.odir/journal.jsonl`.
+use crate::downloader::manifest::ImageManifest;
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use crate::downloader::utils::{Ownership, ensure_ownership, expand_models_path};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the ODIR metadata directory inside the models path
+const ODIR_DIR: &str = ".odir";
+
+/// Name of the journal file inside the ODIR metadata directory
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// A single entry in the download journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Time of the download in RFC 3339 format (UTC)
+    pub timestamp: String,
+
+    /// The downloaded model identifier, e.g., llama3.1:8b
+    pub identifier: String,
+
+    /// Total size of all downloaded BLOBs in bytes
+    pub total_bytes: u64,
+
+    /// Digests of the configuration and layer BLOBs
+    pub digests: Vec<String>,
+
+    /// Host of the registry the model was downloaded from
+    pub source: String,
+
+    /// Whether the digests of the BLOBs were verified, which is only not the case
+    /// if verification was disabled with `--no-verify-digest`
+    #[serde(default = "default_digest_verified")]
+    pub digest_verified: bool,
+
+    /// Time taken by the download in seconds, which entries recorded before it was
+    /// measured lack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_secs: Option<f64>,
+}
+
+/// Entries recorded before digest verification could be disabled were always verified
+fn default_digest_verified() -> bool {
+    true
+}
+
+impl JournalEntry {
+    /// Create a journal entry for a downloaded manifest, timestamped now.
+    ///
+    /// # Arguments
+    /// * `identifier` - The downloaded model identifier
+    /// * `source` - Host of the registry the model was downloaded from
+    /// * `manifest` - The downloaded image manifest
+    ///
+    /// # Returns
+    /// * `Self` - The journal entry
+    pub fn from_manifest(identifier: &str, source: &str, manifest: &ImageManifest) -> Self {
+        let digests = std::iter::once(manifest.config.digest.clone())
+            .chain(manifest.unique_layers().iter().map(|l| l.digest.clone()))
+            .collect();
+
+        Self {
+            timestamp: format_rfc3339_utc(SystemTime::now()),
+            identifier: identifier.to_string(),
+            total_bytes: manifest.total_size(),
+            digests,
+            source: source.to_string(),
+            digest_verified: true,
+            elapsed_secs: None,
+        }
+    }
+}
+
+/// Get the path of the journal file for a models path.
+///
+/// # Arguments
+/// * `models_path` - Path to the Ollama models directory
+///
+/// # Returns
+/// * `Result<PathBuf>` - Path to the journal file
+pub fn journal_file_path(models_path: &str) -> Result<PathBuf> {
+    Ok(expand_models_path(models_path)?
+        .join(ODIR_DIR)
+        .join(JOURNAL_FILE))
+}
+
+/// Append an entry to the download journal, creating the journal if needed.
+///
+/// # Arguments
+/// * `models_path` - Path to the Ollama models directory
+/// * `entry` - The entry to append
+/// * `models_dir_ownership` - Ownership to apply to the journal when running as root
+///
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn append_entry(
+    models_path: &str,
+    entry: &JournalEntry,
+    models_dir_ownership: Option<Ownership>,
+) -> Result<()> {
+    let models_root = expand_models_path(models_path)?;
+    let journal_file = journal_file_path(models_path)?;
+    if let Some(journal_dir) = journal_file.parent()
+        && !journal_dir.exists()
+    {
+        fs::create_dir_all(journal_dir)?;
+        if let Some(ownership) = models_dir_ownership {
+            ensure_ownership(&models_root, journal_dir, ownership);
+        }
+    }
+
+    let line = serde_json::to_string(entry)
+        .map_err(|e| DownloaderError::Other(format!("Failed to serialize journal entry: {}", e)))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&journal_file)?;
+    writeln!(file, "{}", line)?;
+
+    if let Some(ownership) = models_dir_ownership {
+        ensure_ownership(&models_root, &journal_file, ownership);
+    }
+    debug!(
+        "Appended {} to journal {:?}",
+        entry.identifier, journal_file
+    );
+
+    Ok(())
+}
+
+/// Read all entries from the download journal, oldest first.
+///
+/// Lines that cannot be parsed are skipped with a warning.
+///
+/// # Arguments
+/// * `models_path` - Path to the Ollama models directory
+///
+/// # Returns
+/// * `Result<Vec<JournalEntry>>` - The journal entries, empty if there is no journal
+pub fn read_entries(models_path: &str) -> Result<Vec<JournalEntry>> {
+    let journal_file = journal_file_path(models_path)?;
+    if !journal_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&journal_file)?;
+    let entries = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(
+            |(index, line)| match serde_json::from_str::<JournalEntry>(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!(
+                        "Skipping malformed journal line {} in {:?}: {}",
+                        index + 1,
+                        journal_file,
+                        e
+                    );
+                    None
+                }
+            },
+        )
+        .collect();
+
+    Ok(entries)
+}
+
+/// Format a point in time as an RFC 3339 timestamp in UTC, e.g., 2026-02-20T09:30:00Z.
+///
+/// # Arguments
+/// * `time` - The point in time to format
+///
+/// # Returns
+/// * `String` - The formatted timestamp
+pub fn format_rfc3339_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+    let secs_of_day = secs % 86_400;
+
+    // Convert days since the epoch to a civil date (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        (secs_of_day % 3_600) / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::downloader::manifest::{ImageManifestConfig, ImageManifestLayerEntry};
+    use std::time::Duration;
+
+    fn test_manifest() -> ImageManifest {
+        ImageManifest {
+            schema_version: 2,
+            media_type: "application/vnd.docker.distribution.manifest.v2+json".to_string(),
+            config: ImageManifestConfig {
+                media_type: "application/vnd.docker.container.image.v1+json".to_string(),
+                size: 100,
+                digest: "sha256:config".to_string(),
+            },
+            layers: Some(vec![ImageManifestLayerEntry {
+                media_type: "application/vnd.ollama.image.model".to_string(),
+                size: 1000,
+                digest: "sha256:model".to_string(),
+                urls: None,
+            }]),
+        }
+    }
+
+    #[test]
+    fn test_format_rfc3339_utc() {
+        assert_eq!(format_rfc3339_utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let time = UNIX_EPOCH + Duration::from_secs(1_771_579_800);
+        assert_eq!(format_rfc3339_utc(time), "2026-02-20T09:30:00Z");
+    }
+
+    #[test]
+    fn test_entry_from_manifest() {
+        let entry = JournalEntry::from_manifest("test:latest", "registry.test", &test_manifest());
+        assert_eq!(entry.identifier, "test:latest");
+        assert_eq!(entry.total_bytes, 1100);
+        assert_eq!(entry.digests, vec!["sha256:config", "sha256:model"]);
+        assert_eq!(entry.source, "registry.test");
+        assert!(entry.digest_verified);
+    }
+
+    #[test]
+    fn test_append_and_read_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let models_path = dir.path().to_str().unwrap();
+        assert!(read_entries(models_path).unwrap().is_empty());
+
+        let entry = JournalEntry::from_manifest("test:latest", "registry.test", &test_manifest());
+        append_entry(models_path, &entry, None).unwrap();
+        append_entry(models_path, &entry, None).unwrap();
+
+        let entries = read_entries(models_path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].identifier, "test:latest");
+    }
+
+    #[test]
+    fn test_entries_without_digest_verified_were_verified() {
+        let line = r#"{"timestamp":"2026-02-20T09:30:00Z","identifier":"test:latest","total_bytes":1,"digests":[],"source":"registry.test"}"#;
+        let entry: JournalEntry = serde_json::from_str(line).unwrap();
+        assert!(entry.digest_verified);
+        assert_eq!(entry.elapsed_secs, None);
+    }
+}
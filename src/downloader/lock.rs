@@ -0,0 +1,228 @@
+//! Advisory lock on the models path for the Ollama Downloader in Rust (ODIR), so that
+//! processes downloading into the same models path, e.g., in CI that provisions models in
+//! parallel, do not race on the same files. The lock is the file
+//! `<models_path>/.odir/lock`, which holds the PID of its owner and when it was taken.
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use crate::downloader::utils::{Ownership, ensure_ownership};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the ODIR metadata directory inside the models path
+const ODIR_DIR: &str = ".odir";
+
+/// Name of the lock file inside the ODIR metadata directory
+const LOCK_FILE: &str = "lock";
+
+/// Age after which a lock is stale, even if its owner cannot be checked
+const STALE_LOCK_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Age after which a lock that cannot be read is stale, leaving its owner time to write it
+const UNREADABLE_LOCK_AGE: Duration = Duration::from_secs(10);
+
+/// The owner of a lock, as written into the lock file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LockOwner {
+    /// The process ID of the owner
+    pid: u32,
+
+    /// When the lock was taken, in seconds since the UNIX epoch
+    timestamp: u64,
+}
+
+/// An advisory lock on a models path, released when dropped
+#[derive(Debug)]
+pub struct ModelsPathLock {
+    path: PathBuf,
+}
+
+impl ModelsPathLock {
+    /// Take the lock on a models path. A lock left behind by a process that no longer runs,
+    /// or taken more than a day ago, is stale and taken over with a warning.
+    ///
+    /// # Arguments
+    /// * `models_root` - Path to the Ollama models directory
+    /// * `models_dir_ownership` - The ownership to give the lock file, if any
+    ///
+    /// # Returns
+    /// * `Result<Self>` - The lock, or an error naming the process that holds it
+    pub fn acquire(models_root: &Path, models_dir_ownership: Option<Ownership>) -> Result<Self> {
+        let lock_dir = models_root.join(ODIR_DIR);
+        if !lock_dir.exists() {
+            fs::create_dir_all(&lock_dir)?;
+            if let Some(ownership) = models_dir_ownership {
+                ensure_ownership(models_root, &lock_dir, ownership);
+            }
+        }
+        let path = lock_dir.join(LOCK_FILE);
+        let owner = LockOwner {
+            pid: std::process::id(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+
+        // Try again once if a stale lock was removed, in case another process took it first
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let content = serde_json::to_string(&owner).map_err(|e| {
+                        DownloaderError::Other(format!("Failed to serialize lock: {}", e))
+                    })?;
+                    file.write_all(content.as_bytes())?;
+                    if let Some(ownership) = models_dir_ownership {
+                        ensure_ownership(models_root, &path, ownership);
+                    }
+                    debug!("Locked models path with {:?}", path);
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let holder = read_owner(&path);
+                    if !is_stale(&path, holder.as_ref()) {
+                        let holder = match holder {
+                            Some(holder) => format!("process {}", holder.pid),
+                            None => "another process".to_string(),
+                        };
+                        return Err(DownloaderError::Other(format!(
+                            "The models path is locked by {} ({:?}). Wait for it to finish, or pass --no-lock to download anyway",
+                            holder, path
+                        )));
+                    }
+                    warn!("Removing stale lock {:?} of {:?}", path, holder);
+                    match fs::remove_file(&path) {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(DownloaderError::Other(format!(
+            "Failed to lock the models path with {:?}, which another process keeps taking",
+            path
+        )))
+    }
+}
+
+impl Drop for ModelsPathLock {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.path) {
+            Ok(()) => debug!("Unlocked models path"),
+            Err(e) => warn!("Failed to remove lock {:?}: {}", self.path, e),
+        }
+    }
+}
+
+/// Take the lock on a models path for a download, unless locking is turned off.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `models_dir_ownership` - The ownership to give the lock file, if any
+/// * `no_lock` - Whether locking is turned off
+///
+/// # Returns
+/// * `Result<Option<ModelsPathLock>>` - The lock, None if locking is turned off, or an error
+///   naming the process that holds it
+pub fn lock_models_path(
+    models_root: &Path,
+    models_dir_ownership: Option<Ownership>,
+    no_lock: bool,
+) -> Result<Option<ModelsPathLock>> {
+    if no_lock {
+        debug!("Not locking the models path");
+        return Ok(None);
+    }
+    ModelsPathLock::acquire(models_root, models_dir_ownership).map(Some)
+}
+
+/// Read the owner of a lock, if the lock file can be read and parsed.
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Check whether a lock is stale: its owner no longer runs, it was taken more than a day
+/// ago, or it cannot be read and is older than its owner needs to write it.
+fn is_stale(path: &Path, owner: Option<&LockOwner>) -> bool {
+    let Some(owner) = owner else {
+        return fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > UNREADABLE_LOCK_AGE);
+    };
+    let age = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .saturating_sub(Duration::from_secs(owner.timestamp));
+    age > STALE_LOCK_AGE || !is_process_running(owner.pid)
+}
+
+/// Check whether a process runs, assuming that it does if this cannot be checked.
+fn is_process_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return true;
+        };
+        // Signal 0 only checks whether the process exists
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            return true;
+        }
+        io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = ModelsPathLock::acquire(dir.path(), None).unwrap();
+        let owner = read_owner(&dir.path().join(".odir/lock")).unwrap();
+        assert_eq!(owner.pid, std::process::id());
+
+        let err = ModelsPathLock::acquire(dir.path(), None).unwrap_err();
+        assert!(err.to_string().contains("--no-lock"));
+
+        drop(lock);
+        assert!(!dir.path().join(".odir/lock").exists());
+        assert!(ModelsPathLock::acquire(dir.path(), None).is_ok());
+    }
+
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".odir")).unwrap();
+        let path = dir.path().join(".odir/lock");
+        let old = LockOwner {
+            pid: std::process::id(),
+            timestamp: 0,
+        };
+        fs::write(&path, serde_json::to_string(&old).unwrap()).unwrap();
+
+        let _lock = ModelsPathLock::acquire(dir.path(), None).unwrap();
+        assert_ne!(read_owner(&path).unwrap().timestamp, 0);
+    }
+
+    #[test]
+    fn test_unreadable_lock_is_fresh_at_first() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".odir")).unwrap();
+        let path = dir.path().join(".odir/lock");
+        fs::write(&path, "").unwrap();
+        assert!(!is_stale(&path, None));
+    }
+}
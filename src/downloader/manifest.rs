@@ -1,6 +1,64 @@
 //! Data models for the Ollama Downloader in Rust (ODIR),
 //! including the image manifest structure based on the OCI Image Manifest specification.
+use crate::downloader::model_downloader::{DownloaderError, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Media types of layers that hold model weights Ollama can run
+pub const MODEL_LAYER_MEDIA_TYPES: &[&str] = &["application/vnd.ollama.image.model"];
+
+/// Media types of the image configuration that Ollama understands
+pub const OLLAMA_CONFIG_MEDIA_TYPES: &[&str] = &["application/vnd.docker.container.image.v1+json"];
+
+/// Prefix of the media types of all layers that Ollama understands
+pub const OLLAMA_LAYER_MEDIA_TYPE_PREFIX: &str = "application/vnd.ollama.image.";
+
+/// The only manifest schema version ODIR understands, shared by the Docker image manifest v2,
+/// schema 2, and the OCI image manifest
+pub const SUPPORTED_SCHEMA_VERSION: u32 = 2;
+
+/// How the digest of a BLOB is written in its URL
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DigestFormat {
+    /// `sha256-<hex>`, as Ollama requests and stores BLOBs
+    #[default]
+    Dashed,
+
+    /// `sha256:<hex>`, as in the OCI distribution specification
+    Plain,
+}
+
+/// Get the file name under which Ollama stores a BLOB, e.g., `sha256-<hex>`.
+///
+/// The same file name results from `sha256:<hex>` and `sha256-<hex>`, so that a BLOB
+/// is stored only once, whichever source it was downloaded from.
+///
+/// # Arguments
+/// * `digest` - The digest of the BLOB
+///
+/// # Returns
+/// * `String` - The file name of the BLOB in the `blobs` directory
+pub fn blob_file_name(digest: &str) -> String {
+    digest.replacen(':', "-", 1)
+}
+
+/// Construct the URL of a BLOB in a registry.
+///
+/// # Arguments
+/// * `base_url` - Base URL of the registry, ending with a slash
+/// * `repository` - The repository of the model, e.g., llama3.1 or user/repo
+/// * `digest` - The digest of the BLOB
+/// * `format` - How the digest is written in the URL
+///
+/// # Returns
+/// * `String` - The URL of the BLOB
+pub fn blob_url(base_url: &str, repository: &str, digest: &str, format: DigestFormat) -> String {
+    let digest = match format {
+        DigestFormat::Dashed => blob_file_name(digest),
+        DigestFormat::Plain => digest.replacen('-', ":", 1),
+    };
+    format!("{}{}/blobs/{}", base_url, repository, digest)
+}
 
 /// Configuration section of the image manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,3 +110,450 @@ pub struct ImageManifest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub layers: Option<Vec<ImageManifestLayerEntry>>,
 }
+
+impl ImageManifest {
+    /// Parse a manifest, reporting a manifest of another schema version, e.g., the Docker
+    /// schema 1 that some old private registries still serve, as unsupported, instead of
+    /// failing with a parse error about its missing fields.
+    ///
+    /// # Arguments
+    /// * `json` - The manifest as JSON
+    ///
+    /// # Returns
+    /// * `Result<Self>` - The parsed manifest, or an error if it cannot be parsed or is of
+    ///   another schema version
+    pub fn parse(json: &str) -> Result<Self> {
+        let unsupported = |version: u64| {
+            DownloaderError::InvalidManifest(format!(
+                "unsupported manifest schema {}: ODIR only supports schema {} manifests, i.e., \
+                Docker image manifests v2, schema 2, and OCI image manifests. Please upgrade \
+                your registry, or configure it to serve schema {} manifests",
+                version, SUPPORTED_SCHEMA_VERSION, SUPPORTED_SCHEMA_VERSION
+            ))
+        };
+        match serde_json::from_str::<Self>(json) {
+            Ok(manifest) if manifest.schema_version == SUPPORTED_SCHEMA_VERSION => Ok(manifest),
+            Ok(manifest) => Err(unsupported(manifest.schema_version.into())),
+            Err(e) => {
+                let version = serde_json::from_str::<serde_json::Value>(json)
+                    .ok()
+                    .and_then(|value| value.get("schemaVersion")?.as_u64());
+                match version {
+                    Some(version) if version != u64::from(SUPPORTED_SCHEMA_VERSION) => {
+                        Err(unsupported(version))
+                    }
+                    _ => Err(DownloaderError::ParseError(format!(
+                        "Failed to parse manifest: {}",
+                        e
+                    ))),
+                }
+            }
+        }
+    }
+
+    /// Get the layers of the manifest with distinct digests, in manifest order.
+    ///
+    /// Layers that share the digest of the configuration or of an earlier layer refer to the
+    /// same BLOB, so they are left out to download and store each BLOB only once.
+    ///
+    /// # Returns
+    /// * `Vec<&ImageManifestLayerEntry>` - The layers with distinct digests
+    pub fn unique_layers(&self) -> Vec<&ImageManifestLayerEntry> {
+        let mut seen_digests = HashSet::from([self.config.digest.as_str()]);
+        self.layers
+            .iter()
+            .flatten()
+            .filter(|layer| seen_digests.insert(layer.digest.as_str()))
+            .collect()
+    }
+
+    /// Compute the total size of the distinct BLOBs referenced by the manifest.
+    ///
+    /// # Returns
+    /// * `u64` - The total size in bytes
+    pub fn total_size(&self) -> u64 {
+        self.config.size
+            + self
+                .unique_layers()
+                .iter()
+                .map(|layer| layer.size)
+                .sum::<u64>()
+    }
+
+    /// Check whether the manifest references at least one layer holding model weights.
+    ///
+    /// # Returns
+    /// * `bool` - True if a layer has one of the [`MODEL_LAYER_MEDIA_TYPES`]
+    pub fn has_model_layer(&self) -> bool {
+        self.layers.iter().flatten().any(|layer| {
+            MODEL_LAYER_MEDIA_TYPES
+                .iter()
+                .any(|media_type| layer.media_type == *media_type)
+        })
+    }
+
+    /// Get the media types in the manifest that Ollama does not understand.
+    ///
+    /// # Returns
+    /// * `Vec<&str>` - The distinct unknown media types of the configuration and the layers,
+    ///   in manifest order, empty if Ollama understands the manifest
+    pub fn unsupported_media_types(&self) -> Vec<&str> {
+        let mut unsupported = Vec::new();
+        if !OLLAMA_CONFIG_MEDIA_TYPES.contains(&self.config.media_type.as_str()) {
+            unsupported.push(self.config.media_type.as_str());
+        }
+        for layer in self.layers.iter().flatten() {
+            let media_type = layer.media_type.as_str();
+            if !media_type.starts_with(OLLAMA_LAYER_MEDIA_TYPE_PREFIX)
+                && !unsupported.contains(&media_type)
+            {
+                unsupported.push(media_type);
+            }
+        }
+        unsupported
+    }
+
+    /// Get the distinct BLOBs referenced by the manifest: the configuration, followed by the
+    /// [`unique_layers`](Self::unique_layers).
+    ///
+    /// # Returns
+    /// * `Vec<ManifestBlob>` - The BLOBs, in manifest order
+    pub fn blobs(&self) -> Vec<ManifestBlob> {
+        std::iter::once(ManifestBlob {
+            media_type: self.config.media_type.clone(),
+            digest: self.config.digest.clone(),
+            size: self.config.size,
+        })
+        .chain(self.unique_layers().into_iter().map(|layer| ManifestBlob {
+            media_type: layer.media_type.clone(),
+            digest: layer.digest.clone(),
+            size: layer.size,
+        }))
+        .collect()
+    }
+
+    /// Compare this manifest, the local one, with a remote manifest of the same model.
+    ///
+    /// BLOBs with the same digest in both manifests are unchanged. Of the others, a BLOB is
+    /// changed if it is the only one of its media type in each manifest, e.g., the weights
+    /// of a retrained model, and added or removed otherwise.
+    ///
+    /// # Arguments
+    /// * `remote` - The remote manifest
+    ///
+    /// # Returns
+    /// * `ManifestDiff` - The BLOBs that were added, removed or changed
+    pub fn diff(&self, remote: &ImageManifest) -> ManifestDiff {
+        let local_blobs = self.blobs();
+        let remote_blobs = remote.blobs();
+        let local_digests: HashSet<&str> = local_blobs.iter().map(|b| b.digest.as_str()).collect();
+        let remote_digests: HashSet<&str> =
+            remote_blobs.iter().map(|b| b.digest.as_str()).collect();
+
+        let mut removed: Vec<ManifestBlob> = local_blobs
+            .iter()
+            .filter(|blob| !remote_digests.contains(blob.digest.as_str()))
+            .cloned()
+            .collect();
+        let mut added: Vec<ManifestBlob> = remote_blobs
+            .iter()
+            .filter(|blob| !local_digests.contains(blob.digest.as_str()))
+            .cloned()
+            .collect();
+
+        let count = |blobs: &[ManifestBlob], media_type: &str| {
+            blobs
+                .iter()
+                .filter(|blob| blob.media_type == media_type)
+                .count()
+        };
+        let mut changed = Vec::new();
+        let mut index = 0;
+        while index < added.len() {
+            let media_type = added[index].media_type.clone();
+            let replaced = removed
+                .iter()
+                .position(|blob| blob.media_type == media_type)
+                .filter(|_| {
+                    count(&local_blobs, &media_type) == 1 && count(&remote_blobs, &media_type) == 1
+                });
+            match replaced {
+                Some(position) => changed.push(BlobChange {
+                    media_type,
+                    local: removed.remove(position),
+                    remote: added.remove(index),
+                }),
+                None => index += 1,
+            }
+        }
+
+        ManifestDiff {
+            added,
+            removed,
+            changed,
+        }
+    }
+}
+
+/// A BLOB referenced by a manifest, either its configuration or one of its layers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestBlob {
+    /// The media type of the BLOB
+    pub media_type: String,
+
+    /// The digest of the BLOB
+    pub digest: String,
+
+    /// The size of the BLOB in bytes
+    pub size: u64,
+}
+
+/// A BLOB of the same media type whose digest differs between two manifests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlobChange {
+    /// The media type of the BLOB
+    pub media_type: String,
+
+    /// The BLOB referenced by the local manifest
+    pub local: ManifestBlob,
+
+    /// The BLOB referenced by the remote manifest
+    pub remote: ManifestBlob,
+}
+
+/// The difference between the local and the remote manifest of a model, by digest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ManifestDiff {
+    /// BLOBs that only the remote manifest references
+    pub added: Vec<ManifestBlob>,
+
+    /// BLOBs that only the local manifest references
+    pub removed: Vec<ManifestBlob>,
+
+    /// BLOBs of a media type that both manifests reference once, but with different digests
+    pub changed: Vec<BlobChange>,
+}
+
+impl ManifestDiff {
+    /// Check whether both manifests reference the same BLOBs.
+    ///
+    /// # Returns
+    /// * `bool` - True if no BLOB was added, removed or changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_other_schema_versions() {
+        let manifest = ImageManifest::parse(
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 2, "digest": "sha256:c"},
+                "layers": [{"mediaType": "application/vnd.ollama.image.model", "size": 5, "digest": "sha256:m"}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.total_size(), 7);
+
+        let schema1 = r#"{
+            "schemaVersion": 1,
+            "name": "library/model",
+            "tag": "latest",
+            "architecture": "amd64",
+            "fsLayers": [{"blobSum": "sha256:m"}],
+            "history": [{"v1Compatibility": "{}"}]
+        }"#;
+        let err = ImageManifest::parse(schema1).unwrap_err();
+        assert!(matches!(err, DownloaderError::InvalidManifest(_)));
+        assert!(
+            err.to_string().contains("unsupported manifest schema 1"),
+            "{}",
+            err
+        );
+
+        let schema3 = r#"{
+            "schemaVersion": 3,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 2, "digest": "sha256:c"}
+        }"#;
+        assert!(
+            ImageManifest::parse(schema3)
+                .unwrap_err()
+                .to_string()
+                .contains("unsupported manifest schema 3")
+        );
+
+        let err = ImageManifest::parse(r#"{"schemaVersion": 2}"#).unwrap_err();
+        assert!(matches!(err, DownloaderError::ParseError(_)));
+        assert!(ImageManifest::parse("not json").is_err());
+    }
+
+    fn manifest_with_layers(layers: Option<Vec<&str>>) -> ImageManifest {
+        ImageManifest {
+            schema_version: 2,
+            media_type: "application/vnd.docker.distribution.manifest.v2+json".to_string(),
+            config: ImageManifestConfig {
+                media_type: "application/vnd.docker.container.image.v1+json".to_string(),
+                size: 1,
+                digest: "sha256:config".to_string(),
+            },
+            layers: layers.map(|media_types| {
+                media_types
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, media_type)| ImageManifestLayerEntry {
+                        media_type: media_type.to_string(),
+                        size: 1,
+                        digest: format!("sha256:layer{}", index),
+                        urls: None,
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let local = manifest_with_layers(Some(vec![
+            "application/vnd.ollama.image.model",
+            "application/vnd.ollama.image.license",
+            "application/vnd.ollama.image.license",
+        ]));
+        assert!(local.diff(&local).is_empty());
+
+        let mut remote = local.clone();
+        let layers = remote.layers.as_mut().unwrap();
+        layers[0].digest = "sha256:retrained".to_string();
+        layers[2].digest = "sha256:newlicense".to_string();
+        layers.push(ImageManifestLayerEntry {
+            media_type: "application/vnd.ollama.image.template".to_string(),
+            size: 1,
+            digest: "sha256:template".to_string(),
+            urls: None,
+        });
+
+        let diff = local.diff(&remote);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].local.digest, "sha256:layer0");
+        assert_eq!(diff.changed[0].remote.digest, "sha256:retrained");
+        // Licenses are not paired up, since there is more than one of them
+        let digests = |blobs: &[ManifestBlob]| -> Vec<String> {
+            blobs.iter().map(|blob| blob.digest.clone()).collect()
+        };
+        assert_eq!(
+            digests(&diff.added),
+            vec!["sha256:newlicense", "sha256:template"]
+        );
+        assert_eq!(digests(&diff.removed), vec!["sha256:layer2"]);
+    }
+
+    #[test]
+    fn test_has_model_layer() {
+        let manifest = manifest_with_layers(Some(vec![
+            "application/vnd.ollama.image.model",
+            "application/vnd.ollama.image.template",
+        ]));
+        assert!(manifest.has_model_layer());
+    }
+
+    #[test]
+    fn test_has_model_layer_without_model() {
+        assert!(!manifest_with_layers(None).has_model_layer());
+        assert!(!manifest_with_layers(Some(vec![])).has_model_layer());
+        assert!(
+            !manifest_with_layers(Some(vec!["application/vnd.ollama.image.license"]))
+                .has_model_layer()
+        );
+    }
+
+    #[test]
+    fn test_unsupported_media_types() {
+        let manifest = manifest_with_layers(Some(vec![
+            "application/vnd.ollama.image.model",
+            "application/vnd.ollama.image.template",
+        ]));
+        assert!(manifest.unsupported_media_types().is_empty());
+
+        let mut manifest = manifest_with_layers(Some(vec![
+            "application/vnd.ollama.image.model",
+            "application/octet-stream",
+            "application/octet-stream",
+        ]));
+        manifest.config.media_type = "application/vnd.oci.image.config.v1+json".to_string();
+        assert_eq!(
+            manifest.unsupported_media_types(),
+            vec![
+                "application/vnd.oci.image.config.v1+json",
+                "application/octet-stream"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_total_size() {
+        assert_eq!(manifest_with_layers(None).total_size(), 1);
+        let manifest = manifest_with_layers(Some(vec![
+            "application/vnd.ollama.image.model",
+            "application/vnd.ollama.image.template",
+        ]));
+        assert_eq!(manifest.total_size(), 3);
+    }
+
+    #[test]
+    fn test_unique_layers_skips_repeated_digests() {
+        let mut manifest = manifest_with_layers(Some(vec![
+            "application/vnd.ollama.image.model",
+            "application/vnd.ollama.image.template",
+        ]));
+        let layers = manifest.layers.as_mut().unwrap();
+        let mut repeated = layers[0].clone();
+        repeated.media_type = "application/vnd.ollama.image.projector".to_string();
+        layers.push(repeated);
+        let mut config_copy = layers[1].clone();
+        config_copy.digest = "sha256:config".to_string();
+        layers.push(config_copy);
+
+        let unique_digests: Vec<&str> = manifest
+            .unique_layers()
+            .iter()
+            .map(|layer| layer.digest.as_str())
+            .collect();
+        assert_eq!(unique_digests, vec!["sha256:layer0", "sha256:layer1"]);
+        assert_eq!(manifest.total_size(), 3);
+    }
+
+    #[test]
+    fn test_blob_file_name_is_independent_of_the_source() {
+        // Hugging Face manifests and Ollama manifests refer to the same content alike
+        assert_eq!(blob_file_name("sha256:abc123"), "sha256-abc123");
+        assert_eq!(blob_file_name("sha256-abc123"), "sha256-abc123");
+    }
+
+    #[test]
+    fn test_blob_url() {
+        assert_eq!(
+            blob_url(
+                "https://registry.test/v2/library/",
+                "model",
+                "sha256:abc123",
+                DigestFormat::Dashed
+            ),
+            "https://registry.test/v2/library/model/blobs/sha256-abc123"
+        );
+        assert_eq!(
+            blob_url(
+                "https://hf.test/v2/",
+                "user/repo",
+                "sha256:abc123",
+                DigestFormat::Plain
+            ),
+            "https://hf.test/v2/user/repo/blobs/sha256:abc123"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Manifest cache for the Ollama Downloader in Rust (ODIR),
+//! keeping the last fetched manifest of each model along with its `ETag`,
+//! so that manifests that have not changed are not downloaded again.
+use crate::downloader::events::emit_event;
+use crate::downloader::http_date;
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use crate::downloader::utils::{ActivitySpinner, TracedRequest, write_file_atomically};
+use log::{debug, info, warn};
+use reqwest::StatusCode;
+use reqwest::blocking::Client;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+/// A cached manifest along with the `ETag` it was served with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedManifest {
+    /// The URL the manifest was fetched from
+    pub url: String,
+
+    /// The `ETag` of the manifest response
+    pub etag: String,
+
+    /// The manifest JSON
+    pub manifest: String,
+}
+
+/// Cache of manifests, with a file per manifest URL
+#[derive(Debug, Clone)]
+pub struct ManifestCache {
+    dir: PathBuf,
+}
+
+impl ManifestCache {
+    /// Create a manifest cache in a directory, which is created when the first manifest is stored.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory of the cache
+    ///
+    /// # Returns
+    /// * `Self` - The manifest cache
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Create a manifest cache in the user's config directory.
+    ///
+    /// # Returns
+    /// * `Option<Self>` - The manifest cache, or None if the config directory cannot be determined
+    pub fn in_config_dir() -> Option<Self> {
+        crate::config::get_manifest_cache_dir().map(Self::new)
+    }
+
+    /// Get the path of the cache file for a manifest URL.
+    ///
+    /// The URL identifies both the model and the registry it is fetched from, so the
+    /// same model from different registries is cached separately.
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Look up the cached manifest for a URL.
+    ///
+    /// # Arguments
+    /// * `url` - The manifest URL
+    ///
+    /// # Returns
+    /// * `Option<CachedManifest>` - The cached manifest, or None if there is no usable entry
+    pub fn load(&self, url: &str) -> Option<CachedManifest> {
+        let path = self.entry_path(url);
+        let content = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<CachedManifest>(&content) {
+            Ok(entry) if entry.url == url => Some(entry),
+            Ok(_) => None,
+            Err(e) => {
+                debug!("Ignoring unreadable manifest cache entry {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Store a manifest in the cache. Failures are logged, since the cache is only an optimisation.
+    ///
+    /// # Arguments
+    /// * `entry` - The manifest to cache
+    pub fn store(&self, entry: &CachedManifest) {
+        let path = self.entry_path(&entry.url);
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| serde_json::to_string(entry).map_err(std::io::Error::other))
+            .and_then(|content| write_file_atomically(&path, content.as_bytes()));
+        if let Err(e) = result {
+            warn!("Failed to cache manifest in {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Fetch a manifest, sending the `ETag` of the cached manifest, if any, as `If-None-Match`
+/// and using the cached manifest if the server reports that it has not been modified.
+///
+/// # Arguments
+/// * `client` - The HTTP client
+/// * `url` - The manifest URL
+/// * `cache` - The manifest cache, if any
+/// * `spinner` - The spinner of the fetch, checked for interrupts
+///
+/// # Returns
+/// * `Result<String>` - The manifest JSON
+pub fn fetch_manifest(
+    client: &Client,
+    url: &str,
+    cache: Option<&ManifestCache>,
+    spinner: &ActivitySpinner,
+) -> Result<String> {
+    let cached = cache.and_then(|cache| cache.load(url));
+
+    let mut request = client.get(url);
+    if let Some(cached) = &cached {
+        debug!("Requesting manifest with If-None-Match: {}", cached.etag);
+        request = request.header(IF_NONE_MATCH, &cached.etag);
+    }
+    let response = request.send_traced()?;
+    spinner.check_interrupt()?;
+    http_date::warn_on_clock_skew(response.headers());
+
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        info!("Manifest not modified, using the cached manifest");
+        emit_event("manifest_fetched", json!({"url": url, "cached": true}));
+        return Ok(cached.manifest);
+    }
+
+    if !response.status().is_success() {
+        return Err(DownloaderError::HttpError(
+            response.error_for_status().unwrap_err(),
+        ));
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let manifest_json = response.text()?;
+    spinner.check_interrupt()?;
+
+    if let (Some(cache), Some(etag)) = (cache, etag) {
+        cache.store(&CachedManifest {
+            url: url.to_string(),
+            etag,
+            manifest: manifest_json.clone(),
+        });
+    }
+
+    emit_event("manifest_fetched", json!({"url": url, "cached": false}));
+    Ok(manifest_json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_cache_store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ManifestCache::new(dir.path().join("manifests"));
+        let url = "https://registry.ollama.ai/v2/library/llama3.1/manifests/8b";
+        assert_eq!(cache.load(url), None);
+
+        let entry = CachedManifest {
+            url: url.to_string(),
+            etag: "\"abc\"".to_string(),
+            manifest: "{}".to_string(),
+        };
+        cache.store(&entry);
+        assert_eq!(cache.load(url), Some(entry));
+        assert_eq!(
+            cache.load("https://example.com/v2/llama3.1/manifests/8b"),
+            None
+        );
+
+        // Unreadable entries are ignored
+        fs::write(cache.entry_path(url), "not json").unwrap();
+        assert_eq!(cache.load(url), None);
+    }
+}
@@ -1,10 +1,23 @@
 //! Downloaders for the Ollama Downloader in Rust (ODIR),
 //! including implementations for Hugging Face and Ollama library models,
 //! as well as utility functions for downloading and managing model files.
+pub mod archive;
+pub mod batch;
+pub mod bundle;
+pub mod checksums;
+pub mod connectivity;
+pub mod events;
+pub mod failures;
 pub mod hf_downloader;
+pub mod http_date;
+pub mod journal;
+pub mod lock;
 pub mod manifest;
+pub mod manifest_cache;
 pub mod model_downloader;
 pub mod ollama_downloader;
+pub mod progress;
+pub mod store;
 pub mod utils;
 
 pub use hf_downloader::HuggingFaceModelDownloader;
@@ -1,5 +1,14 @@
 //! Model downloader trait and error definitions for the Ollama Downloader in Rust (ODIR).
+use crate::downloader::manifest::{ImageManifest, ManifestDiff, blob_file_name};
+use crate::downloader::store::remove_orphaned_blobs;
+use crate::downloader::utils::{format_byte_size, is_blob_intact};
+use log::info;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
 /// Error types for model downloading operations
@@ -20,12 +29,519 @@ pub enum DownloaderError {
     #[error("Invalid model identifier: {0}")]
     InvalidIdentifier(String),
 
+    #[error("Invalid manifest: {0}")]
+    InvalidManifest(String),
+
+    #[error("Download size limit exceeded: {0}")]
+    SizeLimitExceeded(String),
+
+    #[error("Overall deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+
+    #[error("Download stalled: {0}")]
+    Stalled(String),
+
     #[error("{0}")]
     Other(String),
 }
 
+impl DownloaderError {
+    /// Get the process exit code that reports this error.
+    ///
+    /// # Returns
+    /// * `i32` - 3 if a download was refused for exceeding the size limit, 1 otherwise
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DownloaderError::SizeLimitExceeded(_) => 3,
+            _ => 1,
+        }
+    }
+
+    /// Get a stable name for the kind of this error, for tools that parse errors.
+    ///
+    /// # Returns
+    /// * `&'static str` - The kind of error, e.g., `model_not_found`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DownloaderError::HttpError(_) => "http",
+            DownloaderError::ParseError(_) => "parse",
+            DownloaderError::ModelNotFound(_) => "model_not_found",
+            DownloaderError::IoError(_) => "io",
+            DownloaderError::InvalidIdentifier(_) => "invalid_identifier",
+            DownloaderError::InvalidManifest(_) => "invalid_manifest",
+            DownloaderError::SizeLimitExceeded(_) => "size_limit_exceeded",
+            DownloaderError::DeadlineExceeded(_) => "deadline_exceeded",
+            DownloaderError::Stalled(_) => "stalled",
+            DownloaderError::Other(_) => "other",
+        }
+    }
+
+    /// Get the HTTP status code that caused this error, if any.
+    ///
+    /// # Returns
+    /// * `Option<u16>` - The HTTP status code, or None if the error was not an HTTP error status
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            DownloaderError::HttpError(e) => e.status().map(|status| status.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// Describe this error as a JSON envelope, e.g.,
+    /// `{"error": {"kind": "model_not_found", "message": "...", "status": null}}`.
+    ///
+    /// # Returns
+    /// * `serde_json::Value` - The error envelope
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "error": {
+                "kind": self.kind(),
+                "message": self.to_string(),
+                "status": self.http_status(),
+            }
+        })
+    }
+}
+
 pub type Result<T> = std::result::Result<T, DownloaderError>;
 
+/// The version of a model to download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelReference {
+    /// A tag, e.g., 8b or Q4_K_M, which may be moved to another version of the model
+    Tag(String),
+
+    /// The digest of a manifest, e.g., sha256:<hex>, which always refers to the same version
+    Digest(String),
+}
+
+impl ModelReference {
+    /// Get the reference as it appears in manifest URLs, e.g., /manifests/<reference>.
+    ///
+    /// # Returns
+    /// * `&str` - The tag or the digest
+    pub fn as_url_component(&self) -> &str {
+        match self {
+            ModelReference::Tag(tag) => tag,
+            ModelReference::Digest(digest) => digest,
+        }
+    }
+
+    /// Get the name of the file the manifest is saved as, which is the tag, or
+    /// sha256-<hex> for a digest, since ':' is not valid in file names on all platforms.
+    ///
+    /// # Returns
+    /// * `String` - The manifest file name
+    pub fn manifest_file_name(&self) -> String {
+        match self {
+            ModelReference::Tag(tag) => tag.clone(),
+            ModelReference::Digest(digest) => blob_file_name(digest),
+        }
+    }
+
+    /// Format a model name with this reference, as {model}:{tag} or {model}@{digest}.
+    ///
+    /// # Arguments
+    /// * `model` - The name of the model
+    ///
+    /// # Returns
+    /// * `String` - The model identifier
+    pub fn qualify(&self, model: &str) -> String {
+        match self {
+            ModelReference::Tag(tag) => format!("{}:{}", model, tag),
+            ModelReference::Digest(digest) => format!("{}@{}", model, digest),
+        }
+    }
+
+    /// Check that a fetched manifest is the one this reference pins, if it is a digest.
+    ///
+    /// # Arguments
+    /// * `manifest_json` - The manifest, exactly as fetched
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success, or an error if the manifest does not match the digest
+    pub fn verify_manifest(&self, manifest_json: &str) -> Result<()> {
+        let ModelReference::Digest(digest) = self else {
+            return Ok(());
+        };
+        let computed = format!("sha256:{:x}", Sha256::digest(manifest_json.as_bytes()));
+        if &computed != digest {
+            return Err(DownloaderError::InvalidManifest(format!(
+                "expected a manifest with digest {}, but got one with digest {}",
+                digest, computed
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Parse a model identifier into the model name and the version to download.
+///
+/// The version is given either as a tag, {model}:{tag}, or pinned to a manifest
+/// digest, {model}@sha256:<hex>. Without either, the default tag is assumed.
+///
+/// # Arguments
+/// * `model_identifier` - The model identifier, e.g., llama3.1:8b or llama3.1@sha256:<hex>
+/// * `default_tag` - The tag to assume if none is given
+///
+/// # Returns
+/// * `Result<(String, ModelReference)>` - The model name and the version to download
+pub fn parse_model_identifier(
+    model_identifier: &str,
+    default_tag: &str,
+) -> Result<(String, ModelReference)> {
+    let invalid = |reason: &str| {
+        DownloaderError::InvalidIdentifier(format!("{}: {}", model_identifier, reason))
+    };
+
+    let (model, reference) = match model_identifier.split_once('@') {
+        Some((model, digest)) => {
+            let hex = digest
+                .strip_prefix("sha256:")
+                .ok_or_else(|| invalid("a pinned digest must be given as sha256:<hex>"))?;
+            if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid("a sha256 digest must have 64 hexadecimal digits"));
+            }
+            if model.contains(':') {
+                return Err(invalid("give either a tag or a digest, not both"));
+            }
+            (
+                model,
+                ModelReference::Digest(format!("sha256:{}", hex.to_ascii_lowercase())),
+            )
+        }
+        None => match model_identifier.split_once(':') {
+            Some((model, tag)) => {
+                if tag.is_empty() || tag.contains(':') {
+                    return Err(invalid("a tag must not be empty or contain ':'"));
+                }
+                (model, ModelReference::Tag(tag.to_string()))
+            }
+            None => (
+                model_identifier,
+                ModelReference::Tag(default_tag.to_string()),
+            ),
+        },
+    };
+
+    if model.is_empty() {
+        return Err(invalid("the model name is missing"));
+    }
+    Ok((model.to_string(), reference))
+}
+
+/// Parse the identifier of a model to download, assuming the tag `latest` if neither a
+/// tag nor a digest is given, and saying so, or refusing to if a tag is required.
+///
+/// # Arguments
+/// * `model_identifier` - The model identifier, e.g., llama3.1:8b or llama3.1@sha256:<hex>
+/// * `strict_tag` - Whether a tag or digest must be given explicitly
+///
+/// # Returns
+/// * `Result<(String, ModelReference)>` - The model name and the version to download
+pub fn parse_download_identifier(
+    model_identifier: &str,
+    strict_tag: bool,
+) -> Result<(String, ModelReference)> {
+    if !model_identifier.contains([':', '@']) {
+        if strict_tag {
+            return Err(DownloaderError::InvalidIdentifier(format!(
+                "{}: no tag given, which --strict-tag requires, e.g., {}:latest",
+                model_identifier, model_identifier
+            )));
+        }
+        info!(
+            "No tag specified for {}, assuming ':latest'",
+            model_identifier
+        );
+    }
+    parse_model_identifier(model_identifier, "latest")
+}
+
+/// Key facts of a successful model download
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DownloadSummary {
+    /// The downloaded model identifier, e.g., llama3.1:8b
+    pub identifier: String,
+
+    /// Total size of the fetched BLOBs in bytes
+    pub total_bytes: u64,
+
+    /// Number of fetched BLOBs
+    pub blobs: usize,
+
+    /// Time taken by the download in seconds, including fetching the manifest
+    pub elapsed_secs: f64,
+
+    /// Average download speed in bytes per second
+    pub bytes_per_sec: f64,
+
+    /// Whether the model was already up to date, so that nothing was downloaded
+    pub up_to_date: bool,
+}
+
+impl DownloadSummary {
+    /// Summarise a download, computing its average speed.
+    ///
+    /// # Arguments
+    /// * `identifier` - The downloaded model identifier
+    /// * `total_bytes` - Total size of the fetched BLOBs in bytes
+    /// * `blobs` - Number of fetched BLOBs
+    /// * `elapsed` - Time taken by the download
+    ///
+    /// # Returns
+    /// * `Self` - The summary of the download
+    pub fn new(identifier: &str, total_bytes: u64, blobs: usize, elapsed: Duration) -> Self {
+        let elapsed_secs = elapsed.as_secs_f64();
+        let bytes_per_sec = if elapsed_secs > 0.0 {
+            total_bytes as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+        Self {
+            identifier: identifier.to_string(),
+            total_bytes,
+            blobs,
+            elapsed_secs,
+            bytes_per_sec,
+            up_to_date: false,
+        }
+    }
+
+    /// Summarise a download that was skipped because the model was already up to date.
+    ///
+    /// # Arguments
+    /// * `identifier` - The model identifier
+    /// * `elapsed` - Time taken to find out that the model was up to date
+    ///
+    /// # Returns
+    /// * `Self` - The summary of the skipped download
+    pub fn already_up_to_date(identifier: &str, elapsed: Duration) -> Self {
+        Self {
+            up_to_date: true,
+            ..Self::new(identifier, 0, 0, elapsed)
+        }
+    }
+
+    /// Format the facts of the download, without the model identifier.
+    ///
+    /// # Returns
+    /// * `String` - The facts, e.g., `4.7 GiB in 3 BLOB(s), 60.0 s, 80.0 MiB/s`
+    pub fn facts(&self) -> String {
+        if self.up_to_date {
+            return "already up to date, nothing downloaded".to_string();
+        }
+        format!(
+            "{} in {} BLOB(s), {:.1} s, {}/s",
+            format_byte_size(self.total_bytes),
+            self.blobs,
+            self.elapsed_secs,
+            format_byte_size(self.bytes_per_sec as u64)
+        )
+    }
+
+    /// Format the summary as a single line for logs.
+    ///
+    /// # Returns
+    /// * `String` - The summary, e.g., `llama3.1:8b: 4.7 GiB in 3 BLOB(s), 60.0 s, 80.0 MiB/s`
+    pub fn line(&self) -> String {
+        format!("{}: {}", self.identifier, self.facts())
+    }
+}
+
+/// A BLOB that a download would fetch, unless it is already present
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedBlob {
+    /// The media type of the BLOB
+    pub media_type: String,
+
+    /// The digest of the BLOB
+    pub digest: String,
+
+    /// The size of the BLOB in bytes
+    pub size: u64,
+
+    /// Whether the BLOB is already intact in the models directory
+    pub present: bool,
+}
+
+/// What downloading a model would do, worked out without downloading it, e.g., for
+/// provisioning systems to diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DownloadPlan {
+    /// The resolved model identifier, e.g., llama3.1:8b
+    pub identifier: String,
+
+    /// The host of the registry the model would be downloaded from
+    pub registry: String,
+
+    /// The digest of the manifest of the model
+    pub manifest_digest: String,
+
+    /// The BLOBs the manifest references, in download order
+    pub blobs: Vec<PlannedBlob>,
+
+    /// Total size of the BLOBs in bytes
+    pub total_bytes: u64,
+
+    /// Total size in bytes of the BLOBs that are not yet present
+    pub missing_bytes: u64,
+}
+
+impl DownloadPlan {
+    /// Plan the download of a model from its manifest, checking which of its BLOBs are
+    /// already intact in the models directory.
+    ///
+    /// # Arguments
+    /// * `identifier` - The resolved model identifier
+    /// * `registry` - The host of the registry
+    /// * `manifest_json` - The manifest, exactly as fetched
+    /// * `manifest` - The parsed manifest
+    /// * `models_root` - Path to the Ollama models directory
+    ///
+    /// # Returns
+    /// * `Result<Self>` - The plan, or an error if a BLOB cannot be checked
+    pub fn new(
+        identifier: &str,
+        registry: &str,
+        manifest_json: &str,
+        manifest: &ImageManifest,
+        models_root: &Path,
+    ) -> Result<Self> {
+        let blobs = manifest
+            .blobs()
+            .into_iter()
+            .map(|blob| {
+                Ok(PlannedBlob {
+                    present: is_blob_intact(models_root, &blob.digest)?,
+                    media_type: blob.media_type,
+                    digest: blob.digest,
+                    size: blob.size,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            identifier: identifier.to_string(),
+            registry: registry.to_string(),
+            manifest_digest: format!("sha256:{:x}", Sha256::digest(manifest_json.as_bytes())),
+            total_bytes: blobs.iter().map(|blob| blob.size).sum(),
+            missing_bytes: blobs
+                .iter()
+                .filter(|blob| !blob.present)
+                .map(|blob| blob.size)
+                .sum(),
+            blobs,
+        })
+    }
+}
+
+/// Comparison of the local copy of a model with the current version in the registry
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelComparison {
+    /// The compared model identifier, e.g., llama3.1:8b
+    pub identifier: String,
+
+    /// Whether a manifest of the model is in the models directory
+    pub local_present: bool,
+
+    /// The BLOBs that differ, where all BLOBs are added if the model is not present locally
+    pub diff: ManifestDiff,
+}
+
+impl ModelComparison {
+    /// Compare the local manifest of a model, if any, with its remote manifest.
+    ///
+    /// # Arguments
+    /// * `identifier` - The model identifier
+    /// * `local` - The local manifest, or None if the model is not present locally
+    /// * `remote` - The remote manifest
+    ///
+    /// # Returns
+    /// * `Self` - The comparison
+    pub fn new(identifier: &str, local: Option<&ImageManifest>, remote: &ImageManifest) -> Self {
+        let diff = match local {
+            Some(local) => local.diff(remote),
+            None => ManifestDiff {
+                added: remote.blobs(),
+                ..ManifestDiff::default()
+            },
+        };
+        Self {
+            identifier: identifier.to_string(),
+            local_present: local.is_some(),
+            diff,
+        }
+    }
+
+    /// Check whether the local copy of the model is the version in the registry.
+    ///
+    /// # Returns
+    /// * `bool` - True if the model is present locally with the same BLOBs as remotely
+    pub fn is_up_to_date(&self) -> bool {
+        self.local_present && self.diff.is_empty()
+    }
+}
+
+/// Outcome of updating a model to the current version in the registry
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpdateReport {
+    /// The comparison of the local copy with the registry before the update
+    pub comparison: ModelComparison,
+
+    /// The summary of the download, or None if the model was already up to date
+    pub download: Option<DownloadSummary>,
+
+    /// Number of BLOBs of the previous version that no model references any longer
+    pub removed_blobs: usize,
+
+    /// Total size of the removed BLOBs in bytes
+    pub freed_bytes: u64,
+}
+
+/// Update a model to the current version in the registry: only the BLOBs that are new are
+/// downloaded, since BLOBs already in the models directory are kept, the manifest is
+/// replaced, and the BLOBs of the previous version that no model references any longer
+/// are removed.
+///
+/// # Arguments
+/// * `downloader` - The downloader for the source of the model
+/// * `models_root` - Path to the Ollama models directory
+/// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+///
+/// # Returns
+/// * `Result<UpdateReport>` - The outcome of the update
+pub fn update_model(
+    downloader: &dyn ModelDownloader,
+    models_root: &Path,
+    model_identifier: &str,
+) -> Result<UpdateReport> {
+    let comparison = downloader.compare(model_identifier)?;
+    if comparison.is_up_to_date() {
+        return Ok(UpdateReport {
+            comparison,
+            download: None,
+            removed_blobs: 0,
+            freed_bytes: 0,
+        });
+    }
+
+    let download = downloader.download_model(model_identifier)?;
+    let previous: Vec<&str> = comparison
+        .diff
+        .removed
+        .iter()
+        .chain(comparison.diff.changed.iter().map(|change| &change.local))
+        .map(|blob| blob.digest.as_str())
+        .collect();
+    let (removed_blobs, freed_bytes) = remove_orphaned_blobs(models_root, &previous)?;
+    Ok(UpdateReport {
+        comparison,
+        download: Some(download),
+        removed_blobs,
+        freed_bytes,
+    })
+}
+
 /// Trait defining the common interface for model downloaders
 pub trait ModelDownloader {
     /// Download a model from the model source.
@@ -34,8 +550,8 @@ pub trait ModelDownloader {
     /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
     ///
     /// # Returns
-    /// * `Result<bool>` - True if download successful
-    fn download_model(&self, model_identifier: &str) -> Result<bool>;
+    /// * `Result<DownloadSummary>` - The key facts of the download, if successful
+    fn download_model(&self, model_identifier: &str) -> Result<DownloadSummary>;
 
     /// List available models from the model source.
     ///
@@ -59,4 +575,335 @@ pub trait ModelDownloader {
     /// # Returns
     /// * `Result<Vec<String>>` - List of available tags for the model
     fn list_model_tags(&self, model_identifier: &str) -> Result<Vec<String>>;
+
+    /// Check whether a model is present in the Ollama server.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if the Ollama server lists the model
+    fn is_model_present(&self, model_identifier: &str) -> Result<bool>;
+
+    /// Rebuild the manifest of a model whose BLOBs are already in the models directory,
+    /// without downloading them again. The manifest is fetched, or taken from the manifest
+    /// cache, and only saved if every BLOB it references is present and intact.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    ///
+    /// # Returns
+    /// * `Result<PathBuf>` - The path of the saved manifest
+    fn relink(&self, model_identifier: &str) -> Result<PathBuf>;
+
+    /// Compare the local copy of a model with the current version in the registry, by the
+    /// digests of the BLOBs their manifests reference.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    ///
+    /// # Returns
+    /// * `Result<ModelComparison>` - The BLOBs that were added, removed or changed remotely
+    fn compare(&self, model_identifier: &str) -> Result<ModelComparison>;
+
+    /// Get the path the manifest of a model is saved at in the models directory, whether
+    /// or not the model has been downloaded.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    ///
+    /// # Returns
+    /// * `Result<PathBuf>` - The path of the manifest
+    fn manifest_path(&self, model_identifier: &str) -> Result<PathBuf>;
+
+    /// Work out what downloading a model would do, without downloading or changing anything:
+    /// the manifest is fetched, and each BLOB it references is checked for being already
+    /// intact in the models directory.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    ///
+    /// # Returns
+    /// * `Result<DownloadPlan>` - The plan of the download
+    fn plan(&self, model_identifier: &str) -> Result<DownloadPlan>;
+
+    /// Fetch the configuration of a model, e.g., its format, family and file type, by
+    /// downloading only the configuration BLOB its manifest references, without the weights.
+    /// Nothing is saved in the models directory.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    ///
+    /// # Returns
+    /// * `Result<serde_json::Value>` - The parsed configuration of the model
+    fn model_config(&self, model_identifier: &str) -> Result<serde_json::Value>;
+
+    /// Stream the weights of a model whose manifest references a single BLOB of them into a
+    /// writer, e.g., stdout, to pipe into another tool, verifying their digest. Nothing is
+    /// saved in the models directory.
+    ///
+    /// # Arguments
+    /// * `model_identifier` - The model identifier (e.g., "llama2:latest" or "user/repo:tag")
+    /// * `out` - Where the weights are written
+    ///
+    /// # Returns
+    /// * `Result<u64>` - The number of bytes streamed
+    fn stream_weights(&self, model_identifier: &str, out: &mut dyn std::io::Write) -> Result<u64>;
+}
+
+/// Check whether a model is already up to date in the models directory: its local manifest
+/// references the same BLOBs as the remote one, as compared by `compare`, and each of them
+/// is present with the size the manifest records. The BLOBs are not hashed again, since
+/// they were verified when they were saved.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `manifest_path` - Path of the local manifest of the model
+/// * `model_identifier` - The model identifier
+/// * `remote` - The remote manifest
+///
+/// # Returns
+/// * `Result<bool>` - True if the model is up to date, false if it is absent, outdated or
+///   incomplete, or an error if the local manifest cannot be read
+pub fn is_already_up_to_date(
+    models_root: &Path,
+    manifest_path: &Path,
+    model_identifier: &str,
+    remote: &ImageManifest,
+) -> Result<bool> {
+    let local_json = match fs::read_to_string(manifest_path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    // A local manifest that cannot be parsed is replaced by downloading the model again
+    let Ok(local) = ImageManifest::parse(&local_json) else {
+        return Ok(false);
+    };
+    if !ModelComparison::new(model_identifier, Some(&local), remote).is_up_to_date() {
+        return Ok(false);
+    }
+    for blob in remote.blobs() {
+        let path = models_root.join("blobs").join(blob_file_name(&blob.digest));
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() == blob.size => {}
+            Ok(_) => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_json_envelope() {
+        let error = DownloaderError::ModelNotFound("llama3.1:8b".to_string());
+        assert_eq!(
+            error.to_json(),
+            serde_json::json!({
+                "error": {
+                    "kind": "model_not_found",
+                    "message": "Model not found: llama3.1:8b",
+                    "status": null,
+                }
+            })
+        );
+        assert_eq!(
+            DownloaderError::SizeLimitExceeded(String::new()).kind(),
+            "size_limit_exceeded"
+        );
+    }
+
+    #[test]
+    fn test_parse_model_identifier() {
+        let digest = format!("sha256:{}", "ab".repeat(32));
+        assert_eq!(
+            parse_model_identifier("llama3.1:8b", "latest").unwrap(),
+            (
+                "llama3.1".to_string(),
+                ModelReference::Tag("8b".to_string())
+            )
+        );
+        assert_eq!(
+            parse_model_identifier("llama3.1", "latest").unwrap(),
+            (
+                "llama3.1".to_string(),
+                ModelReference::Tag("latest".to_string())
+            )
+        );
+        let (model, reference) =
+            parse_model_identifier(&format!("user/repo@sha256:{}", "AB".repeat(32)), "latest")
+                .unwrap();
+        assert_eq!(model, "user/repo");
+        assert_eq!(reference, ModelReference::Digest(digest.clone()));
+        assert_eq!(reference.qualify(&model), format!("user/repo@{}", digest));
+        assert_eq!(
+            reference.manifest_file_name(),
+            format!("sha256-{}", "ab".repeat(32))
+        );
+
+        assert!(parse_model_identifier("llama3.1@sha256:abc", "latest").is_err());
+        assert!(parse_model_identifier("llama3.1@md5:abc", "latest").is_err());
+        assert!(parse_model_identifier(&format!("llama3.1:8b@{}", digest), "latest").is_err());
+        assert!(parse_model_identifier("llama3.1:", "latest").is_err());
+        assert!(parse_model_identifier(":8b", "latest").is_err());
+
+        assert_eq!(
+            parse_download_identifier("llama3.1", false).unwrap(),
+            (
+                "llama3.1".to_string(),
+                ModelReference::Tag("latest".to_string())
+            )
+        );
+        assert!(parse_download_identifier("llama3.1", true).is_err());
+        assert!(parse_download_identifier("llama3.1:latest", true).is_ok());
+        assert!(parse_download_identifier(&format!("llama3.1@{}", digest), true).is_ok());
+    }
+
+    #[test]
+    fn test_download_summary() {
+        let summary =
+            DownloadSummary::new("llama3.1:8b", 3 * 1024 * 1024, 3, Duration::from_secs(2));
+        assert_eq!(summary.bytes_per_sec, 1536.0 * 1024.0);
+        assert_eq!(
+            summary.line(),
+            "llama3.1:8b: 3.0 MiB in 3 BLOB(s), 2.0 s, 1.5 MiB/s"
+        );
+        assert_eq!(
+            DownloadSummary::new("tiny:1b", 10, 1, Duration::ZERO).bytes_per_sec,
+            0.0
+        );
+        assert_eq!(
+            DownloadSummary::already_up_to_date("tiny:1b", Duration::ZERO).line(),
+            "tiny:1b: already up to date, nothing downloaded"
+        );
+    }
+
+    #[test]
+    fn test_model_comparison() {
+        let manifest: ImageManifest = serde_json::from_str(
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 100, "digest": "sha256:aaa"},
+                "layers": [{"mediaType": "application/vnd.ollama.image.model", "size": 900, "digest": "sha256:bbb"}]
+            }"#,
+        )
+        .unwrap();
+
+        let comparison = ModelComparison::new("test:latest", Some(&manifest), &manifest);
+        assert!(comparison.is_up_to_date());
+
+        let comparison = ModelComparison::new("test:latest", None, &manifest);
+        assert!(!comparison.is_up_to_date());
+        assert_eq!(comparison.diff.added.len(), 2);
+        assert!(comparison.diff.removed.is_empty());
+    }
+
+    #[test]
+    fn test_is_already_up_to_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_digest = format!("sha256:{}", "a".repeat(64));
+        let layer_digest = format!("sha256:{}", "b".repeat(64));
+        let manifest_json = format!(
+            r#"{{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {{"mediaType": "application/vnd.docker.container.image.v1+json", "size": 6, "digest": "{}"}},
+                "layers": [{{"mediaType": "application/vnd.ollama.image.model", "size": 5, "digest": "{}"}}]
+            }}"#,
+            config_digest, layer_digest
+        );
+        let manifest: ImageManifest = serde_json::from_str(&manifest_json).unwrap();
+        let manifest_path = dir.path().join("manifest");
+        let blobs = dir.path().join("blobs");
+        let up_to_date =
+            || is_already_up_to_date(dir.path(), &manifest_path, "test:latest", &manifest).unwrap();
+
+        assert!(!up_to_date());
+        fs::write(&manifest_path, &manifest_json).unwrap();
+        fs::create_dir(&blobs).unwrap();
+        fs::write(blobs.join(blob_file_name(&config_digest)), "config").unwrap();
+        assert!(!up_to_date());
+        fs::write(blobs.join(blob_file_name(&layer_digest)), "mode").unwrap();
+        assert!(!up_to_date());
+        fs::write(blobs.join(blob_file_name(&layer_digest)), "model").unwrap();
+        assert!(up_to_date());
+
+        fs::write(&manifest_path, "not a manifest").unwrap();
+        assert!(!up_to_date());
+    }
+
+    #[test]
+    fn test_download_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = b"config";
+        let config_digest = format!("sha256:{:x}", Sha256::digest(config));
+        let manifest_json = format!(
+            r#"{{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {{"mediaType": "application/vnd.docker.container.image.v1+json", "size": 6, "digest": "{}"}},
+                "layers": [{{"mediaType": "application/vnd.ollama.image.model", "size": 900, "digest": "sha256:{}"}}]
+            }}"#,
+            config_digest,
+            "b".repeat(64)
+        );
+        let manifest: ImageManifest = serde_json::from_str(&manifest_json).unwrap();
+        std::fs::create_dir(dir.path().join("blobs")).unwrap();
+        std::fs::write(
+            dir.path()
+                .join("blobs")
+                .join(blob_file_name(&config_digest)),
+            config,
+        )
+        .unwrap();
+
+        let plan = DownloadPlan::new(
+            "test:latest",
+            "registry.ollama.ai",
+            &manifest_json,
+            &manifest,
+            dir.path(),
+        )
+        .unwrap();
+        assert_eq!(
+            plan.manifest_digest,
+            format!("sha256:{:x}", Sha256::digest(manifest_json.as_bytes()))
+        );
+        assert_eq!(
+            plan.blobs
+                .iter()
+                .map(|blob| blob.present)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert_eq!(plan.total_bytes, 906);
+        assert_eq!(plan.missing_bytes, 900);
+    }
+
+    #[test]
+    fn test_verify_manifest() {
+        let manifest = r#"{"schemaVersion":2}"#;
+        let digest = format!("sha256:{:x}", Sha256::digest(manifest.as_bytes()));
+        assert!(
+            ModelReference::Digest(digest)
+                .verify_manifest(manifest)
+                .is_ok()
+        );
+        assert!(
+            ModelReference::Digest(format!("sha256:{}", "0".repeat(64)))
+                .verify_manifest(manifest)
+                .is_err()
+        );
+        assert!(
+            ModelReference::Tag("latest".to_string())
+                .verify_manifest(manifest)
+                .is_ok()
+        );
+    }
 }
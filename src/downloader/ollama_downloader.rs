@@ -1,26 +1,166 @@
 //! Downloader implementation for Ollama library models.
-use crate::config::AppSettings;
-use crate::downloader::manifest::ImageManifest;
-use crate::downloader::model_downloader::{DownloaderError, ModelDownloader, Result};
+use crate::config::{AppSettings, RunOptions};
+use crate::downloader::connectivity::{EndpointCheck, check_endpoint};
+use crate::downloader::journal::{self, JournalEntry};
+use crate::downloader::lock::lock_models_path;
+use crate::downloader::manifest::{DigestFormat, ImageManifest, blob_url};
+use crate::downloader::manifest_cache::{self, ManifestCache};
+use crate::downloader::model_downloader::{
+    DownloadPlan, DownloadSummary, DownloaderError, ModelComparison, ModelDownloader, Result,
+    is_already_up_to_date, parse_download_identifier, parse_model_identifier,
+};
+use crate::downloader::progress::{NoProgress, SharedProgress};
 use crate::downloader::utils::{
-    Ownership, cleanup_unnecessary_files, download_model_blob, expand_models_path,
-    infer_models_dir_ownership, is_model_present_in_ollama, save_blob, save_manifest,
-    warn_if_models_path_requires_root,
+    ActivitySpinner, BlobDownloadOptions, MISSING_BLOB_RETRY_DELAY, Ownership, TracedRequest,
+    apply_blob_mtime, apply_network_settings, blob_download_timeout, cleanup_unnecessary_files,
+    compare_tags, download_deadline, download_model_blob, ensure_blobs_intact,
+    ensure_enough_inodes, ensure_enough_space, ensure_manifest_has_model, ensure_models_dir,
+    ensure_secure_transport, ensure_within_size_limit, expand_models_path, explain_deadline,
+    fetch_json_blob, infer_models_dir_ownership, is_blob_intact, is_model_present_in_ollama,
+    redirect_policy, report_kept_files, resolve_temp_dir, retry_missing_blob, save_blob,
+    save_manifest, stream_model_weights, suggest_similar, verify_saved_blob, warn_if_insecure_http,
+    warn_if_models_path_requires_root, write_raw_manifest,
 };
 use log::{debug, error, info, warn};
-use reqwest::blocking::Client;
+use reqwest::Url;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
 use scraper::{Html, Selector};
+use std::cell::RefCell;
 use std::collections::HashSet;
-use std::fs;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Which parts of a model a download fetches, e.g., to repair a model without a full re-pull
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DownloadScope {
+    /// The manifest and all BLOBs
+    #[default]
+    Full,
+
+    /// Only the BLOBs that are absent or corrupt, according to the local manifest
+    BlobsOnly,
+
+    /// Only the manifest, leaving the BLOBs untouched
+    ManifestOnly,
+}
+
+/// Get the name of the model that a link on the library page points to, if any.
+///
+/// Relative and absolute links are accepted, as long as they point to a model directly
+/// under the library path on the host of the library, e.g., `/library/llama3.1`. Links to
+/// other pages, e.g., the blog, the docs or the tags of a model, are ignored.
+///
+/// # Arguments
+/// * `href` - The target of the link
+/// * `library_url` - The URL of the library page
+///
+/// # Returns
+/// * `Option<String>` - The name of the model, if the link points to one
+fn library_model_name(href: &str, library_url: &Url) -> Option<String> {
+    let url = library_url.join(href).ok()?;
+    if url.host_str() != library_url.host_str() {
+        return None;
+    }
+    let mut segments = url.path_segments()?;
+    let (Some("library"), Some(name), None) = (segments.next(), segments.next(), segments.next())
+    else {
+        return None;
+    };
+    let is_model_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    is_model_name.then(|| name.to_string())
+}
+
+/// Parse the models listed on the HTML page of the Ollama library.
+///
+/// # Arguments
+/// * `html` - The HTML of the library page
+/// * `library_url` - The URL of the library page, to resolve relative links against
+///
+/// # Returns
+/// * `Result<Vec<String>>` - The names of the listed models, without duplicates
+fn parse_library_html(html: &str, library_url: &Url) -> Result<Vec<String>> {
+    let document = Html::parse_document(html);
+    let link_selector = Selector::parse("a[href]")
+        .map_err(|e| DownloaderError::ParseError(format!("Invalid selector: {:?}", e)))?;
+
+    let mut models: Vec<String> = document
+        .select(&link_selector)
+        .filter_map(|element| element.value().attr("href"))
+        .filter_map(|href| library_model_name(href, library_url))
+        .collect();
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+/// Parse the models listed by a library that answers with JSON, in the format of the
+/// `/api/tags` endpoint of Ollama, e.g., `{"models": [{"name": "llama3.1:8b"}]}`.
+///
+/// # Arguments
+/// * `json` - The JSON listing of the library
+///
+/// # Returns
+/// * `Result<Vec<String>>` - The names of the listed models, without tags or duplicates
+fn parse_library_json(json: &str) -> Result<Vec<String>> {
+    #[derive(serde::Deserialize)]
+    struct Listing {
+        models: Vec<ListedModel>,
+    }
+    #[derive(serde::Deserialize)]
+    struct ListedModel {
+        name: String,
+    }
+
+    let listing: Listing = serde_json::from_str(json).map_err(|e| {
+        DownloaderError::ParseError(format!("Invalid JSON listing of the library: {}", e))
+    })?;
+    let mut models: Vec<String> = listing
+        .models
+        .into_iter()
+        .filter_map(|model| {
+            let name = model.name.split(':').next().unwrap_or_default();
+            let name = name.strip_prefix("library/").unwrap_or(name);
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect();
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+/// Number of times a failed request for the models list of the Ollama library is retried
+const LIBRARY_REQUEST_RETRIES: u32 = 2;
 
 /// Downloader for Ollama library models
 pub struct OllamaModelDownloader {
     settings: AppSettings,
+    /// Options of this run, given on the command line
+    options: RunOptions,
     user_agent: String,
     client: Client,
     unnecessary_files: HashSet<PathBuf>,
     models_dir_ownership: Option<Ownership>,
+    /// Cache of previously fetched manifests, if the config directory is available
+    manifest_cache: Option<ManifestCache>,
+    /// Whether BLOBs whose digest does not match are rejected
+    verify_digest: bool,
+    /// How many times a BLOB that the registry reports as missing is retried
+    missing_blob_retries: u32,
+    /// Whether saved BLOBs are read back and verified against their digest
+    verify_after: bool,
+    /// Receiver of the progress of downloads
+    progress: SharedProgress,
+    /// How digests are written in BLOB URLs
+    digest_format: DigestFormat,
+    /// Which parts of a model are downloaded
+    scope: DownloadScope,
+    /// Whether BLOBs already intact in the models directory are kept instead of downloaded
+    skip_existing: bool,
 }
 
 impl OllamaModelDownloader {
@@ -28,34 +168,154 @@ impl OllamaModelDownloader {
     ///
     /// # Arguments
     /// * `settings` - Application settings
+    /// * `options` - Options of this run
     ///
     /// # Returns
     /// * `Result<Self>` - New downloader instance or error
-    pub fn new(settings: AppSettings) -> Result<Self> {
+    pub fn new(settings: AppSettings, options: RunOptions) -> Result<Self> {
+        options
+            .validate(&settings.ollama_library)
+            .map_err(DownloaderError::Other)?;
         let pkg_version = env!("CARGO_PKG_VERSION");
         let os_info = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
         let user_agent = format!("odir/{} ({})", pkg_version, os_info);
 
-        let client = Client::builder()
+        let extra_headers = settings
+            .ollama_library
+            .extra_header_map()
+            .map_err(DownloaderError::Other)?;
+        if !extra_headers.is_empty() {
+            debug!(
+                "Sending extra headers: {}",
+                settings.ollama_library.redacted_extra_headers()
+            );
+        }
+
+        let client_builder = Client::builder()
             .user_agent(&user_agent)
+            .redirect(redirect_policy(&extra_headers))
+            .default_headers(extra_headers)
             .danger_accept_invalid_certs(!settings.ollama_library.verify_ssl)
             .timeout(std::time::Duration::from_secs_f64(
                 settings.ollama_library.timeout,
-            ))
-            .build()?;
+            ));
+        let client =
+            apply_network_settings(client_builder, &settings.ollama_library, &options).build()?;
 
         let models_dir_ownership =
             infer_models_dir_ownership(&settings.ollama_library.models_path)?;
+        warn_if_insecure_http(&Self::source_urls(&settings));
 
         Ok(Self {
             settings,
+            options,
             user_agent,
             client,
             unnecessary_files: HashSet::new(),
             models_dir_ownership,
+            manifest_cache: ManifestCache::in_config_dir(),
+            verify_digest: true,
+            missing_blob_retries: 0,
+            verify_after: false,
+            progress: Rc::new(RefCell::new(NoProgress)),
+            digest_format: DigestFormat::default(),
+            scope: DownloadScope::default(),
+            skip_existing: false,
         })
     }
 
+    /// Set how digests are written in BLOB URLs, e.g., plain for registries that
+    /// only accept digests as in the OCI distribution specification.
+    ///
+    /// # Arguments
+    /// * `digest_format` - How digests are written in BLOB URLs
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the digest format applied
+    pub fn with_digest_format(mut self, digest_format: DigestFormat) -> Self {
+        self.digest_format = digest_format;
+        self
+    }
+
+    /// Set how many times a BLOB that the registry reports as missing is retried, after
+    /// a growing delay, before the download fails.
+    ///
+    /// # Arguments
+    /// * `missing_blob_retries` - How many times a missing BLOB is retried
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the retries set
+    pub fn with_missing_blob_retries(mut self, missing_blob_retries: u32) -> Self {
+        self.missing_blob_retries = missing_blob_retries;
+        self
+    }
+
+    /// Set which parts of a model are downloaded. With [`DownloadScope::BlobsOnly`], the
+    /// manifest is read from the models directory instead of the registry.
+    ///
+    /// # Arguments
+    /// * `scope` - Which parts of a model are downloaded
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the scope set
+    pub fn with_scope(mut self, scope: DownloadScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Set whether BLOBs that are already intact in the models directory are kept instead
+    /// of downloaded again, e.g., to update a model by fetching only its new BLOBs.
+    ///
+    /// # Arguments
+    /// * `skip_existing` - Whether intact BLOBs are kept
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with skipping of existing BLOBs set
+    pub fn with_skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    /// Set the receiver of the progress of downloads, e.g., progress bars on a terminal.
+    /// Without one, progress is not reported.
+    ///
+    /// # Arguments
+    /// * `progress` - The receiver of the progress of downloads
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with the progress receiver set
+    pub fn with_progress(mut self, progress: SharedProgress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Set whether each BLOB is read back from the models directory once saved, and its
+    /// digest computed again, to catch corruption on disk. This costs an extra read of
+    /// every BLOB.
+    ///
+    /// # Arguments
+    /// * `verify_after` - Whether saved BLOBs are verified
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with verification after saving set
+    pub fn with_verify_after(mut self, verify_after: bool) -> Self {
+        self.verify_after = verify_after;
+        self
+    }
+
+    /// Set whether BLOBs whose digest does not match are rejected. Turning verification
+    /// off is an escape hatch for debugging registries, and is recorded in the journal.
+    ///
+    /// # Arguments
+    /// * `verify_digest` - Whether BLOBs whose digest does not match are rejected
+    ///
+    /// # Returns
+    /// * `Self` - The downloader with digest verification set
+    pub fn with_digest_verification(mut self, verify_digest: bool) -> Self {
+        self.verify_digest = verify_digest;
+        self
+    }
+
     /// Construct the manifest URL for a given model identifier
     fn make_manifest_url(&self, model: &str, tag: &str) -> String {
         format!(
@@ -69,76 +329,179 @@ impl OllamaModelDownloader {
         let url = self.make_manifest_url(model, tag);
         info!("Downloading manifest from {}", url);
 
-        let response = self.client.get(&url).send()?;
+        let spinner = ActivitySpinner::start(&format!("Fetching manifest for {}:{}", model, tag));
+        let manifest_json = manifest_cache::fetch_manifest(
+            &self.client,
+            &url,
+            self.manifest_cache.as_ref(),
+            &spinner,
+        )?;
+        write_raw_manifest(self.options.raw_manifest_out.as_deref(), &manifest_json)?;
+        Ok(manifest_json)
+    }
 
-        if !response.status().is_success() {
-            return Err(DownloaderError::HttpError(
-                response.error_for_status().unwrap_err(),
-            ));
+    /// Read the manifest of a previously downloaded model from the models directory
+    fn read_local_manifest(&self, model: &str, tag: &str) -> Result<String> {
+        let path = self.manifests_dir(model)?.join(tag);
+        info!("Reading local manifest {:?}", path);
+        if !path.is_file() {
+            return Err(DownloaderError::ModelNotFound(format!(
+                "{}:{} (no local manifest at {:?} to fetch the BLOBs of)",
+                model, tag, path
+            )));
         }
+        Ok(std::fs::read_to_string(path)?)
+    }
 
-        Ok(response.text()?)
+    /// Fetch the models list of the Ollama library, preferring a JSON listing, which a
+    /// library or mirror may offer, over the HTML page. The request has its own timeout, if
+    /// configured, since the page may be large, and is retried on connection failures and
+    /// server errors, which the library occasionally answers with.
+    ///
+    /// # Arguments
+    /// * `library_url` - The URL of the library
+    /// * `spinner` - The spinner shown while fetching, to check for interrupts
+    ///
+    /// # Returns
+    /// * `Result<Response>` - The successful response, or the error of the last attempt
+    fn fetch_library_listing(
+        &self,
+        library_url: &Url,
+        spinner: &ActivitySpinner,
+    ) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .client
+                .get(library_url.clone())
+                .header(ACCEPT, "application/json, text/html;q=0.9");
+            if let Some(timeout) = self.settings.ollama_library.library_request_timeout {
+                request = request.timeout(Duration::from_secs_f64(timeout));
+            }
+            let result = request.send_traced();
+            spinner.check_interrupt()?;
+            let error = match result {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) if response.status().is_server_error() => {
+                    response.error_for_status().unwrap_err()
+                }
+                Ok(response) => {
+                    return Err(DownloaderError::HttpError(
+                        response.error_for_status().unwrap_err(),
+                    ));
+                }
+                Err(error) => error,
+            };
+
+            if attempt >= LIBRARY_REQUEST_RETRIES {
+                return Err(DownloaderError::HttpError(error));
+            }
+            attempt += 1;
+            warn!(
+                "Fetching the Ollama library models list failed: {}. Retrying ({}/{})",
+                error, attempt, LIBRARY_REQUEST_RETRIES
+            );
+            std::thread::sleep(Duration::from_secs(u64::from(attempt)));
+        }
     }
 
     /// Construct the blob URL for a given model and digest
     fn make_blob_url(&self, model: &str, digest: &str) -> String {
-        format!(
-            "{}{}/blobs/{}",
-            self.settings.ollama_library.registry_base_url,
+        blob_url(
+            &self.settings.ollama_library.registry_base_url,
             model,
-            digest.replace(':', "-")
+            digest,
+            self.digest_format,
         )
     }
 
-    /// Download a model blob with progress tracking
+    /// Download a model blob with progress tracking, within the deadline for the model, if any
     fn download_model_blob(
         &mut self,
         model: &str,
         named_digest: &str,
-    ) -> Result<(PathBuf, String)> {
+        expected_size: u64,
+        deadline: Option<Instant>,
+    ) -> Result<(PathBuf, String, Option<SystemTime>)> {
         let url = self.make_blob_url(model, named_digest);
-        download_model_blob(
-            &self.client,
-            &url,
+        let temp_dir = resolve_temp_dir(
+            &self.settings.ollama_library.models_path,
+            self.settings.ollama_library.temp_dir.as_deref(),
+        )?;
+        retry_missing_blob(
             named_digest,
-            &mut self.unnecessary_files,
+            self.missing_blob_retries,
+            MISSING_BLOB_RETRY_DELAY,
+            deadline,
+            || {
+                let library = &self.settings.ollama_library;
+                let options = BlobDownloadOptions {
+                    timeout: blob_download_timeout(
+                        library.blob_timeout,
+                        library.timeout,
+                        deadline,
+                    )?,
+                    max_bandwidth_bytes_per_sec: library.max_bandwidth_bytes_per_sec,
+                    stall_timeout: library.stall_timeout_secs.map(Duration::from_secs_f64),
+                    min_throughput_bytes_per_sec: library.min_throughput_bytes_per_sec,
+                    keep_partial: self.options.keep_partial_files,
+                    expected_size: Some(expected_size),
+                };
+                download_model_blob(
+                    &self.client,
+                    &url,
+                    named_digest,
+                    &temp_dir,
+                    options,
+                    &mut *self.progress.borrow_mut(),
+                    &mut self.unnecessary_files,
+                )
+            },
         )
+        .map_err(|e| explain_deadline(e, deadline))
     }
 
-    /// Save the blob to the models directory
+    /// Save the blob to the models directory, giving it the modification time asked for, if any
     fn save_blob(
         &mut self,
         source: &Path,
         named_digest: &str,
         computed_digest: &str,
+        source_mtime: Option<SystemTime>,
     ) -> Result<PathBuf> {
-        save_blob(
+        let target_file = save_blob(
             &self.settings.ollama_library.models_path,
             source,
             named_digest,
             computed_digest,
+            self.verify_digest,
             self.models_dir_ownership,
             &mut self.unnecessary_files,
-        )
+        )?;
+        if self.verify_after {
+            verify_saved_blob(&target_file, named_digest, computed_digest)?;
+        }
+        if let Some(preserve_mtime) = self.options.preserve_mtime {
+            apply_blob_mtime(&target_file, preserve_mtime, source_mtime)?;
+        }
+        Ok(target_file)
+    }
+
+    /// Directory of the manifests of a model in the models directory
+    fn manifests_dir(&self, model: &str) -> Result<PathBuf> {
+        let models_path = expand_models_path(&self.settings.ollama_library.models_path)?;
+        Ok(models_path
+            .join("manifests")
+            .join(self.registry_host())
+            .join("library")
+            .join(model))
     }
 
     /// Save the manifest to the models directory
     fn save_manifest(&mut self, data: &str, model: &str, tag: &str) -> Result<PathBuf> {
         let models_path = expand_models_path(&self.settings.ollama_library.models_path)?;
         let manifests_toplevel_dir = models_path.join("manifests");
-
-        // Parse registry hostname from URL
-        let registry_url = &self.settings.ollama_library.registry_base_url;
-        let registry_host = registry_url
-            .split("//")
-            .nth(1)
-            .and_then(|s| s.split('/').next())
-            .unwrap_or("registry.ollama.ai");
-
-        let manifests_dir = manifests_toplevel_dir
-            .join(registry_host)
-            .join("library")
-            .join(model);
+        let manifests_dir = self.manifests_dir(model)?;
 
         save_manifest(
             data,
@@ -151,56 +514,209 @@ impl OllamaModelDownloader {
         )
     }
 
-    /// Cleanup unnecessary files on error
+    /// Parse the registry hostname from the registry base URL
+    fn registry_host(&self) -> &str {
+        self.settings
+            .ollama_library
+            .registry_base_url
+            .split("//")
+            .nth(1)
+            .and_then(|s| s.split('/').next())
+            .unwrap_or("registry.ollama.ai")
+    }
+
+    /// Names under which the Ollama server may list a library model
+    fn model_names_in_ollama(&self, model: &str, tag: &str) -> Vec<String> {
+        let model_name = format!("{}:{}", model, tag);
+        vec![
+            model_name.clone(),
+            format!("library/{}", model_name),
+            format!("{}/library/{}", self.registry_host(), model_name),
+        ]
+    }
+
+    /// The configured URLs that models and their lists are fetched from, with the names of
+    /// their settings
+    fn source_urls(settings: &AppSettings) -> [(&'static str, &str); 2] {
+        [
+            (
+                "registry_base_url",
+                &settings.ollama_library.registry_base_url,
+            ),
+            (
+                "library_base_url",
+                &settings.ollama_library.library_base_url,
+            ),
+        ]
+    }
+
+    /// Check whether the registry and the library can be reached with the HTTP client of
+    /// the downloader, as configured.
+    ///
+    /// # Returns
+    /// * `Vec<EndpointCheck>` - The outcome of checking each of them
+    pub fn check_connectivity(&self) -> Vec<EndpointCheck> {
+        Self::source_urls(&self.settings)
+            .into_iter()
+            .map(|(name, url)| {
+                check_endpoint(
+                    &self.client,
+                    name,
+                    url,
+                    self.settings.ollama_library.verify_ssl,
+                )
+            })
+            .collect()
+    }
+
+    /// Cleanup unnecessary files on error, unless they are to be kept for inspection
     fn cleanup_unnecessary_files(&mut self) {
-        cleanup_unnecessary_files(&mut self.unnecessary_files);
+        if self.options.keep_partial_files {
+            report_kept_files(&self.unnecessary_files);
+        } else {
+            cleanup_unnecessary_files(&mut self.unnecessary_files);
+        }
     }
 }
 
 impl ModelDownloader for OllamaModelDownloader {
-    fn download_model(&self, model_identifier: &str) -> Result<bool> {
+    fn download_model(&self, model_identifier: &str) -> Result<DownloadSummary> {
+        let started = Instant::now();
         // Warn about ownership issues before attempting download
         warn_if_models_path_requires_root(&self.settings.ollama_library.models_path, true);
+        let models_root = ensure_models_dir(&self.settings.ollama_library.models_path)?;
+        ensure_secure_transport(
+            &Self::source_urls(&self.settings),
+            self.options.allow_insecure_http,
+        )?;
+        // Held until the download has finished or been cleaned up
+        let _lock = lock_models_path(
+            &models_root,
+            self.models_dir_ownership,
+            self.options.no_lock,
+        )?;
 
-        let (model, tag) = if model_identifier.contains(':') {
-            let parts: Vec<&str> = model_identifier.split(':').collect();
-            (parts[0].to_string(), parts[1].to_string())
-        } else {
-            (model_identifier.to_string(), "latest".to_string())
-        };
+        let (model, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model);
 
-        println!("Downloading Ollama library model {}:{}", model, tag);
+        eprintln!("Downloading Ollama library model {}", identifier);
 
         // Make self mutable for this scope
         let mut self_mut = Self {
             settings: self.settings.clone(),
+            options: self.options.clone(),
             user_agent: self.user_agent.clone(),
             client: self.client.clone(),
             unnecessary_files: HashSet::new(),
             models_dir_ownership: self.models_dir_ownership,
+            manifest_cache: self.manifest_cache.clone(),
+            verify_digest: self.verify_digest,
+            missing_blob_retries: self.missing_blob_retries,
+            verify_after: self.verify_after,
+            progress: Rc::clone(&self.progress),
+            digest_format: self.digest_format,
+            scope: self.scope,
+            skip_existing: self.skip_existing,
         };
 
-        // Fetch and parse manifest
-        let manifest_json = match self_mut.fetch_manifest(&model, &tag) {
+        // Fetch and parse manifest, or read it from the models directory to repair BLOBs
+        let manifest_json = match self_mut.scope {
+            DownloadScope::BlobsOnly => {
+                self_mut.read_local_manifest(&model, &reference.manifest_file_name())
+            }
+            DownloadScope::Full | DownloadScope::ManifestOnly => self_mut
+                .fetch_manifest(&model, reference.as_url_component())
+                .and_then(|json| reference.verify_manifest(&json).map(|_| json)),
+        };
+        let manifest_json = match manifest_json {
             Ok(json) => json,
             Err(e) => {
-                error!("Failed to fetch manifest for {}:{}: {}", model, tag, e);
+                error!("Failed to fetch manifest for {}: {}", identifier, e);
                 self_mut.cleanup_unnecessary_files();
                 return Err(e);
             }
         };
-        info!("Validating manifest for {}:{}", model, tag);
+        info!("Validating manifest for {}", identifier);
+
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+        ensure_manifest_has_model(&manifest, &identifier)?;
+        // A model whose BLOBs are all in place is not downloaded again, unless forced
+        if self_mut.scope == DownloadScope::Full
+            && !self.options.force
+            && is_already_up_to_date(
+                &models_root,
+                &self_mut
+                    .manifests_dir(&model)?
+                    .join(reference.manifest_file_name()),
+                &identifier,
+                &manifest,
+            )?
+        {
+            eprintln!("Model {} is already up to date, nothing to do", identifier);
+            let summary = DownloadSummary::already_up_to_date(&identifier, started.elapsed());
+            self.progress.borrow_mut().on_model_complete(&summary);
+            return Ok(summary);
+        }
+        if self_mut.scope != DownloadScope::ManifestOnly {
+            ensure_within_size_limit(
+                &manifest,
+                &identifier,
+                self.settings.ollama_library.max_download_size,
+            )?;
+            ensure_enough_inodes(
+                &expand_models_path(&self.settings.ollama_library.models_path)?,
+                manifest.unique_layers().len() + 2,
+                self.settings.ollama_library.min_free_inodes,
+                self.options.force,
+            )?;
+            ensure_enough_space(
+                &manifest,
+                &models_root,
+                &resolve_temp_dir(
+                    &self.settings.ollama_library.models_path,
+                    self.settings.ollama_library.temp_dir.as_deref(),
+                )?,
+                self.options.min_free_after,
+                self.options.force,
+            )?;
+        }
+
+        // Only BLOBs that are absent or corrupt are fetched when repairing them
+        let models_root = expand_models_path(&self.settings.ollama_library.models_path)?;
+        let needs_download = |digest: &str| -> Result<bool> {
+            match self.scope {
+                DownloadScope::Full => {
+                    Ok(!self.skip_existing || !is_blob_intact(&models_root, digest)?)
+                }
+                DownloadScope::BlobsOnly => Ok(!is_blob_intact(&models_root, digest)?),
+                DownloadScope::ManifestOnly => Ok(false),
+            }
+        };
 
-        let manifest: ImageManifest = serde_json::from_str(&manifest_json)
-            .map_err(|e| DownloaderError::ParseError(format!("Failed to parse manifest: {}", e)))?;
+        // The deadline covers the downloads of all BLOBs of the model
+        let deadline = download_deadline(self.settings.ollama_library.download_deadline);
 
-        // Track files to be saved (source_path, named_digest, computed_digest)
-        let mut files_to_be_copied: Vec<(PathBuf, String, String)> = Vec::new();
+        // Track files to be saved (source_path, named_digest, computed_digest, source_mtime)
+        let mut files_to_be_copied: Vec<(PathBuf, String, String, Option<SystemTime>)> = Vec::new();
+        let mut fetched_bytes = 0;
+
+        if !self_mut.verify_digest {
+            warn!(
+                "Digest verification is DISABLED: the integrity of the downloaded BLOBs will NOT be verified"
+            );
+        }
 
         // Download model configuration BLOB
-        info!("Downloading model configuration {}", manifest.config.digest);
-        let (file_model_config, digest_model_config) =
-            match self_mut.download_model_blob(&model, &manifest.config.digest) {
+        if needs_download(&manifest.config.digest)? {
+            info!("Downloading model configuration {}", manifest.config.digest);
+            let (file_model_config, digest_model_config, mtime_model_config) = match self_mut
+                .download_model_blob(
+                    &model,
+                    &manifest.config.digest,
+                    manifest.config.size,
+                    deadline,
+                ) {
                 Ok(result) => result,
                 Err(e) => {
                     error!("Failed to download model configuration: {}", e);
@@ -208,57 +724,68 @@ impl ModelDownloader for OllamaModelDownloader {
                     return Err(e);
                 }
             };
-        files_to_be_copied.push((
-            file_model_config,
-            manifest.config.digest.clone(),
-            digest_model_config,
-        ));
-
-        // Download layers if present
-        if let Some(layers) = &manifest.layers {
-            for layer in layers {
-                debug!(
-                    "Layer: {}, Size: {} bytes, Digest: {}",
-                    layer.media_type, layer.size, layer.digest
-                );
+            files_to_be_copied.push((
+                file_model_config,
+                manifest.config.digest.clone(),
+                digest_model_config,
+                mtime_model_config,
+            ));
+            fetched_bytes += manifest.config.size;
+        }
 
-                // Check for interruption between layer downloads
-                if crate::signal_handler::is_interrupted() {
-                    warn!("Download interrupted during layer download");
-                    self_mut.cleanup_unnecessary_files();
-                    return Err(DownloaderError::Other(
-                        "Download interrupted by user".to_string(),
-                    ));
-                }
-                if crate::signal_handler::confirm_pending_interrupt() {
-                    warn!("Download interrupted during layer download");
-                    self_mut.cleanup_unnecessary_files();
-                    return Err(DownloaderError::Other(
-                        "Download interrupted by user".to_string(),
-                    ));
-                }
+        // Download each distinct layer BLOB once, even if the manifest repeats its digest
+        let layers = manifest.unique_layers();
+        let repeated_layers = manifest.layers.as_ref().map_or(0, |l| l.len()) - layers.len();
+        if repeated_layers > 0 {
+            info!(
+                "Skipping {} layer(s) whose BLOB is already part of the download",
+                repeated_layers
+            );
+        }
+        for layer in layers {
+            debug!(
+                "Layer: {}, Size: {} bytes, Digest: {}",
+                layer.media_type, layer.size, layer.digest
+            );
+            if !needs_download(&layer.digest)? {
+                continue;
+            }
 
-                info!("Downloading {} layer {}", layer.media_type, layer.digest);
-                let (file_layer, digest_layer) =
-                    match self_mut.download_model_blob(&model, &layer.digest) {
-                        Ok(result) => result,
-                        Err(e) => {
-                            error!("Failed to download layer {}: {}", layer.digest, e);
-                            self_mut.cleanup_unnecessary_files();
-                            return Err(e);
-                        }
-                    };
-                files_to_be_copied.push((file_layer, layer.digest.clone(), digest_layer));
+            // Check for interruption between layer downloads
+            if crate::signal_handler::is_interrupted() {
+                warn!("Download interrupted during layer download");
+                self_mut.cleanup_unnecessary_files();
+                return Err(DownloaderError::Other(
+                    "Download interrupted by user".to_string(),
+                ));
             }
+            if crate::signal_handler::confirm_pending_interrupt() {
+                warn!("Download interrupted during layer download");
+                self_mut.cleanup_unnecessary_files();
+                return Err(DownloaderError::Other(
+                    "Download interrupted by user".to_string(),
+                ));
+            }
+
+            info!("Downloading {} layer {}", layer.media_type, layer.digest);
+            let (file_layer, digest_layer, mtime_layer) =
+                match self_mut.download_model_blob(&model, &layer.digest, layer.size, deadline) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        error!("Failed to download layer {}: {}", layer.digest, e);
+                        self_mut.cleanup_unnecessary_files();
+                        return Err(e);
+                    }
+                };
+            files_to_be_copied.push((file_layer, layer.digest.clone(), digest_layer, mtime_layer));
+            fetched_bytes += layer.size;
         }
 
         // All BLOBs downloaded, now save them
-        for (source, named_digest, computed_digest) in files_to_be_copied {
-            match self_mut.save_blob(&source, &named_digest, &computed_digest) {
-                Ok(_) => {
-                    // Cleanup source file
-                    let _ = fs::remove_file(&source);
-                }
+        let fetched_blobs = files_to_be_copied.len();
+        for (source, named_digest, computed_digest, source_mtime) in files_to_be_copied {
+            match self_mut.save_blob(&source, &named_digest, &computed_digest, source_mtime) {
+                Ok(_) => {}
                 Err(e) => {
                     error!("Failed to save BLOB {}: {}", named_digest, e);
                     self_mut.cleanup_unnecessary_files();
@@ -267,47 +794,38 @@ impl ModelDownloader for OllamaModelDownloader {
             }
         }
 
-        // Save the manifest
-        match self_mut.save_manifest(&manifest_json, &model, &tag) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Failed to save manifest: {}", e);
-                if self.settings.ollama_server.remove_downloaded_on_error {
-                    self_mut.cleanup_unnecessary_files();
+        // Save the manifest, unless it was read from the models directory
+        if self_mut.scope != DownloadScope::BlobsOnly {
+            match self_mut.save_manifest(&manifest_json, &model, &reference.manifest_file_name()) {
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Failed to save manifest: {}", e);
+                    if self.settings.ollama_server.remove_downloaded_on_error
+                        && !self.options.keep_partial_files
+                    {
+                        self_mut.cleanup_unnecessary_files();
+                    }
+                    return Err(e);
                 }
-                return Err(e);
             }
         }
 
         // Verify the model is present in the Ollama server if configured
         if self.settings.ollama_server.check_model_presence {
-            info!(
-                "Verifying model {}:{} is present in Ollama server",
-                model, tag
-            );
-            let model_name = format!("{}:{}", model, tag);
-            let registry_host = self
-                .settings
-                .ollama_library
-                .registry_base_url
-                .split("//")
-                .nth(1)
-                .and_then(|s| s.split('/').next())
-                .unwrap_or("registry.ollama.ai");
-            let model_names = vec![
-                model_name.clone(),
-                format!("library/{}", model_name),
-                format!("{}/library/{}", registry_host, model_name),
-            ];
+            info!("Verifying model {} is present in Ollama server", identifier);
+            let model_names = self.model_names_in_ollama(&model, &reference.manifest_file_name());
             let model_present = match is_model_present_in_ollama(
                 &self_mut.client,
-                &self.settings.ollama_server.url,
+                &self.settings.ollama_server.resolved_url(),
+                self.settings.ollama_server.api_key.as_deref(),
                 &model_names,
             ) {
                 Ok(present) => present,
                 Err(e) => {
                     error!("Failed to verify model with Ollama server: {}", e);
-                    if self.settings.ollama_server.remove_downloaded_on_error {
+                    if self.settings.ollama_server.remove_downloaded_on_error
+                        && !self.options.keep_partial_files
+                    {
                         info!("Removing downloaded files due to verification failure");
                         self_mut.cleanup_unnecessary_files();
                     }
@@ -317,18 +835,20 @@ impl ModelDownloader for OllamaModelDownloader {
 
             if !model_present {
                 let err_msg = format!(
-                    "Model {}:{} not found in Ollama server after download",
-                    model, tag
+                    "Model {} not found in Ollama server after download",
+                    identifier
                 );
                 error!("{}", err_msg);
-                if self.settings.ollama_server.remove_downloaded_on_error {
+                if self.settings.ollama_server.remove_downloaded_on_error
+                    && !self.options.keep_partial_files
+                {
                     info!("Removing downloaded files because model not found in Ollama server");
                     self_mut.cleanup_unnecessary_files();
                 }
                 return Err(DownloaderError::Other(err_msg));
             }
 
-            info!("Model {}:{} verified in Ollama server", model, tag);
+            info!("Model {} verified in Ollama server", identifier);
         } else {
             debug!("Model presence check is disabled via settings");
         }
@@ -336,9 +856,163 @@ impl ModelDownloader for OllamaModelDownloader {
         // Clear unnecessary files list on success
         self_mut.unnecessary_files.clear();
 
-        println!("Model {}:{} successfully downloaded", model, tag);
+        let summary =
+            DownloadSummary::new(&identifier, fetched_bytes, fetched_blobs, started.elapsed());
+        self.progress.borrow_mut().on_model_complete(&summary);
+        match self.scope {
+            DownloadScope::Full => {
+                let entry = JournalEntry {
+                    digest_verified: self.verify_digest,
+                    elapsed_secs: Some(summary.elapsed_secs),
+                    ..JournalEntry::from_manifest(&identifier, self.registry_host(), &manifest)
+                };
+                if let Err(e) = journal::append_entry(
+                    &self.settings.ollama_library.models_path,
+                    &entry,
+                    self.models_dir_ownership,
+                ) {
+                    warn!("Failed to record download in journal: {}", e);
+                }
+
+                eprintln!("Model {} successfully downloaded", identifier);
+            }
+            DownloadScope::BlobsOnly => eprintln!(
+                "Model {} successfully repaired, {} absent or corrupt BLOB(s) fetched",
+                identifier, fetched_blobs
+            ),
+            DownloadScope::ManifestOnly => {
+                eprintln!("Manifest of model {} successfully downloaded", identifier)
+            }
+        }
+
+        Ok(summary)
+    }
+
+    fn is_model_present(&self, model_identifier: &str) -> Result<bool> {
+        let (model, reference) = parse_model_identifier(model_identifier, "latest")?;
+        info!(
+            "Checking if model {} is present in Ollama server",
+            reference.qualify(&model)
+        );
+        is_model_present_in_ollama(
+            &self.client,
+            &self.settings.ollama_server.resolved_url(),
+            self.settings.ollama_server.api_key.as_deref(),
+            &self.model_names_in_ollama(&model, &reference.manifest_file_name()),
+        )
+    }
+
+    fn relink(&self, model_identifier: &str) -> Result<PathBuf> {
+        let models_root = ensure_models_dir(&self.settings.ollama_library.models_path)?;
+        let (model, reference) = parse_model_identifier(model_identifier, "latest")?;
+        let identifier = reference.qualify(&model);
+        info!(
+            "Relinking model {} to the BLOBs in {:?}",
+            identifier, models_root
+        );
+
+        let manifest_json = self.fetch_manifest(&model, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+        ensure_manifest_has_model(&manifest, &identifier)?;
+        ensure_blobs_intact(&models_root, &manifest, &identifier)?;
+
+        // Nothing was downloaded, so nothing needs cleaning up if saving fails
+        let manifests_dir = self.manifests_dir(&model)?;
+        save_manifest(
+            &manifest_json,
+            &models_root,
+            &manifests_dir,
+            &reference.manifest_file_name(),
+            self.models_dir_ownership,
+            &[&manifests_dir, &models_root.join("manifests")],
+            &mut HashSet::new(),
+        )
+    }
+
+    fn manifest_path(&self, model_identifier: &str) -> Result<PathBuf> {
+        let (model, reference) = parse_model_identifier(model_identifier, "latest")?;
+        Ok(self
+            .manifests_dir(&model)?
+            .join(reference.manifest_file_name()))
+    }
 
-        Ok(true)
+    fn plan(&self, model_identifier: &str) -> Result<DownloadPlan> {
+        let (model, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model);
+        info!("Planning the download of model {}", identifier);
+
+        let manifest_json = self.fetch_manifest(&model, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+        ensure_manifest_has_model(&manifest, &identifier)?;
+
+        let models_root = expand_models_path(&self.settings.ollama_library.models_path)?;
+        DownloadPlan::new(
+            &identifier,
+            self.registry_host(),
+            &manifest_json,
+            &manifest,
+            &models_root,
+        )
+    }
+
+    fn model_config(&self, model_identifier: &str) -> Result<serde_json::Value> {
+        let (model, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model);
+        info!("Fetching the configuration of model {}", identifier);
+
+        let manifest_json = self.fetch_manifest(&model, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+
+        let library = &self.settings.ollama_library;
+        fetch_json_blob(
+            &self.client,
+            &self.make_blob_url(&model, &manifest.config.digest),
+            &manifest.config.digest,
+            &resolve_temp_dir(&library.models_path, library.temp_dir.as_deref())?,
+            library.blob_timeout.map(Duration::from_secs_f64),
+        )
+    }
+
+    fn stream_weights(&self, model_identifier: &str, out: &mut dyn std::io::Write) -> Result<u64> {
+        let (model, reference) =
+            parse_download_identifier(model_identifier, self.options.strict_tag)?;
+        let identifier = reference.qualify(&model);
+
+        let manifest_json = self.fetch_manifest(&model, reference.as_url_component())?;
+        reference.verify_manifest(&manifest_json)?;
+        let manifest: ImageManifest = ImageManifest::parse(&manifest_json)?;
+
+        stream_model_weights(
+            &self.client,
+            &manifest,
+            &identifier,
+            |digest| self.make_blob_url(&model, digest),
+            &self.settings.ollama_library,
+            &mut *self.progress.borrow_mut(),
+            out,
+        )
+    }
+
+    fn compare(&self, model_identifier: &str) -> Result<ModelComparison> {
+        let (model, reference) = parse_model_identifier(model_identifier, "latest")?;
+        let identifier = reference.qualify(&model);
+        info!("Comparing model {} with the registry", identifier);
+
+        let remote_json = self.fetch_manifest(&model, reference.as_url_component())?;
+        reference.verify_manifest(&remote_json)?;
+        let remote: ImageManifest = ImageManifest::parse(&remote_json)?;
+
+        let local = match self.read_local_manifest(&model, &reference.manifest_file_name()) {
+            Ok(json) => Some(ImageManifest::parse(&json)?),
+            Err(DownloaderError::ModelNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(ModelComparison::new(&identifier, local.as_ref(), &remote))
     }
 
     fn list_available_models(
@@ -351,38 +1025,26 @@ impl ModelDownloader for OllamaModelDownloader {
             self.settings.ollama_library.library_base_url
         );
 
-        let response = self
-            .client
-            .get(&self.settings.ollama_library.library_base_url)
-            .send()?;
-
-        if !response.status().is_success() {
-            return Err(DownloaderError::HttpError(
-                response.error_for_status().unwrap_err(),
-            ));
-        }
-
-        let html_content = response.text()?;
-        let document = Html::parse_document(&html_content);
-
-        // Select all anchor tags
-        let link_selector = Selector::parse("a[href]")
-            .map_err(|e| DownloaderError::ParseError(format!("Invalid selector: {:?}", e)))?;
-
-        let library_prefix = "/library/";
-        let mut available_models: Vec<String> = Vec::new();
-
-        for element in document.select(&link_selector) {
-            if let Some(href) = element.value().attr("href")
-                && href.starts_with(library_prefix)
-            {
-                let model_name = href.trim_start_matches(library_prefix).to_string();
-                // Only add if not empty and doesn't end with slash (avoid directory links)
-                if !model_name.is_empty() && !model_name.ends_with('/') {
-                    available_models.push(model_name);
-                }
-            }
-        }
+        let library_url = Url::parse(&self.settings.ollama_library.library_base_url)
+            .map_err(|e| DownloaderError::Other(format!("Invalid library base URL: {}", e)))?;
+
+        let spinner = ActivitySpinner::start("Fetching the Ollama library models list");
+        let response = self.fetch_library_listing(&library_url, &spinner)?;
+
+        let is_json = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+        let content = response.text()?;
+        spinner.check_interrupt()?;
+        let mut available_models = if is_json {
+            debug!("Parsing the JSON listing of the Ollama library");
+            parse_library_json(&content)?
+        } else {
+            debug!("Scraping the HTML page of the Ollama library");
+            parse_library_html(&content, &library_url)?
+        };
 
         debug!(
             "Found {} models in the Ollama library",
@@ -414,14 +1076,26 @@ impl ModelDownloader for OllamaModelDownloader {
     }
 
     fn list_model_tags(&self, model_identifier: &str) -> Result<Vec<String>> {
-        // Check if model exists first
+        // Check if model exists first, ignoring case, and use the library's spelling of it
         let available_models = self.list_available_models(None, None)?;
-        if !available_models.contains(&model_identifier.to_string()) {
-            return Err(DownloaderError::ModelNotFound(format!(
-                "Model {} not found in the library models list",
-                model_identifier
-            )));
-        }
+        let model_identifier = match available_models
+            .iter()
+            .find(|m| m.eq_ignore_ascii_case(model_identifier))
+        {
+            Some(model) => model.as_str(),
+            None => {
+                let suggestions = suggest_similar(model_identifier, &available_models, 3);
+                let hint = if suggestions.is_empty() {
+                    String::new()
+                } else {
+                    format!(". Did you mean: {}?", suggestions.join(", "))
+                };
+                return Err(DownloaderError::ModelNotFound(format!(
+                    "Model {} not found in the library models list{}",
+                    model_identifier, hint
+                )));
+            }
+        };
 
         let tags_url = format!(
             "{}{}/tags",
@@ -433,7 +1107,9 @@ impl ModelDownloader for OllamaModelDownloader {
             model_identifier
         );
 
-        let response = self.client.get(&tags_url).send()?;
+        let spinner = ActivitySpinner::start(&format!("Fetching tags for {}", model_identifier));
+        let response = self.client.get(&tags_url).send_traced()?;
+        spinner.check_interrupt()?;
 
         if !response.status().is_success() {
             return Err(DownloaderError::HttpError(
@@ -442,6 +1118,7 @@ impl ModelDownloader for OllamaModelDownloader {
         }
 
         let html_content = response.text()?;
+        spinner.check_interrupt()?;
         let document = Html::parse_document(&html_content);
 
         debug!("Parsing tags for model {}.", model_identifier);
@@ -454,6 +1131,7 @@ impl ModelDownloader for OllamaModelDownloader {
         let mut named_model_unique_tags = std::collections::HashSet::new();
 
         for element in document.select(&link_selector) {
+            spinner.check_interrupt()?;
             if let Some(href) = element.value().attr("href")
                 && href.starts_with(&model_tag_prefix)
             {
@@ -464,8 +1142,8 @@ impl ModelDownloader for OllamaModelDownloader {
 
         let mut models_tags: Vec<String> = named_model_unique_tags.into_iter().collect();
 
-        // Sort tags case-insensitively
-        models_tags.sort_by_key(|a| a.to_lowercase());
+        // Sort tags in their natural order, e.g., 7b before 13b
+        models_tags.sort_by(|a, b| compare_tags(a, b));
 
         Ok(models_tags)
     }
@@ -474,14 +1152,368 @@ impl ModelDownloader for OllamaModelDownloader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::downloader::manifest::blob_file_name;
+    use crate::downloader::utils::tests::{redirect_to, serve_once};
 
     #[test]
     fn test_ollama_downloader_creation() {
         let settings = AppSettings::default();
-        let downloader = OllamaModelDownloader::new(settings);
+        let downloader = OllamaModelDownloader::new(settings, RunOptions::default());
         assert!(downloader.is_ok());
     }
 
+    fn downloader_with_headers(headers: &[(&str, &str)]) -> OllamaModelDownloader {
+        let mut settings = AppSettings::default();
+        for (name, value) in headers {
+            settings
+                .ollama_library
+                .extra_headers
+                .insert(name.to_string(), value.to_string());
+        }
+        OllamaModelDownloader::new(settings, RunOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_redirect_to_other_host_drops_authorization() {
+        let (cdn, cdn_request) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nblob".to_string(),
+        );
+        let (origin, origin_request) = serve_once(redirect_to(&format!("{}/blob", cdn)));
+
+        let downloader =
+            downloader_with_headers(&[("Authorization", "Bearer secret"), ("X-Team", "models")]);
+        let response = downloader.client.get(&origin).send().unwrap();
+        assert_eq!(response.text().unwrap(), "blob");
+
+        assert!(
+            origin_request
+                .join()
+                .unwrap()
+                .contains("authorization: bearer secret")
+        );
+        let cdn_request = cdn_request.join().unwrap();
+        assert!(!cdn_request.contains("authorization"));
+        assert!(!cdn_request.contains("secret"));
+        assert!(cdn_request.contains("x-team: models"));
+    }
+
+    #[test]
+    fn test_redirect_to_other_host_refused_with_credential_headers() {
+        let (origin, origin_request) = serve_once(redirect_to("http://127.0.0.1:9/blob"));
+
+        let downloader = downloader_with_headers(&[("X-Api-Key", "secret")]);
+        let error = downloader.client.get(&origin).send().unwrap_err();
+        assert!(error.is_redirect());
+        assert!(format!("{:?}", error).contains("x-api-key"));
+        origin_request.join().unwrap();
+    }
+
+    #[test]
+    fn test_parse_library_html() {
+        let library_url = Url::parse("https://ollama.com/library").unwrap();
+        let html = include_str!("testdata/ollama_library.html");
+        assert_eq!(
+            parse_library_html(html, &library_url).unwrap(),
+            vec![
+                "Gemma3",
+                "deepseek-r1",
+                "llama3.1",
+                "nomic-embed-text",
+                "qwen3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_library_model_name() {
+        let library_url = Url::parse("https://ollama.com/library").unwrap();
+        let name = |href| library_model_name(href, &library_url);
+        assert_eq!(name("/library/llama3.1"), Some("llama3.1".to_string()));
+        assert_eq!(name("library/qwen3"), Some("qwen3".to_string()));
+        assert_eq!(
+            name("https://ollama.com/library/qwen3?tab=readme"),
+            Some("qwen3".to_string())
+        );
+        assert_eq!(name("/library/"), None);
+        assert_eq!(name("/library/llama3.1/tags"), None);
+        assert_eq!(name("/blog/library"), None);
+        assert_eq!(name("https://docs.ollama.com/library/qwen3"), None);
+    }
+
+    #[test]
+    fn test_parse_library_json() {
+        let json = r#"{"models": [{"name": "qwen3:4b"}, {"name": "library/llama3.1:8b"}, {"name": "qwen3:8b"}, {"name": ""}]}"#;
+        assert_eq!(parse_library_json(json).unwrap(), vec!["llama3.1", "qwen3"]);
+        assert!(parse_library_json("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_library_listing_retries_server_errors() {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let library_base_url = format!("http://{}/library/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let body = r#"{"models": [{"name": "qwen3:4b"}]}"#;
+            for status in [503, 200] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let mut settings = AppSettings::default();
+        settings.ollama_library.library_base_url = library_base_url;
+        settings.ollama_library.library_request_timeout = Some(10.0);
+        let downloader = OllamaModelDownloader::new(settings, RunOptions::default()).unwrap();
+        assert_eq!(
+            downloader.list_available_models(None, None).unwrap(),
+            vec!["qwen3"]
+        );
+    }
+
+    #[test]
+    fn test_make_blob_url() {
+        let downloader =
+            OllamaModelDownloader::new(AppSettings::default(), RunOptions::default()).unwrap();
+        assert_eq!(
+            downloader.make_blob_url("llama3.1", "sha256:abc123"),
+            "https://registry.ollama.ai/v2/library/llama3.1/blobs/sha256-abc123"
+        );
+        let downloader = downloader.with_digest_format(DigestFormat::Plain);
+        assert_eq!(
+            downloader.make_blob_url("llama3.1", "sha256:abc123"),
+            "https://registry.ollama.ai/v2/library/llama3.1/blobs/sha256:abc123"
+        );
+    }
+
+    #[test]
+    fn test_is_model_present() {
+        let hex = "a".repeat(64);
+        for (model_identifier, listed_name) in [
+            ("llama3.1:8b", "llama3.1:8b".to_string()),
+            ("llama3.1", "library/llama3.1:latest".to_string()),
+            (
+                &format!("llama3.1@sha256:{}", hex) as &str,
+                format!("llama3.1:sha256-{}", hex),
+            ),
+        ] {
+            let body = serde_json::json!({"models": [{"name": listed_name}]}).to_string();
+            let (server_url, request) = serve_once(format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            ));
+            let mut settings = AppSettings::default();
+            settings.ollama_server.url = server_url;
+            let downloader = OllamaModelDownloader::new(settings, RunOptions::default()).unwrap();
+            assert!(
+                downloader.is_model_present(model_identifier).unwrap(),
+                "{} is not found as {}",
+                model_identifier,
+                listed_name
+            );
+            assert!(request.join().unwrap().contains("/api/tags"));
+        }
+    }
+
+    #[test]
+    fn test_model_names_in_ollama() {
+        let downloader =
+            OllamaModelDownloader::new(AppSettings::default(), RunOptions::default()).unwrap();
+        assert_eq!(
+            downloader.model_names_in_ollama("llama3.1", "8b"),
+            vec![
+                "llama3.1:8b".to_string(),
+                "library/llama3.1:8b".to_string(),
+                "registry.ollama.ai/library/llama3.1:8b".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_models_path_must_be_a_directory() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut settings = AppSettings::default();
+        settings.ollama_library.models_path = file.path().to_string_lossy().into_owned();
+        let downloader = OllamaModelDownloader::new(settings, RunOptions::default()).unwrap();
+
+        match downloader.download_model("tiny:1b") {
+            Err(DownloaderError::Other(message)) => assert_eq!(
+                message,
+                format!(
+                    "Models path {:?} is not a directory; check 'models_path' in the settings",
+                    file.path()
+                )
+            ),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_blobs_only_reads_local_manifest() {
+        use sha2::{Digest, Sha256};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut settings = AppSettings::default();
+        settings.ollama_library.models_path = dir.path().to_string_lossy().into_owned();
+        // Nothing listens here, so any request to the registry would fail the download
+        settings.ollama_library.registry_base_url = "http://127.0.0.1:9/v2/library/".to_string();
+        settings.ollama_server.check_model_presence = false;
+        let downloader = OllamaModelDownloader::new(settings, RunOptions::default())
+            .unwrap()
+            .with_scope(DownloadScope::BlobsOnly);
+
+        assert!(matches!(
+            downloader.download_model("tiny:1b"),
+            Err(DownloaderError::ModelNotFound(_))
+        ));
+
+        let blobs_dir = dir.path().join("blobs");
+        std::fs::create_dir_all(&blobs_dir).unwrap();
+        let mut digests = Vec::new();
+        for content in [&b"config"[..], &b"weights"[..]] {
+            let digest = format!("sha256:{:x}", Sha256::digest(content));
+            std::fs::write(blobs_dir.join(digest.replacen(':', "-", 1)), content).unwrap();
+            digests.push(digest);
+        }
+        let manifest = serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {
+                "mediaType": "application/vnd.docker.container.image.v1+json",
+                "digest": digests[0],
+                "size": 6,
+            },
+            "layers": [{
+                "mediaType": "application/vnd.ollama.image.model",
+                "digest": digests[1],
+                "size": 7,
+            }],
+        });
+        let manifests_dir = downloader.manifests_dir("tiny").unwrap();
+        std::fs::create_dir_all(&manifests_dir).unwrap();
+        std::fs::write(manifests_dir.join("1b"), manifest.to_string()).unwrap();
+
+        // All BLOBs are intact, so nothing is fetched
+        let summary = downloader.download_model("tiny:1b").unwrap();
+        assert_eq!((summary.blobs, summary.total_bytes), (0, 0));
+    }
+
+    #[test]
+    fn test_download_model_fetches_repeated_layer_once() {
+        use sha2::{Digest, Sha256};
+        use std::collections::HashMap;
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::{Arc, Mutex};
+
+        let config = b"{}";
+        let weights = b"weights";
+        let config_digest = format!("sha256:{:x}", Sha256::digest(config));
+        let weights_digest = format!("sha256:{:x}", Sha256::digest(weights));
+        let manifest = serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {
+                "mediaType": "application/vnd.docker.container.image.v1+json",
+                "digest": config_digest,
+                "size": config.len(),
+            },
+            "layers": [
+                {
+                    "mediaType": "application/vnd.ollama.image.model",
+                    "digest": weights_digest,
+                    "size": weights.len(),
+                },
+                {
+                    "mediaType": "application/vnd.ollama.image.projector",
+                    "digest": weights_digest,
+                    "size": weights.len(),
+                },
+            ],
+        });
+        let bodies: HashMap<String, Vec<u8>> = HashMap::from([
+            (
+                "/v2/library/tiny/manifests/1b".to_string(),
+                manifest.to_string().into_bytes(),
+            ),
+            (
+                format!("/v2/library/tiny/blobs/{}", blob_file_name(&config_digest)),
+                config.to_vec(),
+            ),
+            (
+                format!("/v2/library/tiny/blobs/{}", blob_file_name(&weights_digest)),
+                weights.to_vec(),
+            ),
+        ]);
+
+        // Serve each path from the registry, recording every request
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let registry_base_url = format!("http://{}/v2/library/", listener.local_addr().unwrap());
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requested);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+                let body = &bodies[&path];
+                recorded.lock().unwrap().push(path);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("blobs")).unwrap();
+        let mut settings = AppSettings::default();
+        settings.ollama_library.models_path = dir.path().to_string_lossy().into_owned();
+        settings.ollama_library.registry_base_url = registry_base_url;
+        settings.ollama_server.check_model_presence = false;
+        let mut downloader = OllamaModelDownloader::new(settings, RunOptions::default()).unwrap();
+        downloader.manifest_cache = None;
+
+        let summary = downloader.download_model("tiny:1b").unwrap();
+        assert_eq!(summary.blobs, 2);
+        let weights_requests = requested
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|path| path.ends_with(&blob_file_name(&weights_digest)))
+            .count();
+        assert_eq!(weights_requests, 1);
+        assert_eq!(
+            std::fs::read(
+                dir.path()
+                    .join("blobs")
+                    .join(blob_file_name(&weights_digest))
+            )
+            .unwrap(),
+            weights
+        );
+    }
+
     #[test]
     #[ignore] // Run manually with: cargo test -- --ignored
     fn test_ollama_model_download() {
@@ -489,7 +1521,8 @@ mod tests {
         let _ = env_logger::builder().is_test(true).try_init();
 
         let settings = AppSettings::default();
-        let downloader = OllamaModelDownloader::new(settings).expect("Failed to create downloader");
+        let downloader = OllamaModelDownloader::new(settings, RunOptions::default())
+            .expect("Failed to create downloader");
 
         // Download a small model for testing
         let model_identifier = "all-minilm:22m";
@@ -498,9 +1531,9 @@ mod tests {
         let result = downloader.download_model(model_identifier);
 
         match result {
-            Ok(success) => {
-                assert!(success, "Download should return true on success");
-                println!("Successfully downloaded {}", model_identifier);
+            Ok(summary) => {
+                assert!(summary.blobs > 0, "Download should fetch BLOBs");
+                println!("Successfully downloaded {}", summary.line());
             }
             Err(e) => {
                 panic!("Download failed: {:?}", e);
@@ -0,0 +1,382 @@
+//! Progress reporting for the Ollama Downloader in Rust (ODIR), keeping how the progress of
+//! a download is shown apart from the download itself. The command line shows progress
+//! bars, or JSON events for frontends, while other callers can implement [`DownloadProgress`]
+//! to follow downloads their own way.
+use crate::config::{OllamaLibrary, RunOptions};
+use crate::downloader::model_downloader::DownloadSummary;
+use crate::downloader::utils::format_byte_size;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{debug, warn};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Receiver of the progress of model downloads. Every method does nothing by default, so
+/// that an implementation only handles the events it is interested in.
+pub trait DownloadProgress {
+    /// Called when the download of a BLOB starts.
+    ///
+    /// # Arguments
+    /// * `digest` - The digest of the BLOB, e.g., sha256:<hex>
+    /// * `total_bytes` - The size of the BLOB, or 0 if the registry did not report it
+    fn on_blob_start(&mut self, _digest: &str, _total_bytes: u64) {}
+
+    /// Called for every chunk of the BLOB being downloaded.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of bytes in the chunk
+    fn on_chunk(&mut self, _bytes: u64) {}
+
+    /// Called once the BLOB being downloaded is complete.
+    ///
+    /// # Arguments
+    /// * `digest` - The digest of the BLOB
+    fn on_blob_complete(&mut self, _digest: &str) {}
+
+    /// Called when the download of the BLOB fails, stalls or is interrupted.
+    ///
+    /// # Arguments
+    /// * `digest` - The digest of the BLOB
+    fn on_blob_abandoned(&mut self, _digest: &str) {}
+
+    /// Called once a model has been downloaded and saved.
+    ///
+    /// # Arguments
+    /// * `summary` - The summary of the download
+    fn on_model_complete(&mut self, _summary: &DownloadSummary) {}
+
+    /// Run an interactive prompt, e.g., to confirm an interrupt, without the progress
+    /// display drawing over it.
+    ///
+    /// # Arguments
+    /// * `prompt` - The prompt to run
+    ///
+    /// # Returns
+    /// * `bool` - The answer of the prompt
+    fn suspend(&mut self, prompt: &mut dyn FnMut() -> bool) -> bool {
+        prompt()
+    }
+}
+
+/// A progress receiver shared by a downloader and the downloads it runs
+pub type SharedProgress = Rc<RefCell<dyn DownloadProgress>>;
+
+/// Progress receiver that ignores all progress, used unless another one is set
+#[derive(Debug, Default)]
+pub struct NoProgress;
+
+impl DownloadProgress for NoProgress {}
+
+/// Get the progress receiver of the command line: progress bars on stderr, or JSON
+/// events on stderr if `progress_json` is set.
+///
+/// # Arguments
+/// * `library` - The library settings with the progress options
+/// * `options` - The options of this run
+///
+/// # Returns
+/// * `SharedProgress` - The progress receiver
+pub fn terminal_progress(library: &OllamaLibrary, options: &RunOptions) -> SharedProgress {
+    if options.progress_json {
+        Rc::new(RefCell::new(ProgressEvents::new(
+            io::stderr(),
+            library.progress_refresh_hz,
+        )))
+    } else {
+        Rc::new(RefCell::new(ProgressBars::new(
+            library.progress_refresh_hz,
+            library.max_bandwidth_bytes_per_sec,
+            library.progress_template.as_deref(),
+        )))
+    }
+}
+
+/// Default template of the progress bars
+const DEFAULT_PROGRESS_TEMPLATE: &str = "{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})";
+
+/// Default template of the progress bars when the bandwidth is limited, showing the achieved
+/// rate next to the limit, which replaces `{limit}`
+const LIMITED_PROGRESS_TEMPLATE: &str =
+    "{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} (limit {limit}/s) ({eta})";
+
+/// Characters that draw the filled, current and empty parts of a progress bar
+const PROGRESS_CHARS: &str = "#>-";
+
+/// Check whether a progress bar template is valid.
+///
+/// # Arguments
+/// * `template` - The template, in the syntax of `indicatif`
+///
+/// # Returns
+/// * `Result<(), String>` - Success, or a description of why the template is invalid
+pub fn validate_progress_template(template: &str) -> Result<(), String> {
+    ProgressStyle::default_bar()
+        .template(template)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Get the style of the progress bars: the configured template, or the default one if none
+/// is configured or the configured one is invalid, which is warned about.
+///
+/// # Arguments
+/// * `template` - The configured template, if any
+/// * `max_bandwidth_bytes_per_sec` - The bandwidth limit shown by the default template,
+///   where 0 means unlimited
+///
+/// # Returns
+/// * `ProgressStyle` - The style of the progress bars
+fn progress_style(template: Option<&str>, max_bandwidth_bytes_per_sec: u64) -> ProgressStyle {
+    if let Some(template) = template {
+        match ProgressStyle::default_bar().template(template) {
+            Ok(style) => return style.progress_chars(PROGRESS_CHARS),
+            Err(e) => warn!(
+                "Invalid 'ollama_library.progress_template' {:?}: {}. Using the default template",
+                template, e
+            ),
+        }
+    }
+    let template = if max_bandwidth_bytes_per_sec > 0 {
+        LIMITED_PROGRESS_TEMPLATE.replace("{limit}", &format_byte_size(max_bandwidth_bytes_per_sec))
+    } else {
+        DEFAULT_PROGRESS_TEMPLATE.to_string()
+    };
+    ProgressStyle::default_bar()
+        .template(&template)
+        .unwrap()
+        .progress_chars(PROGRESS_CHARS)
+}
+
+/// Progress bars on stderr, one for each BLOB, so that stdout only carries the results of
+/// a command. Bars are redrawn at a capped rate, so that fast downloads do not spend their
+/// time drawing.
+pub struct ProgressBars {
+    refresh_hz: u8,
+    style: ProgressStyle,
+    bar: Option<ProgressBar>,
+}
+
+impl ProgressBars {
+    /// Create progress bars.
+    ///
+    /// # Arguments
+    /// * `refresh_hz` - Maximum number of times per second a bar is redrawn
+    /// * `max_bandwidth_bytes_per_sec` - The bandwidth limit to show, where 0 means unlimited
+    /// * `template` - The template of the bars, or None for the default one
+    ///
+    /// # Returns
+    /// * `Self` - The progress bars
+    pub fn new(refresh_hz: u8, max_bandwidth_bytes_per_sec: u64, template: Option<&str>) -> Self {
+        Self {
+            refresh_hz,
+            style: progress_style(template, max_bandwidth_bytes_per_sec),
+            bar: None,
+        }
+    }
+}
+
+impl DownloadProgress for ProgressBars {
+    fn on_blob_start(&mut self, digest: &str, total_bytes: u64) {
+        if let Some(bar) = self.bar.take() {
+            bar.abandon();
+        }
+        let bar = ProgressBar::with_draw_target(
+            Some(total_bytes),
+            ProgressDrawTarget::stderr_with_hz(self.refresh_hz),
+        );
+        bar.set_style(self.style.clone());
+        bar.set_message(format!("Downloading BLOB {}", digest));
+        self.bar = Some(bar);
+    }
+
+    fn on_chunk(&mut self, bytes: u64) {
+        if let Some(bar) = &self.bar {
+            bar.inc(bytes);
+        }
+    }
+
+    fn on_blob_complete(&mut self, _digest: &str) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_with_message("Downloaded");
+        }
+    }
+
+    fn on_blob_abandoned(&mut self, _digest: &str) {
+        if let Some(bar) = self.bar.take() {
+            bar.abandon();
+        }
+    }
+
+    fn suspend(&mut self, prompt: &mut dyn FnMut() -> bool) -> bool {
+        match &self.bar {
+            Some(bar) => bar.suspend(prompt),
+            None => prompt(),
+        }
+    }
+}
+
+/// Progress as newline-delimited JSON events, for frontends that wrap ODIR: `blob_start`,
+/// then `blob_progress` at most at the refresh rate, and `blob_done` once the BLOB has
+/// been downloaded, for each BLOB, and finally `model_done` with the summary of the model.
+pub struct ProgressEvents<W: Write> {
+    writer: W,
+    interval: Duration,
+    digest: String,
+    total: u64,
+    downloaded: u64,
+    last_reported: Option<Instant>,
+}
+
+impl<W: Write> ProgressEvents<W> {
+    /// Create a reporter of progress events.
+    ///
+    /// # Arguments
+    /// * `writer` - Where the events are written, e.g., stderr
+    /// * `refresh_hz` - Maximum number of progress events per second for a BLOB
+    ///
+    /// # Returns
+    /// * `Self` - The reporter
+    pub fn new(writer: W, refresh_hz: u8) -> Self {
+        Self {
+            writer,
+            interval: Duration::from_secs(1) / u32::from(refresh_hz.max(1)),
+            digest: String::new(),
+            total: 0,
+            downloaded: 0,
+            last_reported: None,
+        }
+    }
+
+    /// Account for downloaded bytes, emitting a `blob_progress` event if one is due.
+    fn progress(&mut self, bytes: u64, now: Instant) {
+        self.downloaded += bytes;
+        if self
+            .last_reported
+            .is_some_and(|last| now.saturating_duration_since(last) < self.interval)
+        {
+            return;
+        }
+        self.last_reported = Some(now);
+        self.emit(serde_json::json!({
+            "event": "blob_progress",
+            "digest": self.digest,
+            "downloaded": self.downloaded,
+            "total": self.total,
+        }));
+    }
+
+    fn emit(&mut self, event: Value) {
+        if let Err(e) = writeln!(self.writer, "{}", event) {
+            debug!("Failed to report progress: {}", e);
+        }
+    }
+}
+
+impl<W: Write> DownloadProgress for ProgressEvents<W> {
+    fn on_blob_start(&mut self, digest: &str, total_bytes: u64) {
+        self.digest = digest.to_string();
+        self.total = total_bytes;
+        self.downloaded = 0;
+        self.last_reported = None;
+        self.emit(serde_json::json!({
+            "event": "blob_start",
+            "digest": self.digest,
+            "total": total_bytes,
+        }));
+    }
+
+    fn on_chunk(&mut self, bytes: u64) {
+        self.progress(bytes, Instant::now());
+    }
+
+    fn on_blob_complete(&mut self, _digest: &str) {
+        self.emit(serde_json::json!({
+            "event": "blob_done",
+            "digest": self.digest,
+            "downloaded": self.downloaded,
+        }));
+    }
+
+    fn on_model_complete(&mut self, summary: &DownloadSummary) {
+        match serde_json::to_value(summary) {
+            Ok(Value::Object(mut event)) => {
+                event.insert("event".to_string(), Value::from("model_done"));
+                self.emit(Value::Object(event));
+            }
+            Ok(_) | Err(_) => debug!("Failed to report the summary of {}", summary.identifier),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_events() {
+        let mut output = Vec::new();
+        let start = Instant::now();
+        let mut events = ProgressEvents::new(&mut output, 10);
+        events.on_blob_start("sha256:abc", 300);
+        events.progress(100, start);
+        // Too soon after the last event to report again
+        events.progress(100, start + Duration::from_millis(50));
+        events.progress(100, start + Duration::from_millis(100));
+        events.on_blob_complete("sha256:abc");
+        events.on_model_complete(&DownloadSummary::new(
+            "llama3.1:8b",
+            300,
+            1,
+            Duration::from_secs(3),
+        ));
+
+        let events: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                serde_json::json!({"event": "blob_start", "digest": "sha256:abc", "total": 300}),
+                serde_json::json!({"event": "blob_progress", "digest": "sha256:abc", "downloaded": 100, "total": 300}),
+                serde_json::json!({"event": "blob_progress", "digest": "sha256:abc", "downloaded": 300, "total": 300}),
+                serde_json::json!({"event": "blob_done", "digest": "sha256:abc", "downloaded": 300}),
+                serde_json::json!({"event": "model_done", "identifier": "llama3.1:8b", "total_bytes": 300, "blobs": 1, "elapsed_secs": 3.0, "bytes_per_sec": 100.0, "up_to_date": false}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_progress_events_restart_for_each_blob() {
+        let mut output = Vec::new();
+        let mut events = ProgressEvents::new(&mut output, 1);
+        events.on_blob_start("sha256:abc", 10);
+        events.on_chunk(10);
+        events.on_blob_complete("sha256:abc");
+        events.on_blob_start("sha256:def", 5);
+        events.on_chunk(5);
+        events.on_blob_complete("sha256:def");
+
+        let last: Value =
+            serde_json::from_str(String::from_utf8(output).unwrap().lines().last().unwrap())
+                .unwrap();
+        assert_eq!(
+            last,
+            serde_json::json!({"event": "blob_done", "digest": "sha256:def", "downloaded": 5})
+        );
+    }
+
+    #[test]
+    fn test_validate_progress_template() {
+        assert!(validate_progress_template(DEFAULT_PROGRESS_TEMPLATE).is_ok());
+        assert!(
+            validate_progress_template(&LIMITED_PROGRESS_TEMPLATE.replace("{limit}", "5 MB"))
+                .is_ok()
+        );
+        assert!(validate_progress_template("{msg} {wide_bar} {percent}%").is_ok());
+        assert!(validate_progress_template("{msg} {bar:wide}").is_err());
+    }
+}
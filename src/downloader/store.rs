@@ -0,0 +1,1007 @@
+//! Statistics and maintenance of the local model store for the Ollama Downloader in Rust
+//! (ODIR), summarising the installed models, the BLOBs they use, and the BLOBs that no
+//! installed model references any longer, pruning old tags of models, and removing the
+//! BLOBs left behind by updated models, locating the files of an installed model, and
+//! checking its BLOBs against their digests.
+use crate::downloader::archive::find_local_model_dir;
+use crate::downloader::lock::lock_models_path;
+use crate::downloader::manifest::{ImageManifest, blob_file_name};
+use crate::downloader::model_downloader::{DownloaderError, Result};
+use crate::downloader::utils::{Ownership, is_blob_intact};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Registry host and namespace of Ollama library models, which Ollama leaves out of their names
+const OLLAMA_LIBRARY_PREFIX: &str = "registry.ollama.ai/library/";
+
+/// An installed model, i.e., a tag with a manifest in the models directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LocalModel {
+    /// The name of the model as Ollama lists it, e.g., llama3.1:8b or hf.co/user/repo:Q4_K_M
+    pub name: String,
+
+    /// The total size in bytes of the distinct BLOBs referenced by its manifest
+    pub size: u64,
+}
+
+/// Summary of the local model store
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StoreStats {
+    /// Number of distinct models, regardless of their tags
+    pub models: usize,
+
+    /// Number of installed tags, i.e., of readable manifests
+    pub tags: usize,
+
+    /// Number of BLOBs in the `blobs` directory
+    pub blobs: usize,
+
+    /// Total size in bytes of the BLOBs in the `blobs` directory
+    pub blob_bytes: u64,
+
+    /// Number of BLOBs that no manifest references
+    pub orphan_blobs: usize,
+
+    /// Total size in bytes of the BLOBs that no manifest references
+    pub orphan_blob_bytes: u64,
+
+    /// Number of BLOBs referenced by a manifest but absent from the `blobs` directory
+    pub missing_blobs: usize,
+
+    /// Number of manifests that could not be read or parsed
+    pub unreadable_manifests: usize,
+
+    /// The largest installed models, largest first
+    pub largest: Vec<LocalModel>,
+}
+
+/// Outcome of pruning the tags of a model
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PruneReport {
+    /// The tags that were kept, newest first
+    pub kept: Vec<String>,
+
+    /// The tags whose manifests were removed, or would be removed in a dry run, newest first
+    pub removed: Vec<String>,
+
+    /// Number of BLOBs that no manifest references any longer and were removed
+    pub removed_blobs: usize,
+
+    /// Total size in bytes of the removed BLOBs
+    pub freed_bytes: u64,
+
+    /// Whether nothing was actually removed
+    pub dry_run: bool,
+}
+
+/// Where the files of an installed model are on disk
+#[derive(Debug, PartialEq, Eq)]
+pub struct ModelLocation {
+    /// The absolute path of the manifest
+    pub manifest: PathBuf,
+
+    /// The absolute paths of the BLOBs the manifest references, in manifest order
+    pub blobs: Vec<PathBuf>,
+}
+
+/// State of a BLOB of an installed model, checked against its digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobState {
+    /// The BLOB is present and matches its digest
+    Intact,
+
+    /// The BLOB is absent from the `blobs` directory
+    Missing,
+
+    /// The BLOB is present, but does not match its digest
+    Corrupt,
+}
+
+/// Outcome of checking a BLOB of an installed model against its digest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlobCheck {
+    /// The digest of the BLOB, e.g., sha256:<hex>
+    pub digest: String,
+
+    /// The size of the BLOB in bytes, according to the manifest
+    pub size: u64,
+
+    /// Whether the BLOB is intact, missing or corrupt
+    pub state: BlobState,
+}
+
+/// Get the name under which Ollama lists a model from the path of its manifest.
+///
+/// # Arguments
+/// * `relative_path` - Path of the manifest relative to the `manifests` directory,
+///   e.g., registry.ollama.ai/library/llama3.1/8b
+///
+/// # Returns
+/// * `Option<String>` - The name, e.g., llama3.1:8b, or None if the path is not that of a manifest
+pub fn model_name_from_manifest_path(relative_path: &Path) -> Option<String> {
+    let components: Vec<&str> = relative_path
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<_>>()?;
+    let (tag, repository) = components.split_last()?;
+    if repository.len() < 2 {
+        return None;
+    }
+    let repository = repository.join("/");
+    let repository = repository
+        .strip_prefix(OLLAMA_LIBRARY_PREFIX)
+        .unwrap_or(&repository);
+    Some(format!("{}:{}", repository, tag))
+}
+
+/// Visit all files below a directory as the walk finds them, without collecting them first.
+fn visit_files(dir: &Path, visit: &mut dyn FnMut(PathBuf) -> Result<()>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            visit_files(&entry.path(), visit)?;
+        } else if file_type.is_file() {
+            visit(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Read and parse the manifests in the models directory one by one, visiting each readable
+/// manifest as soon as it is parsed.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `visit` - Called with the path and content of each readable manifest
+///
+/// # Returns
+/// * `Result<usize>` - The number of manifests that could not be read or parsed
+fn visit_manifests(
+    models_root: &Path,
+    visit: &mut dyn FnMut(PathBuf, ImageManifest) -> Result<()>,
+) -> Result<usize> {
+    let manifests_dir = models_root.join("manifests");
+    let mut unreadable = 0;
+    if !manifests_dir.is_dir() {
+        return Ok(unreadable);
+    }
+    visit_files(&manifests_dir, &mut |path| {
+        let manifest = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| ImageManifest::parse(&content).map_err(|e| e.to_string()));
+        match manifest {
+            Ok(manifest) => visit(path, manifest),
+            Err(e) => {
+                warn!("Skipping unreadable manifest {:?}: {}", path, e);
+                unreadable += 1;
+                Ok(())
+            }
+        }
+    })?;
+    Ok(unreadable)
+}
+
+/// Read and parse every manifest in the models directory.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+///
+/// # Returns
+/// * `Result<(Vec<(PathBuf, ImageManifest)>, usize)>` - The paths and contents of the readable
+///   manifests, and the number of manifests that could not be read or parsed
+fn read_manifests(models_root: &Path) -> Result<(Vec<(PathBuf, ImageManifest)>, usize)> {
+    let mut manifests = Vec::new();
+    let unreadable = visit_manifests(models_root, &mut |path, manifest| {
+        manifests.push((path, manifest));
+        Ok(())
+    })?;
+    Ok((manifests, unreadable))
+}
+
+/// Get the file names of the BLOBs referenced by a manifest.
+fn referenced_blobs(manifest: &ImageManifest) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(blob_file_name(&manifest.config.digest)).chain(
+        manifest
+            .layers
+            .iter()
+            .flatten()
+            .map(|layer| blob_file_name(&layer.digest)),
+    )
+}
+
+/// Get the file names and sizes of the BLOBs in the BLOBs directory, statting them on up to
+/// `jobs` threads, sorted by name whichever thread statted them.
+fn blob_sizes(blobs_dir: &Path, jobs: usize) -> Result<Vec<(String, u64)>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(blobs_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+
+    let chunk_size = paths.len().div_ceil(jobs.max(1)).max(1);
+    let chunks = std::thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| {
+                            let name = path
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned();
+                            Ok((name, fs::metadata(path)?.len()))
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.join().map_err(|_| {
+                    DownloaderError::Other("A thread statting BLOBs panicked".to_string())
+                })?
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+    let mut sizes: Vec<(String, u64)> = chunks.into_iter().flatten().collect();
+    sizes.sort();
+    Ok(sizes)
+}
+
+/// Summarise the models directory without modifying it: the installed models and their
+/// sizes, according to their manifests, and the BLOBs that are used, unused or missing.
+///
+/// Each installed model is passed to `on_model` as soon as the walk of the `manifests`
+/// directory finds it, so that it can be streamed, and only the largest models are kept.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `largest` - How many of the largest models to include
+/// * `jobs` - How many threads stat the BLOBs
+/// * `on_model` - Called with each installed model as it is found
+///
+/// # Returns
+/// * `Result<StoreStats>` - The summary of the models directory
+pub fn collect_stats(
+    models_root: &Path,
+    largest: usize,
+    jobs: usize,
+    on_model: &mut dyn FnMut(&LocalModel),
+) -> Result<StoreStats> {
+    let mut stats = StoreStats::default();
+
+    let manifests_dir = models_root.join("manifests");
+    // The smallest of the largest models so far is on top, to be replaced by a larger one
+    let mut models = BinaryHeap::new();
+    let mut repositories = HashSet::new();
+    let mut referenced = HashSet::new();
+    let unreadable_manifests = visit_manifests(models_root, &mut |path, manifest| {
+        let relative_path = path.strip_prefix(&manifests_dir).unwrap_or(&path);
+        let Some(name) = model_name_from_manifest_path(relative_path) else {
+            warn!(
+                "Skipping unreadable manifest {:?}: not a path of a model tag",
+                path
+            );
+            stats.unreadable_manifests += 1;
+            return Ok(());
+        };
+
+        referenced.extend(referenced_blobs(&manifest));
+        if let Some((repository, _)) = name.rsplit_once(':') {
+            repositories.insert(repository.to_string());
+        }
+        let model = LocalModel {
+            name,
+            size: manifest.total_size(),
+        };
+        on_model(&model);
+        stats.tags += 1;
+        models.push(Reverse((model.size, Reverse(model.name))));
+        if models.len() > largest {
+            models.pop();
+        }
+        Ok(())
+    })?;
+    stats.unreadable_manifests += unreadable_manifests;
+    stats.models = repositories.len();
+
+    let blobs_dir = models_root.join("blobs");
+    let mut present = HashSet::new();
+    if blobs_dir.is_dir() {
+        for (file_name, size) in blob_sizes(&blobs_dir, jobs)? {
+            stats.blobs += 1;
+            stats.blob_bytes += size;
+            if !referenced.contains(&file_name) {
+                stats.orphan_blobs += 1;
+                stats.orphan_blob_bytes += size;
+            }
+            present.insert(file_name);
+        }
+    }
+    stats.missing_blobs = referenced.difference(&present).count();
+
+    // Largest first, and by name among models of the same size
+    stats.largest = models
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((size, Reverse(name)))| LocalModel { name, size })
+        .collect();
+
+    Ok(stats)
+}
+
+/// Remove the manifests of all but the newest tags of a model, by modification time, and
+/// then those of the BLOBs they referenced that no remaining manifest references. Other
+/// BLOBs are left alone, e.g., those of a download whose manifest has not been written yet,
+/// and the models path is locked while tags are pruned.
+///
+/// BLOBs are only removed if every manifest in the models directory could be read, since an
+/// unreadable manifest may reference any of them.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `model` - The model, without a tag, e.g., llama3.1 or user/repo
+/// * `keep` - How many of the newest tags to keep
+/// * `dry_run` - Whether to only report what would be removed
+/// * `models_dir_ownership` - The ownership to give the lock file, if any
+/// * `no_lock` - Whether to prune without locking the models path
+///
+/// # Returns
+/// * `Result<PruneReport>` - The kept and removed tags, and the removed BLOBs
+pub fn prune_tags(
+    models_root: &Path,
+    model: &str,
+    keep: usize,
+    dry_run: bool,
+    models_dir_ownership: Option<Ownership>,
+    no_lock: bool,
+) -> Result<PruneReport> {
+    let model_dir = models_root.join(find_local_model_dir(models_root, model)?);
+    let _lock = lock_models_path(models_root, models_dir_ownership, no_lock)?;
+
+    let mut tags = Vec::new();
+    for entry in fs::read_dir(&model_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            let modified = entry.metadata()?.modified()?;
+            tags.push((modified, entry.file_name().to_string_lossy().into_owned()));
+        }
+    }
+    tags.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    let removed: Vec<String> = tags.iter().skip(keep).map(|(_, tag)| tag.clone()).collect();
+    let mut report = PruneReport {
+        kept: tags.iter().take(keep).map(|(_, tag)| tag.clone()).collect(),
+        dry_run,
+        ..PruneReport::default()
+    };
+
+    let removed_paths: HashSet<PathBuf> = removed.iter().map(|tag| model_dir.join(tag)).collect();
+    let mut removed_digests = Vec::new();
+    for path in &removed_paths {
+        match fs::read_to_string(path)
+            .map_err(DownloaderError::from)
+            .and_then(|content| ImageManifest::parse(&content))
+        {
+            Ok(manifest) => {
+                removed_digests.extend(manifest.blobs().into_iter().map(|blob| blob.digest))
+            }
+            Err(e) => warn!(
+                "Not removing the BLOBs of manifest {:?}, which cannot be read: {}",
+                path, e
+            ),
+        }
+        if !dry_run {
+            fs::remove_file(path)?;
+        }
+        info!(
+            "Removed manifest {:?}{}",
+            path,
+            if dry_run { " (dry run)" } else { "" }
+        );
+    }
+    report.removed = removed;
+    if report.removed.is_empty() {
+        return Ok(report);
+    }
+
+    let digests: Vec<&str> = removed_digests.iter().map(String::as_str).collect();
+    (report.removed_blobs, report.freed_bytes) = if dry_run {
+        let orphaned = orphaned_blobs(models_root, &digests, &removed_paths)?;
+        (orphaned.len(), orphaned.iter().map(|(_, size)| size).sum())
+    } else {
+        remove_orphaned_blobs(models_root, &digests)?
+    };
+    Ok(report)
+}
+
+/// Remove those of the given BLOBs that no manifest in the models directory references,
+/// e.g., the BLOBs of the previous version of an updated model. Nothing is removed if any
+/// manifest cannot be read, since it may reference the BLOBs.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `digests` - Digests of the BLOBs that may no longer be referenced
+///
+/// # Returns
+/// * `Result<(usize, u64)>` - The number of removed BLOBs and the bytes they freed
+pub fn remove_orphaned_blobs(models_root: &Path, digests: &[&str]) -> Result<(usize, u64)> {
+    let mut removed = 0;
+    let mut freed_bytes = 0;
+    for (path, size) in orphaned_blobs(models_root, digests, &HashSet::new())? {
+        fs::remove_file(&path)?;
+        debug!("Removed unreferenced BLOB {:?}", path);
+        removed += 1;
+        freed_bytes += size;
+    }
+    Ok((removed, freed_bytes))
+}
+
+/// Find those of the given BLOBs that are present, but that no manifest in the models
+/// directory references, other than the ignored ones. Partially downloaded files are never
+/// included, and nothing is if any manifest cannot be read, since it may reference the BLOBs.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `digests` - Digests of the BLOBs that may no longer be referenced
+/// * `ignored_manifests` - Paths of manifests whose references do not count, e.g., those
+///   that a dry run would remove
+///
+/// # Returns
+/// * `Result<Vec<(PathBuf, u64)>>` - The paths and sizes of the unreferenced BLOBs
+fn orphaned_blobs(
+    models_root: &Path,
+    digests: &[&str],
+    ignored_manifests: &HashSet<PathBuf>,
+) -> Result<Vec<(PathBuf, u64)>> {
+    if digests.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (manifests, unreadable_manifests) = read_manifests(models_root)?;
+    if unreadable_manifests > 0 {
+        warn!(
+            "Not removing unreferenced BLOBs, since {} manifest(s) could not be read",
+            unreadable_manifests
+        );
+        return Ok(Vec::new());
+    }
+    let referenced: HashSet<String> = manifests
+        .iter()
+        .filter(|(path, _)| !ignored_manifests.contains(path))
+        .flat_map(|(_, manifest)| referenced_blobs(manifest))
+        .collect();
+
+    let mut orphaned = Vec::new();
+    let mut seen = HashSet::new();
+    for digest in digests {
+        let file_name = blob_file_name(digest);
+        let path = models_root.join("blobs").join(&file_name);
+        if file_name.contains("-partial")
+            || referenced.contains(&file_name)
+            || !seen.insert(file_name)
+            || !path.is_file()
+        {
+            continue;
+        }
+        let size = fs::metadata(&path)?.len();
+        orphaned.push((path, size));
+    }
+    Ok(orphaned)
+}
+
+/// Locate the files of an installed model: its manifest and the BLOBs it references,
+/// whether or not they are present.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `manifest_path` - Path of the manifest of the model
+/// * `identifier` - The model identifier, for the error if the model is not installed
+///
+/// # Returns
+/// * `Result<ModelLocation>` - The paths of the files, or an error if the model is not
+///   installed or its manifest cannot be read
+pub fn locate_model(
+    models_root: &Path,
+    manifest_path: &Path,
+    identifier: &str,
+) -> Result<ModelLocation> {
+    let manifest = read_installed_manifest(manifest_path, identifier)?;
+    let blobs_dir = std::path::absolute(models_root.join("blobs"))?;
+    Ok(ModelLocation {
+        manifest: std::path::absolute(manifest_path)?,
+        blobs: manifest
+            .blobs()
+            .iter()
+            .map(|blob| blobs_dir.join(blob_file_name(&blob.digest)))
+            .collect(),
+    })
+}
+
+/// Check every BLOB that the manifest of an installed model references against its digest,
+/// reading the BLOBs on up to `jobs` threads, without touching the network.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `manifest_path` - Path of the manifest of the model
+/// * `identifier` - The model identifier, for the error if the model is not installed
+/// * `jobs` - The number of threads to read BLOBs on
+///
+/// # Returns
+/// * `Result<Vec<BlobCheck>>` - The outcome for each BLOB, in manifest order, or an error if
+///   the model is not installed or a BLOB cannot be read
+pub fn verify_model_blobs(
+    models_root: &Path,
+    manifest_path: &Path,
+    identifier: &str,
+    jobs: usize,
+) -> Result<Vec<BlobCheck>> {
+    let blobs = read_installed_manifest(manifest_path, identifier)?.blobs();
+    // Threads take the next BLOB when done, since BLOBs differ widely in size
+    let next = AtomicUsize::new(0);
+    let checked = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..jobs.clamp(1, blobs.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut checked = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(blob) = blobs.get(index) else {
+                            return Ok(checked);
+                        };
+                        let path = models_root.join("blobs").join(blob_file_name(&blob.digest));
+                        let state = if !path.is_file() {
+                            BlobState::Missing
+                        } else if is_blob_intact(models_root, &blob.digest)? {
+                            BlobState::Intact
+                        } else {
+                            BlobState::Corrupt
+                        };
+                        debug!("BLOB {} is {:?}", blob.digest, state);
+                        checked.push((index, state));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.join().map_err(|_| {
+                    DownloaderError::Other("A thread checking BLOBs panicked".to_string())
+                })?
+            })
+            .collect::<Result<Vec<Vec<(usize, BlobState)>>>>()
+    })?;
+    let mut checked: Vec<(usize, BlobState)> = checked.into_iter().flatten().collect();
+    checked.sort_by_key(|(index, _)| *index);
+    Ok(checked
+        .into_iter()
+        .map(|(index, state)| BlobCheck {
+            digest: blobs[index].digest.clone(),
+            size: blobs[index].size,
+            state,
+        })
+        .collect())
+}
+
+/// Read and parse the manifest of an installed model.
+///
+/// # Arguments
+/// * `manifest_path` - Path of the manifest of the model
+/// * `identifier` - The model identifier, for the error if the model is not installed
+///
+/// # Returns
+/// * `Result<ImageManifest>` - The manifest, or an error if the model is not installed or
+///   its manifest cannot be read
+fn read_installed_manifest(manifest_path: &Path, identifier: &str) -> Result<ImageManifest> {
+    if !manifest_path.is_file() {
+        return Err(DownloaderError::ModelNotFound(format!(
+            "{} is not installed (no manifest at {:?})",
+            identifier, manifest_path
+        )));
+    }
+    ImageManifest::parse(&fs::read_to_string(manifest_path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_manifest(models_root: &Path, path: &str, config: &str, layers: &[(&str, u64)]) {
+        let layers: Vec<_> = layers
+            .iter()
+            .map(|(digest, size)| {
+                serde_json::json!({
+                    "mediaType": "application/vnd.ollama.image.model",
+                    "digest": digest,
+                    "size": size,
+                })
+            })
+            .collect();
+        let manifest = serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {
+                "mediaType": "application/vnd.docker.container.image.v1+json",
+                "digest": config,
+                "size": 10,
+            },
+            "layers": layers,
+        });
+        let path = models_root.join("manifests").join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, manifest.to_string()).unwrap();
+    }
+
+    fn write_blob(models_root: &Path, name: &str, size: usize) {
+        let blobs_dir = models_root.join("blobs");
+        fs::create_dir_all(&blobs_dir).unwrap();
+        fs::write(blobs_dir.join(name), vec![0u8; size]).unwrap();
+    }
+
+    #[test]
+    fn test_model_name_from_manifest_path() {
+        assert_eq!(
+            model_name_from_manifest_path(Path::new("registry.ollama.ai/library/llama3.1/8b")),
+            Some("llama3.1:8b".to_string())
+        );
+        assert_eq!(
+            model_name_from_manifest_path(Path::new("hf.co/user/repo/Q4_K_M")),
+            Some("hf.co/user/repo:Q4_K_M".to_string())
+        );
+        assert_eq!(model_name_from_manifest_path(Path::new("stray")), None);
+    }
+
+    #[test]
+    fn test_collect_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_manifest(
+            root,
+            "registry.ollama.ai/library/llama3.1/8b",
+            "sha256:config",
+            &[("sha256:shared", 100), ("sha256:big", 1000)],
+        );
+        write_manifest(
+            root,
+            "registry.ollama.ai/library/llama3.1/latest",
+            "sha256:config",
+            &[("sha256:shared", 100)],
+        );
+        write_manifest(
+            root,
+            "hf.co/user/repo/Q4_K_M",
+            "sha256:config",
+            &[("sha256:missing", 50)],
+        );
+        fs::write(root.join("manifests/hf.co/user/repo/broken"), "not json").unwrap();
+        write_blob(root, "sha256-config", 10);
+        write_blob(root, "sha256-shared", 100);
+        write_blob(root, "sha256-big", 1000);
+        write_blob(root, "sha256-orphan", 7);
+
+        let mut found = Vec::new();
+        let stats = collect_stats(root, 2, 1, &mut |model| found.push(model.name.clone())).unwrap();
+        found.sort();
+        assert_eq!(
+            found,
+            vec!["hf.co/user/repo:Q4_K_M", "llama3.1:8b", "llama3.1:latest"]
+        );
+        assert_eq!(stats.models, 2);
+        assert_eq!(stats.tags, 3);
+        assert_eq!(stats.blobs, 4);
+        assert_eq!(stats.blob_bytes, 1117);
+        assert_eq!(stats.orphan_blobs, 1);
+        assert_eq!(stats.orphan_blob_bytes, 7);
+        assert_eq!(stats.missing_blobs, 1);
+        assert_eq!(stats.unreadable_manifests, 1);
+        assert_eq!(
+            stats.largest,
+            vec![
+                LocalModel {
+                    name: "llama3.1:8b".to_string(),
+                    size: 1110,
+                },
+                LocalModel {
+                    name: "llama3.1:latest".to_string(),
+                    size: 110,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blob_sizes_are_sorted_whatever_the_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        for (index, name) in ["sha256-c", "sha256-a", "sha256-d", "sha256-b"]
+            .iter()
+            .enumerate()
+        {
+            write_blob(dir.path(), name, index);
+        }
+        fs::create_dir(dir.path().join("blobs").join("not-a-blob")).unwrap();
+        let expected = vec![
+            ("sha256-a".to_string(), 1),
+            ("sha256-b".to_string(), 3),
+            ("sha256-c".to_string(), 0),
+            ("sha256-d".to_string(), 2),
+        ];
+        for jobs in [0, 1, 3, 16] {
+            assert_eq!(
+                blob_sizes(&dir.path().join("blobs"), jobs).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_prune_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let library = "registry.ollama.ai/library/llama3.1";
+        write_manifest(
+            root,
+            &format!("{}/old", library),
+            "sha256:config",
+            &[("sha256:old", 100)],
+        );
+        write_manifest(
+            root,
+            &format!("{}/new", library),
+            "sha256:config",
+            &[("sha256:new", 10)],
+        );
+        write_manifest(
+            root,
+            "hf.co/user/repo/Q4_K_M",
+            "sha256:config",
+            &[("sha256:hf", 1)],
+        );
+        for blob in ["sha256-config", "sha256-old", "sha256-new", "sha256-hf"] {
+            write_blob(root, blob, 100);
+        }
+        let old = fs::File::options()
+            .write(true)
+            .open(root.join("manifests").join(library).join("old"))
+            .unwrap();
+        old.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+
+        let report = prune_tags(root, "llama3.1", 1, true, None, false).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                kept: vec!["new".to_string()],
+                removed: vec!["old".to_string()],
+                removed_blobs: 1,
+                freed_bytes: 100,
+                dry_run: true,
+            }
+        );
+        assert!(root.join("manifests").join(library).join("old").is_file());
+        assert!(root.join("blobs/sha256-old").is_file());
+
+        let report = prune_tags(root, "llama3.1", 1, false, None, false).unwrap();
+        assert_eq!(report.removed_blobs, 1);
+        assert!(!root.join("manifests").join(library).join("old").exists());
+        assert!(!root.join("blobs/sha256-old").exists());
+        for blob in ["sha256-config", "sha256-new", "sha256-hf"] {
+            assert!(root.join("blobs").join(blob).is_file());
+        }
+
+        let report = prune_tags(root, "llama3.1", 1, false, None, false).unwrap();
+        assert!(report.removed.is_empty());
+        assert!(prune_tags(root, "missing", 1, false, None, false).is_err());
+    }
+
+    #[test]
+    fn test_prune_tags_keeps_blobs_of_other_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let library = "registry.ollama.ai/library/llama3.1";
+        write_manifest(
+            root,
+            &format!("{}/old", library),
+            "sha256:config",
+            &[("sha256:old", 100)],
+        );
+        write_manifest(
+            root,
+            &format!("{}/new", library),
+            "sha256:config",
+            &[("sha256:new", 10)],
+        );
+        for blob in [
+            "sha256-config",
+            "sha256-old",
+            "sha256-new",
+            // Partially downloaded by Ollama, and downloaded without a manifest yet
+            "sha256-old-partial",
+            "sha256-old-partial-0",
+            "sha256-pending",
+        ] {
+            write_blob(root, blob, 100);
+        }
+        let old = fs::File::options()
+            .write(true)
+            .open(root.join("manifests").join(library).join("old"))
+            .unwrap();
+        old.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+
+        let report = prune_tags(root, "llama3.1", 1, false, None, false).unwrap();
+        assert_eq!((report.removed_blobs, report.freed_bytes), (1, 100));
+        assert!(!root.join("blobs/sha256-old").exists());
+        for blob in [
+            "sha256-config",
+            "sha256-new",
+            "sha256-old-partial",
+            "sha256-old-partial-0",
+            "sha256-pending",
+        ] {
+            assert!(root.join("blobs").join(blob).is_file(), "{}", blob);
+        }
+        assert!(!root.join(".odir/lock").exists());
+    }
+
+    #[test]
+    fn test_prune_tags_is_refused_while_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_manifest(
+            root,
+            "registry.ollama.ai/library/llama3.1/latest",
+            "sha256:config",
+            &[],
+        );
+        let _lock = lock_models_path(root, None, false).unwrap();
+        assert!(prune_tags(root, "llama3.1", 1, false, None, false).is_err());
+        assert!(prune_tags(root, "llama3.1", 1, false, None, true).is_ok());
+    }
+
+    #[test]
+    fn test_collect_stats_of_empty_store() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            collect_stats(dir.path(), 5, 1, &mut |_| {}).unwrap(),
+            StoreStats::default()
+        );
+    }
+
+    #[test]
+    fn test_remove_orphaned_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_manifest(
+            root,
+            "registry.ollama.ai/library/llama3.1/latest",
+            "sha256:config",
+            &[("sha256:new", 100)],
+        );
+        write_manifest(
+            root,
+            "registry.ollama.ai/library/llama3.1/8b",
+            "sha256:config",
+            &[("sha256:shared", 100)],
+        );
+        write_blob(root, "sha256-config", 10);
+        write_blob(root, "sha256-new", 100);
+        write_blob(root, "sha256-shared", 100);
+        write_blob(root, "sha256-old", 50);
+        write_blob(root, "sha256-unrelated", 7);
+
+        let (removed, freed_bytes) =
+            remove_orphaned_blobs(root, &["sha256:old", "sha256:shared", "sha256:gone"]).unwrap();
+        assert_eq!((removed, freed_bytes), (1, 50));
+        assert!(!root.join("blobs/sha256-old").exists());
+        assert!(root.join("blobs/sha256-shared").exists());
+        // Only the given BLOBs are considered, even if others are unreferenced too
+        assert!(root.join("blobs/sha256-unrelated").exists());
+    }
+
+    #[test]
+    fn test_locate_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir
+            .path()
+            .join("manifests/registry.ollama.ai/library/llama3.1/8b");
+        let err = locate_model(dir.path(), &manifest_path, "llama3.1:8b").unwrap_err();
+        assert!(matches!(err, DownloaderError::ModelNotFound(_)));
+
+        write_manifest(
+            dir.path(),
+            "registry.ollama.ai/library/llama3.1/8b",
+            "sha256:c1",
+            &[("sha256:l1", 100), ("sha256:c1", 10)],
+        );
+        let location = locate_model(dir.path(), &manifest_path, "llama3.1:8b").unwrap();
+        assert_eq!(location.manifest, manifest_path);
+        assert_eq!(
+            location.blobs,
+            vec![
+                dir.path().join("blobs/sha256-c1"),
+                dir.path().join("blobs/sha256-l1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_1_manifests_are_reported_as_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir
+            .path()
+            .join("manifests/registry.ollama.ai/library/llama3.1/8b");
+        fs::create_dir_all(manifest_path.parent().unwrap()).unwrap();
+        fs::write(
+            &manifest_path,
+            r#"{"schemaVersion": 1, "name": "library/llama3.1", "tag": "8b", "fsLayers": []}"#,
+        )
+        .unwrap();
+
+        let err = locate_model(dir.path(), &manifest_path, "llama3.1:8b").unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported manifest schema 1"),
+            "{}",
+            err
+        );
+        let stats = collect_stats(dir.path(), 5, 1, &mut |_| {}).unwrap();
+        assert_eq!(stats.unreadable_manifests, 1);
+    }
+
+    #[test]
+    fn test_verify_model_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let digest = |content: &[u8]| {
+            use sha2::Digest;
+            format!("sha256:{:x}", sha2::Sha256::digest(content))
+        };
+        let (config, intact, corrupt, missing) = (
+            digest(b"config"),
+            digest(b"intact"),
+            digest(b"corrupt"),
+            digest(b"missing"),
+        );
+        write_manifest(
+            root,
+            "registry.ollama.ai/library/llama3.1/8b",
+            &config,
+            &[(&intact, 6), (&corrupt, 7), (&missing, 7), (&intact, 6)],
+        );
+        let blobs_dir = root.join("blobs");
+        fs::create_dir_all(&blobs_dir).unwrap();
+        fs::write(blobs_dir.join(blob_file_name(&config)), b"config").unwrap();
+        fs::write(blobs_dir.join(blob_file_name(&intact)), b"intact").unwrap();
+        fs::write(blobs_dir.join(blob_file_name(&corrupt)), b"damaged").unwrap();
+        let manifest_path = root.join("manifests/registry.ollama.ai/library/llama3.1/8b");
+
+        for jobs in [1, 2, 8] {
+            let checks = verify_model_blobs(root, &manifest_path, "llama3.1:8b", jobs).unwrap();
+            let states: Vec<(&str, BlobState)> = checks
+                .iter()
+                .map(|check| (check.digest.as_str(), check.state))
+                .collect();
+            assert_eq!(
+                states,
+                vec![
+                    (config.as_str(), BlobState::Intact),
+                    (intact.as_str(), BlobState::Intact),
+                    (corrupt.as_str(), BlobState::Corrupt),
+                    (missing.as_str(), BlobState::Missing),
+                ]
+            );
+        }
+
+        let err = verify_model_blobs(root, &root.join("manifests/none"), "none:1", 2).unwrap_err();
+        assert!(matches!(err, DownloaderError::ModelNotFound(_)));
+    }
+}
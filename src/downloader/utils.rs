@@ -1,25 +1,99 @@
 //! Utility functions for the Ollama Downloader in Rust (ODIR),
 //! including model presence checks, downloading blobs, saving manifests,
 //! and cleaning up temporary files.
+use crate::config::{AddressFamily, OllamaLibrary, PreserveMtime, RunOptions, is_sensitive_header};
+use crate::downloader::http_date::parse_http_date;
+use crate::downloader::manifest::{
+    ImageManifest, MODEL_LAYER_MEDIA_TYPES, OLLAMA_CONFIG_MEDIA_TYPES,
+    OLLAMA_LAYER_MEDIA_TYPE_PREFIX, blob_file_name,
+};
 use crate::downloader::model_downloader::{DownloaderError, Result};
-use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, error, info, warn};
-use reqwest::blocking::Client;
+use crate::downloader::progress::{DownloadProgress, NoProgress};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{Level, debug, error, info, log_enabled, trace, warn};
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, ETAG, HeaderMap, HeaderName,
+    LAST_MODIFIED, PROXY_AUTHORIZATION,
+};
+use reqwest::redirect::Policy;
 use serde_json::Value;
 use sha2::{Digest, Sha256};
 use std::collections::HashSet;
 use std::env;
 use std::fs;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tempfile::NamedTempFile;
 
+/// Maximum number of redirects followed for a single request, e.g., from a registry to its CDN
+pub const MAX_REDIRECTS: usize = 5;
+
+/// A spinner shown on stderr during long blocking operations, such as listing models or
+/// fetching manifests. Interrupt signals are not deferred while it is active, since a
+/// blocking request could defer them until it times out, but the operation checks for them
+/// between its steps.
+pub struct ActivitySpinner {
+    pb: ProgressBar,
+}
+
+impl ActivitySpinner {
+    /// Start a spinner with a message describing the operation.
+    ///
+    /// # Arguments
+    /// * `message` - The message shown next to the spinner
+    ///
+    /// # Returns
+    /// * `Self` - The running spinner, which is cleared when dropped
+    pub fn start(message: &str) -> Self {
+        let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner} {msg}")
+                .unwrap(),
+        );
+        pb.set_message(message.to_string());
+        pb.enable_steady_tick(Duration::from_millis(100));
+        Self { pb }
+    }
+
+    /// Check whether the user has interrupted the operation, prompting for confirmation
+    /// of a pending interrupt with the spinner suspended.
+    ///
+    /// # Returns
+    /// * `Result<()>` - An error if the operation should stop because of an interrupt
+    pub fn check_interrupt(&self) -> Result<()> {
+        let interrupted = crate::signal_handler::is_interrupted()
+            || (crate::signal_handler::interrupt_requested()
+                && self
+                    .pb
+                    .suspend(crate::signal_handler::confirm_pending_interrupt));
+        if interrupted {
+            warn!("Operation interrupted by user");
+            return Err(DownloaderError::Other(
+                "Operation interrupted by user".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ActivitySpinner {
+    fn drop(&mut self) {
+        self.pb.finish_and_clear();
+    }
+}
+
 /// Check if a model is present in the Ollama server.
 ///
 /// # Arguments
 /// * `client` - HTTP client for making requests
 /// * `server_url` - Base URL of the Ollama server
+/// * `api_key` - Optional API key, sent as a bearer token
 /// * `model_names` - Model names to check (exact match)
 ///
 /// # Returns
@@ -27,6 +101,7 @@ use tempfile::NamedTempFile;
 pub fn is_model_present_in_ollama(
     client: &Client,
     server_url: &str,
+    api_key: Option<&str>,
     model_names: &[String],
 ) -> Result<bool> {
     let tags_url = format!("{}/api/tags", server_url.trim_end_matches('/'));
@@ -36,7 +111,11 @@ pub fn is_model_present_in_ollama(
         model_names, tags_url
     );
 
-    let response = client.get(&tags_url).send()?;
+    let mut request = client.get(&tags_url);
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+    let response = request.send_traced()?;
 
     if !response.status().is_success() {
         return Err(DownloaderError::HttpError(
@@ -67,13 +146,784 @@ pub fn is_model_present_in_ollama(
     ))
 }
 
-pub fn expand_models_path(models_path: &str) -> Result<PathBuf> {
-    if models_path.starts_with('~') {
-        let home = env::var("HOME")
-            .map_err(|_| DownloaderError::Other("HOME environment variable not set".to_string()))?;
-        Ok(PathBuf::from(models_path.replacen('~', &home, 1)))
+/// Ensure that a manifest describes a model that Ollama can run.
+///
+/// # Arguments
+/// * `manifest` - The parsed manifest
+/// * `model_identifier` - The model identifier, for the error message
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if no layer holds model weights
+pub fn ensure_manifest_has_model(manifest: &ImageManifest, model_identifier: &str) -> Result<()> {
+    if manifest.has_model_layer() {
+        return Ok(());
+    }
+
+    let layer_count = manifest.layers.as_ref().map_or(0, |layers| layers.len());
+    error!(
+        "Manifest for {} has {} layer(s), none of which holds model weights",
+        model_identifier, layer_count
+    );
+    Err(DownloaderError::InvalidManifest(format!(
+        "The manifest for {} does not reference any model weights (a layer of media type {}), \
+        so Ollama would not be able to run it. Check that the tag refers to a model, \
+        and that the registry serves Ollama compatible manifests.",
+        model_identifier,
+        MODEL_LAYER_MEDIA_TYPES.join(" or ")
+    )))
+}
+
+/// Ensure that a manifest only uses media types that Ollama understands.
+///
+/// # Arguments
+/// * `manifest` - The parsed manifest
+/// * `model_identifier` - The model identifier, for the error message
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error naming the media types Ollama does not understand
+pub fn ensure_ollama_compatible(manifest: &ImageManifest, model_identifier: &str) -> Result<()> {
+    let unsupported = manifest.unsupported_media_types();
+    if unsupported.is_empty() {
+        return Ok(());
+    }
+
+    error!(
+        "Manifest for {} uses media type(s) unknown to Ollama: {}",
+        model_identifier,
+        unsupported.join(", ")
+    );
+    Err(DownloaderError::InvalidManifest(format!(
+        "The manifest for {} uses media type(s) {}, so this repo is not Ollama-compatible. \
+        Ollama only understands layers of media type {}* with a configuration of media type {}.",
+        model_identifier,
+        unsupported.join(", "),
+        OLLAMA_LAYER_MEDIA_TYPE_PREFIX,
+        OLLAMA_CONFIG_MEDIA_TYPES.join(" or ")
+    )))
+}
+
+/// Get the number of inodes that unprivileged users can still use on the filesystem of a path.
+///
+/// # Arguments
+/// * `path` - A path on the filesystem
+///
+/// # Returns
+/// * `io::Result<Option<u64>>` - The number of free inodes, or None if the filesystem does not
+///   have a fixed number of them, e.g., btrfs, or they cannot be queried on this platform
+#[cfg(unix)]
+pub fn free_inodes(path: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Filesystems that allocate inodes on demand report none in total
+    if stats.f_files == 0 {
+        return Ok(None);
+    }
+    // The count is narrower than u64 on some platforms
+    #[allow(clippy::useless_conversion)]
+    Ok(Some(u64::from(stats.f_favail)))
+}
+
+/// Get the number of inodes that unprivileged users can still use on the filesystem of a path.
+///
+/// # Arguments
+/// * `path` - A path on the filesystem
+///
+/// # Returns
+/// * `io::Result<Option<u64>>` - Always None, since inodes cannot be queried on this platform
+#[cfg(not(unix))]
+pub fn free_inodes(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Ensure that enough inodes remain free on the filesystem of the models path for a download,
+/// since writing files fails on a filesystem that has run out of inodes, even if it has
+/// space left.
+///
+/// # Arguments
+/// * `models_path` - The models path
+/// * `new_files` - The number of files the download creates
+/// * `min_free_inodes` - The number of inodes that must remain free, where 0 skips the check
+/// * `force` - Whether too few free inodes only cause a warning
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if too few inodes are free and `force` is not set
+pub fn ensure_enough_inodes(
+    models_path: &Path,
+    new_files: usize,
+    min_free_inodes: u64,
+    force: bool,
+) -> Result<()> {
+    if min_free_inodes == 0 {
+        return Ok(());
+    }
+    let Some(free) = free_inodes(models_path)? else {
+        debug!(
+            "Free inodes of {:?} are unknown, not checking them",
+            models_path
+        );
+        return Ok(());
+    };
+
+    let needed = new_files as u64 + min_free_inodes;
+    if free >= needed {
+        debug!("{:?} has {} free inodes", models_path, free);
+        return Ok(());
+    }
+    let message = format!(
+        "The filesystem of {:?} has only {} free inodes, while the download creates {} files and {} should remain free",
+        models_path, free, new_files, min_free_inodes
+    );
+    if force {
+        warn!("{}; downloading anyway, as forced", message);
+        Ok(())
+    } else {
+        Err(DownloaderError::Other(format!(
+            "{}; free some inodes, lower 'min_free_inodes' in the settings or use --force",
+            message
+        )))
+    }
+}
+
+/// Get the number of bytes that unprivileged users can still write on the filesystem of a path.
+///
+/// # Arguments
+/// * `path` - A path on the filesystem
+///
+/// # Returns
+/// * `io::Result<Option<u64>>` - The free space, or None if it cannot be queried on this platform
+#[cfg(unix)]
+pub fn free_space(path: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The counts are narrower than u64 on some platforms
+    #[allow(clippy::useless_conversion)]
+    Ok(Some(
+        u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)),
+    ))
+}
+
+/// Get the number of bytes that unprivileged users can still write on the filesystem of a path.
+///
+/// # Arguments
+/// * `path` - A path on the filesystem
+///
+/// # Returns
+/// * `io::Result<Option<u64>>` - Always None, since free space cannot be queried on this platform
+#[cfg(not(unix))]
+pub fn free_space(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// How BLOBs are moved from the temporary directory into the models path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlobTransfer {
+    /// Renamed, since both are on the same mount of a filesystem
+    Rename,
+
+    /// Copied within one filesystem, since it is mounted at two places, e.g., by a bind
+    /// mount, across which a rename fails
+    CopyWithinFilesystem,
+
+    /// Copied to another filesystem
+    CopyAcrossFilesystems,
+}
+
+/// Find out how BLOBs are moved from the temporary directory into the models path. Paths that
+/// do not exist yet are judged by their nearest existing ancestor.
+///
+/// # Arguments
+/// * `temp_dir` - The directory BLOBs are downloaded into
+/// * `models_path` - The models path
+///
+/// # Returns
+/// * `BlobTransfer` - How BLOBs are moved, which is a copy across filesystems if unknown
+fn blob_transfer(temp_dir: &Path, models_path: &Path) -> BlobTransfer {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let device = |path: &Path| {
+            path.ancestors()
+                .find_map(|ancestor| fs::metadata(ancestor).ok())
+                .map(|metadata| metadata.dev())
+        };
+        match (device(temp_dir), device(models_path)) {
+            (Some(a), Some(b)) if a == b => {
+                if mount_point(temp_dir) == mount_point(models_path) {
+                    BlobTransfer::Rename
+                } else {
+                    BlobTransfer::CopyWithinFilesystem
+                }
+            }
+            _ => BlobTransfer::CopyAcrossFilesystems,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (temp_dir, models_path);
+        BlobTransfer::CopyAcrossFilesystems
+    }
+}
+
+/// Find the mount point of the mount that a path is on, judging a path that does not exist
+/// yet by its nearest existing ancestor. On Linux, it is looked up in
+/// `/proc/self/mountinfo`, which also tells bind mounts of the same filesystem apart.
+///
+/// # Arguments
+/// * `path` - The path
+///
+/// # Returns
+/// * `Option<PathBuf>` - The mount point, or None if it cannot be determined
+#[cfg(unix)]
+fn mount_point(path: &Path) -> Option<PathBuf> {
+    let path = path
+        .ancestors()
+        .find_map(|ancestor| fs::canonicalize(ancestor).ok())?;
+    #[cfg(target_os = "linux")]
+    {
+        let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+        mountinfo
+            .lines()
+            .filter_map(|line| line.split(' ').nth(4))
+            .map(|mount_point| PathBuf::from(unescape_mount_point(mount_point)))
+            .filter(|mount_point| path.starts_with(mount_point))
+            .max_by_key(|mount_point| mount_point.components().count())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // The topmost ancestor that is still on the same device
+        let device = fs::metadata(&path).ok()?.dev();
+        path.ancestors()
+            .take_while(|ancestor| {
+                fs::metadata(ancestor).is_ok_and(|metadata| metadata.dev() == device)
+            })
+            .last()
+            .map(Path::to_path_buf)
+    }
+}
+
+/// Decode the octal escapes, e.g., `\040` for a space, of a mount point in
+/// `/proc/self/mountinfo`.
+///
+/// # Arguments
+/// * `mount_point` - The mount point as written in `/proc/self/mountinfo`
+///
+/// # Returns
+/// * `String` - The mount point
+#[cfg(target_os = "linux")]
+fn unescape_mount_point(mount_point: &str) -> String {
+    let bytes = mount_point.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(octal) = mount_point.get(i + 1..i + 4)
+            && let Ok(byte) = u8::from_str_radix(octal, 8)
+        {
+            decoded.push(byte);
+            i += 4;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Compute the free space that a download needs on the filesystem of the models path and, if
+/// it is on another filesystem, on that of the temporary directory.
+///
+/// Every BLOB is downloaded into the temporary directory before any is moved into the models
+/// path. A BLOB that is renamed needs no more space, so the total of the BLOBs is enough. A
+/// BLOB that is copied within the same filesystem, e.g., across bind mounts, briefly takes up
+/// space twice, so the largest BLOB is needed on top. On another filesystem, the temporary
+/// directory holds every BLOB and the models path receives every BLOB, without needing both
+/// copies of any on the same filesystem.
+///
+/// # Arguments
+/// * `blob_sizes` - The sizes of the BLOBs of the download in bytes
+/// * `transfer` - How BLOBs are moved from the temporary directory into the models path
+/// * `min_free_after` - Bytes that must remain free in the models path after the download
+///
+/// # Returns
+/// * `(u64, Option<u64>)` - The free space needed in the models path, and in the temporary
+///   directory if it is on another filesystem, in bytes
+fn required_free_space(
+    blob_sizes: &[u64],
+    transfer: BlobTransfer,
+    min_free_after: u64,
+) -> (u64, Option<u64>) {
+    let total: u64 = blob_sizes.iter().sum();
+    match transfer {
+        BlobTransfer::Rename => (total.saturating_add(min_free_after), None),
+        BlobTransfer::CopyWithinFilesystem => {
+            let copy_overhead = blob_sizes.iter().copied().max().unwrap_or(0);
+            (
+                total
+                    .saturating_add(copy_overhead)
+                    .saturating_add(min_free_after),
+                None,
+            )
+        }
+        BlobTransfer::CopyAcrossFilesystems => (total.saturating_add(min_free_after), Some(total)),
+    }
+}
+
+/// Ensure that the filesystem of the models path, and that of the temporary directory if it
+/// is another one, have enough free space for a download, so that it does not fail at the
+/// last BLOB.
+///
+/// # Arguments
+/// * `manifest` - The parsed manifest of the model
+/// * `models_path` - The models path
+/// * `temp_dir` - The directory BLOBs are downloaded into
+/// * `min_free_after` - Bytes that must remain free after the download
+/// * `force` - Whether too little free space only causes a warning
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if too little space is free and `force` is not set
+pub fn ensure_enough_space(
+    manifest: &ImageManifest,
+    models_path: &Path,
+    temp_dir: &Path,
+    min_free_after: u64,
+    force: bool,
+) -> Result<()> {
+    let blob_sizes: Vec<u64> = std::iter::once(manifest.config.size)
+        .chain(manifest.unique_layers().iter().map(|layer| layer.size))
+        .collect();
+    let transfer = blob_transfer(temp_dir, models_path);
+    let (models_needed, temp_needed) = required_free_space(&blob_sizes, transfer, min_free_after);
+
+    let mut shortages = Vec::new();
+    if let Some(free) = free_space(models_path)?
+        && free < models_needed
+    {
+        shortages.push(format!(
+            "The filesystem of {:?} has only {} free, while the download needs {}{} and {} should remain free",
+            models_path,
+            format_byte_size(free),
+            format_byte_size(manifest.total_size()),
+            if transfer == BlobTransfer::CopyWithinFilesystem {
+                format!(
+                    ", plus {} to copy its largest BLOB from {:?}, which is on another mount",
+                    format_byte_size(blob_sizes.iter().copied().max().unwrap_or(0)),
+                    temp_dir
+                )
+            } else {
+                String::new()
+            },
+            format_byte_size(min_free_after)
+        ));
+    }
+    // The temporary directory may not exist yet, but its nearest ancestor is on its filesystem
+    if let Some(temp_needed) = temp_needed
+        && let Some(existing) = temp_dir.ancestors().find(|path| path.exists())
+        && let Some(free) = free_space(existing)?
+        && free < temp_needed
+    {
+        shortages.push(format!(
+            "The filesystem of the temporary directory {:?} has only {} free, while the download needs {}",
+            temp_dir,
+            format_byte_size(free),
+            format_byte_size(temp_needed)
+        ));
+    }
+    if shortages.is_empty() {
+        debug!(
+            "Enough free space for {} in {:?}{}",
+            format_byte_size(models_needed),
+            models_path,
+            temp_needed.map_or_else(String::new, |needed| format!(
+                " and {} in {:?}",
+                format_byte_size(needed),
+                temp_dir
+            ))
+        );
+        return Ok(());
+    }
+    let message = shortages.join("; ");
+    if force {
+        warn!("{}; downloading anyway, as forced", message);
+        Ok(())
     } else {
-        Ok(PathBuf::from(models_path))
+        Err(DownloaderError::Other(format!(
+            "{}; free some space, lower --min-free-after or use --force",
+            message
+        )))
+    }
+}
+
+/// Ensure that the total size of a model does not exceed the download size limit, if any.
+///
+/// # Arguments
+/// * `manifest` - The parsed manifest
+/// * `model_identifier` - The model identifier, for the error message
+/// * `max_download_size` - The optional download size limit in bytes
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if the model is larger than the limit
+pub fn ensure_within_size_limit(
+    manifest: &ImageManifest,
+    model_identifier: &str,
+    max_download_size: Option<u64>,
+) -> Result<()> {
+    let total_size = manifest.total_size();
+    match max_download_size {
+        Some(limit) if total_size > limit => Err(DownloaderError::SizeLimitExceeded(format!(
+            "{} needs {} ({} bytes), which exceeds the limit of {} ({} bytes)",
+            model_identifier,
+            format_byte_size(total_size),
+            total_size,
+            format_byte_size(limit),
+            limit
+        ))),
+        _ => {
+            info!(
+                "Model {} needs {} in total",
+                model_identifier,
+                format_byte_size(total_size)
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Format a size in bytes with a binary unit, e.g., 4.0 GiB.
+///
+/// # Arguments
+/// * `bytes` - The size in bytes
+///
+/// # Returns
+/// * `String` - The formatted size
+pub fn format_byte_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Expand the models path: a leading `~` becomes the home directory, and `$VAR` and `${VAR}`
+/// references, as well as `%VAR%` references on Windows, become the values of the
+/// environment variables.
+///
+/// # Arguments
+/// * `models_path` - Path to the Ollama models directory, as configured
+///
+/// # Returns
+/// * `Result<PathBuf>` - The expanded path, or an error naming an undefined variable
+pub fn expand_models_path(models_path: &str) -> Result<PathBuf> {
+    let lookup = |name: &str| env::var(name).ok();
+    // Variables are only expanded in what follows the `~`, not in the home directory itself
+    let (home, rest) = match models_path.strip_prefix('~') {
+        Some(rest) => {
+            let home = env::var("HOME").map_err(|_| {
+                DownloaderError::Other("HOME environment variable not set".to_string())
+            })?;
+            (home, rest)
+        }
+        None => (String::new(), models_path),
+    };
+    let rest = expand_env_vars(rest, cfg!(windows), lookup).map_err(|e| {
+        DownloaderError::Other(format!("Cannot expand models path {}: {}", models_path, e))
+    })?;
+    Ok(PathBuf::from(home + &rest))
+}
+
+/// Expand the environment variable references in a string, where a `$` that does not start
+/// a variable name is kept as it is.
+///
+/// # Arguments
+/// * `text` - The string to expand
+/// * `percent_style` - Whether `%VAR%` references are expanded too, as on Windows
+/// * `lookup` - Gets the value of a variable, or None if it is not defined
+///
+/// # Returns
+/// * `std::result::Result<String, String>` - The expanded string, or the reason it cannot be
+///   expanded
+fn expand_env_vars(
+    text: &str,
+    percent_style: bool,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let value = |name: &str| {
+        lookup(name).ok_or_else(|| format!("environment variable {} is not defined", name))
+    };
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c| c == '$' || (percent_style && c == '%')) {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if rest[start..].starts_with('%') {
+            match after.find('%') {
+                Some(end) if end > 0 && after[..end].chars().all(is_name_char) => {
+                    expanded.push_str(&value(&after[..end])?);
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    expanded.push('%');
+                    rest = after;
+                }
+            }
+        } else if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("unterminated ${{ in {}", text))?;
+            let name = &braced[..end];
+            if name.is_empty() || !name.chars().all(is_name_char) {
+                return Err(format!("invalid variable name ${{{}}}", name));
+            }
+            expanded.push_str(&value(name)?);
+            rest = &braced[end + 1..];
+        } else {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            if end == 0 || after.starts_with(|c: char| c.is_ascii_digit()) {
+                expanded.push('$');
+                rest = after;
+            } else {
+                expanded.push_str(&value(&after[..end])?);
+                rest = &after[end..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Check whether a URL uses plain HTTP to a host other than the local machine, over which
+/// credentials and manifests would travel in cleartext.
+///
+/// # Arguments
+/// * `url` - The URL to check
+///
+/// # Returns
+/// * `bool` - True if the URL uses `http://` and its host is not a loopback address
+pub fn is_insecure_http(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    if url.scheme() != "http" {
+        return false;
+    }
+    match url.host_str() {
+        Some(host) if host.eq_ignore_ascii_case("localhost") => false,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map_or(true, |addr| !addr.is_loopback()),
+        None => false,
+    }
+}
+
+/// Warn about each URL that uses plain HTTP to a host other than the local machine.
+///
+/// # Arguments
+/// * `urls` - The URLs to check, with the names of the settings they come from
+pub fn warn_if_insecure_http(urls: &[(&str, &str)]) {
+    for (name, url) in urls {
+        if is_insecure_http(url) {
+            warn!(
+                "INSECURE: '{}' is {}, which uses plain HTTP to a remote host: credentials and manifests travel in cleartext",
+                name, url
+            );
+        }
+    }
+}
+
+/// Ensure that no URL uses plain HTTP to a host other than the local machine, unless it is
+/// allowed, before downloading over it.
+///
+/// # Arguments
+/// * `urls` - The URLs to check, with the names of the settings they come from
+/// * `allow_insecure_http` - Whether plain HTTP to remote hosts is allowed
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error naming the first insecure URL
+pub fn ensure_secure_transport(urls: &[(&str, &str)], allow_insecure_http: bool) -> Result<()> {
+    if allow_insecure_http {
+        return Ok(());
+    }
+    match urls.iter().find(|(_, url)| is_insecure_http(url)) {
+        Some((name, url)) => Err(DownloaderError::Other(format!(
+            "Refusing to download over plain HTTP from a remote host: '{}' is {}. Use https://, or pass --allow-insecure-http to proceed anyway",
+            name, url
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Ensure that the models path is an existing directory, before anything is downloaded into it.
+///
+/// # Arguments
+/// * `models_path` - Path to the Ollama models directory, possibly starting with `~`
+///
+/// # Returns
+/// * `Result<PathBuf>` - The expanded models path, or an error naming it if it is not a directory
+pub fn ensure_models_dir(models_path: &str) -> Result<PathBuf> {
+    let models_root = expand_models_path(models_path)?;
+    if !models_root.exists() {
+        return Err(DownloaderError::Other(format!(
+            "Models path {:?} does not exist; check 'models_path' in the settings",
+            models_root
+        )));
+    }
+    if !models_root.is_dir() {
+        return Err(DownloaderError::Other(format!(
+            "Models path {:?} is not a directory; check 'models_path' in the settings",
+            models_root
+        )));
+    }
+    Ok(models_root)
+}
+
+/// Compute the Levenshtein edit distance between two strings, ignoring case.
+///
+/// # Arguments
+/// * `a` - The first string
+/// * `b` - The second string
+///
+/// # Returns
+/// * `usize` - The number of single-character edits needed to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Suggest candidates that closely match a name, closest first.
+///
+/// # Arguments
+/// * `name` - The name that was not found
+/// * `candidates` - The known names to compare against
+/// * `max_suggestions` - Maximum number of suggestions to return
+///
+/// # Returns
+/// * `Vec<String>` - Candidates within a small edit distance of `name`
+pub fn suggest_similar(name: &str, candidates: &[String], max_suggestions: usize) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &String)> = candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    scored.sort_by_key(|(distance, candidate)| (*distance, candidate.to_lowercase()));
+    scored
+        .into_iter()
+        .take(max_suggestions)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// A part of a tag, as far as the order of tags is concerned
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TagPart {
+    /// A size in millions of parameters, e.g., from `7b` or `135m`
+    Size(u64),
+
+    /// A quantisation with its number of bits, e.g., from `Q4_K_M` or `iq2_xs`
+    Quant(u32, String),
+
+    /// Anything else, ignoring case
+    Text(String),
+}
+
+/// Split a tag into the parts it is ordered by, at every `:` and `-`.
+fn tag_parts(tag: &str) -> Vec<TagPart> {
+    tag.split([':', '-'])
+        .map(|part| {
+            let part = part.to_lowercase();
+            let size = part
+                .strip_suffix('b')
+                .map(|number| (number, 1000.0))
+                .or_else(|| part.strip_suffix('m').map(|number| (number, 1.0)))
+                .filter(|(number, _)| number.starts_with(|c: char| c.is_ascii_digit()))
+                .and_then(|(number, scale)| number.parse::<f64>().ok().map(|n| n * scale));
+            if let Some(millions) = size {
+                return TagPart::Size(millions.round() as u64);
+            }
+            let bits = part
+                .strip_prefix("iq")
+                .or_else(|| part.strip_prefix('q'))
+                .map(|rest| {
+                    rest.chars()
+                        .take_while(char::is_ascii_digit)
+                        .collect::<String>()
+                })
+                .and_then(|digits| digits.parse::<u32>().ok());
+            match bits {
+                Some(bits) => TagPart::Quant(bits, part),
+                None => TagPart::Text(part),
+            }
+        })
+        .collect()
+}
+
+/// Compare tags in their natural order: sizes such as `7b` and `13b` by the number of
+/// parameters, quantisations such as `Q2_K` and `Q4_K_M` by their number of bits, and
+/// anything else case-insensitively. Sizes come before quantisations, which come before
+/// other parts, e.g., `latest`.
+///
+/// # Arguments
+/// * `a` - A tag, e.g., `llama3.1:8b` or `Q4_K_M`
+/// * `b` - Another tag
+///
+/// # Returns
+/// * `std::cmp::Ordering` - The order of the tags
+pub fn compare_tags(a: &str, b: &str) -> std::cmp::Ordering {
+    tag_parts(a)
+        .cmp(&tag_parts(b))
+        .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+        .then_with(|| a.cmp(b))
+}
+
+/// Resolve the directory in which temporary, partially downloaded, files are created.
+///
+/// # Arguments
+/// * `models_path` - Path to the Ollama models directory
+/// * `temp_dir` - Optional user-configured temporary directory
+///
+/// # Returns
+/// * `Result<PathBuf>` - The configured directory, or a `.tmp` subdirectory of `models_path`
+pub fn resolve_temp_dir(models_path: &str, temp_dir: Option<&str>) -> Result<PathBuf> {
+    match temp_dir {
+        Some(dir) => expand_models_path(dir),
+        None => Ok(expand_models_path(models_path)?.join(".tmp")),
     }
 }
 
@@ -160,12 +1010,507 @@ fn is_running_as_root() -> bool {
     }
 }
 
+/// Compute the deadline for downloading all BLOBs of a model, starting now.
+///
+/// # Arguments
+/// * `download_deadline` - The configured deadline in seconds, if any
+///
+/// # Returns
+/// * `Option<Instant>` - The point in time by which all BLOBs must be downloaded
+pub fn download_deadline(download_deadline: Option<f64>) -> Option<Instant> {
+    download_deadline.map(|seconds| Instant::now() + Duration::from_secs_f64(seconds))
+}
+
+/// Compute the timeout for downloading the next BLOB, which never extends beyond the deadline.
+///
+/// # Arguments
+/// * `blob_timeout` - The configured timeout for a single BLOB in seconds, if any
+/// * `client_timeout` - The timeout of the client in seconds, which applies if `blob_timeout`
+///   is not set
+/// * `deadline` - The deadline for downloading all BLOBs of the model, if any
+///
+/// # Returns
+/// * `Result<Option<Duration>>` - The timeout, None to use the client's timeout,
+///   or an error if the deadline has already passed
+pub fn blob_download_timeout(
+    blob_timeout: Option<f64>,
+    client_timeout: f64,
+    deadline: Option<Instant>,
+) -> Result<Option<Duration>> {
+    let blob_timeout = blob_timeout.map(Duration::from_secs_f64);
+    let Some(deadline) = deadline else {
+        return Ok(blob_timeout);
+    };
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(deadline_exceeded_error());
+    }
+    let timeout = blob_timeout.unwrap_or_else(|| Duration::from_secs_f64(client_timeout));
+    Ok(Some(timeout.min(remaining)))
+}
+
+/// Replace the error of a BLOB download with a deadline error, if the deadline has passed.
+///
+/// A download cut short by the deadline fails with a timeout, which is reported more
+/// clearly as the deadline having been exceeded.
+///
+/// # Arguments
+/// * `error` - The error of the BLOB download
+/// * `deadline` - The deadline for downloading all BLOBs of the model, if any
+///
+/// # Returns
+/// * `DownloaderError` - The deadline error, or the original error
+pub fn explain_deadline(error: DownloaderError, deadline: Option<Instant>) -> DownloaderError {
+    match deadline {
+        Some(deadline) if Instant::now() >= deadline => deadline_exceeded_error(),
+        _ => error,
+    }
+}
+
+fn deadline_exceeded_error() -> DownloaderError {
+    DownloaderError::DeadlineExceeded(
+        "the BLOBs of the model could not be downloaded within the configured download_deadline"
+            .to_string(),
+    )
+}
+
+/// Resolve a host name to the socket addresses of one address family only.
+///
+/// # Arguments
+/// * `host` - The host name, or an IP address
+/// * `family` - The address family to keep
+///
+/// # Returns
+/// * `io::Result<Vec<SocketAddr>>` - The addresses of the family, or an error if there are none
+pub fn resolve_in_family(host: &str, family: AddressFamily) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = (host, 0)
+        .to_socket_addrs()?
+        .filter(|addr| family.contains(&addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no {} address", host, family),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Resolver of host names that only connects over one address family
+struct FamilyResolver {
+    family: AddressFamily,
+}
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let result = resolve_in_family(name.as_str(), self.family)
+            .map(|addrs| Box::new(addrs.into_iter()) as Addrs)
+            .map_err(Into::into);
+        Box::pin(std::future::ready(result))
+    }
+}
+
+/// Build the redirect policy for requests to registries, which may redirect BLOBs to a CDN
+/// on another host, as Hugging Face and the Ollama registry do.
+///
+/// At most `MAX_REDIRECTS` redirects are followed. When a redirect leads to another host,
+/// reqwest drops the `Authorization`, `Cookie` and `Proxy-Authorization` headers, but sends
+/// any other extra header as is, so such redirects are refused if another extra header
+/// carries a credential, rather than leaking it to the other host.
+///
+/// # Arguments
+/// * `extra_headers` - The extra headers sent with every request
+///
+/// # Returns
+/// * `Policy` - The redirect policy
+pub fn redirect_policy(extra_headers: &HeaderMap) -> Policy {
+    let forwarded_credentials: Vec<String> = extra_headers
+        .keys()
+        .filter(|name| ![AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION].contains(name))
+        .filter(|name| crate::config::is_sensitive_header(name.as_str()))
+        .map(|name| name.to_string())
+        .collect();
+
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            return attempt.error(format!("more than {} redirects", MAX_REDIRECTS));
+        }
+        let Some(previous) = attempt.previous().last() else {
+            return attempt.follow();
+        };
+        let previous_host = previous.host_str().unwrap_or_default().to_string();
+        let cross_host = attempt.url().host_str() != previous.host_str()
+            || attempt.url().port_or_known_default() != previous.port_or_known_default();
+        if cross_host {
+            if !forwarded_credentials.is_empty() {
+                return attempt.error(format!(
+                    "refusing to follow the redirect from {} to another host, which would receive the extra headers {}; send credentials in the Authorization header instead",
+                    previous_host,
+                    forwarded_credentials.join(", ")
+                ));
+            }
+            debug!(
+                "Following redirect from {} to {} without credentials",
+                previous_host,
+                attempt.url().host_str().unwrap_or_default()
+            );
+        }
+        attempt.follow()
+    })
+}
+
+/// Apply the network settings shared by all downloaders to an HTTP client: the addresses
+/// to connect to instead of resolving host names, the local address to connect from, and
+/// the only address family to connect with.
+///
+/// # Arguments
+/// * `client_builder` - The builder of the HTTP client
+/// * `library` - The settings to apply
+/// * `options` - The options of this run to apply
+///
+/// # Returns
+/// * `ClientBuilder` - The builder with the network settings applied
+pub fn apply_network_settings(
+    mut client_builder: ClientBuilder,
+    library: &OllamaLibrary,
+    options: &RunOptions,
+) -> ClientBuilder {
+    for resolve_override in &options.resolve_overrides {
+        client_builder = client_builder.resolve(&resolve_override.host, resolve_override.addr);
+    }
+    if let Some(local_address) = library.local_address {
+        debug!("Sending requests from {}", local_address);
+        client_builder = client_builder.local_address(local_address);
+    }
+    if let Some(family) = options.address_family {
+        client_builder = client_builder.dns_resolver(Arc::new(FamilyResolver { family }));
+    }
+    client_builder
+}
+
+/// Response headers logged by [`TracedRequest::send_traced`]
+const TRACED_RESPONSE_HEADERS: [HeaderName; 3] = [CONTENT_LENGTH, CONTENT_TYPE, ETAG];
+
+/// Sending of HTTP requests that are logged at trace level, for debugging exchanges with
+/// registries and mirrors without a packet capture
+pub trait TracedRequest {
+    /// Send the request, logging its method, URL and headers, and the status and selected
+    /// headers of its response, at trace level. The values of sensitive headers are redacted.
+    ///
+    /// # Returns
+    /// * `reqwest::Result<Response>` - The response, or the error of sending the request
+    fn send_traced(self) -> reqwest::Result<Response>;
+}
+
+impl TracedRequest for RequestBuilder {
+    fn send_traced(self) -> reqwest::Result<Response> {
+        if !log_enabled!(Level::Trace) {
+            return self.send();
+        }
+        let (client, request) = self.build_split();
+        let request = request?;
+        let method = request.method().clone();
+        let url = request.url().clone();
+        trace!(
+            "HTTP request: {} {} [{}]",
+            method,
+            url,
+            describe_headers(request.headers())
+        );
+        let result = client.execute(request);
+        match &result {
+            Ok(response) => {
+                let mut selected = HeaderMap::new();
+                for name in TRACED_RESPONSE_HEADERS {
+                    if let Some(value) = response.headers().get(&name) {
+                        selected.insert(name, value.clone());
+                    }
+                }
+                trace!(
+                    "HTTP response: {} {} from {} [{}]",
+                    response.status(),
+                    method,
+                    response.url(),
+                    describe_headers(&selected)
+                );
+            }
+            Err(e) => trace!("HTTP request failed: {} {}: {}", method, url, e),
+        }
+        result
+    }
+}
+
+/// Describe HTTP headers for logging, redacting the values of sensitive ones.
+///
+/// # Arguments
+/// * `headers` - The headers
+///
+/// # Returns
+/// * `String` - The headers as comma-separated `name: value` pairs
+fn describe_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            if is_sensitive_header(name.as_str()) {
+                format!("{}: <redacted>", name)
+            } else {
+                format!("{}: {}", name, value.to_str().unwrap_or("<binary>"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Delay before the first retry of a BLOB that the registry reports as missing,
+/// which grows linearly with each further retry
+pub const MISSING_BLOB_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Download a BLOB, retrying it if the registry reports it as missing (HTTP 404), e.g.,
+/// while a newly pushed model is still propagating through the registry, or if its
+/// download stalled. Other errors fail immediately.
+///
+/// # Arguments
+/// * `named_digest` - The digest of the BLOB, for logging
+/// * `retries` - How many times a missing BLOB is retried
+/// * `delay` - The delay before the first retry
+/// * `deadline` - The deadline for downloading all BLOBs of the model, if any, which no retry waits past
+/// * `download` - Downloads the BLOB
+///
+/// # Returns
+/// * `Result<T>` - The result of the first download that neither reported the BLOB as
+///   missing nor stalled, or the error of the last attempt
+pub fn retry_missing_blob<T>(
+    named_digest: &str,
+    retries: u32,
+    delay: Duration,
+    deadline: Option<Instant>,
+    mut download: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        let error = match download() {
+            Err(e) if e.http_status() == Some(404) || matches!(e, DownloaderError::Stalled(_)) => e,
+            result => return result,
+        };
+        let problem = match error {
+            DownloaderError::Stalled(_) => "stalled",
+            _ => "is missing",
+        };
+        if attempt >= retries {
+            return Err(error);
+        }
+        attempt += 1;
+        let wait = delay * attempt;
+        if deadline.is_some_and(|deadline| Instant::now() + wait >= deadline) {
+            warn!(
+                "BLOB {} {} and retrying it would run past the download deadline",
+                named_digest, problem
+            );
+            return Err(error);
+        }
+        warn!(
+            "BLOB {} {}. Retrying in {} seconds ({}/{})",
+            named_digest,
+            problem,
+            wait.as_secs_f64(),
+            attempt,
+            retries
+        );
+        if !sleep_unless_interrupted(wait, blob_retry_interrupted) {
+            warn!("Retrying BLOB {} interrupted by user", named_digest);
+            return Err(DownloaderError::Other(
+                "Download interrupted by user".to_string(),
+            ));
+        }
+    }
+}
+
+/// Interval at which a retry wait checks whether the user has interrupted it
+const RETRY_INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Check whether the user has interrupted a retry wait, prompting for confirmation of a
+/// pending interrupt.
+fn blob_retry_interrupted() -> bool {
+    crate::signal_handler::is_interrupted()
+        || (crate::signal_handler::interrupt_requested()
+            && crate::signal_handler::confirm_pending_interrupt())
+}
+
+/// Sleep for a while in short steps, stopping early once interrupted.
+///
+/// # Arguments
+/// * `wait` - How long to sleep
+/// * `interrupted` - Checks whether the sleep has been interrupted
+///
+/// # Returns
+/// * `bool` - Whether the whole wait was slept without being interrupted
+pub fn sleep_unless_interrupted(wait: Duration, mut interrupted: impl FnMut() -> bool) -> bool {
+    let until = Instant::now() + wait;
+    loop {
+        if interrupted() {
+            return false;
+        }
+        let remaining = until.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return true;
+        }
+        std::thread::sleep(remaining.min(RETRY_INTERRUPT_POLL_INTERVAL));
+    }
+}
+
+/// Token bucket that limits the rate at which bytes are read to a configured bandwidth.
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    /// Bytes that may be read without waiting; negative when reads ran ahead of the rate
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter for a bandwidth.
+    ///
+    /// # Arguments
+    /// * `bytes_per_sec` - The bandwidth in bytes per second, where 0 means unlimited
+    ///
+    /// # Returns
+    /// * `Option<Self>` - The rate limiter, or None if the bandwidth is unlimited
+    pub fn new(bytes_per_sec: u64) -> Option<Self> {
+        (bytes_per_sec > 0).then(|| Self {
+            bytes_per_sec,
+            tokens: 0.0,
+            last_refill: Instant::now(),
+        })
+    }
+
+    /// Get the size of reads that keeps each wait short, so that interrupts are noticed quickly.
+    ///
+    /// # Arguments
+    /// * `buffer_size` - The size of the read buffer
+    ///
+    /// # Returns
+    /// * `usize` - The number of bytes to read at once
+    pub fn chunk_size(&self, buffer_size: usize) -> usize {
+        usize::try_from(self.bytes_per_sec / 10)
+            .unwrap_or(usize::MAX)
+            .clamp(1, buffer_size)
+    }
+
+    /// Account for bytes that were read and compute how long to wait to stay within the bandwidth.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of bytes that were read
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    /// * `Duration` - How long to wait before reading more
+    pub fn consume(&mut self, bytes: usize, now: Instant) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.last_refill = now;
+        // Allow bursts of at most one second worth of bytes after an idle period
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// Length of the windows over which the throughput of a BLOB download is measured
+const STALL_WINDOW: Duration = Duration::from_secs(1);
+
+/// Detector of BLOB downloads whose throughput stays below a floor for too long, e.g.,
+/// because the connection trickles bytes without ever timing out.
+pub struct StallDetector {
+    min_bytes_per_sec: u64,
+    stall_timeout: Duration,
+    window_start: Instant,
+    window_bytes: u64,
+    /// Start of the first window of the current run of windows below the floor
+    slow_since: Option<Instant>,
+}
+
+impl StallDetector {
+    /// Create a stall detector for a download starting now.
+    ///
+    /// # Arguments
+    /// * `stall_timeout` - How long the throughput may stay below the floor, or None to not detect stalls
+    /// * `min_bytes_per_sec` - The throughput floor, where 0 means that stalls are not detected
+    /// * `now` - The start of the download
+    ///
+    /// # Returns
+    /// * `Option<Self>` - The stall detector, or None if stalls are not detected
+    pub fn new(
+        stall_timeout: Option<Duration>,
+        min_bytes_per_sec: u64,
+        now: Instant,
+    ) -> Option<Self> {
+        let stall_timeout = stall_timeout?;
+        (min_bytes_per_sec > 0).then_some(Self {
+            min_bytes_per_sec,
+            stall_timeout,
+            window_start: now,
+            window_bytes: 0,
+            slow_since: None,
+        })
+    }
+
+    /// Account for bytes that were read and check whether the download stalled.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of bytes that were read
+    /// * `now` - The current time
+    ///
+    /// # Returns
+    /// * `bool` - True if the throughput stayed below the floor for longer than the stall timeout
+    pub fn observe(&mut self, bytes: u64, now: Instant) -> bool {
+        self.window_bytes += bytes;
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= STALL_WINDOW {
+            let throughput = self.window_bytes as f64 / elapsed.as_secs_f64();
+            if throughput < self.min_bytes_per_sec as f64 {
+                self.slow_since.get_or_insert(self.window_start);
+            } else {
+                self.slow_since = None;
+            }
+            self.window_start = now;
+            self.window_bytes = 0;
+        }
+        self.slow_since
+            .is_some_and(|since| now.saturating_duration_since(since) > self.stall_timeout)
+    }
+}
+
+/// How a BLOB is downloaded: its timeout, its bandwidth and when it counts as stalled
+#[derive(Debug, Clone, Copy)]
+pub struct BlobDownloadOptions {
+    /// Timeout for downloading the BLOB, or None to use the client's timeout
+    pub timeout: Option<Duration>,
+    /// Maximum download bandwidth in bytes per second, where 0 means unlimited
+    pub max_bandwidth_bytes_per_sec: u64,
+    /// How long the throughput may stay below `min_throughput_bytes_per_sec`, or None to not detect stalls
+    pub stall_timeout: Option<Duration>,
+    /// Throughput in bytes per second below which the download counts as stalled
+    pub min_throughput_bytes_per_sec: u64,
+    /// Whether a partially downloaded BLOB is left on disk if the download fails
+    pub keep_partial: bool,
+    /// The size the manifest declares for the BLOB, if known, to reject an empty body early
+    pub expected_size: Option<u64>,
+}
+
 pub fn download_model_blob(
     client: &Client,
     url: &str,
     named_digest: &str,
+    temp_dir: &Path,
+    options: BlobDownloadOptions,
+    progress: &mut dyn DownloadProgress,
     unnecessary_files: &mut HashSet<PathBuf>,
-) -> Result<(PathBuf, String)> {
+) -> Result<(PathBuf, String, Option<SystemTime>)> {
     // Check for interruption before starting download
     if crate::signal_handler::is_interrupted() {
         warn!("Download interrupted by user");
@@ -180,13 +1525,58 @@ pub fn download_model_blob(
         ));
     }
 
-    let mut hasher = Sha256::new();
-    let mut temp_file = NamedTempFile::new().map_err(DownloaderError::IoError)?;
+    if !temp_dir.exists() {
+        debug!("Creating temporary directory {:?}", temp_dir);
+        fs::create_dir_all(temp_dir)?;
+    }
+    let mut temp_file = NamedTempFile::new_in(temp_dir).map_err(DownloaderError::IoError)?;
+    temp_file.disable_cleanup(options.keep_partial);
 
     let temp_path = temp_file.path().to_path_buf();
     unnecessary_files.insert(temp_path.clone());
 
-    let response = client.get(url).send()?;
+    let (computed_digest, last_modified) =
+        stream_blob(client, url, named_digest, options, progress, &mut temp_file)?;
+    debug!("Downloaded {} to {:?}", url, temp_path);
+    debug!("Computed SHA256 digest: {}", computed_digest);
+
+    // Persist the temp file
+    let persisted_path = temp_file.into_temp_path();
+    let final_path = persisted_path
+        .keep()
+        .map_err(|e| DownloaderError::Other(format!("Failed to persist temp file: {}", e)))?;
+
+    Ok((final_path, computed_digest, last_modified))
+}
+
+/// Download a BLOB and stream its content into a writer, hashing it on the way, with the
+/// bandwidth limit, stall detection and interrupt handling of BLOB downloads.
+///
+/// # Arguments
+/// * `client` - The HTTP client
+/// * `url` - URL of the BLOB
+/// * `named_digest` - The digest the BLOB is advertised with, e.g., sha256:<hex>
+/// * `options` - How the BLOB is downloaded
+/// * `progress` - Where the progress of the download is reported
+/// * `out` - Where the content of the BLOB is written
+///
+/// # Returns
+/// * `Result<(String, Option<SystemTime>)>` - The hex SHA256 digest of the content, which
+///   the caller verifies, and the Last-Modified time reported for the BLOB, if any
+pub fn stream_blob(
+    client: &Client,
+    url: &str,
+    named_digest: &str,
+    options: BlobDownloadOptions,
+    progress: &mut dyn DownloadProgress,
+    out: &mut dyn Write,
+) -> Result<(String, Option<SystemTime>)> {
+    let mut hasher = Sha256::new();
+    let mut request = client.get(url);
+    if let Some(timeout) = options.timeout {
+        request = request.timeout(timeout);
+    }
+    let response = request.send_traced()?;
 
     if !response.status().is_success() {
         return Err(DownloaderError::HttpError(
@@ -195,16 +1585,18 @@ pub fn download_model_blob(
     }
 
     let total_size = response.content_length().unwrap_or(0);
-
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .unwrap()
-            .progress_chars("#>-"),
+    let last_modified = response
+        .headers()
+        .get(LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date);
+    progress.on_blob_start(named_digest, total_size);
+    let mut rate_limiter = RateLimiter::new(options.max_bandwidth_bytes_per_sec);
+    let mut stall_detector = StallDetector::new(
+        options.stall_timeout,
+        options.min_throughput_bytes_per_sec,
+        Instant::now(),
     );
-    pb.set_message(format!("Downloading BLOB {}", &named_digest));
-
     struct ProgressGuard;
     impl Drop for ProgressGuard {
         fn drop(&mut self) {
@@ -218,52 +1610,372 @@ pub fn download_model_blob(
     // Stream chunks from the response
     let mut response_reader = response;
     let mut buffer = [0u8; 8192];
+    let mut received_bytes: u64 = 0;
 
     loop {
         // Check for interruption signal during download
         if crate::signal_handler::is_interrupted() {
             warn!("Download interrupted by user while downloading BLOB");
-            pb.abandon();
+            progress.on_blob_abandoned(named_digest);
             return Err(DownloaderError::Other(
                 "Download interrupted by user".to_string(),
             ));
         }
 
         if crate::signal_handler::interrupt_requested() {
-            let should_exit = pb.suspend(crate::signal_handler::confirm_pending_interrupt);
+            let should_exit =
+                progress.suspend(&mut crate::signal_handler::confirm_pending_interrupt);
             if should_exit {
                 warn!("Download interrupted by user while downloading BLOB");
-                pb.abandon();
+                progress.on_blob_abandoned(named_digest);
                 return Err(DownloaderError::Other(
                     "Download interrupted by user".to_string(),
                 ));
             }
         }
 
-        let bytes_read = response_reader.read(&mut buffer)?;
+        let chunk_size = rate_limiter
+            .as_ref()
+            .map_or(buffer.len(), |limiter| limiter.chunk_size(buffer.len()));
+        let bytes_read = match response_reader.read(&mut buffer[..chunk_size]) {
+            Ok(bytes_read) => bytes_read,
+            Err(e) => {
+                progress.on_blob_abandoned(named_digest);
+                return Err(e.into());
+            }
+        };
         if bytes_read == 0 {
             break;
         }
 
+        received_bytes += bytes_read as u64;
         let chunk = &buffer[..bytes_read];
         hasher.update(chunk);
-        temp_file.write_all(chunk)?;
-        pb.inc(bytes_read as u64);
+        out.write_all(chunk)?;
+        progress.on_chunk(bytes_read as u64);
+        if stall_detector
+            .as_mut()
+            .is_some_and(|detector| detector.observe(bytes_read as u64, Instant::now()))
+        {
+            warn!(
+                "Download of BLOB {} stalled below {}/s",
+                named_digest,
+                format_byte_size(options.min_throughput_bytes_per_sec)
+            );
+            progress.on_blob_abandoned(named_digest);
+            return Err(DownloaderError::Stalled(format!(
+                "BLOB {} was downloaded at less than {}/s for more than {} seconds",
+                named_digest,
+                format_byte_size(options.min_throughput_bytes_per_sec),
+                options.stall_timeout.unwrap_or_default().as_secs_f64()
+            )));
+        }
+
+        if let Some(limiter) = rate_limiter.as_mut() {
+            let wait = limiter.consume(bytes_read, Instant::now());
+            if !wait.is_zero() {
+                std::thread::sleep(wait);
+            }
+        }
+    }
+
+    // Some broken CDNs answer with an empty body, whose digest mismatch would be misleading
+    if let Some(expected_size) = options.expected_size.filter(|size| *size > 0)
+        && received_bytes == 0
+    {
+        warn!(
+            "Received an empty body for BLOB {} from {}",
+            named_digest, url
+        );
+        progress.on_blob_abandoned(named_digest);
+        return Err(DownloaderError::Other(format!(
+            "Received an empty body for BLOB {} that should be {} bytes",
+            named_digest, expected_size
+        )));
     }
 
-    pb.finish_with_message("Downloaded");
+    progress.on_blob_complete(named_digest);
 
     let computed_digest = format!("{:x}", hasher.finalize());
-    debug!("Downloaded {} to {:?}", url, temp_path);
-    debug!("Computed SHA256 digest: {}", computed_digest);
 
-    // Persist the temp file
-    let persisted_path = temp_file.into_temp_path();
-    let final_path = persisted_path
-        .keep()
-        .map_err(|e| DownloaderError::Other(format!("Failed to persist temp file: {}", e)))?;
+    Ok((computed_digest, last_modified))
+}
+
+/// Stream the single BLOB of model weights that a manifest references into a writer, e.g.,
+/// stdout, without saving it to the models directory. The BLOB is downloaded into the
+/// temporary directory and verified against its digest first, so that only verified weights
+/// are ever written.
+///
+/// # Arguments
+/// * `client` - The HTTP client
+/// * `manifest` - The parsed manifest of the model
+/// * `model_identifier` - The model identifier, for messages
+/// * `blob_url` - Gives the URL of a BLOB from its digest
+/// * `library` - The settings of BLOB downloads, e.g., the bandwidth limit and the
+///   temporary directory
+/// * `progress` - Where the progress of the download is reported
+/// * `out` - Where the weights are written
+///
+/// # Returns
+/// * `Result<u64>` - The number of bytes written, or an error if the manifest does not
+///   reference exactly one BLOB of model weights, or the downloaded weights do not match
+///   their digest, in which case nothing is written
+pub fn stream_model_weights(
+    client: &Client,
+    manifest: &ImageManifest,
+    model_identifier: &str,
+    blob_url: impl Fn(&str) -> String,
+    library: &OllamaLibrary,
+    progress: &mut dyn DownloadProgress,
+    out: &mut dyn Write,
+) -> Result<u64> {
+    let weights: Vec<_> = manifest
+        .unique_layers()
+        .into_iter()
+        .filter(|layer| MODEL_LAYER_MEDIA_TYPES.contains(&layer.media_type.as_str()))
+        .collect();
+    let [layer] = weights.as_slice() else {
+        return Err(DownloaderError::InvalidManifest(format!(
+            "{} has {} BLOBs of model weights, while only a model with a single one can be \
+            streamed; download it with model-download or hf-model-download instead",
+            model_identifier,
+            weights.len()
+        )));
+    };
+
+    info!(
+        "Streaming the weights of {}, {} ({})",
+        model_identifier,
+        layer.digest,
+        format_byte_size(layer.size)
+    );
+    let options = BlobDownloadOptions {
+        timeout: library.blob_timeout.map(Duration::from_secs_f64),
+        max_bandwidth_bytes_per_sec: library.max_bandwidth_bytes_per_sec,
+        stall_timeout: library.stall_timeout_secs.map(Duration::from_secs_f64),
+        min_throughput_bytes_per_sec: library.min_throughput_bytes_per_sec,
+        keep_partial: false,
+        expected_size: Some(layer.size),
+    };
+    let temp_dir = resolve_temp_dir(&library.models_path, library.temp_dir.as_deref())?;
+    let mut unnecessary_files = HashSet::new();
+    let result = download_model_blob(
+        client,
+        &blob_url(&layer.digest),
+        &layer.digest,
+        &temp_dir,
+        options,
+        progress,
+        &mut unnecessary_files,
+    )
+    .and_then(|(path, computed_digest, _)| {
+        verify_blob_digest(&layer.digest, &computed_digest, true)?;
+        let written = io::copy(&mut fs::File::open(&path)?, out)?;
+        out.flush()?;
+        Ok(written)
+    });
+    // The BLOB is only written out, so it is never kept
+    cleanup_unnecessary_files(&mut unnecessary_files);
+    result
+}
+
+/// Download a small BLOB holding JSON, e.g., the configuration of a model, verify its digest
+/// and parse it, without saving it to the models directory.
+///
+/// # Arguments
+/// * `client` - The HTTP client
+/// * `url` - URL of the BLOB
+/// * `named_digest` - The digest the BLOB is advertised with, e.g., sha256:<hex>
+/// * `temp_dir` - Directory the BLOB is downloaded into before it is parsed
+/// * `timeout` - Timeout for downloading the BLOB, or None to use the client's timeout
+///
+/// # Returns
+/// * `Result<Value>` - The parsed content of the BLOB
+pub fn fetch_json_blob(
+    client: &Client,
+    url: &str,
+    named_digest: &str,
+    temp_dir: &Path,
+    timeout: Option<Duration>,
+) -> Result<Value> {
+    let options = BlobDownloadOptions {
+        timeout,
+        max_bandwidth_bytes_per_sec: 0,
+        stall_timeout: None,
+        min_throughput_bytes_per_sec: 0,
+        keep_partial: false,
+        expected_size: None,
+    };
+    let mut unnecessary_files = HashSet::new();
+    let result = download_model_blob(
+        client,
+        url,
+        named_digest,
+        temp_dir,
+        options,
+        &mut NoProgress,
+        &mut unnecessary_files,
+    )
+    .and_then(|(path, computed_digest, _)| {
+        verify_blob_digest(named_digest, &computed_digest, true)?;
+        serde_json::from_str(&fs::read_to_string(&path)?).map_err(|e| {
+            DownloaderError::ParseError(format!("Failed to parse BLOB {}: {}", named_digest, e))
+        })
+    });
+    // The BLOB is only read, so it is never kept
+    cleanup_unnecessary_files(&mut unnecessary_files);
+    result
+}
+
+/// Verify that the digest computed for a downloaded BLOB matches its named digest.
+///
+/// Verification can be skipped as an escape hatch for debugging registries that serve
+/// content not matching its advertised digest, in which case the BLOB is accepted with
+/// a warning that its integrity is NOT verified.
+///
+/// # Arguments
+/// * `named_digest` - The digest the BLOB is advertised with, e.g., sha256:<hex>
+/// * `computed_digest` - The hex digest computed from the downloaded content
+/// * `verify_digest` - Whether a mismatch fails the download
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if the digests do not match and verification is on
+pub fn verify_blob_digest(
+    named_digest: &str,
+    computed_digest: &str,
+    verify_digest: bool,
+) -> Result<()> {
+    // Compare without the "sha256:" prefix
+    let expected_digest = &named_digest[7..];
+    if !verify_digest {
+        warn!(
+            "Integrity of BLOB {} is NOT verified, since digest verification is disabled",
+            named_digest
+        );
+        if computed_digest != expected_digest {
+            error!(
+                "Digest mismatch ignored: expected {}, got {}. The BLOB may be corrupt or tampered with!",
+                expected_digest, computed_digest
+            );
+        }
+        return Ok(());
+    }
+
+    if computed_digest != expected_digest {
+        error!(
+            "Digest mismatch: expected {}, got {}",
+            expected_digest, computed_digest
+        );
+        return Err(DownloaderError::Other(format!(
+            "Digest mismatch for {}",
+            named_digest
+        )));
+    }
+
+    info!("BLOB {} digest verified successfully.", named_digest);
+    Ok(())
+}
+
+/// Check whether a BLOB is present in the models directory with content matching its digest.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `named_digest` - The digest of the BLOB, e.g., sha256:<hex>
+///
+/// # Returns
+/// * `Result<bool>` - True if the BLOB is present and intact, false if it is absent or corrupt
+pub fn is_blob_intact(models_root: &Path, named_digest: &str) -> Result<bool> {
+    let path = models_root.join("blobs").join(blob_file_name(named_digest));
+    if !path.is_file() {
+        debug!("BLOB {} is absent from {:?}", named_digest, path);
+        return Ok(false);
+    }
+
+    let computed_digest = file_sha256(&path)?;
+    let intact = named_digest.get(7..) == Some(computed_digest.as_str());
+    if !intact {
+        warn!("BLOB {:?} does not match its digest {}", path, named_digest);
+    }
+    Ok(intact)
+}
+
+/// Ensure that every BLOB referenced by a manifest is present in the models directory with
+/// content matching its digest, e.g., before relinking the manifest without downloading.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `manifest` - The parsed manifest
+/// * `model_identifier` - The model identifier, for the error message
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error listing the BLOBs that are absent or corrupt
+pub fn ensure_blobs_intact(
+    models_root: &Path,
+    manifest: &ImageManifest,
+    model_identifier: &str,
+) -> Result<()> {
+    let mut damaged = Vec::new();
+    let digests = std::iter::once(&manifest.config.digest).chain(
+        manifest
+            .unique_layers()
+            .into_iter()
+            .map(|layer| &layer.digest),
+    );
+    for digest in digests {
+        if !is_blob_intact(models_root, digest)? {
+            damaged.push(digest.as_str());
+        }
+    }
+
+    if damaged.is_empty() {
+        Ok(())
+    } else {
+        Err(DownloaderError::Other(format!(
+            "{} references BLOBs that are absent or corrupt: {}",
+            model_identifier,
+            damaged.join(", ")
+        )))
+    }
+}
+
+/// Compute the SHA256 digest of a file.
+///
+/// # Arguments
+/// * `path` - Path of the file
+///
+/// # Returns
+/// * `io::Result<String>` - The hex digest of the content of the file
+fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-    Ok((final_path, computed_digest))
+/// Verify a saved BLOB by reading it back from its final path, to catch corruption while
+/// it was moved or copied there, which the digest computed while downloading cannot.
+///
+/// # Arguments
+/// * `path` - The final path of the BLOB
+/// * `named_digest` - The digest of the BLOB, e.g., sha256:<hex>, for messages
+/// * `computed_digest` - The hex digest computed while downloading, which matches the named
+///   digest unless digest verification is disabled
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if the saved content differs from the downloaded one
+pub fn verify_saved_blob(path: &Path, named_digest: &str, computed_digest: &str) -> Result<()> {
+    let saved_digest = file_sha256(path)?;
+    if saved_digest != computed_digest {
+        error!(
+            "Saved BLOB {:?} is corrupt: expected {}, got {}",
+            path, computed_digest, saved_digest
+        );
+        return Err(DownloaderError::Other(format!(
+            "BLOB {} was corrupted while saving it to {:?}",
+            named_digest, path
+        )));
+    }
+    debug!("Saved BLOB {:?} verified", path);
+    Ok(())
 }
 
 pub fn save_blob(
@@ -271,23 +1983,11 @@ pub fn save_blob(
     source: &Path,
     named_digest: &str,
     computed_digest: &str,
+    verify_digest: bool,
     models_dir_ownership: Option<Ownership>,
     unnecessary_files: &mut HashSet<PathBuf>,
 ) -> Result<PathBuf> {
-    // Verify digest matches (skip "sha256:" prefix)
-    let expected_digest = &named_digest[7..];
-    if computed_digest != expected_digest {
-        error!(
-            "Digest mismatch: expected {}, got {}",
-            expected_digest, computed_digest
-        );
-        return Err(DownloaderError::Other(format!(
-            "Digest mismatch for {}",
-            named_digest
-        )));
-    }
-
-    info!("BLOB {} digest verified successfully.", named_digest);
+    verify_blob_digest(named_digest, computed_digest, verify_digest)?;
 
     let models_path = expand_models_path(models_path)?;
     let blobs_dir = models_path.join("blobs");
@@ -306,12 +2006,12 @@ pub fn save_blob(
         )));
     }
 
-    let target_file = blobs_dir.join(named_digest.replace(':', "-"));
-    fs::copy(source, &target_file)?;
+    let target_file = blobs_dir.join(blob_file_name(named_digest));
+    move_file(source, &target_file)?;
 
     if let Some(ownership) = models_dir_ownership {
-        ensure_ownership(&target_file, ownership);
-        ensure_ownership(&blobs_dir, ownership);
+        ensure_ownership(&models_path, &target_file, ownership);
+        ensure_ownership(&models_path, &blobs_dir, ownership);
     }
 
     // Remove source from unnecessary files and add target
@@ -323,6 +2023,111 @@ pub fn save_blob(
     Ok(target_file)
 }
 
+/// Give a saved BLOB a deterministic modification time instead of the time it was saved at.
+///
+/// # Arguments
+/// * `path` - Path of the saved BLOB
+/// * `preserve_mtime` - The modification time to give the BLOB
+/// * `source_mtime` - The time the source reports for the BLOB, if any
+///
+/// # Returns
+/// * `Result<()>` - Success, also if the source reports no time and the BLOB is left as it
+///   is, or an error if the time cannot be set
+pub fn apply_blob_mtime(
+    path: &Path,
+    preserve_mtime: PreserveMtime,
+    source_mtime: Option<SystemTime>,
+) -> Result<()> {
+    let mtime = match (preserve_mtime, source_mtime) {
+        (PreserveMtime::Epoch, _) => UNIX_EPOCH,
+        (PreserveMtime::Source, Some(mtime)) => mtime,
+        (PreserveMtime::Source, None) => {
+            warn!(
+                "The source reports no modification time for {:?}, keeping the current one",
+                path
+            );
+            return Ok(());
+        }
+    };
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(mtime)?;
+    debug!("Set the modification time of {:?} to {:?}", path, mtime);
+    Ok(())
+}
+
+/// Write a file atomically: the data is written to a temporary file in the same directory,
+/// which is then renamed over the file, so that the file holds either its previous or its
+/// complete new content, never a part of it, e.g., if ODIR is interrupted while writing.
+///
+/// # Arguments
+/// * `path` - Path of the file to write
+/// * `data` - The content of the file
+///
+/// # Returns
+/// * `io::Result<()>` - Success or error
+pub fn write_file_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".odir-").suffix(".tmp");
+    // As readable as a file created by fs::write, e.g., for an Ollama server running as another user
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let mut temp_file = builder.tempfile_in(dir)?;
+    temp_file.write_all(data)?;
+    temp_file.as_file().sync_all()?;
+    temp_file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Write a fetched manifest to a file exactly as it was received, before it is parsed, if a
+/// file is given.
+///
+/// # Arguments
+/// * `raw_manifest_out` - The file to write the manifest to, if any
+/// * `manifest_json` - The manifest as received
+///
+/// # Returns
+/// * `Result<()>` - Success, also if no file is given, or an error if it cannot be written
+pub fn write_raw_manifest(raw_manifest_out: Option<&Path>, manifest_json: &str) -> Result<()> {
+    let Some(path) = raw_manifest_out else {
+        return Ok(());
+    };
+    fs::write(path, manifest_json).map_err(|e| {
+        DownloaderError::Other(format!("Failed to write the manifest to {:?}: {}", path, e))
+    })?;
+    info!("Wrote the manifest as received to {:?}", path);
+    Ok(())
+}
+
+/// Move a file, renaming it where possible and falling back to copy and remove
+/// when the source and target are on different filesystems.
+///
+/// # Arguments
+/// * `source` - Path of the file to move
+/// * `target` - Destination path of the file
+///
+/// # Returns
+/// * `io::Result<()>` - Success or error
+fn move_file(source: &Path, target: &Path) -> io::Result<()> {
+    match fs::rename(source, target) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            debug!(
+                "Cannot rename {:?} to {:?} across filesystems, copying instead",
+                source, target
+            );
+            fs::copy(source, target)?;
+            fs::remove_file(source)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 pub fn save_manifest(
     data: &str,
     models_root: &Path,
@@ -342,13 +2147,13 @@ pub fn save_manifest(
     }
 
     let target_file = manifests_dir.join(tag);
-    fs::write(&target_file, data)?;
+    write_file_atomically(&target_file, data.as_bytes())?;
 
     if let Some(ownership) = models_dir_ownership {
         ensure_ownership_for_dir_tree(models_root, manifests_dir, ownership);
-        ensure_ownership(&target_file, ownership);
+        ensure_ownership(models_root, &target_file, ownership);
         for dir in chown_dirs {
-            ensure_ownership(dir, ownership);
+            ensure_ownership(models_root, dir, ownership);
         }
     }
     info!("Saved manifest to {:?}", target_file);
@@ -383,11 +2188,51 @@ pub fn cleanup_unnecessary_files(unnecessary_files: &mut HashSet<PathBuf>) {
     }
 }
 
-fn ensure_ownership(path: &Path, ownership: Ownership) {
+/// Log where the files of a failed or interrupted download were left, instead of removing
+/// them, so that they can be inspected.
+///
+/// # Arguments
+/// * `unnecessary_files` - The files created by the download
+pub fn report_kept_files(unnecessary_files: &HashSet<PathBuf>) {
+    let mut kept_files: Vec<&PathBuf> = unnecessary_files
+        .iter()
+        .filter(|path| path.is_file())
+        .collect();
+    if kept_files.is_empty() {
+        return;
+    }
+    kept_files.sort();
+    warn!(
+        "Cleanup is disabled, leaving {} file(s) of the download on disk:",
+        kept_files.len()
+    );
+    for path in kept_files {
+        warn!("  {:?}", path);
+    }
+}
+
+/// Apply the ownership of the models directory to a path inside it, if it differs.
+///
+/// Symbolic links are not followed, and paths that resolve to a location outside the
+/// models directory are left untouched, so that running as root cannot change the
+/// ownership of files elsewhere on the system.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `path` - The path to apply the ownership to
+/// * `ownership` - The ownership of the models directory
+pub fn ensure_ownership(models_root: &Path, path: &Path, ownership: Ownership) {
     #[cfg(unix)]
     {
         use std::os::unix::fs::MetadataExt;
-        match fs::metadata(path) {
+        if !is_within_models_root(models_root, path) {
+            warn!(
+                "Refusing to change ownership of {:?}, which resolves outside the models directory {:?}",
+                path, models_root
+            );
+            return;
+        }
+        match fs::symlink_metadata(path) {
             Ok(metadata) => {
                 if metadata.uid() != ownership.uid || metadata.gid() != ownership.gid {
                     apply_ownership(path, ownership);
@@ -398,18 +2243,24 @@ fn ensure_ownership(path: &Path, ownership: Ownership) {
     }
     #[cfg(not(unix))]
     {
-        let _ = (path, ownership);
+        let _ = (models_root, path, ownership);
     }
 }
 
-fn ensure_ownership_for_dir_tree(models_root: &Path, dir: &Path, ownership: Ownership) {
+/// Apply the ownership of the models directory to a directory and all its parents up to it.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `dir` - The directory inside the models directory
+/// * `ownership` - The ownership of the models directory
+pub fn ensure_ownership_for_dir_tree(models_root: &Path, dir: &Path, ownership: Ownership) {
     if !dir.starts_with(models_root) {
         return;
     }
 
     let mut current = dir;
     loop {
-        ensure_ownership(current, ownership);
+        ensure_ownership(models_root, current, ownership);
         if current == models_root {
             break;
         }
@@ -421,14 +2272,37 @@ fn ensure_ownership_for_dir_tree(models_root: &Path, dir: &Path, ownership: Owne
     }
 }
 
+/// Check whether a path lies inside the models directory once symbolic links are resolved.
+///
+/// The last component of the path is not resolved, since ownership is applied to
+/// a symbolic link itself rather than to its target.
+///
+/// # Arguments
+/// * `models_root` - Path to the Ollama models directory
+/// * `path` - The path to check
+///
+/// # Returns
+/// * `bool` - True if the path is the models directory or lies inside it
+fn is_within_models_root(models_root: &Path, path: &Path) -> bool {
+    if path == models_root {
+        return true;
+    }
+    let Ok(canonical_root) = fs::canonicalize(models_root) else {
+        return false;
+    };
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent).map(|parent| parent.join(name)),
+        _ => fs::canonicalize(path),
+    };
+    resolved.is_ok_and(|resolved| resolved.starts_with(&canonical_root))
+}
+
 fn apply_ownership(path: &Path, ownership: Ownership) {
     #[cfg(unix)]
     {
-        let spec = format!("{}:{}", ownership.uid, ownership.gid);
-        match Command::new("chown").arg(&spec).arg(path).status() {
-            Ok(status) if status.success() => {}
-            Ok(status) => warn!("Failed to chown {:?}: exit status {}", path, status),
-            Err(e) => warn!("Failed to chown {:?}: {}", path, e),
+        // Change the ownership of symbolic links themselves, never of their targets
+        if let Err(e) = std::os::unix::fs::lchown(path, Some(ownership.uid), Some(ownership.gid)) {
+            warn!("Failed to chown {:?}: {}", path, e);
         }
     }
     #[cfg(not(unix))]
@@ -436,3 +2310,905 @@ fn apply_ownership(path: &Path, ownership: Ownership) {
         let _ = (path, ownership);
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    /// Serve a single HTTP request on a local port with a fixed response, returning the
+    /// address and a handle that yields the headers of the request received.
+    pub(crate) fn serve_once(response: String) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            request.to_lowercase()
+        });
+        (address, handle)
+    }
+
+    pub(crate) fn redirect_to(location: &str) -> String {
+        format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            location
+        )
+    }
+
+    /// Serve one HTTP response per connection on a local port, with the given responses.
+    fn serve_responses(responses: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        address
+    }
+
+    /// Serve one HTTP response per connection on a local port, with the given status codes.
+    fn serve_statuses(statuses: Vec<u16>) -> String {
+        serve_responses(
+            statuses
+                .into_iter()
+                .map(|status| {
+                    format!(
+                        "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// A successful HTTP response with the given body.
+    fn ok_response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    fn fetch_status(client: &Client, url: &str, attempts: &mut u32) -> Result<u16> {
+        *attempts += 1;
+        let response = client.get(url).send()?.error_for_status()?;
+        Ok(response.status().as_u16())
+    }
+
+    #[test]
+    fn test_resolve_in_family() {
+        let addrs = resolve_in_family("127.0.0.1", AddressFamily::Ipv4).unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:0".parse().unwrap()]);
+        let error = resolve_in_family("127.0.0.1", AddressFamily::Ipv6).unwrap_err();
+        assert!(error.to_string().contains("no IPv6 address"));
+        assert_eq!(
+            resolve_in_family("::1", AddressFamily::Ipv6).unwrap(),
+            vec!["[::1]:0".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_describe_headers_redacts_sensitive_values() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        let described = describe_headers(&headers);
+        assert!(described.contains("content-type: application/json"));
+        assert!(described.contains("authorization: <redacted>"));
+        assert!(described.contains("x-api-key: <redacted>"));
+        assert!(!described.contains("secret"));
+        assert_eq!(describe_headers(&HeaderMap::new()), "");
+    }
+
+    #[test]
+    fn test_stall_detector() {
+        let start = Instant::now();
+        assert!(StallDetector::new(None, 1024, start).is_none());
+        assert!(StallDetector::new(Some(Duration::from_secs(5)), 0, start).is_none());
+
+        let mut detector = StallDetector::new(Some(Duration::from_secs(5)), 1024, start).unwrap();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        // Trickling bytes stall the download once the stall timeout has passed
+        assert!(!detector.observe(10, at(1)));
+        assert!(!detector.observe(10, at(3)));
+        assert!(!detector.observe(10, at(5)));
+        assert!(detector.observe(10, at(6)));
+
+        // A window above the floor resets the detector
+        let mut detector = StallDetector::new(Some(Duration::from_secs(5)), 1024, start).unwrap();
+        assert!(!detector.observe(10, at(4)));
+        assert!(!detector.observe(8192, at(5)));
+        assert!(!detector.observe(10, at(9)));
+        assert!(detector.observe(10, at(11)));
+    }
+
+    #[test]
+    fn test_retry_missing_blob() {
+        let client = Client::new();
+
+        // A missing BLOB is retried until it appears
+        let url = serve_statuses(vec![404, 404, 200]);
+        let mut attempts = 0;
+        let result = retry_missing_blob("sha256:abc", 3, Duration::ZERO, None, || {
+            fetch_status(&client, &url, &mut attempts)
+        });
+        assert_eq!(result.unwrap(), 200);
+        assert_eq!(attempts, 3);
+
+        // Retries are limited
+        let url = serve_statuses(vec![404, 404]);
+        let mut attempts = 0;
+        let result = retry_missing_blob("sha256:abc", 1, Duration::ZERO, None, || {
+            fetch_status(&client, &url, &mut attempts)
+        });
+        assert_eq!(result.unwrap_err().http_status(), Some(404));
+        assert_eq!(attempts, 2);
+
+        // Other errors are not retried
+        let url = serve_statuses(vec![500]);
+        let mut attempts = 0;
+        let result = retry_missing_blob("sha256:abc", 3, Duration::ZERO, None, || {
+            fetch_status(&client, &url, &mut attempts)
+        });
+        assert_eq!(result.unwrap_err().http_status(), Some(500));
+        assert_eq!(attempts, 1);
+
+        // No retry waits past the deadline
+        let url = serve_statuses(vec![404]);
+        let mut attempts = 0;
+        let deadline = Some(Instant::now() + Duration::from_secs(1));
+        let result = retry_missing_blob("sha256:abc", 3, Duration::from_secs(60), deadline, || {
+            fetch_status(&client, &url, &mut attempts)
+        });
+        assert_eq!(result.unwrap_err().http_status(), Some(404));
+        assert_eq!(attempts, 1);
+
+        // Stalled downloads are retried too
+        let mut attempts = 0;
+        let result = retry_missing_blob("sha256:abc", 3, Duration::ZERO, None, || {
+            attempts += 1;
+            if attempts < 2 {
+                Err(DownloaderError::Stalled("slow".to_string()))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_sleep_unless_interrupted() {
+        // An uninterrupted sleep lasts the whole wait
+        let start = Instant::now();
+        assert!(sleep_unless_interrupted(Duration::from_millis(150), || {
+            false
+        }));
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        // An interrupted sleep stops at the next check rather than waiting out the rest
+        let start = Instant::now();
+        let mut checks = 0;
+        assert!(!sleep_unless_interrupted(Duration::from_secs(60), || {
+            checks += 1;
+            checks > 2
+        }));
+        assert_eq!(checks, 3);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("llama3.1", "llama3.1"), 0);
+        assert_eq!(edit_distance("Llama3.1", "llama3.1"), 0);
+        assert_eq!(edit_distance("llama3", "llama3.1"), 2);
+        assert_eq!(edit_distance("mistral", "mixtral"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_suggest_similar() {
+        let candidates = vec![
+            "llama3.1".to_string(),
+            "llama3.2".to_string(),
+            "mistral".to_string(),
+            "qwen3".to_string(),
+        ];
+        assert_eq!(
+            suggest_similar("lama3.1", &candidates, 3),
+            vec!["llama3.1".to_string(), "llama3.2".to_string()]
+        );
+        assert!(suggest_similar("gemma", &candidates, 3).is_empty());
+    }
+
+    #[test]
+    fn test_move_file_renames_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        fs::write(&source, b"blob").unwrap();
+
+        move_file(&source, &target).unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"blob");
+    }
+
+    #[test]
+    fn test_compare_tags() {
+        let mut tags = vec![
+            "llama:latest",
+            "llama:70b",
+            "llama:13b",
+            "llama:7b-q8_0",
+            "llama:7b-q4_K_M",
+            "llama:7b",
+            "llama:1.5b",
+            "llama:135m",
+        ];
+        tags.sort_by(|a, b| compare_tags(a, b));
+        assert_eq!(
+            tags,
+            vec![
+                "llama:135m",
+                "llama:1.5b",
+                "llama:7b",
+                "llama:7b-q4_K_M",
+                "llama:7b-q8_0",
+                "llama:13b",
+                "llama:70b",
+                "llama:latest",
+            ]
+        );
+
+        let mut quants = vec!["F16", "Q8_0", "q4_K_M", "IQ2_XS", "Q3_K_S", "Q2_K", "BF16"];
+        quants.sort_by(|a, b| compare_tags(a, b));
+        assert_eq!(
+            quants,
+            vec!["IQ2_XS", "Q2_K", "Q3_K_S", "q4_K_M", "Q8_0", "BF16", "F16"]
+        );
+
+        // Tags without sizes or quantisations are compared ignoring case
+        assert_eq!(
+            compare_tags("Instruct", "base"),
+            std::cmp::Ordering::Greater
+        );
+        assert_eq!(compare_tags("7b", "13b"), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn test_write_file_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latest");
+        write_file_atomically(&path, b"old").unwrap();
+        write_file_atomically(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        // No temporary file is left behind next to the file
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let missing_dir = dir.path().join("missing").join("latest");
+        assert!(write_file_atomically(&missing_dir, b"new").is_err());
+        assert!(!missing_dir.exists());
+    }
+
+    #[test]
+    fn test_apply_blob_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sha256-blob");
+        fs::write(&path, b"blob").unwrap();
+        let mtime = || fs::metadata(&path).unwrap().modified().unwrap();
+
+        apply_blob_mtime(&path, PreserveMtime::Epoch, None).unwrap();
+        assert_eq!(mtime(), UNIX_EPOCH);
+
+        let source = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        apply_blob_mtime(&path, PreserveMtime::Source, Some(source)).unwrap();
+        assert_eq!(mtime(), source);
+
+        // Without a time from the source, the BLOB keeps its time
+        apply_blob_mtime(&path, PreserveMtime::Source, None).unwrap();
+        assert_eq!(mtime(), source);
+    }
+
+    #[test]
+    fn test_ensure_enough_inodes() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ensure_enough_inodes(dir.path(), 3, 0, false).is_ok());
+        assert!(ensure_enough_inodes(dir.path(), 3, u64::MAX / 2, true).is_ok());
+        match free_inodes(dir.path()).unwrap() {
+            Some(free) => {
+                assert_eq!(
+                    ensure_enough_inodes(dir.path(), 3, 1, false).is_ok(),
+                    free >= 4
+                );
+                assert!(ensure_enough_inodes(dir.path(), 3, u64::MAX / 2, false).is_err());
+            }
+            None => assert!(ensure_enough_inodes(dir.path(), 3, u64::MAX / 2, false).is_ok()),
+        }
+    }
+
+    #[test]
+    fn test_write_raw_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let manifest_json = "{\"schemaVersion\": 2,\n  \"layers\": []}";
+        write_raw_manifest(None, manifest_json).unwrap();
+        assert!(!path.exists());
+        write_raw_manifest(Some(&path), manifest_json).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), manifest_json);
+
+        let err =
+            write_raw_manifest(Some(&dir.path().join("missing/manifest.json")), "{}").unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to write the manifest"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_required_free_space() {
+        // A rename needs no space beyond the BLOBs themselves
+        let rename = BlobTransfer::Rename;
+        assert_eq!(
+            required_free_space(&[100, 900, 50], rename, 0),
+            (1050, None)
+        );
+        assert_eq!(
+            required_free_space(&[100, 900, 50], rename, 1000),
+            (2050, None)
+        );
+        assert_eq!(required_free_space(&[], rename, 10), (10, None));
+    }
+
+    #[test]
+    fn test_required_free_space_when_copying_within_a_filesystem() {
+        // The largest BLOB briefly takes up space twice
+        let copy = BlobTransfer::CopyWithinFilesystem;
+        assert_eq!(required_free_space(&[100, 900, 50], copy, 0), (1950, None));
+        assert_eq!(
+            required_free_space(&[100, 900, 50], copy, 1000),
+            (2950, None)
+        );
+        assert_eq!(required_free_space(&[], copy, 10), (10, None));
+    }
+
+    #[test]
+    fn test_required_free_space_on_separate_filesystems() {
+        // The temporary directory holds every BLOB, and the models path receives every BLOB
+        // without a copy overhead, while only the models path has to keep space free
+        let copy = BlobTransfer::CopyAcrossFilesystems;
+        assert_eq!(
+            required_free_space(&[100, 900, 50], copy, 0),
+            (1050, Some(1050))
+        );
+        assert_eq!(
+            required_free_space(&[100, 900, 50], copy, 1000),
+            (2050, Some(1050))
+        );
+        assert_eq!(required_free_space(&[], copy, 10), (10, Some(0)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unescape_mount_point() {
+        assert_eq!(unescape_mount_point("/mnt/data"), "/mnt/data");
+        assert_eq!(unescape_mount_point("/mnt/my\\040models"), "/mnt/my models");
+        assert_eq!(unescape_mount_point("/mnt/a\\134b"), "/mnt/a\\b");
+        assert_eq!(
+            unescape_mount_point("/mnt/trailing\\04"),
+            "/mnt/trailing\\04"
+        );
+    }
+
+    #[test]
+    fn test_ensure_enough_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp_dir = dir.path().join(".tmp");
+        assert_eq!(blob_transfer(&temp_dir, dir.path()), BlobTransfer::Rename);
+        let manifest: ImageManifest = serde_json::from_str(
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 100, "digest": "sha256:aaa"},
+                "layers": [{"mediaType": "application/vnd.ollama.image.model", "size": 900, "digest": "sha256:bbb"}]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(ensure_enough_space(&manifest, dir.path(), &temp_dir, u64::MAX / 2, true).is_ok());
+        match free_space(dir.path()).unwrap() {
+            Some(free) => {
+                assert_eq!(
+                    ensure_enough_space(&manifest, dir.path(), &temp_dir, 0, false).is_ok(),
+                    free >= 1000
+                );
+                let err =
+                    ensure_enough_space(&manifest, dir.path(), &temp_dir, u64::MAX / 2, false)
+                        .unwrap_err();
+                assert!(err.to_string().contains("--min-free-after"), "{}", err);
+            }
+            None => assert!(
+                ensure_enough_space(&manifest, dir.path(), &temp_dir, u64::MAX / 2, false).is_ok()
+            ),
+        }
+    }
+
+    #[test]
+    fn test_ensure_models_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let models_path = dir.path().to_str().unwrap();
+        assert_eq!(ensure_models_dir(models_path).unwrap(), dir.path());
+
+        let missing = dir.path().join("missing");
+        let error = ensure_models_dir(missing.to_str().unwrap()).unwrap_err();
+        assert!(error.to_string().contains("does not exist"), "{}", error);
+    }
+
+    #[test]
+    fn test_resolve_temp_dir_defaults_to_models_path() {
+        let temp_dir = resolve_temp_dir("/test/models", None).unwrap();
+        assert_eq!(temp_dir, PathBuf::from("/test/models/.tmp"));
+    }
+
+    #[test]
+    fn test_resolve_temp_dir_uses_configured_directory() {
+        let temp_dir = resolve_temp_dir("/test/models", Some("/scratch/odir")).unwrap();
+        assert_eq!(temp_dir, PathBuf::from("/scratch/odir"));
+    }
+
+    #[test]
+    fn test_format_byte_size() {
+        assert_eq!(format_byte_size(512), "512 B");
+        assert_eq!(format_byte_size(1536), "1.5 KiB");
+        assert_eq!(format_byte_size(4 * 1024 * 1024 * 1024), "4.0 GiB");
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        let lookup = |name: &str| match name {
+            "OLLAMA_HOME" => Some("/srv/ollama".to_string()),
+            "APPDATA" => Some("C:/Users/me/AppData".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_env_vars("$OLLAMA_HOME/models", false, lookup).unwrap(),
+            "/srv/ollama/models"
+        );
+        assert_eq!(
+            expand_env_vars("${OLLAMA_HOME}_old/models", false, lookup).unwrap(),
+            "/srv/ollama_old/models"
+        );
+        assert_eq!(
+            expand_env_vars("%APPDATA%/ollama", true, lookup).unwrap(),
+            "C:/Users/me/AppData/ollama"
+        );
+        // Percent signs are only references on Windows, and a lone $ is kept
+        assert_eq!(
+            expand_env_vars("/data/100%/$/$1", false, lookup).unwrap(),
+            "/data/100%/$/$1"
+        );
+        assert_eq!(
+            expand_env_vars("/data/100%", true, lookup).unwrap(),
+            "/data/100%"
+        );
+
+        let err = expand_env_vars("$MISSING/models", false, lookup).unwrap_err();
+        assert!(err.contains("MISSING"));
+        assert!(expand_env_vars("${OLLAMA_HOME/models", false, lookup).is_err());
+    }
+
+    #[test]
+    fn test_expand_models_path_with_tilde_and_variable() {
+        let home = env::var("HOME").unwrap();
+        let path = env::var("PATH").unwrap();
+        assert_eq!(
+            expand_models_path("~/$PATH/models").unwrap(),
+            PathBuf::from(format!("{}/{}/models", home, path))
+        );
+        assert_eq!(
+            expand_models_path("~/models").unwrap(),
+            PathBuf::from(format!("{}/models", home))
+        );
+        let err = expand_models_path("~/${ODIR_UNDEFINED_TEST_VARIABLE}/models").unwrap_err();
+        assert!(err.to_string().contains("ODIR_UNDEFINED_TEST_VARIABLE"));
+    }
+
+    #[test]
+    fn test_is_insecure_http() {
+        assert!(is_insecure_http("http://registry.example.com/v2/library/"));
+        assert!(is_insecure_http("http://192.168.1.10:5000/v2/"));
+        assert!(!is_insecure_http("https://registry.ollama.ai/v2/library/"));
+        assert!(!is_insecure_http("http://localhost:11434/"));
+        assert!(!is_insecure_http("http://127.0.0.1:5000/v2/"));
+        assert!(!is_insecure_http("http://[::1]:5000/v2/"));
+
+        let urls = [
+            (
+                "registry_base_url",
+                "http://registry.example.com/v2/library/",
+            ),
+            ("library_base_url", "https://ollama.com/library"),
+        ];
+        let err = ensure_secure_transport(&urls, false).unwrap_err();
+        assert!(err.to_string().contains("registry_base_url"));
+        assert!(ensure_secure_transport(&urls, true).is_ok());
+        assert!(ensure_secure_transport(&urls[1..], false).is_ok());
+    }
+
+    #[test]
+    fn test_ensure_within_size_limit() {
+        let manifest: ImageManifest = serde_json::from_str(
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 100, "digest": "sha256:aaa"},
+                "layers": [{"mediaType": "application/vnd.ollama.image.model", "size": 900, "digest": "sha256:bbb"}]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(ensure_within_size_limit(&manifest, "test:latest", None).is_ok());
+        assert!(ensure_within_size_limit(&manifest, "test:latest", Some(1000)).is_ok());
+        let err = ensure_within_size_limit(&manifest, "test:latest", Some(999)).unwrap_err();
+        assert!(matches!(err, DownloaderError::SizeLimitExceeded(_)));
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_within_models_root_rejects_symlink_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let models_root = dir.path().join("models");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(models_root.join("blobs")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, models_root.join("manifests")).unwrap();
+        std::os::unix::fs::symlink(&outside, models_root.join("blobs/link")).unwrap();
+
+        assert!(is_within_models_root(&models_root, &models_root));
+        assert!(is_within_models_root(
+            &models_root,
+            &models_root.join("blobs")
+        ));
+        // A symbolic link inside the models directory is itself inside it
+        assert!(is_within_models_root(
+            &models_root,
+            &models_root.join("blobs/link")
+        ));
+        // A path reached through a symbolic link that points outside is not
+        assert!(!is_within_models_root(
+            &models_root,
+            &models_root.join("manifests/registry.ollama.ai")
+        ));
+        assert!(!is_within_models_root(&models_root, &outside));
+    }
+
+    #[test]
+    fn test_blob_download_timeout() {
+        assert_eq!(blob_download_timeout(None, 30.0, None).unwrap(), None);
+        assert_eq!(
+            blob_download_timeout(Some(30.0), 30.0, None).unwrap(),
+            Some(Duration::from_secs(30))
+        );
+
+        // The timeout never extends beyond the deadline
+        let deadline = download_deadline(Some(10.0));
+        let timeout = blob_download_timeout(Some(30.0), 30.0, deadline)
+            .unwrap()
+            .unwrap();
+        assert!(timeout <= Duration::from_secs(10));
+        let timeout = blob_download_timeout(Some(5.0), 30.0, deadline)
+            .unwrap()
+            .unwrap();
+        assert_eq!(timeout, Duration::from_secs(5));
+
+        let passed = Some(Instant::now() - Duration::from_secs(1));
+        assert!(matches!(
+            blob_download_timeout(Some(30.0), 30.0, passed),
+            Err(DownloaderError::DeadlineExceeded(_))
+        ));
+        assert!(matches!(
+            explain_deadline(DownloaderError::Other("timed out".to_string()), passed),
+            DownloaderError::DeadlineExceeded(_)
+        ));
+    }
+
+    #[test]
+    fn test_blob_download_timeout_without_blob_timeout() {
+        // The timeout of the client applies, unless the deadline is closer
+        let deadline = download_deadline(Some(7200.0));
+        assert_eq!(
+            blob_download_timeout(None, 30.0, deadline).unwrap(),
+            Some(Duration::from_secs(30))
+        );
+        let deadline = download_deadline(Some(10.0));
+        let timeout = blob_download_timeout(None, 30.0, deadline)
+            .unwrap()
+            .unwrap();
+        assert!(timeout <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_is_blob_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs_dir = dir.path().join("blobs");
+        fs::create_dir(&blobs_dir).unwrap();
+        let digest = format!("sha256:{:x}", Sha256::digest(b"blob"));
+        assert!(!is_blob_intact(dir.path(), &digest).unwrap());
+
+        fs::write(blobs_dir.join(blob_file_name(&digest)), b"blob").unwrap();
+        assert!(is_blob_intact(dir.path(), &digest).unwrap());
+
+        fs::write(blobs_dir.join(blob_file_name(&digest)), b"corrupt").unwrap();
+        assert!(!is_blob_intact(dir.path(), &digest).unwrap());
+    }
+
+    #[test]
+    fn test_ensure_blobs_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs_dir = dir.path().join("blobs");
+        fs::create_dir(&blobs_dir).unwrap();
+        let config = format!("sha256:{:x}", Sha256::digest(b"config"));
+        let model = format!("sha256:{:x}", Sha256::digest(b"model"));
+        let manifest: ImageManifest = serde_json::from_str(&format!(
+            r#"{{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {{"mediaType": "application/vnd.docker.container.image.v1+json", "size": 6, "digest": "{}"}},
+                "layers": [{{"mediaType": "application/vnd.ollama.image.model", "size": 5, "digest": "{}"}}]
+            }}"#,
+            config, model
+        ))
+        .unwrap();
+        fs::write(blobs_dir.join(blob_file_name(&config)), b"config").unwrap();
+
+        let err = ensure_blobs_intact(dir.path(), &manifest, "test:latest").unwrap_err();
+        assert!(err.to_string().contains(&model));
+        assert!(!err.to_string().contains(&config));
+
+        fs::write(blobs_dir.join(blob_file_name(&model)), b"model").unwrap();
+        assert!(ensure_blobs_intact(dir.path(), &manifest, "test:latest").is_ok());
+    }
+
+    #[test]
+    fn test_download_model_blob_keeps_partial_file() {
+        let client = Client::new();
+        for keep_partial in [false, true] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let url = serve_statuses(vec![500]);
+            let options = BlobDownloadOptions {
+                timeout: None,
+                max_bandwidth_bytes_per_sec: 0,
+                stall_timeout: None,
+                min_throughput_bytes_per_sec: 0,
+                keep_partial,
+                expected_size: None,
+            };
+            let mut unnecessary_files = HashSet::new();
+            let result = download_model_blob(
+                &client,
+                &url,
+                "sha256:abc",
+                temp_dir.path(),
+                options,
+                &mut NoProgress,
+                &mut unnecessary_files,
+            );
+            assert!(result.is_err());
+            assert_eq!(unnecessary_files.len(), 1);
+            let partial_file = unnecessary_files.iter().next().unwrap();
+            assert_eq!(partial_file.is_file(), keep_partial);
+        }
+    }
+
+    #[test]
+    fn test_download_model_blob_rejects_empty_body() {
+        let client = Client::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let url = serve_statuses(vec![200, 200]);
+        let options = |expected_size| BlobDownloadOptions {
+            timeout: None,
+            max_bandwidth_bytes_per_sec: 0,
+            stall_timeout: None,
+            min_throughput_bytes_per_sec: 0,
+            keep_partial: false,
+            expected_size: Some(expected_size),
+        };
+        let mut unnecessary_files = HashSet::new();
+
+        let err = download_model_blob(
+            &client,
+            &url,
+            "sha256:abc",
+            temp_dir.path(),
+            options(1024),
+            &mut NoProgress,
+            &mut unnecessary_files,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Received an empty body for BLOB sha256:abc that should be 1024 bytes"
+        );
+
+        // An empty BLOB is fine if the manifest declares it to be empty
+        let (_, computed_digest, _) = download_model_blob(
+            &client,
+            &url,
+            "sha256:abc",
+            temp_dir.path(),
+            options(0),
+            &mut NoProgress,
+            &mut unnecessary_files,
+        )
+        .unwrap();
+        assert_eq!(computed_digest, format!("{:x}", Sha256::digest(b"")));
+    }
+
+    #[test]
+    fn test_stream_model_weights() {
+        let body = "weights";
+        let digest = format!("sha256:{:x}", Sha256::digest(body.as_bytes()));
+        let url = serve_responses(vec![ok_response(body); 2]);
+        let manifest_with = |layers: &[(&str, &str)]| -> ImageManifest {
+            let layers: Vec<_> = layers
+                .iter()
+                .map(|(media_type, digest)| {
+                    serde_json::json!({"mediaType": media_type, "size": body.len(), "digest": digest})
+                })
+                .collect();
+            serde_json::from_value(serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                "config": {"mediaType": "application/vnd.docker.container.image.v1+json", "size": 2, "digest": "sha256:config"},
+                "layers": layers,
+            }))
+            .unwrap()
+        };
+        let client = Client::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let library = OllamaLibrary {
+            temp_dir: Some(temp_dir.path().to_string_lossy().into_owned()),
+            ..OllamaLibrary::default()
+        };
+        let blob_url = |_: &str| url.clone();
+
+        let manifest = manifest_with(&[
+            ("application/vnd.ollama.image.model", &digest),
+            ("application/vnd.ollama.image.template", "sha256:template"),
+        ]);
+        let mut out = Vec::new();
+        let size = stream_model_weights(
+            &client,
+            &manifest,
+            "test:latest",
+            blob_url,
+            &library,
+            &mut NoProgress,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(size, body.len() as u64);
+        assert_eq!(out, body.as_bytes());
+
+        // Content that does not match the digest is never written
+        let manifest = manifest_with(&[("application/vnd.ollama.image.model", "sha256:abc")]);
+        let mut out = Vec::new();
+        assert!(
+            stream_model_weights(
+                &client,
+                &manifest,
+                "test:latest",
+                blob_url,
+                &library,
+                &mut NoProgress,
+                &mut out,
+            )
+            .is_err()
+        );
+        assert!(out.is_empty());
+        // Nothing is left behind in the temporary directory
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        let manifest = manifest_with(&[
+            ("application/vnd.ollama.image.model", "sha256:a"),
+            ("application/vnd.ollama.image.model", "sha256:b"),
+        ]);
+        let err = stream_model_weights(
+            &client,
+            &manifest,
+            "test:latest",
+            blob_url,
+            &library,
+            &mut NoProgress,
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("has 2 BLOBs of model weights"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_fetch_json_blob() {
+        let body = r#"{"model_family": "llama"}"#;
+        let digest = format!("sha256:{:x}", Sha256::digest(body.as_bytes()));
+        let url = serve_responses(vec![ok_response(body); 2]);
+        let client = Client::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let config = fetch_json_blob(&client, &url, &digest, temp_dir.path(), None).unwrap();
+        assert_eq!(config["model_family"], "llama");
+        assert!(fetch_json_blob(&client, &url, "sha256:abc", temp_dir.path(), None).is_err());
+        // Nothing is left behind in the temporary directory
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_verify_saved_blob() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sha256-blob");
+        let computed_digest = format!("{:x}", Sha256::digest(b"blob"));
+        fs::write(&path, b"blob").unwrap();
+        assert!(verify_saved_blob(&path, "sha256:blob", &computed_digest).is_ok());
+
+        fs::write(&path, b"blub").unwrap();
+        assert!(verify_saved_blob(&path, "sha256:blob", &computed_digest).is_err());
+        assert!(
+            verify_saved_blob(&dir.path().join("missing"), "sha256:blob", &computed_digest)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_verify_blob_digest() {
+        assert!(verify_blob_digest("sha256:abc", "abc", true).is_ok());
+        assert!(verify_blob_digest("sha256:abc", "def", true).is_err());
+        assert!(verify_blob_digest("sha256:abc", "def", false).is_ok());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        assert!(RateLimiter::new(0).is_none());
+
+        let mut limiter = RateLimiter::new(1000).unwrap();
+        assert_eq!(limiter.chunk_size(8192), 100);
+        let start = limiter.last_refill;
+        // Reading ahead of the rate requires waiting for the bytes to be paid for
+        assert_eq!(limiter.consume(500, start), Duration::from_millis(500));
+        // Half a second later, those bytes are paid for
+        assert_eq!(
+            limiter.consume(0, start + Duration::from_millis(500)),
+            Duration::ZERO
+        );
+        // An idle period allows a burst of at most one second worth of bytes
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.consume(1000, later), Duration::ZERO);
+        assert_eq!(limiter.consume(1000, later), Duration::from_secs(1));
+
+        let limiter = RateLimiter::new(u64::MAX).unwrap();
+        assert_eq!(limiter.chunk_size(8192), 8192);
+    }
+}
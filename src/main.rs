@@ -4,15 +4,22 @@
 //! https://github.com/anirbanbasu/odir).
 
 use clap::builder::styling::{AnsiColor, Effects, Styles};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use log::{debug, error, info, warn};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 mod config;
-use config::{AppSettings, Config};
+use config::{AppSettings, BuildInfo, Config, RunOptions};
 
 mod downloader;
+use downloader::events::{emit_event, enable_events};
+use downloader::failures::enable_failure_log;
+use downloader::hf_downloader::{HfModelSort, HfRepoType};
+use downloader::manifest::DigestFormat;
+use downloader::model_downloader::{DownloadPlan, DownloaderError};
+use downloader::ollama_downloader::DownloadScope;
+use downloader::progress::{terminal_progress, validate_progress_template};
 use downloader::{HuggingFaceModelDownloader, ModelDownloader, OllamaModelDownloader};
 
 mod signal_handler;
@@ -37,14 +44,188 @@ const STYLES: Styles = Styles::styled()
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    #[command(flatten)]
+    global: GlobalOptions,
+}
+
+/// Options that apply to any command, overriding the settings for this run only.
+#[derive(Args, Debug)]
+struct GlobalOptions {
+    /// Accepts invalid TLS certificates, e.g., self-signed ones, for this run only,
+    /// regardless of the configured verify_ssl. Use with care.
+    #[arg(long, global = true)]
+    allow_insecure: bool,
+
+    /// Allows downloads over plain HTTP from a registry that is not on the local machine,
+    /// for this run only. Credentials and manifests travel in cleartext. Use with care.
+    #[arg(long, global = true)]
+    allow_insecure_http: bool,
+
+    /// Connects to ADDR for HOST instead of resolving it, as with curl, e.g., to test a
+    /// registry that is not in DNS yet. Can be repeated.
+    #[arg(long, global = true, value_name = "HOST:PORT:ADDR", value_parser = config::parse_resolve)]
+    resolve: Vec<config::ResolveOverride>,
+
+    /// Reports download progress as newline-delimited JSON events on stderr, instead of
+    /// progress bars, for frontends that wrap ODIR.
+    #[arg(long, global = true)]
+    progress_json: bool,
+
+    /// Reports everything the run does as newline-delimited JSON events on stderr: the
+    /// settings being loaded, each manifest fetched, the progress of each BLOB, and each
+    /// model downloaded or failed. Implies --progress-json.
+    #[arg(long, global = true)]
+    ndjson: bool,
+
+    /// Uses the settings of a named profile, kept in profiles/<NAME>.json in the config
+    /// directory and created from the defaults if missing, instead of settings.json.
+    #[arg(long, global = true, value_name = "NAME", value_parser = config::parse_profile_name)]
+    profile: Option<String>,
+
+    /// Connects over IPv4 only.
+    #[arg(long, global = true, conflicts_with = "ipv6")]
+    ipv4: bool,
+
+    /// Connects over IPv6 only.
+    #[arg(long, global = true)]
+    ipv6: bool,
+
+    /// Exits on the first Ctrl+C, after removing partially downloaded files, instead of
+    /// asking for confirmation, e.g., in scripts where Ctrl+C means stop now.
+    #[arg(long, global = true)]
+    no_confirm_interrupt: bool,
+}
+
+/// Output formats for commands that print structured data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable text.
+    Text,
+    /// JSON.
+    Json,
+}
+
+/// Ways of selecting the quantisation of a Hugging Face model given without one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum QuantSelection {
+    /// Pick one from a menu of the available quantisations and their sizes.
+    Interactive,
+}
+
+/// Settings that `edit-config` changes without asking, leaving the others as they are.
+/// If none is given, the settings are edited interactively instead.
+#[derive(Args, Debug, Default)]
+struct SettingsChanges {
+    /// Sets the URL of the Ollama server.
+    #[arg(long, value_name = "URL", value_parser = config::validate_string_as_http_url)]
+    ollama_url: Option<String>,
+
+    /// Sets whether downloaded files are removed if a download fails.
+    #[arg(long, value_name = "BOOL")]
+    remove_downloaded_on_error: Option<bool>,
+
+    /// Sets whether the Ollama server is checked for a model after downloading it.
+    #[arg(long, value_name = "BOOL")]
+    check_model_presence: Option<bool>,
+
+    /// Sets the path to the Ollama models.
+    #[arg(long, value_name = "PATH")]
+    models_path: Option<String>,
+
+    /// Sets the base URL of the Ollama registry.
+    #[arg(long, value_name = "URL", value_parser = config::validate_string_as_http_url)]
+    registry_base_url: Option<String>,
+
+    /// Sets the base URL of the Ollama library.
+    #[arg(long, value_name = "URL", value_parser = config::validate_string_as_http_url)]
+    library_base_url: Option<String>,
+
+    /// Sets whether TLS certificates are verified.
+    #[arg(long, value_name = "BOOL")]
+    verify_ssl: Option<bool>,
+
+    /// Sets the timeout of HTTP requests in seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = config::parse_seconds)]
+    timeout: Option<f64>,
+
+    /// Sets the directory for partially downloaded files.
+    #[arg(long, value_name = "PATH")]
+    temp_dir: Option<String>,
+
+    /// Sets the maximum download size of a model, e.g., 4GiB.
+    #[arg(long, value_name = "SIZE", value_parser = config::parse_byte_size)]
+    max_download_size: Option<u64>,
+}
+
+impl SettingsChanges {
+    /// Checks whether no setting is to be changed.
+    ///
+    /// # Returns
+    /// * `bool` - True if no setting is to be changed
+    fn is_empty(&self) -> bool {
+        self.ollama_url.is_none()
+            && self.remove_downloaded_on_error.is_none()
+            && self.check_model_presence.is_none()
+            && self.models_path.is_none()
+            && self.registry_base_url.is_none()
+            && self.library_base_url.is_none()
+            && self.verify_ssl.is_none()
+            && self.timeout.is_none()
+            && self.temp_dir.is_none()
+            && self.max_download_size.is_none()
+    }
+
+    /// Applies the changes to the settings, leaving the settings that are not to be changed as they are.
+    ///
+    /// # Arguments
+    /// * `settings` - The settings to change
+    fn apply(self, settings: &mut AppSettings) {
+        let server = &mut settings.ollama_server;
+        let library = &mut settings.ollama_library;
+        if let Some(url) = self.ollama_url {
+            server.url = url;
+        }
+        if let Some(remove) = self.remove_downloaded_on_error {
+            server.remove_downloaded_on_error = remove;
+        }
+        if let Some(check) = self.check_model_presence {
+            server.check_model_presence = check;
+        }
+        if let Some(path) = self.models_path {
+            library.models_path = path;
+        }
+        if let Some(url) = self.registry_base_url {
+            library.registry_base_url = url;
+        }
+        if let Some(url) = self.library_base_url {
+            library.library_base_url = url;
+        }
+        if let Some(verify) = self.verify_ssl {
+            library.verify_ssl = verify;
+        }
+        if let Some(seconds) = self.timeout {
+            library.timeout = seconds;
+        }
+        if let Some(dir) = self.temp_dir {
+            library.temp_dir = Some(dir);
+        }
+        if let Some(size) = self.max_download_size {
+            library.max_download_size = Some(size);
+        }
+    }
 }
 
 /// The available commands for the Ollama Downloader in Rust (ODIR) command-line application.
 #[derive(Subcommand)]
 enum Commands {
     #[command(subcommand_help_heading = "Configuration")]
-    /// Shows the application configuration as JSON.
-    ShowConfig,
+    /// Shows the application configuration as JSON, or the value of a single setting.
+    ShowConfig {
+        /// The dotted path of a single setting to show, e.g., ollama_library.models_path.
+        /// Strings, numbers and booleans are printed as they are, without JSON quotes.
+        key: Option<String>,
+    },
 
     #[command(subcommand_help_heading = "Configuration")]
     /// Interactively edits application settings through step-by-step questions.
@@ -53,9 +234,12 @@ enum Commands {
     /// Otherwise, the default configuration values will be used.
     EditConfig {
         /// Optional configuration file path to edit.
-        /// If not provided, uses the default user settings location.
+        /// If not provided, uses the default user settings location, or that of --profile.
         #[arg(long, short)]
         config_file: Option<String>,
+
+        #[command(flatten)]
+        changes: SettingsChanges,
     },
 
     #[command(subcommand_help_heading = "Ollama Library")]
@@ -70,21 +254,177 @@ enum Commands {
         /// The number of models to retrieve per page.
         #[arg(long)]
         page_size: Option<u32>,
+
+        /// Overrides the Ollama library base URL for this run, e.g., for a staging library.
+        #[arg(long, value_name = "URL", value_parser = config::validate_string_as_http_url)]
+        registry: Option<String>,
+
+        /// Picks a model from the list with a numbered menu and downloads it. Without a
+        /// terminal, the list is printed instead.
+        #[arg(long)]
+        interactive: bool,
     },
 
     #[command(subcommand_help_heading = "Ollama Library")]
     /// Lists all tags for a specific model.
     ListTags {
-        /// The name of the model to list tags for, e.g., llama3.1.
-        model_identifier: String,
+        /// The names of the models to list tags for, e.g., llama3.1.
+        #[arg(required = true, value_name = "MODEL_IDENTIFIER")]
+        model_identifiers: Vec<String>,
+
+        /// Continues with the other models if a model does not exist or its tags cannot be
+        /// fetched, and only fails if the tags of no model could be listed.
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Overrides the Ollama library base URL for this run, e.g., for a staging library.
+        #[arg(long, value_name = "URL", value_parser = config::validate_string_as_http_url)]
+        registry: Option<String>,
+    },
+
+    #[command(subcommand_help_heading = "Ollama Library")]
+    /// Prints the configuration of a model as JSON, without downloading its weights.
+    ///
+    /// Only the manifest and the configuration BLOB it references are fetched, and nothing
+    /// is saved in the models path.
+    Config {
+        /// The model and tag to inspect, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
+    },
+
+    #[command(subcommand_help_heading = "Ollama Library")]
+    /// Streams the weights of a model to stdout, e.g., to pipe a GGUF file into another tool,
+    /// instead of saving them in the models path.
+    ///
+    /// Only models with a single BLOB of weights can be streamed. Progress is shown on stderr,
+    /// and the digest of the weights is verified once they are streamed, so ODIR exits with a
+    /// non-zero code if they do not match it.
+    Cat {
+        /// The model and tag to stream, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
     },
 
     #[command(subcommand_help_heading = "Ollama Library")]
     /// Downloads a specific Ollama model with the given tag.
     ModelDownload {
-        /// The name of the model and a specific tag to download, specified as {model}:{tag},
-        /// e.g., llama3.1:8b. If no tag is specified, 'latest' will be assumed.
-        model_tag: String,
+        /// The names of the models and specific tags to download, specified as {model}:{tag},
+        /// e.g., llama3.1:8b. If no tag is specified, 'latest' will be assumed. A model can be
+        /// pinned to a manifest digest instead of a tag, as {model}@sha256:<hex>.
+        #[arg(required_unless_present = "from_file", value_name = "MODEL_TAG")]
+        model_tags: Vec<String>,
+
+        /// Also downloads the models listed in this file, one per line, with `#` starting a
+        /// comment, or in the standard input if the file is `-`.
+        #[arg(long, value_name = "PATH")]
+        from_file: Option<PathBuf>,
+
+        /// Overrides the Ollama registry base URL for this run, e.g., for a staging registry.
+        #[arg(long, value_name = "URL", value_parser = config::validate_string_as_http_url)]
+        registry: Option<String>,
+
+        /// Requests BLOBs by their plain digest, sha256:<hex>, as in the OCI distribution
+        /// specification, instead of sha256-<hex>, for registries that only accept the former.
+        #[arg(long)]
+        plain_digest: bool,
+
+        /// Aborts the download, before fetching any BLOB, if the model is larger than this,
+        /// e.g., 700M or 4GiB. Overrides the configured maximum download size.
+        #[arg(long, value_name = "SIZE", value_parser = config::parse_byte_size)]
+        max_size: Option<u64>,
+
+        /// Limits the download bandwidth to this many bytes per second, as curl's --limit-rate
+        /// does, e.g., 500k or 2M, where k and M are 1024 and 1024^2 as with curl, while
+        /// KB and MB are decimal. Overrides the configured max_bandwidth_bytes_per_sec.
+        #[arg(long, value_name = "RATE", value_parser = config::parse_rate)]
+        limit_rate: Option<u64>,
+
+        /// Fails the download of a BLOB that takes longer than this many seconds.
+        /// Overrides the configured BLOB timeout.
+        #[arg(long, value_name = "SECONDS", value_parser = config::parse_seconds)]
+        timeout_per_blob: Option<f64>,
+
+        /// DANGEROUS: saves BLOBs even if their content does not match their digest.
+        /// Only meant for debugging misbehaving registries; the integrity of the model
+        /// is NOT verified and the download is recorded as unverified in the history.
+        #[arg(long)]
+        no_verify_digest: bool,
+
+        /// Reads each BLOB back once saved and verifies its digest again, to catch
+        /// corruption on disk, at the cost of an extra read of every BLOB.
+        #[arg(long)]
+        verify_after: bool,
+
+        /// Leaves partially downloaded files on disk if a download fails or is interrupted,
+        /// and logs where, for debugging. Overrides the configured remove_downloaded_on_error.
+        #[arg(long)]
+        no_cleanup: bool,
+
+        /// Downloads even if the model is already up to date, or if the filesystem of the
+        /// models path has less free space or fewer free inodes than the download needs and
+        /// what should remain free.
+        #[arg(long)]
+        force: bool,
+
+        /// Aborts the download, before fetching any BLOB, unless this much space, e.g., 10GiB,
+        /// would remain free on the filesystem of the models path after it.
+        #[arg(long, value_name = "SIZE", value_parser = config::parse_byte_size)]
+        min_free_after: Option<u64>,
+
+        /// Writes the manifest of the model to this file exactly as the registry sent it,
+        /// before it is parsed, e.g., to reproduce a problem with parsing it. With several
+        /// models, the file holds the manifest of the last one.
+        #[arg(long, value_name = "PATH")]
+        raw_manifest_out: Option<PathBuf>,
+
+        /// Downloads without locking the models path, which otherwise keeps other ODIR
+        /// processes from downloading into it at the same time.
+        #[arg(long)]
+        no_lock: bool,
+
+        /// Refuses to download a model given without a tag or digest, instead of assuming
+        /// 'latest'.
+        #[arg(long)]
+        strict_tag: bool,
+
+        /// Gives each saved BLOB a deterministic modification time instead of the time it was
+        /// downloaded: the Unix epoch, or the Last-Modified time reported by the registry.
+        #[arg(long, value_enum, value_name = "MODE")]
+        preserve_mtime: Option<config::PreserveMtime>,
+
+        /// Retries a BLOB that the registry reports as missing (HTTP 404), or whose download
+        /// stalled, up to this many times, after a growing delay, e.g., while a new model is
+        /// still propagating.
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        retry_missing: u32,
+
+        /// Repairs a downloaded model by fetching only its BLOBs that are absent or corrupt,
+        /// according to the manifest already in the models directory.
+        #[arg(long, conflicts_with = "manifest_only")]
+        blobs_only: bool,
+
+        /// Fetches only the manifest of the model, leaving its BLOBs untouched.
+        #[arg(long)]
+        manifest_only: bool,
+
+        /// Prints the plan of each download as a line of JSON instead of downloading: the
+        /// resolved identifier, the registry, the manifest digest, and each BLOB with its
+        /// size and whether it is already present. Nothing is downloaded or changed.
+        #[arg(long, conflicts_with_all = ["blobs_only", "manifest_only", "checksum_file"])]
+        plan: bool,
+
+        /// Writes the SHA256 checksums of the manifest and BLOBs of each downloaded model to
+        /// this file, in the format of `sha256sum`, to verify the download again later with
+        /// `sha256sum -c`.
+        #[arg(long, value_name = "PATH")]
+        checksum_file: Option<PathBuf>,
+
+        /// The output format of the summary of each downloaded model.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
     },
 
     #[command(subcommand_help_heading = "Hugging Face Models")]
@@ -97,6 +437,35 @@ enum Commands {
         /// The number of models to retrieve per page.
         #[arg(long, default_value_t = 25)]
         page_size: u32,
+
+        /// The order of the models: trending first, or most recently modified first.
+        #[arg(long, value_enum, default_value_t = HfModelSort::Trending)]
+        sort: HfModelSort,
+
+        /// Only lists models modified at or after this date, or date and time in UTC,
+        /// e.g., 2026-02-20 or 2026-02-20T09:30:00Z.
+        #[arg(long, value_name = "DATE", value_parser = downloader::hf_downloader::parse_since_date)]
+        since: Option<String>,
+
+        /// The kind of Hugging Face repository, e.g., `dataset` for GGUF files published
+        /// in a dataset repository.
+        #[arg(long, value_enum, default_value_t = HfRepoType::Model)]
+        repo_type: HfRepoType,
+
+        /// Lists the models of this app instead of Ollama, e.g., llama.cpp. Does not apply
+        /// to datasets.
+        #[arg(long, value_name = "NAME", value_parser = downloader::hf_downloader::parse_app_name)]
+        app: Option<String>,
+
+        /// Also lists gated models, which needs a Hugging Face token in the `Authorization`
+        /// header of the `extra_headers` setting.
+        #[arg(long)]
+        include_gated: bool,
+
+        /// Picks a model from the list with a numbered menu and downloads it. Without a
+        /// terminal, the list is printed instead.
+        #[arg(long)]
+        interactive: bool,
     },
 
     #[command(subcommand_help_heading = "Hugging Face Models")]
@@ -104,16 +473,405 @@ enum Commands {
     ///
     /// Note that these are NOT the same as Hugging Face model tags.
     HfListTags {
-        /// The name of the model to list tags for, e.g., bartowski/Llama-3.2-1B-Instruct-GGUF.
-        model_identifier: String,
+        /// The names of the models to list tags for, e.g., bartowski/Llama-3.2-1B-Instruct-GGUF.
+        #[arg(required = true, value_name = "MODEL_IDENTIFIER")]
+        model_identifiers: Vec<String>,
+
+        /// Also lists quantisations whose model is split into several GGUF files, each as
+        /// a single tag. These are left out by default.
+        #[arg(long)]
+        include_split: bool,
+
+        /// The kind of Hugging Face repository, e.g., `dataset` for GGUF files published
+        /// in a dataset repository.
+        #[arg(long, value_enum, default_value_t = HfRepoType::Model)]
+        repo_type: HfRepoType,
+
+        /// Continues with the other models if a model does not exist or its tags cannot be
+        /// fetched, and only fails if the tags of no model could be listed.
+        #[arg(long)]
+        keep_going: bool,
     },
 
     #[command(subcommand_help_heading = "Hugging Face Models")]
     /// Downloads a specified Hugging Face model.
     HfModelDownload {
-        /// The name of the specific Hugging Face model to download, specified as
-        /// {username}/{repository}:{quantisation}, e.g., bartowski/Llama-3.2-1B-Instruct-GGUF:Q4_K_M.
-        user_repo_quant: String,
+        /// The names of the specific Hugging Face models to download, specified as
+        /// {username}/{repository}:{quantisation}, e.g., bartowski/Llama-3.2-1B-Instruct-GGUF:Q4_K_M,
+        /// or pinned to a manifest digest as {username}/{repository}@sha256:<hex>.
+        /// The quantisation may be left out if `--prefer` or `--select-quant` is given.
+        #[arg(required_unless_present = "from_file", value_name = "USER_REPO_QUANT")]
+        user_repo_quants: Vec<String>,
+
+        /// Also downloads the models listed in this file, one per line, with `#` starting a
+        /// comment, or in the standard input if the file is `-`.
+        #[arg(long, value_name = "PATH")]
+        from_file: Option<PathBuf>,
+
+        /// Chooses the quantisation of a model given without one: `smallest` or `largest`
+        /// by file size, or a named quantisation or family, e.g., Q4_K_M or q4.
+        #[arg(long, value_name = "PREFERENCE", value_parser = downloader::hf_downloader::parse_quant_preference)]
+        prefer: Option<downloader::hf_downloader::QuantPreference>,
+
+        /// Selects the quantisation of a model given without one: `interactive` picks it from
+        /// a menu of the available quantisations with their sizes. Without a terminal, the
+        /// download fails, listing the available quantisations.
+        #[arg(long, value_enum, value_name = "MODE", conflicts_with = "prefer")]
+        select_quant: Option<QuantSelection>,
+
+        /// The kind of Hugging Face repository, e.g., `dataset` for GGUF files published
+        /// in a dataset repository.
+        #[arg(long, value_enum, default_value_t = HfRepoType::Model)]
+        repo_type: HfRepoType,
+
+        /// Aborts the download, before fetching any BLOB, if the model is larger than this,
+        /// e.g., 700M or 4GiB. Overrides the configured maximum download size.
+        #[arg(long, value_name = "SIZE", value_parser = config::parse_byte_size)]
+        max_size: Option<u64>,
+
+        /// Limits the download bandwidth to this many bytes per second, as curl's --limit-rate
+        /// does, e.g., 500k or 2M, where k and M are 1024 and 1024^2 as with curl, while
+        /// KB and MB are decimal. Overrides the configured max_bandwidth_bytes_per_sec.
+        #[arg(long, value_name = "RATE", value_parser = config::parse_rate)]
+        limit_rate: Option<u64>,
+
+        /// Fails the download of a BLOB that takes longer than this many seconds.
+        /// Overrides the configured BLOB timeout.
+        #[arg(long, value_name = "SECONDS", value_parser = config::parse_seconds)]
+        timeout_per_blob: Option<f64>,
+
+        /// DANGEROUS: saves BLOBs even if their content does not match their digest.
+        /// Only meant for debugging misbehaving registries; the integrity of the model
+        /// is NOT verified and the download is recorded as unverified in the history.
+        #[arg(long)]
+        no_verify_digest: bool,
+
+        /// Reads each BLOB back once saved and verifies its digest again, to catch
+        /// corruption on disk, at the cost of an extra read of every BLOB.
+        #[arg(long)]
+        verify_after: bool,
+
+        /// Leaves partially downloaded files on disk if a download fails or is interrupted,
+        /// and logs where, for debugging. Overrides the configured remove_downloaded_on_error.
+        #[arg(long)]
+        no_cleanup: bool,
+
+        /// Downloads even if the model is already up to date, or if the filesystem of the
+        /// models path has less free space or fewer free inodes than the download needs and
+        /// what should remain free.
+        #[arg(long)]
+        force: bool,
+
+        /// Aborts the download, before fetching any BLOB, unless this much space, e.g., 10GiB,
+        /// would remain free on the filesystem of the models path after it.
+        #[arg(long, value_name = "SIZE", value_parser = config::parse_byte_size)]
+        min_free_after: Option<u64>,
+
+        /// Writes the manifest of the model to this file exactly as the registry sent it,
+        /// before it is parsed, e.g., to reproduce a problem with parsing it. With several
+        /// models, the file holds the manifest of the last one.
+        #[arg(long, value_name = "PATH")]
+        raw_manifest_out: Option<PathBuf>,
+
+        /// Downloads without locking the models path, which otherwise keeps other ODIR
+        /// processes from downloading into it at the same time.
+        #[arg(long)]
+        no_lock: bool,
+
+        /// Refuses to download a model given without a tag or digest, instead of assuming
+        /// 'latest'.
+        #[arg(long)]
+        strict_tag: bool,
+
+        /// Gives each saved BLOB a deterministic modification time instead of the time it was
+        /// downloaded: the Unix epoch, or the Last-Modified time reported by the registry.
+        #[arg(long, value_enum, value_name = "MODE")]
+        preserve_mtime: Option<config::PreserveMtime>,
+
+        /// Retries a BLOB that the registry reports as missing (HTTP 404), or whose download
+        /// stalled, up to this many times, after a growing delay, e.g., while a new model is
+        /// still propagating.
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        retry_missing: u32,
+
+        /// Prints the plan of each download as a line of JSON instead of downloading: the
+        /// resolved identifier, the registry, the manifest digest, and each BLOB with its
+        /// size and whether it is already present. Nothing is downloaded or changed.
+        #[arg(long, conflicts_with = "checksum_file")]
+        plan: bool,
+
+        /// Writes the SHA256 checksums of the manifest and BLOBs of each downloaded model to
+        /// this file, in the format of `sha256sum`, to verify the download again later with
+        /// `sha256sum -c`.
+        #[arg(long, value_name = "PATH")]
+        checksum_file: Option<PathBuf>,
+
+        /// The output format of the summary of each downloaded model.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Bundles")]
+    /// Downloads a model into a fresh directory, independent of any Ollama installation.
+    ///
+    /// The directory gets the `blobs` and `manifests` layout of an Ollama models directory,
+    /// along with an `index.json` of the bundled models, so that it can be copied into the
+    /// models directory of Ollama on another machine.
+    Bundle {
+        /// The model and tag to bundle, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        model_tag: String,
+
+        /// The directory to create the bundle in. It must not exist or be empty.
+        output_dir: String,
+
+        /// Aborts the download, before fetching any BLOB, if the model is larger than this,
+        /// e.g., 700M or 4GiB. Overrides the configured maximum download size.
+        #[arg(long, value_name = "SIZE", value_parser = config::parse_byte_size)]
+        max_size: Option<u64>,
+
+        /// Limits the download bandwidth to this many bytes per second, as curl's --limit-rate
+        /// does, e.g., 500k or 2M, where k and M are 1024 and 1024^2 as with curl, while
+        /// KB and MB are decimal. Overrides the configured max_bandwidth_bytes_per_sec.
+        #[arg(long, value_name = "RATE", value_parser = config::parse_rate)]
+        limit_rate: Option<u64>,
+
+        /// Gives each bundled BLOB a deterministic modification time instead of the time it
+        /// was downloaded: the Unix epoch, or the Last-Modified time reported by the registry.
+        #[arg(long, value_enum, value_name = "MODE")]
+        preserve_mtime: Option<config::PreserveMtime>,
+    },
+
+    #[command(subcommand_help_heading = "Bundles")]
+    /// Exports a downloaded model as a tar archive of its manifest and BLOBs.
+    ///
+    /// The archive keeps the `blobs` and `manifests` layout of the models directory,
+    /// so that it can be imported on another machine without downloading the model again.
+    Export {
+        /// The model to export, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        model_tag: String,
+
+        /// The archive to create. It must not exist.
+        output: PathBuf,
+
+        /// Compresses the archive with gzip.
+        #[arg(long)]
+        compress: bool,
+    },
+
+    #[command(subcommand_help_heading = "Bundles")]
+    /// Imports a model archive created by `export` into the models path.
+    ///
+    /// The digest of every BLOB is verified as it is extracted, and the manifests are
+    /// only written once all BLOBs they reference are in place.
+    Import {
+        /// The archive to import, compressed with gzip or not.
+        archive: PathBuf,
+
+        /// Gives each imported BLOB a deterministic modification time instead of the time it
+        /// was imported: the Unix epoch, or the time recorded for it in the archive.
+        #[arg(long, value_enum, value_name = "MODE")]
+        preserve_mtime: Option<config::PreserveMtime>,
+
+        /// Replaces manifests that already exist in the models path with other content,
+        /// instead of failing.
+        #[arg(long)]
+        force: bool,
+    },
+
+    #[command(subcommand_help_heading = "Ollama Server")]
+    /// Checks whether a model is present in the Ollama server, without downloading it.
+    ///
+    /// Exits with code 0 if the model is present, and 1 otherwise.
+    CheckPresent {
+        /// The model and tag to check, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed for Ollama library models.
+        model_tag: String,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Rebuilds the manifest of a model whose BLOBs are already in the models path, e.g.,
+    /// after the manifests were lost, without downloading the BLOBs again. Every BLOB the
+    /// manifest references must be present and match its digest.
+    Relink {
+        /// The model and tag to relink, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Shows where an installed model is on disk: the path of its manifest and of each BLOB
+    /// it references.
+    Which {
+        /// The model and tag to locate, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
+
+        /// Looks for a Hugging Face model, which is otherwise assumed if the model is given
+        /// as {username}/{repository}.
+        #[arg(long)]
+        hf: bool,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Checks the BLOBs of an installed model against their digests, e.g., in a periodic job.
+    /// Exits with code 0 if all are intact, and 1 otherwise.
+    #[command(group(clap::ArgGroup::new("mode").required(true).args(["hash_only", "fix"])))]
+    Verify {
+        /// The model and tag to verify, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
+
+        /// Looks for a Hugging Face model, which is otherwise assumed if the model is given
+        /// as {username}/{repository}.
+        #[arg(long)]
+        hf: bool,
+
+        /// Only reads the BLOBs of the model from the models path and checks them against
+        /// their digests, without touching the network.
+        #[arg(long)]
+        hash_only: bool,
+
+        /// Downloads the BLOBs of the model that are absent or corrupt again, as
+        /// `model-download --blobs-only` does. Only for Ollama library models.
+        #[arg(long)]
+        fix: bool,
+
+        /// The number of threads that read the BLOBs with --hash-only, by default as many as
+        /// the machine can run in parallel.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), conflicts_with = "fix")]
+        jobs: Option<u16>,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Compares the local copy of a model with the current version in the registry, listing
+    /// the BLOBs that were added, removed or changed. Exits with code 0 if both are the same,
+    /// and 1 otherwise.
+    Compare {
+        /// The model and tag to compare, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Updates a model to the current version in the registry, downloading only the BLOBs
+    /// that changed, and then removes the BLOBs of the previous version that no model
+    /// references any longer.
+    Update {
+        /// The model and tag to update, specified as {model}:{tag} for Ollama library models,
+        /// e.g., llama3.1:8b, or as {username}/{repository}:{quantisation} for Hugging Face models.
+        /// If no tag is specified, 'latest' will be assumed.
+        model_tag: String,
+
+        /// Downloads without locking the models path, which otherwise keeps other ODIR
+        /// processes from downloading into it at the same time.
+        #[arg(long)]
+        no_lock: bool,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Shows the journal of models downloaded into the models path, oldest first.
+    History {
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Shows the most recent failed downloads, oldest first, as recorded when the
+    /// failure_log_entries setting is above 0.
+    Failures {
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Summarises the models path: installed models and tags, the space used by BLOBs,
+    /// BLOBs that no model references, and the largest models.
+    Stats {
+        /// The number of largest models to show.
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        top: usize,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+
+        /// Streams newline-delimited JSON instead: a line of type "model" for each installed
+        /// model as it is found, and a final line of type "summary".
+        #[arg(long, conflicts_with = "output")]
+        json_lines: bool,
+
+        /// The number of threads that stat the BLOBs, by default as many as the machine can
+        /// run in parallel.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+    },
+
+    #[command(subcommand_help_heading = "Local Models")]
+    /// Removes all but the newest tags of a model, by modification time, and then the BLOBs
+    /// that no remaining model references.
+    PruneTags {
+        /// The model without a tag, e.g., llama3.1 for an Ollama library model, or
+        /// hf.co/{username}/{repository} for a Hugging Face model.
+        model: String,
+
+        /// The number of newest tags to keep.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        keep: u32,
+
+        /// Only show what would be removed, without removing anything.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Prunes without locking the models path, which otherwise keeps ODIR processes from
+        /// downloading into it while tags are pruned.
+        #[arg(long)]
+        no_lock: bool,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Information")]
+    /// Checks that the Ollama registry, the Ollama library and the Hugging Face registry can
+    /// be reached with the current settings, before a large download.
+    ///
+    /// Each is requested once with the HTTP client used for downloads, reporting the HTTP
+    /// status, whether TLS succeeded, and the round-trip time. Exits with code 0 if all can
+    /// be reached, and 1 otherwise.
+    ConnectTest {
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+
+    #[command(subcommand_help_heading = "Information")]
+    /// Shows the version of ODIR with build metadata, useful for bug reports.
+    Version {
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
     },
 
     #[command(subcommand_help_heading = "Compatibility")]
@@ -124,6 +882,50 @@ enum Commands {
     },
 }
 
+/// Prints the value of a single setting: strings, numbers and booleans as they are, and
+/// sections as pretty-printed JSON. Exits if there is no such setting.
+///
+/// # Arguments
+/// * `settings` - The settings
+/// * `key` - The dotted path of the setting, e.g., ollama_library.models_path
+fn print_setting(settings: &AppSettings, key: &str) {
+    match settings.value_at(key) {
+        Some(serde_json::Value::String(value)) => println!("{}", value),
+        Some(value @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) => {
+            println!("{:#}", value)
+        }
+        Some(value) => println!("{}", value),
+        None => {
+            error!("There is no setting '{}'", key);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads a line typed by the user after a prompt. At the end of the input, e.g., if stdin
+/// is redirected from /dev/null, an empty line is returned, so that prompts fall back to
+/// their defaults. Exits if stdin cannot be read.
+///
+/// # Returns
+/// * `String` - The line, or an empty string at the end of the input
+fn read_input_line() -> String {
+    let _ = io::stdout().flush();
+
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) => {
+            // Finish the line of the prompt, since the user did not
+            println!();
+            String::new()
+        }
+        Ok(_) => input,
+        Err(e) => {
+            error!("Failed to read input: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Prompts the user for a string input with a default value.
 ///
 /// # Arguments
@@ -134,10 +936,7 @@ enum Commands {
 /// * `String` - The user's input or the default value
 fn prompt_string(prompt: &str, default: &str) -> String {
     print!("{} [{}]: ", prompt, default);
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    let input = read_input_line();
     let input = input.trim();
 
     if input.is_empty() {
@@ -156,10 +955,7 @@ fn prompt_string(prompt: &str, default: &str) -> String {
 /// * `Option<String>` - Some(input) if provided, None if empty
 fn prompt_optional_string(prompt: &str) -> Option<String> {
     print!("{} (press Enter to skip): ", prompt);
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    let input = read_input_line();
     let input = input.trim();
 
     if input.is_empty() {
@@ -169,6 +965,75 @@ fn prompt_optional_string(prompt: &str) -> Option<String> {
     }
 }
 
+/// Checks whether both stdin and stdout are terminals, so that the user can be prompted.
+///
+/// # Returns
+/// * `bool` - True if the user can be prompted
+fn is_interactive_terminal() -> bool {
+    use std::io::IsTerminal;
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Warns that an interactive picker was requested without a terminal to show it in.
+///
+/// # Arguments
+/// * `interactive` - Whether the picker was requested
+fn warn_if_not_interactive(interactive: bool) {
+    if interactive {
+        warn!("Not running in a terminal, printing the list instead of picking a model from it");
+    }
+}
+
+/// Lets the user pick a model from a numbered menu. Typing anything other than a number
+/// narrows the menu down to the models whose names contain it.
+///
+/// # Arguments
+/// * `models` - The models to pick from
+///
+/// # Returns
+/// * `Option<String>` - The picked model, or None if the user quit without picking one
+fn pick_model(models: &[String]) -> Option<String> {
+    if models.is_empty() {
+        println!("No models to pick from");
+        return None;
+    }
+
+    let mut candidates: Vec<&String> = models.iter().collect();
+    loop {
+        for (index, model) in candidates.iter().enumerate() {
+            println!("{:>4}. {}", index + 1, model);
+        }
+        print!("Enter a number to download, text to filter the list, or press Enter to quit: ");
+        let input = read_input_line();
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+
+        if let Ok(number) = input.parse::<usize>() {
+            match number
+                .checked_sub(1)
+                .and_then(|index| candidates.get(index))
+            {
+                Some(model) => return Some((*model).clone()),
+                None => println!("Please enter a number between 1 and {}.", candidates.len()),
+            }
+            continue;
+        }
+
+        let filter = input.to_lowercase();
+        let filtered: Vec<&String> = models
+            .iter()
+            .filter(|model| model.to_lowercase().contains(&filter))
+            .collect();
+        if filtered.is_empty() {
+            println!("No model matches '{}'.", input);
+        } else {
+            candidates = filtered;
+        }
+    }
+}
+
 /// Prompts the user for a boolean (yes/no) input with a default value.
 ///
 /// # Arguments
@@ -180,10 +1045,7 @@ fn prompt_optional_string(prompt: &str) -> Option<String> {
 fn prompt_bool(prompt: &str, default: bool) -> bool {
     let default_str = if default { "Y/n" } else { "y/N" };
     print!("{} [{}]: ", prompt, default_str);
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    let input = read_input_line();
     let input = input.trim().to_lowercase();
 
     if input.is_empty() {
@@ -193,7 +1055,7 @@ fn prompt_bool(prompt: &str, default: bool) -> bool {
     }
 }
 
-/// Prompts the user for a floating-point number with a default value.
+/// Prompts the user for a positive, finite, floating-point number with a default value.
 ///
 /// # Arguments
 /// * `prompt` - The prompt message to display
@@ -204,10 +1066,7 @@ fn prompt_bool(prompt: &str, default: bool) -> bool {
 fn prompt_f64(prompt: &str, default: f64) -> f64 {
     loop {
         print!("{} [{}]: ", prompt, default);
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        let input = read_input_line();
         let input = input.trim();
 
         if input.is_empty() {
@@ -215,6 +1074,64 @@ fn prompt_f64(prompt: &str, default: f64) -> f64 {
         }
 
         match input.parse::<f64>() {
+            Ok(value) if value > 0.0 && value.is_finite() => return value,
+            Ok(_) => {
+                println!("The number must be greater than zero. Please try again.");
+            }
+            Err(_) => {
+                println!("Invalid number. Please try again.");
+            }
+        }
+    }
+}
+
+/// Prompts the user for an optional duration in seconds, keeping the current value on Enter.
+///
+/// # Arguments
+/// * `prompt` - The prompt message to display
+/// * `unset` - What it means for the duration not to be set
+/// * `current` - The current value, if any
+///
+/// # Returns
+/// * `Option<f64>` - The user's input or the current value
+fn prompt_optional_seconds(prompt: &str, unset: &str, current: Option<f64>) -> Option<f64> {
+    if let Some(current_seconds) = current {
+        println!("{} (current: {} seconds)", prompt, current_seconds);
+    }
+    let input = if current.is_some() {
+        prompt_optional_string("  Enter new value in seconds or press Enter to keep current")
+    } else {
+        prompt_optional_string(&format!("{} in seconds (default: {})", prompt, unset))
+    };
+    match input.map(|value| config::parse_seconds(&value)) {
+        Some(Ok(seconds)) => Some(seconds),
+        Some(Err(e)) => {
+            println!("  Invalid duration: {}. Keeping the current value.", e);
+            current
+        }
+        None => current,
+    }
+}
+
+/// Prompts the user for a non-negative integer with a default value.
+///
+/// # Arguments
+/// * `prompt` - The prompt message to display
+/// * `default` - The default value if user presses Enter without input
+///
+/// # Returns
+/// * `u32` - The user's input or the default value
+fn prompt_u32(prompt: &str, default: u32) -> u32 {
+    loop {
+        print!("{} [{}]: ", prompt, default);
+        let input = read_input_line();
+        let input = input.trim();
+
+        if input.is_empty() {
+            return default;
+        }
+
+        match input.parse::<u32>() {
             Ok(value) => return value,
             Err(_) => {
                 println!("Invalid number. Please try again.");
@@ -295,8 +1212,575 @@ fn interactive_config(existing_settings: Option<AppSettings>) -> AppSettings {
         settings.ollama_library.timeout,
     );
 
-    println!("\n=== Configuration Complete ===\n");
-    settings
+    settings.ollama_library.pagination_retries = prompt_u32(
+        "Retries for failed Hugging Face pagination requests",
+        settings.ollama_library.pagination_retries,
+    );
+
+    let hf_manifest_host = prompt_string(
+        "Host directory for Hugging Face manifests",
+        &settings.ollama_library.hf_manifest_host,
+    );
+    match config::validate_manifest_host(&hf_manifest_host) {
+        Ok(()) => settings.ollama_library.hf_manifest_host = hf_manifest_host,
+        Err(e) => println!("  Invalid host: {}. Keeping the current value.", e),
+    }
+
+    // For the temporary directory, show current value or indicate the default location
+    let current_temp_dir = settings.ollama_library.temp_dir.clone();
+    if let Some(ref current_dir) = current_temp_dir {
+        println!("Temporary download directory (current: {})", current_dir);
+        settings.ollama_library.temp_dir =
+            prompt_optional_string("  Enter new directory or press Enter to keep current");
+        if settings.ollama_library.temp_dir.is_none() {
+            settings.ollama_library.temp_dir = Some(current_dir.clone());
+        }
+    } else {
+        settings.ollama_library.temp_dir = prompt_optional_string(
+            "Temporary download directory (default: .tmp inside the models path)",
+        );
+    }
+
+    // For the maximum download size, show current value or indicate that there is no limit
+    let current_max_size = settings.ollama_library.max_download_size;
+    let new_max_size = if let Some(current_limit) = current_max_size {
+        println!("Maximum download size (current: {} bytes)", current_limit);
+        prompt_optional_string("  Enter new size, e.g., 4GiB, or press Enter to keep current")
+    } else {
+        prompt_optional_string("Maximum download size, e.g., 4GiB (default: no limit)")
+    };
+    if let Some(value) = new_max_size {
+        match config::parse_byte_size(&value) {
+            Ok(limit) => settings.ollama_library.max_download_size = Some(limit),
+            Err(e) => println!("  Invalid size: {}. Keeping the current value.", e),
+        }
+    }
+
+    // For the BLOB timeout and the download deadline, show current values or their defaults
+    settings.ollama_library.blob_timeout = prompt_optional_seconds(
+        "Timeout for downloading a single BLOB",
+        "same as the timeout",
+        settings.ollama_library.blob_timeout,
+    );
+    settings.ollama_library.download_deadline = prompt_optional_seconds(
+        "Deadline for downloading all BLOBs of a model",
+        "no deadline",
+        settings.ollama_library.download_deadline,
+    );
+    settings.ollama_library.library_request_timeout = prompt_optional_seconds(
+        "Timeout for fetching the Ollama library models list",
+        "same as the timeout",
+        settings.ollama_library.library_request_timeout,
+    );
+
+    // For the maximum bandwidth, show current value or indicate that it is unlimited
+    let current_bandwidth = settings.ollama_library.max_bandwidth_bytes_per_sec;
+    let new_bandwidth = if current_bandwidth > 0 {
+        println!(
+            "Maximum download bandwidth (current: {} bytes per second)",
+            current_bandwidth
+        );
+        prompt_optional_string(
+            "  Enter new bandwidth per second, e.g., 5M, 0 for unlimited, or press Enter to keep current",
+        )
+    } else {
+        prompt_optional_string(
+            "Maximum download bandwidth per second, e.g., 5M (default: unlimited)",
+        )
+    };
+    if let Some(value) = new_bandwidth {
+        match config::parse_byte_size(&value) {
+            Ok(bandwidth) => settings.ollama_library.max_bandwidth_bytes_per_sec = bandwidth,
+            Err(e) => println!("  Invalid bandwidth: {}. Keeping the current value.", e),
+        }
+    }
+
+    // Stall detection is only active if both the stall timeout and the throughput floor are set
+    settings.ollama_library.stall_timeout_secs = prompt_optional_seconds(
+        "Time after which a BLOB download below the minimum throughput is aborted as stalled",
+        "stalls not detected",
+        settings.ollama_library.stall_timeout_secs,
+    );
+    let current_floor = settings.ollama_library.min_throughput_bytes_per_sec;
+    let new_floor = if current_floor > 0 {
+        println!(
+            "Minimum download throughput (current: {} bytes per second)",
+            current_floor
+        );
+        prompt_optional_string(
+            "  Enter new throughput per second, e.g., 10K, 0 for none, or press Enter to keep current",
+        )
+    } else {
+        prompt_optional_string("Minimum download throughput per second, e.g., 10K (default: none)")
+    };
+    if let Some(value) = new_floor {
+        match config::parse_byte_size(&value) {
+            Ok(floor) => settings.ollama_library.min_throughput_bytes_per_sec = floor,
+            Err(e) => println!("  Invalid throughput: {}. Keeping the current value.", e),
+        }
+    }
+
+    let min_free_inodes = prompt_u32(
+        "Inodes that must remain free after a download, 0 to not check",
+        u32::try_from(settings.ollama_library.min_free_inodes).unwrap_or(u32::MAX),
+    );
+    settings.ollama_library.min_free_inodes = u64::from(min_free_inodes);
+
+    settings.ollama_library.failure_log_entries = prompt_u32(
+        "Recent failed downloads to record, 0 to not record them",
+        settings.ollama_library.failure_log_entries,
+    );
+
+    let progress_refresh_hz = prompt_u32(
+        "Progress bar redraws per second",
+        u32::from(settings.ollama_library.progress_refresh_hz),
+    );
+    match u8::try_from(progress_refresh_hz) {
+        Ok(hz) if hz > 0 => settings.ollama_library.progress_refresh_hz = hz,
+        _ => println!("  Invalid rate: it must be between 1 and 255. Keeping the current value."),
+    }
+
+    // For the progress bar template, show current value or indicate the default template
+    let current_template = settings.ollama_library.progress_template.clone();
+    let new_template = if let Some(ref template) = current_template {
+        println!("Progress bar template (current: {})", template);
+        prompt_optional_string(
+            "  Enter new template, 'none' to unset, or press Enter to keep current",
+        )
+    } else {
+        prompt_optional_string(
+            "Progress bar template, e.g., {msg} {wide_bar} {percent}% (default: built-in)",
+        )
+    };
+    if let Some(value) = new_template {
+        if value.eq_ignore_ascii_case("none") {
+            settings.ollama_library.progress_template = None;
+        } else {
+            match validate_progress_template(&value) {
+                Ok(()) => settings.ollama_library.progress_template = Some(value),
+                Err(e) => println!("  Invalid template: {}. Keeping the current value.", e),
+            }
+        }
+    }
+
+    // For the local address, show current value or indicate that the system chooses
+    let current_local_address = settings.ollama_library.local_address;
+    let new_local_address = if let Some(current_address) = current_local_address {
+        println!(
+            "Local address to send requests from (current: {})",
+            current_address
+        );
+        prompt_optional_string(
+            "  Enter new IP address, 'none' to unset, or press Enter to keep current",
+        )
+    } else {
+        prompt_optional_string(
+            "Local address to send requests from (default: chosen by the system)",
+        )
+    };
+    if let Some(value) = new_local_address {
+        if value.eq_ignore_ascii_case("none") {
+            settings.ollama_library.local_address = None;
+        } else {
+            match value.parse::<std::net::IpAddr>() {
+                Ok(address) => settings.ollama_library.local_address = Some(address),
+                Err(e) => println!("  Invalid IP address: {}. Keeping the current value.", e),
+            }
+        }
+    }
+
+    println!("\n=== Configuration Complete ===\n");
+    settings
+}
+
+/// Gets the path to the settings file, or to that of a profile, exiting with an error if
+/// the config directory cannot be determined or created, e.g., without a home directory.
+///
+/// # Arguments
+/// * `profile` - The name of the profile, if any
+///
+/// # Returns
+/// * `PathBuf` - Path to the settings file
+fn settings_file_path(profile: Option<&str>) -> PathBuf {
+    config::get_settings_file_path(profile).unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Loads the settings, or creates the default settings file, for a command that makes requests.
+///
+/// # Arguments
+/// * `global` - The options that override the settings for this run
+///
+/// # Returns
+/// * `io::Result<AppSettings>` - The settings with the per-run overrides applied, or an error
+fn load_settings(global: &GlobalOptions) -> io::Result<AppSettings> {
+    let settings_file = config::get_settings_file_path(global.profile.as_deref())?;
+    let settings = AppSettings::load_or_create_default(&settings_file)?
+        .with_allow_insecure(global.allow_insecure);
+    settings
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    enable_failure_log(settings.ollama_library.failure_log_entries);
+    emit_event(
+        "config_loaded",
+        serde_json::json!({
+            "settings_file": settings_file,
+            "models_path": settings.ollama_library.models_path,
+        }),
+    );
+    Ok(settings)
+}
+
+/// Checks whether a model is to be looked up on Hugging Face, which it is if requested or if
+/// it is identified as {username}/{repository}.
+///
+/// # Arguments
+/// * `model_tag` - The model identifier given on the command line
+/// * `hf` - Whether Hugging Face was requested, e.g., with `--hf`
+///
+/// # Returns
+/// * `bool` - True if the model is on Hugging Face
+fn is_hf_identifier(model_tag: &str, hf: bool) -> bool {
+    hf || model_tag.contains('/')
+}
+
+/// Gets the options of this run that apply to every command that makes requests.
+///
+/// # Arguments
+/// * `global` - The global options of the command line
+///
+/// # Returns
+/// * `RunOptions` - The options of this run
+fn run_options(global: &GlobalOptions) -> RunOptions {
+    let address_family = if global.ipv4 {
+        Some(config::AddressFamily::Ipv4)
+    } else if global.ipv6 {
+        Some(config::AddressFamily::Ipv6)
+    } else {
+        None
+    };
+    RunOptions::default()
+        .with_allow_insecure_http(global.allow_insecure_http)
+        .with_resolve_overrides(global.resolve.clone())
+        .with_progress_json(global.progress_json || global.ndjson)
+        .with_address_family(address_family)
+}
+
+/// Reports a failed command and exits with the exit code of the error.
+///
+/// With JSON output, the error is printed to stderr as a JSON envelope that tools can
+/// parse, instead of being logged as text.
+///
+/// # Arguments
+/// * `output` - The output format of the command
+/// * `context` - What the command was doing when it failed, for the text log
+/// * `error` - The error that made the command fail
+fn exit_with_error(output: OutputFormat, context: &str, error: DownloaderError) -> ! {
+    match output {
+        OutputFormat::Json => eprintln!("{}", error.to_json()),
+        OutputFormat::Text => error!("{}: {}", context, error),
+    }
+    std::process::exit(error.exit_code());
+}
+
+/// Prints the comparison of the local copy of a model with the registry, one line for each
+/// BLOB that differs: `+` if added, `-` if removed and `~` if changed.
+///
+/// # Arguments
+/// * `comparison` - The comparison to print
+fn print_comparison(comparison: &downloader::model_downloader::ModelComparison) {
+    use downloader::utils::format_byte_size;
+    if comparison.is_up_to_date() {
+        println!("Model {} is up to date", comparison.identifier);
+        return;
+    }
+    if comparison.local_present {
+        println!("Model {} differs from the registry:", comparison.identifier);
+    } else {
+        println!("Model {} is not present locally:", comparison.identifier);
+    }
+    for blob in &comparison.diff.added {
+        println!(
+            "  + {} {} ({})",
+            blob.media_type,
+            blob.digest,
+            format_byte_size(blob.size)
+        );
+    }
+    for blob in &comparison.diff.removed {
+        println!(
+            "  - {} {} ({})",
+            blob.media_type,
+            blob.digest,
+            format_byte_size(blob.size)
+        );
+    }
+    for change in &comparison.diff.changed {
+        println!(
+            "  ~ {} {} -> {} ({})",
+            change.media_type,
+            change.local.digest,
+            change.remote.digest,
+            format_byte_size(change.remote.size)
+        );
+    }
+}
+
+/// Prints the tags listed for a batch of models, followed by a summary of the models whose
+/// tags could not be listed, and exits with a non-zero code if the batch failed.
+///
+/// # Arguments
+/// * `report` - The outcome of the batch
+/// * `keep_going` - Whether the batch continued past failures
+fn finish_tag_listing(report: &downloader::batch::TagListingReport, keep_going: bool) {
+    let single = report.listed.len() + report.failed.len() + report.skipped.len() == 1;
+    for (model_identifier, tags) in &report.listed {
+        if single {
+            println!("Model tags: ({} tags): {:?}", tags.len(), tags);
+        } else {
+            println!("{}: ({} tags): {:?}", model_identifier, tags.len(), tags);
+        }
+    }
+    if !single && (!report.failed.is_empty() || !report.skipped.is_empty()) {
+        println!("\n{}", report.failure_summary());
+    }
+
+    let exit_code = report.exit_code(keep_going);
+    if exit_code != 0 && !signal_handler::is_interrupted() {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Gets the models to download from the command line, followed by those listed in a file,
+/// exiting if the list cannot be read or no model is given at all.
+///
+/// # Arguments
+/// * `identifiers` - The models given on the command line
+/// * `from_file` - The file listing more models, or `-` for the standard input
+/// * `output` - The output format of the command
+///
+/// # Returns
+/// * `Vec<String>` - The models to download, in order
+fn batch_identifiers(
+    mut identifiers: Vec<String>,
+    from_file: Option<PathBuf>,
+    output: OutputFormat,
+) -> Vec<String> {
+    if let Some(path) = from_file {
+        match downloader::batch::read_identifier_list(&path) {
+            Ok(listed) => identifiers.extend(listed),
+            Err(e) => exit_with_error(output, "Failed to read the list of models", e),
+        }
+    }
+    if identifiers.is_empty() {
+        exit_with_error(
+            output,
+            "Nothing to download",
+            DownloaderError::InvalidIdentifier("no models were given".to_string()),
+        );
+    }
+    identifiers
+}
+
+/// Chooses the quantisation of a Hugging Face model given without one, if a preference
+/// or a way of selecting it is given.
+///
+/// # Arguments
+/// * `downloader` - The Hugging Face downloader
+/// * `prefer` - The preferred quantisation, if any
+/// * `select_quant` - The way of selecting the quantisation, if any
+/// * `user_repo_quant` - The model identifier, as given
+///
+/// # Returns
+/// * `Result<String>` - The model identifier with the chosen quantisation, or as given
+///   without a preference
+fn preferred_quant(
+    downloader: &HuggingFaceModelDownloader,
+    prefer: Option<&downloader::hf_downloader::QuantPreference>,
+    select_quant: Option<QuantSelection>,
+    user_repo_quant: &str,
+) -> downloader::model_downloader::Result<String> {
+    let option = match (prefer, select_quant) {
+        (Some(_), _) => "--prefer",
+        (None, Some(_)) => "--select-quant",
+        (None, None) => return Ok(user_repo_quant.to_string()),
+    };
+    if user_repo_quant.contains([':', '@']) {
+        return Err(DownloaderError::InvalidIdentifier(format!(
+            "{} already specifies a quantisation or digest, which cannot be combined with {}",
+            user_repo_quant, option
+        )));
+    }
+    match prefer {
+        Some(preference) => downloader.select_quant(user_repo_quant, preference),
+        None => pick_quant(downloader, user_repo_quant),
+    }
+}
+
+/// Lets the user pick the quantisation of a Hugging Face model from a numbered menu of the
+/// available quantisations with their sizes.
+///
+/// # Arguments
+/// * `downloader` - The Hugging Face downloader
+/// * `user_repo` - The model identifier, specified as {username}/{repository}
+///
+/// # Returns
+/// * `Result<String>` - The model identifier with the picked quantisation, or an error if
+///   there is no terminal to pick in or the user quit without picking one
+fn pick_quant(
+    downloader: &HuggingFaceModelDownloader,
+    user_repo: &str,
+) -> downloader::model_downloader::Result<String> {
+    let quants = downloader.list_quantisations(user_repo)?;
+    if !is_interactive_terminal() {
+        let available: Vec<&str> = quants.iter().map(|(quant, _)| quant.as_str()).collect();
+        return Err(DownloaderError::InvalidIdentifier(format!(
+            "{} does not specify a quantisation, and without a terminal none can be picked. Available quantisations: {}",
+            user_repo,
+            available.join(", ")
+        )));
+    }
+
+    let labels: Vec<String> = quants
+        .iter()
+        .map(|(quant, size)| {
+            let size = size.map_or_else(
+                || "unknown size".to_string(),
+                downloader::utils::format_byte_size,
+            );
+            format!("{} ({})", quant, size)
+        })
+        .collect();
+    println!("Quantisations of {}:", user_repo);
+    let index = pick_model(&labels)
+        .and_then(|picked| labels.iter().position(|label| *label == picked))
+        .ok_or_else(|| {
+            DownloaderError::Other(format!("No quantisation of {} was picked", user_repo))
+        })?;
+    Ok(format!("{}:{}", user_repo, quants[index].0))
+}
+
+/// Prints the plan of the download of each model as a line of JSON, reporting failures as
+/// JSON error envelopes on stderr, and exits with the code of the first failure, if any.
+///
+/// # Arguments
+/// * `identifiers` - The models to plan the downloads of
+/// * `plan` - Plans the download of a model
+fn print_plans(
+    identifiers: &[String],
+    mut plan: impl FnMut(&str) -> downloader::model_downloader::Result<DownloadPlan>,
+) {
+    let mut exit_code = 0;
+    for identifier in identifiers {
+        if signal_handler::is_interrupted() {
+            break;
+        }
+        match plan(identifier).and_then(|plan| {
+            serde_json::to_string(&plan).map_err(|e| {
+                DownloaderError::Other(format!("Failed to serialize download plan: {}", e))
+            })
+        }) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("{}", e.to_json());
+                if exit_code == 0 {
+                    exit_code = e.exit_code();
+                }
+            }
+        }
+    }
+    signal_handler::set_cleanup_done();
+    if exit_code != 0 && !signal_handler::is_interrupted() {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Records the checksums of a downloaded model, for its checksum file.
+///
+/// # Arguments
+/// * `downloader` - The downloader that downloaded the model
+/// * `models_path` - The configured models path
+/// * `summary` - The summary of the download
+/// * `checksums` - The checksum lines recorded so far, to add those of the model to
+///
+/// # Returns
+/// * `Result<()>` - Success, or an error if the manifest of the model cannot be read
+fn record_checksums(
+    downloader: &dyn ModelDownloader,
+    models_path: &str,
+    summary: &downloader::model_downloader::DownloadSummary,
+    checksums: &mut Vec<String>,
+) -> downloader::model_downloader::Result<()> {
+    let models_root = downloader::utils::expand_models_path(models_path)?;
+    let manifest_path = downloader.manifest_path(&summary.identifier)?;
+    checksums.extend(downloader::checksums::model_checksums(
+        &models_root,
+        &manifest_path,
+    )?);
+    Ok(())
+}
+
+/// Writes the checksums of the downloaded models to the checksum file, if one was asked for,
+/// exiting with an error if it cannot be written.
+///
+/// # Arguments
+/// * `checksum_file` - The path of the checksum file, if any
+/// * `checksums` - The checksum lines of the downloaded models
+/// * `output` - The output format of errors
+fn finish_checksums(checksum_file: Option<&Path>, checksums: &[String], output: OutputFormat) {
+    if let Some(path) = checksum_file
+        && let Err(e) = downloader::checksums::write_checksum_file(path, checksums)
+    {
+        exit_with_error(output, "Failed to write checksum file", e);
+    }
+}
+
+/// Reports the outcome of a batch of downloads and exits with a code reflecting it.
+///
+/// A single download is reported with a single summary line, while a batch of several is
+/// summarised in a table marking each model as downloaded, failed or skipped. With JSON
+/// output, the summary of each downloaded model is printed as a line of JSON instead, and
+/// each failure as a JSON error envelope on stderr.
+///
+/// # Arguments
+/// * `report` - The outcome of the batch
+/// * `kind` - The kind of models downloaded, e.g., "Model" or "HuggingFace model"
+/// * `output` - The output format of the summary
+fn finish_batch(report: &downloader::batch::BatchReport, kind: &str, output: OutputFormat) {
+    match output {
+        OutputFormat::Json => {
+            for (_, result) in &report.results {
+                match result {
+                    Ok(summary) => match serde_json::to_string(summary) {
+                        Ok(json) => println!("{}", json),
+                        Err(e) => error!("Failed to serialize download summary: {}", e),
+                    },
+                    Err(e) => eprintln!("{}", e.to_json()),
+                }
+            }
+        }
+        OutputFormat::Text => {
+            // A model that was already up to date has been reported as such by its download
+            if let [(_, Ok(summary))] = report.results.as_slice()
+                && report.skipped.is_empty()
+            {
+                if !summary.up_to_date {
+                    println!("{} downloaded: {}", kind, summary.line());
+                }
+            } else if report.results.len() + report.skipped.len() > 1 {
+                println!("\nDownload summary:\n{}", report.summary());
+            }
+        }
+    }
+
+    // An interrupted batch exits from the signal handler once cleanup is done
+    let exit_code = report.exit_code();
+    signal_handler::set_cleanup_done();
+    if exit_code != 0 && !signal_handler::is_interrupted() {
+        std::process::exit(exit_code);
+    }
 }
 
 /// The main entry point for the Ollama Downloader in Rust (ODIR) command-line application.
@@ -313,46 +1797,54 @@ fn main() {
         "Configuration loaded: log_level={:?}, user_agent={}, settings_file={:?}",
         config.log_level,
         config::get_user_agent(),
-        config::get_settings_file_path()
+        config::get_config_dir()
+            .map(|dir| dir.join("settings.json"))
+            .ok()
     );
 
     // Install signal handlers for graceful shutdown
     signal_handler::install_signal_handlers();
 
     let cli = Cli::parse();
+    if cli.global.ndjson {
+        enable_events();
+    }
 
-    let requires_interrupt_confirmation = matches!(
-        &cli.command,
-        Commands::ListModels { .. }
-            | Commands::ListTags { .. }
-            | Commands::ModelDownload { .. }
-            | Commands::HfListModels { .. }
-            | Commands::HfListTags { .. }
-            | Commands::HfModelDownload { .. }
-    );
+    let requires_interrupt_confirmation = !cli.global.no_confirm_interrupt
+        && matches!(
+            &cli.command,
+            Commands::ListModels { .. }
+                | Commands::ListTags { .. }
+                | Commands::ModelDownload { .. }
+                | Commands::HfListModels { .. }
+                | Commands::HfListTags { .. }
+                | Commands::HfModelDownload { .. }
+                | Commands::Bundle { .. }
+                | Commands::Update { .. }
+        );
     signal_handler::set_confirmation_required(requires_interrupt_confirmation);
 
     match cli.command {
-        Commands::ShowConfig => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match serde_json::to_string_pretty(&settings) {
-                    Ok(json) => {
-                        println!("{}", json);
-                        info!(
-                            "Settings loaded from {:?}",
-                            config::get_settings_file_path()
-                        );
-                    }
-                    Err(e) => {
-                        error!("Failed to serialize settings: {}", e);
-                        std::process::exit(1);
-                    }
+        Commands::ShowConfig { key } => {
+            let settings_file = settings_file_path(cli.global.profile.as_deref());
+            match AppSettings::load_or_create_default(&settings_file) {
+                Ok(settings) => match key {
+                    Some(key) => print_setting(&settings, &key),
+                    None => match serde_json::to_string_pretty(&settings) {
+                        Ok(json) => {
+                            println!("{}", json);
+                            info!("Settings loaded from {:?}", settings_file);
+                        }
+                        Err(e) => {
+                            error!("Failed to serialize settings: {}", e);
+                            std::process::exit(1);
+                        }
+                    },
                 },
                 Err(e) => {
                     error!(
                         "Failed to load or create settings file '{:?}': {}",
-                        config::get_settings_file_path(),
-                        e
+                        settings_file, e
                     );
                     // Provide helpful guidance to the user
                     if e.kind() == io::ErrorKind::InvalidData {
@@ -365,12 +1857,49 @@ fn main() {
                 }
             }
         }
-        Commands::EditConfig { config_file } => {
+        Commands::EditConfig {
+            config_file,
+            changes,
+        } => {
             // Determine config file path
             let config_path = config_file
                 .as_ref()
                 .map(PathBuf::from)
-                .unwrap_or_else(config::get_settings_file_path);
+                .unwrap_or_else(|| settings_file_path(cli.global.profile.as_deref()));
+
+            // Change only the given settings, without asking, if any are given
+            if !changes.is_empty() {
+                let mut settings = if config_path.exists() {
+                    AppSettings::load_settings(&config_path).unwrap_or_else(|e| {
+                        error!(
+                            "Failed to load settings from '{}', not changing them: {}",
+                            config_path.display(),
+                            e
+                        );
+                        std::process::exit(1);
+                    })
+                } else {
+                    AppSettings::default()
+                };
+                changes.apply(&mut settings);
+                if let Err(e) = settings.validate() {
+                    error!("Not saving invalid settings: {}", e);
+                    std::process::exit(1);
+                }
+                if let Err(e) = settings.save_settings(&config_path) {
+                    error!(
+                        "Failed to save settings to '{}': {}",
+                        config_path.display(),
+                        e
+                    );
+                    std::process::exit(1);
+                }
+                println!(
+                    "✓ Settings saved successfully to: {}",
+                    config_path.display()
+                );
+                return;
+            }
 
             // Try to load existing settings from the config file
             let existing_settings = if config_path.exists() {
@@ -429,11 +1958,29 @@ fn main() {
                 }
             }
         }
-        Commands::ListModels { page, page_size } => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match OllamaModelDownloader::new(settings) {
+        Commands::ListModels {
+            page,
+            page_size,
+            registry,
+            interactive,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                match OllamaModelDownloader::new(
+                    settings.with_library_base_url(registry),
+                    run_options(&cli.global),
+                ) {
                     Ok(downloader) => match downloader.list_available_models(page, page_size) {
+                        Ok(models) if interactive && is_interactive_terminal() => {
+                            if let Some(model) = pick_model(&models) {
+                                let report =
+                                    downloader::batch::download_batch(&[model], |model_tag| {
+                                        downloader.download_model(model_tag)
+                                    });
+                                finish_batch(&report, "Model", OutputFormat::Text);
+                            }
+                        }
                         Ok(models) => {
+                            warn_if_not_interactive(interactive);
                             if let (Some(p), Some(_ps)) = (page, page_size) {
                                 println!(
                                     "Model identifiers: ({}, page {}): {:?}",
@@ -454,69 +2001,150 @@ fn main() {
                         error!("Failed to create Ollama downloader: {}", e);
                         std::process::exit(1);
                     }
-                },
-                Err(e) => {
-                    error!("Failed to load settings: {}", e);
-                    std::process::exit(1);
                 }
             }
-        }
-        Commands::ListTags { model_identifier } => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match OllamaModelDownloader::new(settings) {
-                    Ok(downloader) => match downloader.list_model_tags(&model_identifier) {
-                        Ok(tags) => {
-                            println!("Model tags: ({} tags): {:?}", tags.len(), tags);
-                        }
-                        Err(e) => {
-                            error!("Error listing tags for model '{}': {}", model_identifier, e);
-                            std::process::exit(1);
-                        }
-                    },
-                    Err(e) => {
-                        error!("Failed to create Ollama downloader: {}", e);
-                        std::process::exit(1);
-                    }
-                },
-                Err(e) => {
-                    error!("Failed to load settings: {}", e);
-                    std::process::exit(1);
-                }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
             }
-        }
-        Commands::ModelDownload { model_tag } => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match OllamaModelDownloader::new(settings) {
-                    Ok(downloader) => match downloader.download_model(&model_tag) {
-                        Ok(_) => {
-                            println!("Model {} download completed successfully", model_tag);
-                            signal_handler::set_cleanup_done();
-                        }
-                        Err(e) => {
-                            error!("Error downloading model '{}': {}", model_tag, e);
-                            if !signal_handler::is_interrupted() {
-                                std::process::exit(1);
-                            }
-                            signal_handler::set_cleanup_done();
-                        }
-                    },
+        },
+        Commands::ListTags {
+            model_identifiers,
+            registry,
+            keep_going,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                match OllamaModelDownloader::new(
+                    settings.with_library_base_url(registry),
+                    run_options(&cli.global),
+                ) {
+                    Ok(downloader) => {
+                        let report = downloader::batch::list_tags_batch(
+                            &model_identifiers,
+                            keep_going,
+                            |model_identifier| downloader.list_model_tags(model_identifier),
+                        );
+                        finish_tag_listing(&report, keep_going);
+                    }
                     Err(e) => {
                         error!("Failed to create Ollama downloader: {}", e);
                         std::process::exit(1);
                     }
-                },
-                Err(e) => {
-                    error!("Failed to load settings: {}", e);
-                    std::process::exit(1);
                 }
             }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::ModelDownload {
+            model_tags,
+            from_file,
+            registry,
+            plain_digest,
+            max_size,
+            limit_rate,
+            timeout_per_blob,
+            no_verify_digest,
+            verify_after,
+            no_cleanup,
+            force,
+            min_free_after,
+            raw_manifest_out,
+            no_lock,
+            strict_tag,
+            preserve_mtime,
+            retry_missing,
+            blobs_only,
+            manifest_only,
+            plan,
+            checksum_file,
+            output,
+        } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let model_tags = batch_identifiers(model_tags, from_file, output);
+            let options = run_options(&cli.global)
+                .with_no_cleanup(no_cleanup)
+                .with_force(force)
+                .with_min_free_after(min_free_after)
+                .with_raw_manifest_out(raw_manifest_out)
+                .with_no_lock(no_lock)
+                .with_strict_tag(strict_tag)
+                .with_preserve_mtime(preserve_mtime);
+            let progress = terminal_progress(&settings.ollama_library, &options);
+            let models_path = settings.ollama_library.models_path.clone();
+            let downloader = OllamaModelDownloader::new(
+                settings
+                    .with_registry_base_url(registry)
+                    .with_max_download_size(max_size)
+                    .with_max_bandwidth(limit_rate)
+                    .with_blob_timeout(timeout_per_blob),
+                options,
+            )
+            .unwrap_or_else(|e| exit_with_error(output, "Failed to create Ollama downloader", e));
+            let downloader = downloader
+                .with_progress(progress)
+                .with_digest_format(if plain_digest {
+                    DigestFormat::Plain
+                } else {
+                    DigestFormat::Dashed
+                })
+                .with_digest_verification(!no_verify_digest)
+                .with_verify_after(verify_after)
+                .with_missing_blob_retries(retry_missing)
+                .with_scope(if blobs_only {
+                    DownloadScope::BlobsOnly
+                } else if manifest_only {
+                    DownloadScope::ManifestOnly
+                } else {
+                    DownloadScope::Full
+                });
+            if plan {
+                print_plans(&model_tags, |model_tag| downloader.plan(model_tag));
+                return;
+            }
+            let mut checksums = Vec::new();
+            let report = downloader::batch::download_batch(&model_tags, |model_tag| {
+                let summary = downloader.download_model(model_tag)?;
+                if checksum_file.is_some() {
+                    record_checksums(&downloader, &models_path, &summary, &mut checksums)?;
+                }
+                Ok(summary)
+            });
+            finish_checksums(checksum_file.as_deref(), &checksums, output);
+            finish_batch(&report, "Model", output);
         }
-        Commands::HfListModels { page, page_size } => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match HuggingFaceModelDownloader::new(settings) {
+        Commands::HfListModels {
+            page,
+            page_size,
+            sort,
+            since,
+            repo_type,
+            app,
+            include_gated,
+            interactive,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                match HuggingFaceModelDownloader::new(settings, run_options(&cli.global)) {
                     Ok(downloader) => {
+                        let downloader = downloader
+                            .with_listing(sort, since)
+                            .with_catalog_filter(app, include_gated)
+                            .with_repo_type(repo_type);
                         match downloader.list_available_models(Some(page), Some(page_size)) {
+                            Ok(models) if interactive && is_interactive_terminal() => {
+                                if let Some(model) = pick_model(&models) {
+                                    let report =
+                                        downloader::batch::download_batch(&[model], |model| {
+                                            downloader.download_model(model)
+                                        });
+                                    finish_batch(&report, "HuggingFace model", OutputFormat::Text);
+                                }
+                            }
                             Ok(models) => {
+                                warn_if_not_interactive(interactive);
                                 println!(
                                     "Model identifiers: ({}, page {}): {:?}",
                                     models.len(),
@@ -534,69 +2162,889 @@ fn main() {
                         error!("Failed to create HuggingFace downloader: {}", e);
                         std::process::exit(1);
                     }
-                },
-                Err(e) => {
-                    error!("Failed to load settings: {}", e);
-                    std::process::exit(1);
                 }
             }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::HfListTags {
+            model_identifiers,
+            include_split,
+            repo_type,
+            keep_going,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                match HuggingFaceModelDownloader::new(settings, run_options(&cli.global)) {
+                    Ok(downloader) => {
+                        let downloader = downloader
+                            .with_split_quantisations(include_split)
+                            .with_repo_type(repo_type);
+                        let report = downloader::batch::list_tags_batch(
+                            &model_identifiers,
+                            keep_going,
+                            |model_identifier| downloader.list_model_tags(model_identifier),
+                        );
+                        finish_tag_listing(&report, keep_going);
+                    }
+                    Err(e) => {
+                        error!("Failed to create HuggingFace downloader: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::HfModelDownload {
+            user_repo_quants,
+            from_file,
+            prefer,
+            select_quant,
+            repo_type,
+            max_size,
+            limit_rate,
+            timeout_per_blob,
+            no_verify_digest,
+            verify_after,
+            no_cleanup,
+            force,
+            min_free_after,
+            raw_manifest_out,
+            no_lock,
+            strict_tag,
+            preserve_mtime,
+            retry_missing,
+            plan,
+            checksum_file,
+            output,
+        } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let user_repo_quants = batch_identifiers(user_repo_quants, from_file, output);
+            let options = run_options(&cli.global)
+                .with_no_cleanup(no_cleanup)
+                .with_force(force)
+                .with_min_free_after(min_free_after)
+                .with_raw_manifest_out(raw_manifest_out)
+                .with_no_lock(no_lock)
+                .with_strict_tag(strict_tag)
+                .with_preserve_mtime(preserve_mtime);
+            let progress = terminal_progress(&settings.ollama_library, &options);
+            let models_path = settings.ollama_library.models_path.clone();
+            let downloader = HuggingFaceModelDownloader::new(
+                settings
+                    .with_max_download_size(max_size)
+                    .with_max_bandwidth(limit_rate)
+                    .with_blob_timeout(timeout_per_blob),
+                options,
+            )
+            .unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to create HuggingFace downloader", e)
+            });
+            let downloader = downloader
+                .with_progress(progress)
+                .with_repo_type(repo_type)
+                .with_digest_verification(!no_verify_digest)
+                .with_verify_after(verify_after)
+                .with_missing_blob_retries(retry_missing);
+            if plan {
+                print_plans(&user_repo_quants, |user_repo_quant| {
+                    let user_repo_quant = preferred_quant(
+                        &downloader,
+                        prefer.as_ref(),
+                        select_quant,
+                        user_repo_quant,
+                    )?;
+                    downloader.plan(&user_repo_quant)
+                });
+                return;
+            }
+            let mut checksums = Vec::new();
+            let report = downloader::batch::download_batch(&user_repo_quants, |user_repo_quant| {
+                let user_repo_quant =
+                    preferred_quant(&downloader, prefer.as_ref(), select_quant, user_repo_quant)?;
+                let summary = downloader.download_model(&user_repo_quant)?;
+                if checksum_file.is_some() {
+                    record_checksums(&downloader, &models_path, &summary, &mut checksums)?;
+                }
+                Ok(summary)
+            });
+            finish_checksums(checksum_file.as_deref(), &checksums, output);
+            finish_batch(&report, "HuggingFace model", output);
         }
-        Commands::HfListTags { model_identifier } => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match HuggingFaceModelDownloader::new(settings) {
-                    Ok(downloader) => match downloader.list_model_tags(&model_identifier) {
-                        Ok(tags) => {
-                            println!("Model tags: ({} tags): {:?}", tags.len(), tags);
+        Commands::Bundle {
+            model_tag,
+            output_dir,
+            max_size,
+            limit_rate,
+            preserve_mtime,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                let mut settings = settings
+                    .with_max_download_size(max_size)
+                    .with_max_bandwidth(limit_rate);
+                let options = run_options(&cli.global).with_preserve_mtime(preserve_mtime);
+                let output_path = match downloader::utils::expand_models_path(&output_dir) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        error!("Invalid output directory '{}': {}", output_dir, e);
+                        std::process::exit(1);
+                    }
+                };
+                if let Err(e) = downloader::bundle::prepare_output_dir(&output_path) {
+                    error!("Failed to prepare bundle directory '{}': {}", output_dir, e);
+                    std::process::exit(1);
+                }
+
+                // A bundle is independent of any Ollama installation
+                settings.ollama_library.models_path = output_dir.clone();
+                settings.ollama_server.check_model_presence = false;
+
+                let progress = terminal_progress(&settings.ollama_library, &options);
+                let result = if is_hf_identifier(&model_tag, false) {
+                    HuggingFaceModelDownloader::new(settings, options).and_then(|downloader| {
+                        downloader
+                            .with_progress(progress)
+                            .download_model(&model_tag)
+                    })
+                } else {
+                    OllamaModelDownloader::new(settings, options).and_then(|downloader| {
+                        downloader
+                            .with_progress(progress)
+                            .download_model(&model_tag)
+                    })
+                };
+                match result {
+                    Ok(_) => {
+                        downloader::bundle::remove_empty_temp_dir(&output_path);
+                        match downloader::bundle::write_index(&output_path) {
+                            Ok(index_file) => {
+                                println!(
+                                    "Model {} bundled successfully into {} (index: {})",
+                                    model_tag,
+                                    output_path.display(),
+                                    index_file.display()
+                                );
+                            }
+                            Err(e) => {
+                                error!("Failed to write bundle index: {}", e);
+                                std::process::exit(1);
+                            }
                         }
-                        Err(e) => {
-                            error!(
-                                "Error listing tags for HuggingFace model '{}': {}",
-                                model_identifier, e
-                            );
-                            std::process::exit(1);
+                        signal_handler::set_cleanup_done();
+                    }
+                    Err(e) => {
+                        error!("Error bundling model '{}': {}", model_tag, e);
+                        if !signal_handler::is_interrupted() {
+                            std::process::exit(e.exit_code());
                         }
-                    },
+                        signal_handler::set_cleanup_done();
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Export {
+            model_tag,
+            output,
+            compress,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                let result =
+                    downloader::utils::expand_models_path(&settings.ollama_library.models_path)
+                        .and_then(|models_root| {
+                            downloader::archive::export_model(
+                                &models_root,
+                                &model_tag,
+                                &output,
+                                compress,
+                            )
+                        });
+                match result {
+                    Ok(summary) => println!(
+                        "Model {} exported to {} ({} and {} BLOB(s), {})",
+                        model_tag,
+                        output.display(),
+                        summary.manifest,
+                        summary.blobs,
+                        downloader::utils::format_byte_size(summary.total_bytes)
+                    ),
                     Err(e) => {
-                        error!("Failed to create HuggingFace downloader: {}", e);
+                        error!("Error exporting model '{}': {}", model_tag, e);
+                        std::process::exit(e.exit_code());
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Import {
+            archive,
+            preserve_mtime,
+            force,
+        } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                let models_path = &settings.ollama_library.models_path;
+                let result =
+                    downloader::utils::expand_models_path(models_path).and_then(|models_root| {
+                        let ownership = downloader::utils::infer_models_dir_ownership(models_path)?;
+                        downloader::archive::import_model(
+                            &archive,
+                            &models_root,
+                            ownership,
+                            preserve_mtime,
+                            force,
+                        )
+                    });
+                match result {
+                    Ok(manifests) => {
+                        for manifest in manifests {
+                            println!("Imported {} from {}", manifest, archive.display());
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error importing archive '{}': {}", archive.display(), e);
+                        std::process::exit(e.exit_code());
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::CheckPresent { model_tag } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                let presence = if is_hf_identifier(&model_tag, false) {
+                    HuggingFaceModelDownloader::new(settings, run_options(&cli.global))
+                        .and_then(|downloader| downloader.is_model_present(&model_tag))
+                } else {
+                    OllamaModelDownloader::new(settings, run_options(&cli.global))
+                        .and_then(|downloader| downloader.is_model_present(&model_tag))
+                };
+                match presence {
+                    Ok(true) => {
+                        println!("Model {} is present in the Ollama server", model_tag);
+                    }
+                    Ok(false) => {
+                        println!("Model {} is absent from the Ollama server", model_tag);
                         std::process::exit(1);
                     }
-                },
+                    Err(e) => {
+                        error!("Error checking presence of model '{}': {}", model_tag, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Relink { model_tag } => match load_settings(&cli.global) {
+            Ok(settings) => {
+                let relinked = if is_hf_identifier(&model_tag, false) {
+                    HuggingFaceModelDownloader::new(settings, run_options(&cli.global))
+                        .and_then(|downloader| downloader.relink(&model_tag))
+                } else {
+                    OllamaModelDownloader::new(settings, run_options(&cli.global))
+                        .and_then(|downloader| downloader.relink(&model_tag))
+                };
+                match relinked {
+                    Ok(path) => {
+                        println!("Relinked model {} with manifest {:?}", model_tag, path);
+                    }
+                    Err(e) => {
+                        error!("Error relinking model '{}': {}", model_tag, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Which { model_tag, hf } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                error!("Failed to load settings: {}", e);
+                std::process::exit(1);
+            });
+            let models_path = settings.ollama_library.models_path.clone();
+            let manifest_path = if is_hf_identifier(&model_tag, hf) {
+                HuggingFaceModelDownloader::new(settings, run_options(&cli.global))
+                    .and_then(|downloader| downloader.manifest_path(&model_tag))
+            } else {
+                OllamaModelDownloader::new(settings, run_options(&cli.global))
+                    .and_then(|downloader| downloader.manifest_path(&model_tag))
+            };
+            let location = manifest_path.and_then(|manifest_path| {
+                let models_root = downloader::utils::expand_models_path(&models_path)?;
+                downloader::store::locate_model(&models_root, &manifest_path, &model_tag)
+            });
+            match location {
+                Ok(location) => {
+                    println!("manifest {}", location.manifest.display());
+                    for blob in &location.blobs {
+                        let missing = if blob.is_file() { "" } else { " (missing)" };
+                        println!("blob     {}{}", blob.display(), missing);
+                    }
+                }
                 Err(e) => {
-                    error!("Failed to load settings: {}", e);
+                    error!("Error locating model '{}': {}", model_tag, e);
                     std::process::exit(1);
                 }
             }
         }
-        Commands::HfModelDownload { user_repo_quant } => {
-            match AppSettings::load_or_create_default(config::get_settings_file_path()) {
-                Ok(settings) => match HuggingFaceModelDownloader::new(settings) {
-                    Ok(downloader) => match downloader.download_model(&user_repo_quant) {
-                        Ok(_) => {
+        Commands::Verify {
+            model_tag,
+            hf,
+            hash_only,
+            fix,
+            jobs,
+            output,
+        } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let is_hf = is_hf_identifier(&model_tag, hf);
+            // The mode group requires exactly one of --hash-only and --fix
+            if fix {
+                if is_hf {
+                    exit_with_error(
+                        output,
+                        &format!("Failed to fix model '{}'", model_tag),
+                        DownloaderError::Other(
+                            "only the BLOBs of Ollama library models can be fixed; download the Hugging Face model again with hf-model-download --force"
+                                .to_string(),
+                        ),
+                    );
+                }
+                let options = run_options(&cli.global);
+                let progress = terminal_progress(&settings.ollama_library, &options);
+                let downloader = OllamaModelDownloader::new(settings, options)
+                    .unwrap_or_else(|e| {
+                        exit_with_error(output, "Failed to create Ollama downloader", e)
+                    })
+                    .with_progress(progress)
+                    .with_scope(DownloadScope::BlobsOnly);
+                let report = downloader::batch::download_batch(&[model_tag], |model_tag| {
+                    downloader.download_model(model_tag)
+                });
+                finish_batch(&report, "Model", output);
+            } else if hash_only {
+                let models_path = settings.ollama_library.models_path.clone();
+                let manifest_path = if is_hf {
+                    HuggingFaceModelDownloader::new(settings, run_options(&cli.global))
+                        .and_then(|downloader| downloader.manifest_path(&model_tag))
+                } else {
+                    OllamaModelDownloader::new(settings, run_options(&cli.global))
+                        .and_then(|downloader| downloader.manifest_path(&model_tag))
+                };
+                let jobs = jobs.map_or_else(
+                    || std::thread::available_parallelism().map_or(1, |n| n.get()),
+                    usize::from,
+                );
+                let checks = manifest_path
+                    .and_then(|manifest_path| {
+                        let models_root = downloader::utils::expand_models_path(&models_path)?;
+                        downloader::store::verify_model_blobs(
+                            &models_root,
+                            &manifest_path,
+                            &model_tag,
+                            jobs,
+                        )
+                    })
+                    .unwrap_or_else(|e| {
+                        exit_with_error(
+                            output,
+                            &format!("Failed to verify model '{}'", model_tag),
+                            e,
+                        )
+                    });
+                let damaged = checks
+                    .iter()
+                    .filter(|check| check.state != downloader::store::BlobState::Intact)
+                    .count();
+                match output {
+                    OutputFormat::Json => {
+                        let report = serde_json::json!({"model": model_tag, "blobs": checks});
+                        match serde_json::to_string_pretty(&report) {
+                            Ok(json) => println!("{}", json),
+                            Err(e) => exit_with_error(
+                                output,
+                                "Failed to serialize the verification",
+                                DownloaderError::Other(e.to_string()),
+                            ),
+                        }
+                    }
+                    OutputFormat::Text => {
+                        for check in &checks {
+                            let (mark, state) = match check.state {
+                                downloader::store::BlobState::Intact => ("✓", "intact "),
+                                downloader::store::BlobState::Missing => ("✗", "missing"),
+                                downloader::store::BlobState::Corrupt => ("✗", "corrupt"),
+                            };
                             println!(
-                                "HuggingFace model {} download completed successfully",
-                                user_repo_quant
+                                "{} {}  {} ({})",
+                                mark,
+                                state,
+                                check.digest,
+                                downloader::utils::format_byte_size(check.size)
                             );
-                            signal_handler::set_cleanup_done();
                         }
-                        Err(e) => {
-                            error!(
-                                "Error downloading HuggingFace model '{}': {}",
-                                user_repo_quant, e
+                        if damaged == 0 {
+                            println!("All {} BLOBs of {} are intact", checks.len(), model_tag);
+                        } else {
+                            println!(
+                                "{} of {} BLOBs of {} are missing or corrupt",
+                                damaged,
+                                checks.len(),
+                                model_tag
                             );
-                            if !signal_handler::is_interrupted() {
-                                std::process::exit(1);
+                        }
+                    }
+                }
+                if damaged > 0 {
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Cat { model_tag } => {
+            let output = OutputFormat::Text;
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let options = run_options(&cli.global);
+            let progress = terminal_progress(&settings.ollama_library, &options);
+            let mut stdout = io::BufWriter::new(io::stdout().lock());
+            let streamed = if is_hf_identifier(&model_tag, false) {
+                HuggingFaceModelDownloader::new(settings, options).and_then(|downloader| {
+                    downloader
+                        .with_progress(progress)
+                        .stream_weights(&model_tag, &mut stdout)
+                })
+            } else {
+                OllamaModelDownloader::new(settings, options).and_then(|downloader| {
+                    downloader
+                        .with_progress(progress)
+                        .stream_weights(&model_tag, &mut stdout)
+                })
+            };
+            match streamed {
+                Ok(size) => info!(
+                    "Streamed {} of weights of {} to stdout",
+                    downloader::utils::format_byte_size(size),
+                    model_tag
+                ),
+                Err(e) => exit_with_error(
+                    output,
+                    &format!("Failed to stream the weights of model '{}'", model_tag),
+                    e,
+                ),
+            }
+        }
+        Commands::Config { model_tag } => {
+            let output = OutputFormat::Text;
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let config = if is_hf_identifier(&model_tag, false) {
+                HuggingFaceModelDownloader::new(settings, run_options(&cli.global))
+                    .and_then(|downloader| downloader.model_config(&model_tag))
+            } else {
+                OllamaModelDownloader::new(settings, run_options(&cli.global))
+                    .and_then(|downloader| downloader.model_config(&model_tag))
+            }
+            .unwrap_or_else(|e| {
+                exit_with_error(
+                    output,
+                    &format!("Failed to fetch the configuration of model '{}'", model_tag),
+                    e,
+                )
+            });
+            match serde_json::to_string_pretty(&config) {
+                Ok(json) => println!("{}", json),
+                Err(e) => exit_with_error(
+                    output,
+                    "Failed to serialize the configuration",
+                    DownloaderError::Other(e.to_string()),
+                ),
+            }
+        }
+        Commands::Compare { model_tag, output } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let comparison = if is_hf_identifier(&model_tag, false) {
+                HuggingFaceModelDownloader::new(settings, run_options(&cli.global))
+                    .and_then(|downloader| downloader.compare(&model_tag))
+            } else {
+                OllamaModelDownloader::new(settings, run_options(&cli.global))
+                    .and_then(|downloader| downloader.compare(&model_tag))
+            }
+            .unwrap_or_else(|e| {
+                exit_with_error(
+                    output,
+                    &format!("Failed to compare model '{}'", model_tag),
+                    e,
+                )
+            });
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&comparison) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize the comparison",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => print_comparison(&comparison),
+            }
+            if !comparison.is_up_to_date() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Update {
+            model_tag,
+            no_lock,
+            output,
+        } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let options = run_options(&cli.global).with_no_lock(no_lock);
+            let models_root =
+                downloader::utils::expand_models_path(&settings.ollama_library.models_path)
+                    .unwrap_or_else(|e| exit_with_error(output, "Failed to update model", e));
+            let progress = terminal_progress(&settings.ollama_library, &options);
+            let report = if is_hf_identifier(&model_tag, false) {
+                HuggingFaceModelDownloader::new(settings, options).and_then(|downloader| {
+                    downloader::model_downloader::update_model(
+                        &downloader.with_progress(progress).with_skip_existing(true),
+                        &models_root,
+                        &model_tag,
+                    )
+                })
+            } else {
+                OllamaModelDownloader::new(settings, options).and_then(|downloader| {
+                    downloader::model_downloader::update_model(
+                        &downloader.with_progress(progress).with_skip_existing(true),
+                        &models_root,
+                        &model_tag,
+                    )
+                })
+            }
+            .unwrap_or_else(|e| {
+                exit_with_error(
+                    output,
+                    &format!("Failed to update model '{}'", model_tag),
+                    e,
+                )
+            });
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&report) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize the update report",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => {
+                    print_comparison(&report.comparison);
+                    if let Some(download) = &report.download {
+                        println!("Updated {}", download.line());
+                        if report.removed_blobs > 0 {
+                            println!(
+                                "Removed {} BLOB(s) of the previous version, freeing {}",
+                                report.removed_blobs,
+                                downloader::utils::format_byte_size(report.freed_bytes)
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Commands::History { output } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let entries = downloader::journal::read_entries(&settings.ollama_library.models_path)
+                .unwrap_or_else(|e| exit_with_error(output, "Failed to read download history", e));
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&entries) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize download history",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => {
+                    if entries.is_empty() {
+                        println!("No downloads recorded yet");
+                    }
+                    for entry in entries {
+                        println!(
+                            "{}  {}  {} bytes  {} BLOB(s)  from {}{}{}",
+                            entry.timestamp,
+                            entry.identifier,
+                            entry.total_bytes,
+                            entry.digests.len(),
+                            entry.source,
+                            entry
+                                .elapsed_secs
+                                .map(|secs| format!("  in {:.1} s", secs))
+                                .unwrap_or_default(),
+                            if entry.digest_verified {
+                                ""
+                            } else {
+                                "  (digests NOT verified)"
                             }
-                            signal_handler::set_cleanup_done();
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Failures { output } => {
+            let entries = downloader::failures::failures_file_path()
+                .map_err(DownloaderError::from)
+                .and_then(|path| downloader::failures::read_entries(&path))
+                .unwrap_or_else(|e| exit_with_error(output, "Failed to read failed downloads", e));
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&entries) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize failed downloads",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => {
+                    if entries.is_empty() {
+                        println!("No failed downloads recorded");
+                    }
+                    for entry in entries {
+                        println!(
+                            "{}  {}  {}{}  {}",
+                            entry.timestamp,
+                            entry.identifier,
+                            entry.kind,
+                            entry
+                                .status
+                                .map(|status| format!(" (HTTP {})", status))
+                                .unwrap_or_default(),
+                            entry.message
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Stats {
+            top,
+            output,
+            json_lines,
+            jobs,
+        } => {
+            // Errors are reported as JSON envelopes to tools that read the stream
+            let output = if json_lines {
+                OutputFormat::Json
+            } else {
+                output
+            };
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let mut print_model = |model: &downloader::store::LocalModel| {
+                if json_lines {
+                    println!(
+                        "{}",
+                        serde_json::json!({"type": "model", "name": model.name, "size": model.size})
+                    );
+                }
+            };
+            let stats = downloader::utils::expand_models_path(&settings.ollama_library.models_path)
+                .and_then(|models_root| {
+                    let jobs = jobs.map_or_else(
+                        || std::thread::available_parallelism().map_or(1, |n| n.get()),
+                        usize::from,
+                    );
+                    downloader::store::collect_stats(&models_root, top, jobs, &mut print_model)
+                })
+                .unwrap_or_else(|e| {
+                    exit_with_error(output, "Failed to summarise the models path", e)
+                });
+            if json_lines {
+                match serde_json::to_value(&stats) {
+                    Ok(serde_json::Value::Object(mut summary)) => {
+                        summary.insert("type".to_string(), serde_json::Value::from("summary"));
+                        println!("{}", serde_json::Value::Object(summary));
+                    }
+                    Ok(_) | Err(_) => exit_with_error(
+                        output,
+                        "Failed to serialize the summary of the models path",
+                        DownloaderError::Other("not a JSON object".to_string()),
+                    ),
+                }
+                return;
+            }
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&stats) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize the summary of the models path",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => {
+                    use downloader::utils::format_byte_size;
+                    println!("Models:        {} ({} tags)", stats.models, stats.tags);
+                    println!(
+                        "BLOBs:         {} ({})",
+                        stats.blobs,
+                        format_byte_size(stats.blob_bytes)
+                    );
+                    println!(
+                        "Orphan BLOBs:  {} ({})",
+                        stats.orphan_blobs,
+                        format_byte_size(stats.orphan_blob_bytes)
+                    );
+                    if stats.missing_blobs > 0 {
+                        println!("Missing BLOBs: {}", stats.missing_blobs);
+                    }
+                    if stats.unreadable_manifests > 0 {
+                        println!("Unreadable manifests: {}", stats.unreadable_manifests);
+                    }
+                    if !stats.largest.is_empty() {
+                        println!("Largest models:");
+                        for model in &stats.largest {
+                            println!("  {:>10}  {}", format_byte_size(model.size), model.name);
                         }
-                    },
-                    Err(e) => {
-                        error!("Failed to create HuggingFace downloader: {}", e);
-                        std::process::exit(1);
                     }
+                }
+            }
+        }
+        Commands::PruneTags {
+            model,
+            keep,
+            dry_run,
+            no_lock,
+            output,
+        } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let report =
+                downloader::utils::expand_models_path(&settings.ollama_library.models_path)
+                    .and_then(|models_root| {
+                        let ownership = downloader::utils::infer_models_dir_ownership(
+                            &settings.ollama_library.models_path,
+                        )?;
+                        downloader::store::prune_tags(
+                            &models_root,
+                            &model,
+                            keep as usize,
+                            dry_run,
+                            ownership,
+                            no_lock,
+                        )
+                    })
+                    .unwrap_or_else(|e| {
+                        exit_with_error(output, &format!("Failed to prune tags of {}", model), e)
+                    });
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&report) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize the pruned tags",
+                        DownloaderError::Other(e.to_string()),
+                    ),
                 },
-                Err(e) => {
-                    error!("Failed to load settings: {}", e);
-                    std::process::exit(1);
+                OutputFormat::Text => {
+                    let verb = if report.dry_run {
+                        "Would remove"
+                    } else {
+                        "Removed"
+                    };
+                    println!("Kept: {}", report.kept.join(", "));
+                    if report.removed.is_empty() {
+                        println!("No tags of {} to remove.", model);
+                    } else {
+                        println!("{} tags: {}", verb, report.removed.join(", "));
+                        println!(
+                            "{} {} unreferenced BLOB(s), freeing {}",
+                            verb,
+                            report.removed_blobs,
+                            downloader::utils::format_byte_size(report.freed_bytes)
+                        );
+                    }
+                }
+            }
+        }
+        Commands::ConnectTest { output } => {
+            let settings = load_settings(&cli.global).unwrap_or_else(|e| {
+                exit_with_error(output, "Failed to load settings", DownloaderError::from(e))
+            });
+            let options = run_options(&cli.global);
+            let checks = OllamaModelDownloader::new(settings.clone(), options.clone())
+                .map(|downloader| downloader.check_connectivity())
+                .and_then(|mut checks| {
+                    HuggingFaceModelDownloader::new(settings, options).map(|downloader| {
+                        checks.extend(downloader.check_connectivity());
+                        checks
+                    })
+                })
+                .unwrap_or_else(|e| exit_with_error(output, "Failed to set up the HTTP client", e));
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&checks) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize the connectivity checks",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => {
+                    for check in &checks {
+                        println!("{}", check.line());
+                    }
+                }
+            }
+            if !checks.iter().all(|check| check.is_reachable()) {
+                std::process::exit(1);
+            }
+        }
+        Commands::Version { output } => {
+            let info = BuildInfo::current();
+            match output {
+                OutputFormat::Json => match serde_json::to_string_pretty(&info) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => exit_with_error(
+                        output,
+                        "Failed to serialize version information",
+                        DownloaderError::Other(e.to_string()),
+                    ),
+                },
+                OutputFormat::Text => {
+                    println!("odir {}", info.version);
+                    println!("Platform:   {}-{}", info.os, info.arch);
+                    println!("Target:     {}", info.target);
+                    println!("Compiler:   {}", info.rustc);
+                    println!("Build date: {}", info.build_date);
+                    println!("User agent: {}", info.user_agent);
                 }
             }
         }
@@ -605,7 +3053,7 @@ fn main() {
             use std::path::Path;
 
             let source_path = Path::new(&od_settings_file);
-            let dest_path = config::get_settings_file_path();
+            let dest_path = settings_file_path(cli.global.profile.as_deref());
 
             // Check if source file exists
             if !source_path.exists() {
@@ -626,15 +3074,7 @@ fn main() {
             if dest_path.exists() {
                 println!("Settings file already exists at: {}", dest_path.display());
                 print!("Overwrite existing settings file? [y/N]: ");
-                io::stdout().flush().unwrap();
-
-                let mut input = String::new();
-                if let Err(e) = io::stdin().read_line(&mut input) {
-                    error!("Failed to read user input: {}", e);
-                    std::process::exit(1);
-                }
-
-                let input = input.trim().to_lowercase();
+                let input = read_input_line().trim().to_lowercase();
                 if input != "y" && input != "yes" {
                     info!("Operation cancelled by user.");
                     return;